@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use graph_flow::{Context, FlowRunner, GraphBuilder, InMemorySessionStorage, NextAction, Session, SessionStorage, Task, TaskResult, FanOutTask};
+use graph_flow::{
+    Context, FanOutTask, FlowRunner, GraphBuilder, InMemorySessionStorage, NextAction, Session,
+    SessionStorage, Task, TaskResult,
+};
 
 struct Prepare;
 struct ChildA;
@@ -10,16 +13,23 @@ struct Consume;
 
 #[async_trait]
 impl Task for Prepare {
-    fn id(&self) -> &str { "prepare" }
+    fn id(&self) -> &str {
+        "prepare"
+    }
     async fn run(&self, ctx: Context) -> graph_flow::Result<TaskResult> {
         ctx.set("input", "hello".to_string()).await;
-        Ok(TaskResult::new(Some("prepared".to_string()), NextAction::Continue))
+        Ok(TaskResult::new(
+            Some("prepared".to_string()),
+            NextAction::Continue,
+        ))
     }
 }
 
 #[async_trait]
 impl Task for ChildA {
-    fn id(&self) -> &str { "child_a" }
+    fn id(&self) -> &str {
+        "child_a"
+    }
     async fn run(&self, ctx: Context) -> graph_flow::Result<TaskResult> {
         let inp: String = ctx.get("input").await.unwrap_or_default();
         ctx.set("a_out", format!("{}-A", inp)).await;
@@ -29,7 +39,9 @@ impl Task for ChildA {
 
 #[async_trait]
 impl Task for ChildB {
-    fn id(&self) -> &str { "child_b" }
+    fn id(&self) -> &str {
+        "child_b"
+    }
     async fn run(&self, ctx: Context) -> graph_flow::Result<TaskResult> {
         let inp: String = ctx.get("input").await.unwrap_or_default();
         ctx.set("b_out", format!("{}-B", inp)).await;
@@ -39,7 +51,9 @@ impl Task for ChildB {
 
 #[async_trait]
 impl Task for Consume {
-    fn id(&self) -> &str { "consume" }
+    fn id(&self) -> &str {
+        "consume"
+    }
     async fn run(&self, ctx: Context) -> graph_flow::Result<TaskResult> {
         // Read aggregated responses stored by the fanout task
         let a_resp: Option<String> = ctx.get("fanout.child_a.response").await;
@@ -88,4 +102,3 @@ async fn main() -> graph_flow::Result<()> {
 
     Ok(())
 }
-
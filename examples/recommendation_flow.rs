@@ -474,18 +474,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 info!("Workflow completed successfully");
                 break;
             }
-            ExecutionStatus::Paused { next_task_id, reason } => {
-                info!("Workflow paused, will continue to task: {} (reason: {})", next_task_id, reason);
+            ExecutionStatus::Paused {
+                next_task_id,
+                reason,
+            } => {
+                info!(
+                    "Workflow paused, will continue to task: {} (reason: {})",
+                    next_task_id, reason
+                );
                 continue;
             }
             ExecutionStatus::WaitingForInput => {
                 info!("Workflow waiting for user input, continuing...");
                 continue;
             }
+            ExecutionStatus::Sleeping {
+                next_task_id,
+                until,
+            } => {
+                info!(
+                    "Workflow sleeping until {} before task {}",
+                    until, next_task_id
+                );
+                break;
+            }
+            ExecutionStatus::PausedAtBreakpoint {
+                next_task_id,
+                breakpoint,
+            } => {
+                info!(
+                    "Stopped at breakpoint {:?} before task {}",
+                    breakpoint, next_task_id
+                );
+                break;
+            }
             ExecutionStatus::Error(e) => {
                 error!("Workflow error: {}", e);
                 return Err(e.into());
             }
+            status => {
+                info!("Workflow stopped: {:?}", status);
+                break;
+            }
         }
     }
 
@@ -94,18 +94,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("Workflow completed successfully!");
                 break;
             }
-            ExecutionStatus::Paused { next_task_id, reason } => {
-                println!("Workflow paused, will continue to task: {} (reason: {}) – continuing...\n", next_task_id, reason);
+            ExecutionStatus::Paused {
+                next_task_id,
+                reason,
+            } => {
+                println!(
+                    "Workflow paused, will continue to task: {} (reason: {}) – continuing...\n",
+                    next_task_id, reason
+                );
                 continue;
             }
             ExecutionStatus::WaitingForInput => {
                 println!("Workflow waiting for user input – continuing...\n");
                 continue;
             }
+            ExecutionStatus::Sleeping {
+                next_task_id,
+                until,
+            } => {
+                println!(
+                    "Workflow sleeping until {} before task {}",
+                    until, next_task_id
+                );
+                break;
+            }
+            ExecutionStatus::PausedAtBreakpoint {
+                next_task_id,
+                breakpoint,
+            } => {
+                println!(
+                    "Stopped at breakpoint {:?} before task {}",
+                    breakpoint, next_task_id
+                );
+                break;
+            }
             ExecutionStatus::Error(err) => {
                 println!("Error occurred: {}", err);
                 break;
             }
+            status => {
+                println!("Workflow stopped: {:?}", status);
+                break;
+            }
         }
     }
 
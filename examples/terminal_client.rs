@@ -77,18 +77,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Exiting chat.");
             break;
         }
-        
+
         // Check for session info keywords
         if input.eq_ignore_ascii_case("session")
             || input.eq_ignore_ascii_case("session_info")
-            || input.eq_ignore_ascii_case("session_data") {
+            || input.eq_ignore_ascii_case("session_data")
+        {
             match &session_id {
                 Some(sid) => println!("Current session ID: {}", sid),
                 None => println!("No active session (session_id is None)"),
             }
             continue;
         }
-        
+
         content = input.to_string();
     }
 
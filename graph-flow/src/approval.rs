@@ -0,0 +1,418 @@
+//! ApprovalTask – a human approval step with roles, explicit decisions and an audit log
+//!
+//! The first time an `ApprovalTask` runs it asks for an [`ApprovalInput`] and waits.
+//! The reviewer answers through [`FlowRunner::resume`](crate::FlowRunner::resume)
+//! with their identity, an explicit [`ApprovalDecision`] and an optional comment.
+//! Input that is not a well-formed decision is rejected by `resume` before the task runs.
+//!
+//! A reviewer must hold one of the required roles and, with `with_requester_key`,
+//! must not be the person who asked for the approval; while the requester is not
+//! known, nobody may decide. Otherwise the task fails with
+//! [`GraphError::Unauthorized`] and the session keeps waiting. Services can call
+//! [`ApprovalTask::authorize`] before resuming to reject such reviewers up front.
+//!
+//! Every accepted decision is appended to the audit log, a `Vec<ApprovalRecord>`
+//! stored under `"<id>.audit"`, and the latest one is stored under `"<id>.decision"`.
+//! The task then jumps to the target registered for the decision (`on_approve`,
+//! `on_reject`, `on_request_changes`), or follows the graph's edges with
+//! `NextAction::Continue` when none is registered.
+//!
+//! Example:
+//! ```rust
+//! use graph_flow::{
+//!     ApprovalDecision, ApprovalInput, ApprovalRecord, ApprovalTask, Context, INPUT_KEY,
+//!     NextAction, Reviewer, Task,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let approval = ApprovalTask::new("refund_approval", "Approve the refund?")
+//!     .with_required_roles(["finance"])
+//!     .with_requester_key("requested_by")
+//!     .on_approve("issue_refund")
+//!     .on_reject("notify_customer");
+//!
+//! let ctx = Context::new();
+//! ctx.set("requested_by", "alice").await;
+//! let waiting = approval.run(ctx.clone()).await?;
+//! assert_eq!(waiting.next_action, NextAction::WaitForInput);
+//!
+//! // Normally delivered by `FlowRunner::resume`
+//! let input = ApprovalInput {
+//!     reviewer: Reviewer::new("bob", ["finance"]),
+//!     decision: ApprovalDecision::Approve,
+//!     comment: Some("Receipt checked".into()),
+//! };
+//! ctx.set(INPUT_KEY, &input).await;
+//! let decided = approval.run(ctx.clone()).await?;
+//! assert_eq!(decided.next_action, NextAction::GoTo("issue_refund".into()));
+//!
+//! let audit: Vec<ApprovalRecord> = ctx.get(&approval.audit_key()).await.unwrap();
+//! assert_eq!(audit[0].reviewer.id, "bob");
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{Context, GraphError, INPUT_KEY, InputRequest, NextAction, Result, Task, TaskResult};
+
+/// What a reviewer decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approve,
+    Reject,
+    RequestChanges,
+}
+
+/// Who is reviewing, as established by the caller's authentication.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Reviewer {
+    pub id: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Reviewer {
+    pub fn new<S: Into<String>>(id: impl Into<String>, roles: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            id: id.into(),
+            roles: roles.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Input an [`ApprovalTask`] waits for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ApprovalInput {
+    pub reviewer: Reviewer,
+    pub decision: ApprovalDecision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// One accepted decision in an approval audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    /// Id of the approval task that recorded the decision
+    pub task_id: String,
+    pub reviewer: Reviewer,
+    pub decision: ApprovalDecision,
+    pub comment: Option<String>,
+    pub decided_at: DateTime<Utc>,
+}
+
+/// Task that waits for an authorized reviewer's decision and routes on it.
+#[derive(Clone)]
+pub struct ApprovalTask {
+    id: String,
+    prompt: String,
+    required_roles: Vec<String>,   // any one of them; empty: anyone
+    requester_key: Option<String>, // context key holding the requester's id
+    routes: HashMap<ApprovalDecision, String>, // default: Continue
+    audit_key: Option<String>,     // default: "<id>.audit"
+}
+
+impl ApprovalTask {
+    /// Create an `ApprovalTask` that asks reviewers `prompt`.
+    pub fn new(id: impl Into<String>, prompt: impl Into<String>) -> Arc<Self> {
+        Arc::new(Self {
+            id: id.into(),
+            prompt: prompt.into(),
+            required_roles: Vec::new(),
+            requester_key: None,
+            routes: HashMap::new(),
+            audit_key: None,
+        })
+    }
+
+    /// Only accept decisions from reviewers holding at least one of `roles`.
+    pub fn with_required_roles<S: Into<String>>(
+        mut self: Arc<Self>,
+        roles: impl IntoIterator<Item = S>,
+    ) -> Arc<Self> {
+        Arc::make_mut(&mut self).required_roles = roles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Reject decisions from the reviewer whose id is stored under `key`,
+    /// typically whoever asked for the approval.
+    ///
+    /// Every decision is rejected while nothing is stored under `key`, since the
+    /// requester could then approve their own request.
+    pub fn with_requester_key(mut self: Arc<Self>, key: impl Into<String>) -> Arc<Self> {
+        Arc::make_mut(&mut self).requester_key = Some(key.into());
+        self
+    }
+
+    /// Jump to `task_id` after an approval.
+    pub fn on_approve(self: Arc<Self>, task_id: impl Into<String>) -> Arc<Self> {
+        self.route(ApprovalDecision::Approve, task_id)
+    }
+
+    /// Jump to `task_id` after a rejection.
+    pub fn on_reject(self: Arc<Self>, task_id: impl Into<String>) -> Arc<Self> {
+        self.route(ApprovalDecision::Reject, task_id)
+    }
+
+    /// Jump to `task_id` when the reviewer requests changes.
+    pub fn on_request_changes(self: Arc<Self>, task_id: impl Into<String>) -> Arc<Self> {
+        self.route(ApprovalDecision::RequestChanges, task_id)
+    }
+
+    /// Append decisions to the log under `key` instead of `<id>.audit`, e.g. to
+    /// share one log between several approval steps.
+    pub fn with_audit_key(mut self: Arc<Self>, key: impl Into<String>) -> Arc<Self> {
+        Arc::make_mut(&mut self).audit_key = Some(key.into());
+        self
+    }
+
+    fn route(
+        mut self: Arc<Self>,
+        decision: ApprovalDecision,
+        task_id: impl Into<String>,
+    ) -> Arc<Self> {
+        Arc::make_mut(&mut self)
+            .routes
+            .insert(decision, task_id.into());
+        self
+    }
+
+    /// Context key under which the `Vec<ApprovalRecord>` audit log is stored.
+    pub fn audit_key(&self) -> String {
+        self.audit_key
+            .clone()
+            .unwrap_or_else(|| format!("{}.audit", self.id))
+    }
+
+    /// Context key under which the latest `ApprovalRecord` is stored.
+    pub fn decision_key(&self) -> String {
+        format!("{}.decision", self.id)
+    }
+
+    fn pending_key(&self) -> String {
+        format!("{}.pending", self.id)
+    }
+
+    /// The input request reviewers answer.
+    pub fn input_request(&self) -> InputRequest {
+        InputRequest::typed::<ApprovalInput>(self.prompt.clone())
+    }
+
+    /// Check that `reviewer` may decide on the approval pending in `context`.
+    ///
+    /// Fails with [`GraphError::Unauthorized`] if the reviewer lacks every required
+    /// role, is the requester, or the requester is unknown.
+    pub async fn authorize(&self, context: &Context, reviewer: &Reviewer) -> Result<()> {
+        if !self.required_roles.is_empty()
+            && !reviewer
+                .roles
+                .iter()
+                .any(|role| self.required_roles.contains(role))
+        {
+            return Err(GraphError::Unauthorized(format!(
+                "reviewer '{}' needs one of the roles [{}] to decide on '{}'",
+                reviewer.id,
+                self.required_roles.join(", "),
+                self.id
+            )));
+        }
+        if let Some(key) = &self.requester_key {
+            match context.try_get::<String>(key).await? {
+                None => {
+                    return Err(GraphError::Unauthorized(format!(
+                        "the requester of '{}' is unknown (no '{key}' in the context)",
+                        self.id
+                    )));
+                }
+                Some(requester) if requester == reviewer.id => {
+                    return Err(GraphError::Unauthorized(format!(
+                        "reviewer '{}' cannot decide on their own request '{}'",
+                        reviewer.id, self.id
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Task for ApprovalTask {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn run(&self, context: Context) -> Result<TaskResult> {
+        // Whatever is under INPUT_KEY before we asked belongs to someone else
+        if context.get::<bool>(&self.pending_key()).await != Some(true) {
+            context.set(self.pending_key(), true).await;
+            return Ok(TaskResult::request_input(self.input_request()));
+        }
+
+        let input: ApprovalInput = context.try_get(INPUT_KEY).await?.ok_or_else(|| {
+            GraphError::InvalidInput(format!("no decision submitted for '{}'", self.id))
+        })?;
+        self.authorize(&context, &input.reviewer).await?;
+
+        let record = ApprovalRecord {
+            task_id: self.id.clone(),
+            reviewer: input.reviewer,
+            decision: input.decision,
+            comment: input.comment,
+            decided_at: Utc::now(),
+        };
+        let audit_key = self.audit_key();
+        let mut audit: Vec<ApprovalRecord> = context.try_get(&audit_key).await?.unwrap_or_default();
+        audit.push(record.clone());
+        context.set(audit_key, audit).await;
+        context.set(self.decision_key(), &record).await;
+        context.remove(&self.pending_key()).await;
+
+        let next_action = match self.routes.get(&record.decision) {
+            Some(target) => NextAction::GoTo(target.clone()),
+            None => NextAction::Continue,
+        };
+        let status = format!("{:?} by {}", record.decision, record.reviewer.id);
+        Ok(TaskResult::new_with_status(None, next_action, Some(status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTask;
+    use crate::{
+        ExecutionStatus, FlowRunner, GraphBuilder, InMemorySessionStorage, Session, SessionStorage,
+    };
+    use serde_json::json;
+
+    fn decision(reviewer: Reviewer, decision: ApprovalDecision) -> ApprovalInput {
+        ApprovalInput {
+            reviewer,
+            decision,
+            comment: None,
+        }
+    }
+
+    async fn setup() -> (FlowRunner, Arc<InMemorySessionStorage>) {
+        let approval = ApprovalTask::new("approval", "Approve?")
+            .with_required_roles(["manager"])
+            .with_requester_key("requester")
+            .on_approve("approved")
+            .on_reject("rejected");
+        let graph = GraphBuilder::new("approvals")
+            .add_task(approval)
+            .add_task(Arc::new(MockTask::new("approved")))
+            .add_task(Arc::new(MockTask::new("rejected")))
+            .add_task(Arc::new(MockTask::new("revise")))
+            .add_edge("approval", "revise")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        let session = Session::new_from_task("s1".to_string(), "approval");
+        session.context.set("requester", "alice").await;
+        // The requester's own message is still in the input slot
+        session.context.set(INPUT_KEY, "approved").await;
+        storage.save(session).await.unwrap();
+        (FlowRunner::new(Arc::new(graph), storage.clone()), storage)
+    }
+
+    #[tokio::test]
+    async fn only_authorized_reviewers_decide() {
+        let (runner, storage) = setup().await;
+        let result = runner.run("s1").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        assert_eq!(result.input_request.unwrap().prompt, "Approve?");
+
+        // Free text is not a decision
+        let err = runner.resume("s1", "approved").await.unwrap_err();
+        assert!(matches!(err, GraphError::InvalidInput(_)), "{err}");
+
+        // Requester and reviewers without the role are turned away
+        for reviewer in [
+            Reviewer::new("alice", ["manager"]),
+            Reviewer::new("carol", ["clerk"]),
+        ] {
+            let input = decision(reviewer, ApprovalDecision::Approve);
+            let err = runner.resume("s1", input).await.unwrap_err();
+            assert!(matches!(err, GraphError::Unauthorized(_)), "{err}");
+        }
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_some());
+        assert_eq!(
+            session
+                .context
+                .get::<Vec<ApprovalRecord>>("approval.audit")
+                .await,
+            None
+        );
+
+        let input = decision(Reviewer::new("bob", ["manager"]), ApprovalDecision::Reject);
+        let result = runner.resume("s1", input).await.unwrap();
+        assert!(
+            matches!(&result.status, ExecutionStatus::Paused { next_task_id, .. } if next_task_id == "rejected")
+        );
+    }
+
+    #[tokio::test]
+    async fn decisions_are_refused_while_the_requester_is_unknown() {
+        let approval = ApprovalTask::new("approval", "Approve?").with_requester_key("requester");
+        let context = Context::new();
+        let bob = Reviewer::new("bob", ["manager"]);
+
+        let err = approval.authorize(&context, &bob).await.unwrap_err();
+        assert!(matches!(err, GraphError::Unauthorized(_)), "{err}");
+
+        context.set("requester", "alice").await;
+        approval.authorize(&context, &bob).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn every_decision_is_audited_and_routed() {
+        let (runner, storage) = setup().await;
+        let bob = Reviewer::new("bob", ["manager"]);
+        runner.run("s1").await.unwrap();
+
+        // No route for RequestChanges: follow the graph's edge
+        let input = ApprovalInput {
+            comment: Some("Add receipts".into()),
+            ..decision(bob.clone(), ApprovalDecision::RequestChanges)
+        };
+        let result = runner.resume("s1", input).await.unwrap();
+        assert!(
+            matches!(&result.status, ExecutionStatus::Paused { next_task_id, .. } if next_task_id == "revise")
+        );
+
+        // Back for a second round
+        let mut session = storage.get("s1").await.unwrap().unwrap();
+        session.current_task_id = "approval".to_string();
+        storage.save(session).await.unwrap();
+        runner.run("s1").await.unwrap();
+        runner
+            .resume(
+                "s1",
+                json!({"reviewer": {"id": "bob", "roles": ["manager"]}, "decision": "approve"}),
+            )
+            .await
+            .unwrap();
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.current_task_id, "approved");
+        let audit: Vec<ApprovalRecord> = session.context.get("approval.audit").await.unwrap();
+        let decisions: Vec<_> = audit.iter().map(|r| r.decision).collect();
+        assert_eq!(
+            decisions,
+            [ApprovalDecision::RequestChanges, ApprovalDecision::Approve]
+        );
+        assert_eq!(audit[0].comment.as_deref(), Some("Add receipts"));
+        let latest: ApprovalRecord = session.context.get("approval.decision").await.unwrap();
+        assert_eq!(latest, audit[1]);
+    }
+}
@@ -0,0 +1,415 @@
+//! Out-of-line storage for large context values.
+//!
+//! Context values are serialized into the session on every save. Large values
+//! (OCR text, rendered pages, attachments) make that expensive, so a [`Context`]
+//! can be given a [`BlobStore`] and a size threshold: values whose JSON encoding
+//! is larger than the threshold are written to the store and only a small
+//! [`BlobRef`] marker is kept in the context. The value is loaded again the
+//! first time it is read and cached for the lifetime of the context.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{Context, InMemoryBlobStore};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let store = Arc::new(InMemoryBlobStore::new());
+//! let context = Context::new();
+//! context.set_blob_store(store.clone(), 1024);
+//!
+//! // Stored out of line, only a reference is serialized with the session
+//! context.set("ocr_text", "x".repeat(10_000)).await;
+//! let json = serde_json::to_string(&context).unwrap();
+//! assert!(json.len() < 1024);
+//!
+//! // Loaded lazily on read
+//! let restored: Context = serde_json::from_str(&json).unwrap();
+//! restored.set_blob_store(store, 1024);
+//! let text: Option<String> = restored.get("ocr_text").await;
+//! assert_eq!(text.map(|t| t.len()), Some(10_000));
+//! # }
+//! ```
+//!
+//! Overwriting or removing a key supersedes its blob, which is deleted by
+//! [`Context::delete_superseded_blobs`] once the change has been saved; a
+//! [`FlowRunner`](crate::FlowRunner) with a blob store does this after every
+//! step and deletes all of a session's blobs in [`FlowRunner::delete`](crate::FlowRunner::delete).
+//! A fork never deletes the blobs it inherits from its parent.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::error::{GraphError, Result};
+
+#[cfg(doc)]
+use crate::Context;
+
+/// JSON field that marks a context value as a reference to a blob
+const BLOB_MARKER: &str = "$graph_flow_blob";
+
+/// Trait for storing and retrieving large binary values
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store `data` and return the id under which it can be fetched.
+    async fn put(&self, data: Vec<u8>) -> Result<String>;
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>>;
+    async fn delete(&self, id: &str) -> Result<()>;
+}
+
+/// Reference to an offloaded value, stored in the context in place of the value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// Id returned by [`BlobStore::put`]
+    pub id: String,
+    /// Size of the serialized value in bytes
+    pub size: usize,
+}
+
+impl BlobRef {
+    /// Encode the reference as the marker value kept in the context.
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({ BLOB_MARKER: self })
+    }
+
+    /// Decode a marker value, returning `None` for ordinary values.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let object = value.as_object()?;
+        if object.len() != 1 {
+            return None;
+        }
+        serde_json::from_value(object.get(BLOB_MARKER)?.clone()).ok()
+    }
+}
+
+/// In-memory implementation of BlobStore
+pub struct InMemoryBlobStore {
+    blobs: Arc<DashMap<String, Vec<u8>>>,
+}
+
+impl Default for InMemoryBlobStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBlobStore {
+    pub fn new() -> Self {
+        Self {
+            blobs: Arc::new(DashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for InMemoryBlobStore {
+    async fn put(&self, data: Vec<u8>) -> Result<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.blobs.insert(id.clone(), data);
+        Ok(id)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.blobs.get(id).map(|entry| entry.clone()))
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        self.blobs.remove(id);
+        Ok(())
+    }
+}
+
+/// Filesystem implementation of BlobStore, one file per blob in a directory
+pub struct FileBlobStore {
+    dir: PathBuf,
+}
+
+impl FileBlobStore {
+    /// Use `dir` for blob files, creating it if needed.
+    pub async fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        tokio::fs::create_dir_all(&dir).await.map_err(|e| {
+            GraphError::StorageError(format!(
+                "Failed to create blob directory {}: {e}",
+                dir.display()
+            ))
+        })?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        // Ids are generated by `put`; reject anything that could escape the directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(GraphError::StorageError(format!("Invalid blob id: {id}")));
+        }
+        Ok(self.dir.join(id))
+    }
+}
+
+#[async_trait]
+impl BlobStore for FileBlobStore {
+    async fn put(&self, data: Vec<u8>) -> Result<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        tokio::fs::write(self.path(&id)?, data)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to write blob {id}: {e}")))?;
+        Ok(id)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(id)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(GraphError::StorageError(format!(
+                "Failed to read blob {id}: {e}"
+            ))),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(id)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(GraphError::StorageError(format!(
+                "Failed to delete blob {id}: {e}"
+            ))),
+        }
+    }
+}
+
+/// Blob store configuration attached to a context, plus a cache of loaded values.
+#[derive(Clone)]
+pub(crate) struct BlobOffload {
+    store: Arc<dyn BlobStore>,
+    threshold: usize,
+    cache: Arc<DashMap<String, Value>>,
+}
+
+impl std::fmt::Debug for BlobOffload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlobOffload")
+            .field("threshold", &self.threshold)
+            .field("cached", &self.cache.len())
+            .finish()
+    }
+}
+
+impl BlobOffload {
+    pub(crate) fn new(store: Arc<dyn BlobStore>, threshold: usize) -> Self {
+        Self {
+            store,
+            threshold,
+            cache: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Move `value` to the store if it is over the threshold, returning what the
+    /// context should hold. Falls back to keeping the value inline if the store fails.
+    pub(crate) async fn offload(&self, value: Value) -> Value {
+        let Ok(bytes) = serde_json::to_vec(&value) else {
+            return value;
+        };
+        if bytes.len() <= self.threshold {
+            return value;
+        }
+
+        let size = bytes.len();
+        match self.store.put(bytes).await {
+            Ok(id) => {
+                let marker = BlobRef {
+                    id: id.clone(),
+                    size,
+                }
+                .to_value();
+                self.cache.insert(id, value);
+                marker
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, size, "Failed to offload context value, keeping it inline");
+                value
+            }
+        }
+    }
+
+    /// Load the value behind `blob`, from the cache when possible.
+    pub(crate) async fn load(&self, blob: &BlobRef) -> Result<Value> {
+        if let Some(value) = self.cached(blob) {
+            return Ok(value);
+        }
+
+        let bytes = self.store.get(&blob.id).await?.ok_or_else(|| {
+            GraphError::ContextError(format!("Blob {} not found in blob store", blob.id))
+        })?;
+        let value: Value = serde_json::from_slice(&bytes).map_err(|e| {
+            GraphError::ContextError(format!("Failed to decode blob {}: {e}", blob.id))
+        })?;
+        self.cache.insert(blob.id.clone(), value.clone());
+        Ok(value)
+    }
+
+    /// Delete `blob` from the store and the cache.
+    pub(crate) async fn delete(&self, blob: &BlobRef) -> Result<()> {
+        self.store.delete(&blob.id).await?;
+        self.cache.remove(&blob.id);
+        Ok(())
+    }
+
+    pub(crate) fn cached(&self, blob: &BlobRef) -> Option<Value> {
+        self.cache.get(&blob.id).map(|v| v.value().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTask;
+    use crate::{
+        Context, ExecutionStatus, FlowRunner, GraphBuilder, InMemorySessionStorage, NextAction,
+        Session, SessionStorage, TaskResult,
+    };
+
+    #[tokio::test]
+    async fn large_values_are_offloaded_and_loaded_lazily() {
+        let store = Arc::new(InMemoryBlobStore::new());
+        let context = Context::new();
+        context.set_blob_store(store.clone(), 64);
+
+        context.set("small", "inline").await;
+        context.set("large", "x".repeat(1000)).await;
+        assert_eq!(store.blobs.len(), 1);
+
+        // Only the reference is serialized
+        let json = serde_json::to_value(&context).unwrap();
+        let stored = &json["data"]["large"];
+        let blob = BlobRef::from_value(stored).expect("expected a blob marker");
+        assert_eq!(blob.size, 1002);
+        assert_eq!(json["data"]["small"], "inline");
+
+        // A fresh context resolves the reference through the store
+        let restored: Context = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.get_sync::<String>("large"), None);
+        restored.set_blob_store(store, 64);
+        let large: String = restored.try_get("large").await.unwrap().unwrap();
+        assert_eq!(large.len(), 1000);
+        // Now cached, so sync access works too
+        assert_eq!(
+            restored.get_sync::<String>("large").map(|s| s.len()),
+            Some(1000)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_blob_is_reported() {
+        let store = Arc::new(InMemoryBlobStore::new());
+        let context = Context::new();
+        context.set_blob_store(store.clone(), 8);
+        context.set("large", "y".repeat(100)).await;
+
+        let json = serde_json::to_string(&context).unwrap();
+        let restored: Context = serde_json::from_str(&json).unwrap();
+        restored.set_blob_store(Arc::new(InMemoryBlobStore::new()), 8);
+
+        let err = restored.try_get::<String>("large").await.unwrap_err();
+        assert!(matches!(err, GraphError::ContextError(msg) if msg.contains("not found")));
+        assert_eq!(restored.get::<String>("large").await, None);
+    }
+
+    #[tokio::test]
+    async fn superseded_and_deleted_sessions_release_their_blobs() {
+        let graph = GraphBuilder::new("ocr")
+            .add_task(Arc::new(
+                MockTask::new("scan").writes("text", "x".repeat(1000)),
+            ))
+            .add_task(Arc::new(MockTask::new("rescan").then_writing(
+                [("text", Value::from("y".repeat(1000)))],
+                TaskResult::new(None, NextAction::End),
+            )))
+            .add_edge("scan", "rescan")
+            .build();
+        let store = Arc::new(InMemoryBlobStore::new());
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "scan"))
+            .await
+            .unwrap();
+        let runner =
+            FlowRunner::new(Arc::new(graph), storage.clone()).with_blob_store(store.clone(), 64);
+
+        runner.run("s1").await.unwrap();
+        let first = store
+            .blobs
+            .iter()
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(first.len(), 1);
+
+        // The overwritten value's blob is gone once the new one is saved
+        runner.run("s1").await.unwrap();
+        assert_eq!(store.blobs.len(), 1);
+        assert!(!store.blobs.contains_key(&first[0]));
+
+        runner.delete("s1").await.unwrap();
+        assert!(store.blobs.is_empty());
+        assert!(storage.get("s1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn conditional_edges_see_offloaded_values() {
+        let graph = GraphBuilder::new("claims")
+            .add_task(Arc::new(MockTask::new("classify")))
+            .add_task(Arc::new(MockTask::new("car")))
+            .add_task(Arc::new(MockTask::new("other")))
+            .add_conditional_edge(
+                "classify",
+                |ctx| {
+                    ctx.get_sync::<Value>("claim")
+                        .is_some_and(|claim| claim["kind"] == "car")
+                },
+                "car",
+                "other",
+            )
+            .build();
+        let store = Arc::new(InMemoryBlobStore::new());
+        let storage = Arc::new(InMemorySessionStorage::new());
+
+        // Offloaded by an earlier request, so not in the cache of the next one
+        let session = Session::new_from_task("s1".to_string(), "classify");
+        session.context.set_blob_store(store.clone(), 64);
+        session
+            .context
+            .set(
+                "claim",
+                serde_json::json!({"kind": "car", "notes": "z".repeat(1000)}),
+            )
+            .await;
+        storage.save(session).await.unwrap();
+
+        let runner = FlowRunner::new(Arc::new(graph), storage).with_blob_store(store, 64);
+        let result = runner.run("s1").await.unwrap();
+        assert!(
+            matches!(&result.status, ExecutionStatus::Paused { next_task_id, .. } if next_task_id == "car"),
+            "{:?}",
+            result.status
+        );
+    }
+
+    #[tokio::test]
+    async fn file_blob_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("graph-flow-blobs-{}", uuid::Uuid::new_v4()));
+        let store = FileBlobStore::new(&dir).await.unwrap();
+
+        let id = store.put(b"hello".to_vec()).await.unwrap();
+        assert_eq!(store.get(&id).await.unwrap(), Some(b"hello".to_vec()));
+
+        store.delete(&id).await.unwrap();
+        assert_eq!(store.get(&id).await.unwrap(), None);
+        assert!(store.get("../etc/passwd").await.is_err());
+
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+}
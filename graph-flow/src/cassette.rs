@@ -0,0 +1,482 @@
+//! Record and replay external calls for deterministic workflow tests.
+//!
+//! A [`Cassette`] is a JSON Lines fixture file of request/response pairs, one
+//! per line. In [`CassetteMode::Record`] every call goes to the real service and
+//! is appended to the file as it completes. In [`CassetteMode::Replay`] calls are answered from the file,
+//! matched by their (normalized) request, and never reach the network. Tests
+//! pick the mode from the `GRAPH_FLOW_CASSETTE` environment variable, so the
+//! same test records fixtures locally and replays them in CI.
+//!
+//! - [`CassetteLlmClient`] wraps an [`LlmClient`]
+//! - [`Cassette::call`] wraps any other async call, e.g. an HTTP request,
+//!   given a serializable description of the request
+//!
+//! Identical requests are answered in the order they were recorded; once the
+//! recorded answers run out, the last one is repeated.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use graph_flow::{Cassette, CassetteLlmClient, LlmClient, LlmRequest};
+//! use std::sync::Arc;
+//!
+//! # async fn example(real_client: Arc<dyn LlmClient>) -> graph_flow::Result<()> {
+//! // GRAPH_FLOW_CASSETTE=record cargo test   -> calls the model, writes the file
+//! // cargo test                              -> replays the file offline
+//! let cassette = Arc::new(Cassette::from_env("tests/cassettes/classifier.jsonl").await?);
+//! let llm = CassetteLlmClient::new(cassette.clone(), real_client);
+//! let reply = llm.complete(LlmRequest::new("I crashed my car")).await?;
+//!
+//! // Other calls are recorded under their own kind
+//! let status: u16 = cassette
+//!     .call("http", &("GET", "https://example.com/health"), || async { Ok(200) })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{GraphError, Result};
+use crate::llm::{LlmClient, LlmRequest};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+
+/// Environment variable read by [`CassetteMode::from_env`].
+pub const CASSETTE_MODE_ENV: &str = "GRAPH_FLOW_CASSETTE";
+
+/// Rewrites a request before it is recorded or matched, e.g. to drop timestamps.
+pub type RequestNormalizer = Arc<dyn Fn(&mut Value) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Call the real service and write every exchange to the cassette
+    Record,
+    /// Answer from the cassette; unrecorded requests fail
+    Replay,
+    /// Call the real service without recording
+    Passthrough,
+}
+
+impl CassetteMode {
+    /// `record`, `replay` or `off` from `GRAPH_FLOW_CASSETTE`; replay when unset.
+    pub fn from_env() -> Self {
+        match std::env::var(CASSETTE_MODE_ENV)
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "record" => CassetteMode::Record,
+            "off" | "passthrough" => CassetteMode::Passthrough,
+            _ => CassetteMode::Replay,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    kind: String,
+    request: Value,
+    response: Value,
+}
+
+/// A fixture file of recorded request/response pairs.
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    /// Recorded interactions, when replaying
+    interactions: Vec<Interaction>,
+    /// Open cassette file, when recording; held while appending so concurrent
+    /// calls write whole lines
+    writer: tokio::sync::Mutex<Option<tokio::fs::File>>,
+    /// Replay position per distinct request
+    cursors: Mutex<HashMap<String, usize>>,
+    normalizer: Option<RequestNormalizer>,
+}
+
+impl Cassette {
+    /// Open a cassette. Replay mode loads the file; record mode truncates it
+    /// and appends each call as it is made.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::StorageError`] if replaying and the file is
+    /// missing or not a valid cassette, or if recording and the file cannot
+    /// be created.
+    pub async fn open(path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut interactions = Vec::new();
+        let mut writer = None;
+        match mode {
+            CassetteMode::Replay => {
+                let data = tokio::fs::read_to_string(&path).await.map_err(|e| {
+                    GraphError::StorageError(format!(
+                        "Cannot read cassette {}: {e}. Record it with {CASSETTE_MODE_ENV}=record",
+                        path.display()
+                    ))
+                })?;
+                for (number, line) in data.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    interactions.push(serde_json::from_str(line).map_err(|e| {
+                        GraphError::StorageError(format!(
+                            "Invalid cassette {} at line {}: {e}",
+                            path.display(),
+                            number + 1
+                        ))
+                    })?);
+                }
+            }
+            CassetteMode::Record => {
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                        GraphError::StorageError(format!("Cannot create {}: {e}", parent.display()))
+                    })?;
+                }
+                let file = tokio::fs::File::create(&path).await.map_err(|e| {
+                    GraphError::StorageError(format!(
+                        "Cannot create cassette {}: {e}",
+                        path.display()
+                    ))
+                })?;
+                writer = Some(file);
+            }
+            CassetteMode::Passthrough => {}
+        }
+
+        Ok(Self {
+            path,
+            mode,
+            interactions,
+            writer: tokio::sync::Mutex::new(writer),
+            cursors: Mutex::new(HashMap::new()),
+            normalizer: None,
+        })
+    }
+
+    /// Open a cassette in the mode given by `GRAPH_FLOW_CASSETTE`.
+    pub async fn from_env(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, CassetteMode::from_env()).await
+    }
+
+    pub fn with_normalizer<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&mut Value) + Send + Sync + 'static,
+    {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run `call` through the cassette.
+    ///
+    /// `kind` separates different services in one file (e.g. `"llm"`,
+    /// `"pubmed"`), and `request` identifies the call.
+    ///
+    /// # Errors
+    ///
+    /// In replay mode, returns [`GraphError::TaskExecutionFailed`] if no
+    /// matching request was recorded. Otherwise returns errors from `call` or
+    /// from writing the cassette file.
+    pub async fn call<Req, Resp, F, Fut>(&self, kind: &str, request: &Req, call: F) -> Result<Resp>
+    where
+        Req: Serialize + ?Sized,
+        Resp: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Resp>>,
+    {
+        let request = self.normalize(request)?;
+        match self.mode {
+            CassetteMode::Passthrough => call().await,
+            CassetteMode::Replay => {
+                let response = self.replay(kind, &request)?;
+                serde_json::from_value(response).map_err(|e| {
+                    GraphError::StorageError(format!(
+                        "Recorded {kind} response in {} doesn't match the expected type: {e}",
+                        self.path.display()
+                    ))
+                })
+            }
+            CassetteMode::Record => {
+                let response = call().await?;
+                let recorded = serde_json::to_value(&response).map_err(|e| {
+                    GraphError::StorageError(format!("Cannot record {kind} response: {e}"))
+                })?;
+                self.append(Interaction {
+                    kind: kind.to_string(),
+                    request,
+                    response: recorded,
+                })
+                .await?;
+                Ok(response)
+            }
+        }
+    }
+
+    /// Append one interaction to the cassette file as a single line.
+    async fn append(&self, interaction: Interaction) -> Result<()> {
+        let mut line = serde_json::to_vec(&interaction)
+            .map_err(|e| GraphError::StorageError(format!("Cannot serialize cassette: {e}")))?;
+        line.push(b'\n');
+
+        let write_error = |e: std::io::Error| {
+            GraphError::StorageError(format!(
+                "Cannot write cassette {}: {e}",
+                self.path.display()
+            ))
+        };
+        let mut writer = self.writer.lock().await;
+        let file = writer.as_mut().ok_or_else(|| {
+            GraphError::StorageError(format!(
+                "Cassette {} is not open for recording",
+                self.path.display()
+            ))
+        })?;
+        file.write_all(&line).await.map_err(write_error)?;
+        file.flush().await.map_err(write_error)
+    }
+
+    fn normalize<Req: Serialize + ?Sized>(&self, request: &Req) -> Result<Value> {
+        let mut value = serde_json::to_value(request)
+            .map_err(|e| GraphError::StorageError(format!("Cannot serialize request: {e}")))?;
+        if let Some(normalizer) = &self.normalizer {
+            normalizer(&mut value);
+        }
+        Ok(value)
+    }
+
+    fn replay(&self, kind: &str, request: &Value) -> Result<Value> {
+        let matches: Vec<&Interaction> = self
+            .interactions
+            .iter()
+            .filter(|interaction| interaction.kind == kind && &interaction.request == request)
+            .collect();
+        let Some(last) = matches.last() else {
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "No recorded {kind} response in {} for request {request}. \
+                 Re-record with {CASSETTE_MODE_ENV}=record",
+                self.path.display()
+            )));
+        };
+
+        let mut cursors = self.cursors.lock().unwrap();
+        let cursor = cursors.entry(format!("{kind}:{request}")).or_default();
+        let interaction = matches.get(*cursor).unwrap_or(last);
+        *cursor += 1;
+        Ok(interaction.response.clone())
+    }
+}
+
+/// [`LlmClient`] that records to, or replays from, a [`Cassette`].
+pub struct CassetteLlmClient {
+    cassette: Arc<Cassette>,
+    inner: Option<Arc<dyn LlmClient>>,
+}
+
+impl CassetteLlmClient {
+    /// Record calls to `inner`, or replay them, depending on the cassette's mode.
+    pub fn new(cassette: Arc<Cassette>, inner: Arc<dyn LlmClient>) -> Self {
+        Self {
+            cassette,
+            inner: Some(inner),
+        }
+    }
+
+    /// Replay without a real client, e.g. in CI where no API key is available.
+    /// Fails if the cassette is not in replay mode.
+    pub fn replay_only(cassette: Arc<Cassette>) -> Self {
+        Self {
+            cassette,
+            inner: None,
+        }
+    }
+}
+
+/// Request as matched in the cassette: message timestamps and ids differ on every run.
+fn llm_request_key(request: &LlmRequest) -> Result<Value> {
+    let mut key = serde_json::to_value(request)
+        .map_err(|e| GraphError::StorageError(format!("Cannot serialize request: {e}")))?;
+    let Some(map) = key.as_object_mut() else {
+        return Ok(key);
+    };
+    let history = map
+        .get_mut("history")
+        .and_then(Value::as_array_mut)
+        .map(|history| history.as_mut_slice())
+        .unwrap_or_default();
+    for message in history {
+        strip_volatile(message);
+    }
+    if let Some(prompt) = map.get_mut("prompt") {
+        strip_volatile(prompt);
+    }
+    Ok(key)
+}
+
+fn strip_volatile(message: &mut Value) {
+    if let Some(message) = message.as_object_mut() {
+        message.remove("timestamp");
+        message.remove("id");
+    }
+}
+
+#[async_trait]
+impl LlmClient for CassetteLlmClient {
+    async fn complete(&self, request: LlmRequest) -> Result<String> {
+        let key = llm_request_key(&request)?;
+        self.cassette
+            .call("llm", &key, || async {
+                match &self.inner {
+                    Some(inner) => inner.complete(request.clone()).await,
+                    None => Err(GraphError::TaskExecutionFailed(format!(
+                        "Cassette {} needs a real LLM client in {:?} mode",
+                        self.cassette.path().display(),
+                        self.cassette.mode()
+                    ))),
+                }
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockLlmClient;
+
+    fn temp_cassette_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("graph_flow_cassette_{}", uuid::Uuid::new_v4()))
+            .join("cassette.jsonl")
+    }
+
+    #[tokio::test]
+    async fn records_then_replays_offline() {
+        let path = temp_cassette_path();
+        let mock = Arc::new(MockLlmClient::new().with_responses(["one", "two"]));
+
+        let recorder = Arc::new(Cassette::open(&path, CassetteMode::Record).await.unwrap());
+        let llm = CassetteLlmClient::new(recorder.clone(), mock.clone());
+        assert_eq!(llm.complete(LlmRequest::new("hi")).await.unwrap(), "one");
+        assert_eq!(llm.complete(LlmRequest::new("hi")).await.unwrap(), "two");
+        let pages: Vec<String> = recorder
+            .call("http", "GET /pages", || async { Ok(vec!["a".to_string()]) })
+            .await
+            .unwrap();
+        assert_eq!(pages, vec!["a"]);
+
+        let replayer = Arc::new(Cassette::open(&path, CassetteMode::Replay).await.unwrap());
+        let llm = CassetteLlmClient::replay_only(replayer.clone());
+        // Same request answered in recorded order, then the last answer repeats
+        assert_eq!(llm.complete(LlmRequest::new("hi")).await.unwrap(), "one");
+        assert_eq!(llm.complete(LlmRequest::new("hi")).await.unwrap(), "two");
+        assert_eq!(llm.complete(LlmRequest::new("hi")).await.unwrap(), "two");
+        let pages: Vec<String> = replayer
+            .call("http", "GET /pages", || async {
+                panic!("replay must not call through")
+            })
+            .await
+            .unwrap();
+        assert_eq!(pages, vec!["a"]);
+
+        let err = llm.complete(LlmRequest::new("bye")).await.unwrap_err();
+        assert!(matches!(err, GraphError::TaskExecutionFailed(msg) if msg.contains("bye")));
+        assert_eq!(mock.requests().len(), 2);
+
+        tokio::fs::remove_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn normalizer_ignores_volatile_fields() {
+        let path = temp_cassette_path();
+        let strip_date = |value: &mut Value| {
+            if let Some(map) = value.as_object_mut() {
+                map.remove("date");
+            }
+        };
+
+        let recorder = Cassette::open(&path, CassetteMode::Record)
+            .await
+            .unwrap()
+            .with_normalizer(strip_date);
+        let request = serde_json::json!({ "query": "NPH", "date": "2026-01-01" });
+        let count: u32 = recorder
+            .call("pubmed", &request, || async { Ok(7) })
+            .await
+            .unwrap();
+        assert_eq!(count, 7);
+
+        let replayer = Cassette::open(&path, CassetteMode::Replay)
+            .await
+            .unwrap()
+            .with_normalizer(strip_date);
+        let request = serde_json::json!({ "query": "NPH", "date": "2026-10-18" });
+        let count: u32 = replayer
+            .call("pubmed", &request, || async { Ok(0) })
+            .await
+            .unwrap();
+        assert_eq!(count, 7);
+
+        tokio::fs::remove_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_recordings_append_whole_lines() {
+        let path = temp_cassette_path();
+        let recorder = Arc::new(Cassette::open(&path, CassetteMode::Record).await.unwrap());
+
+        let calls = (0..20).map(|i| {
+            let recorder = recorder.clone();
+            tokio::spawn(async move {
+                recorder
+                    .call("http", &format!("GET /items/{i}"), || async move { Ok(i) })
+                    .await
+                    .unwrap()
+            })
+        });
+        for call in calls {
+            call.await.unwrap();
+        }
+
+        let data = tokio::fs::read_to_string(&path).await.unwrap();
+        assert_eq!(data.lines().count(), 20);
+        let replayer = Cassette::open(&path, CassetteMode::Replay).await.unwrap();
+        for i in 0..20 {
+            let value: i32 = replayer
+                .call("http", &format!("GET /items/{i}"), || async { Ok(-1) })
+                .await
+                .unwrap();
+            assert_eq!(value, i);
+        }
+
+        tokio::fs::remove_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_requires_existing_cassette() {
+        let err = Cassette::open(temp_cassette_path(), CassetteMode::Replay)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, GraphError::StorageError(msg) if msg.contains(CASSETTE_MODE_ENV)));
+    }
+}
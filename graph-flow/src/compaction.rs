@@ -0,0 +1,223 @@
+//! Token budgets and summarization for chat history.
+//!
+//! [`ChatHistory`] caps history by message count only, which says little about
+//! whether it fits an LLM context window. This module adds:
+//!
+//! - [`TokenEstimator`]: counts tokens for a message. Plug in a real tokenizer,
+//!   or use [`ApproxTokenEstimator`].
+//! - Budgeted retrieval with [`ChatHistory::window`] and
+//!   [`Context::get_messages_within_budget`], which keep the newest messages that
+//!   fit a token budget.
+//! - Compaction with [`Context::compact_chat_history`], which replaces older
+//!   messages with a single summary produced by a [`ChatSummarizer`].
+//!
+//! Pinned messages (see [`SerializableMessage::pin`]) are never evicted by
+//! pruning, windowing or compaction.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{ApproxTokenEstimator, CompactionPolicy, Context, SerializableMessage};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let context = Context::new();
+//! context
+//!     .add_pinned_system_message("You are a claims assistant.".to_string())
+//!     .await;
+//! for i in 0..50 {
+//!     context.add_user_message(format!("Message number {i} with some words")).await;
+//! }
+//!
+//! // Only what fits in 100 tokens, always including the pinned instructions
+//! let window = context
+//!     .get_messages_within_budget(100, &ApproxTokenEstimator::default())
+//!     .await;
+//! assert!(window[0].pinned);
+//!
+//! // Fold older messages into a summary once the history exceeds 200 tokens
+//! let policy = CompactionPolicy::new(200, 80);
+//! let summarize = |messages: &[SerializableMessage]| {
+//!     format!("{} earlier messages about the claim", messages.len())
+//! };
+//! assert!(context.compact_chat_history(&policy, &summarize).await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::context::SerializableMessage;
+use crate::error::Result;
+
+#[cfg(doc)]
+use crate::context::{ChatHistory, Context};
+
+/// Estimates how many tokens a message takes up in a model's context window.
+///
+/// Any `Fn(&str) -> usize` closure is an estimator that counts the message
+/// content, so an exact tokenizer can be plugged in directly.
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, message: &SerializableMessage) -> usize;
+}
+
+impl<F> TokenEstimator for F
+where
+    F: Fn(&str) -> usize + Send + Sync,
+{
+    fn estimate(&self, message: &SerializableMessage) -> usize {
+        self(&message.content)
+    }
+}
+
+/// Tokenizer-free estimate based on character count.
+///
+/// Roughly four characters per token holds for English text with most
+/// tokenizers; each message also pays a fixed overhead for role markers.
+#[derive(Debug, Clone, Copy)]
+pub struct ApproxTokenEstimator {
+    pub chars_per_token: usize,
+    pub tokens_per_message: usize,
+}
+
+impl Default for ApproxTokenEstimator {
+    fn default() -> Self {
+        Self {
+            chars_per_token: 4,
+            tokens_per_message: 4,
+        }
+    }
+}
+
+impl TokenEstimator for ApproxTokenEstimator {
+    fn estimate(&self, message: &SerializableMessage) -> usize {
+        // Tool call arguments are sent to the model but aren't part of `content`
+        let chars = message.content.chars().count()
+            + message
+                .tool_calls()
+                .map(|call| call.name.len() + call.arguments.to_string().len())
+                .sum::<usize>();
+        chars.div_ceil(self.chars_per_token.max(1)) + self.tokens_per_message
+    }
+}
+
+/// Produces the summary that replaces compacted messages.
+///
+/// Implemented for `Fn(&[SerializableMessage]) -> String` closures, for custom
+/// summaries that don't need an LLM, and (with the `rig` feature) by
+/// [`PromptSummarizer`] for any rig agent.
+#[async_trait]
+pub trait ChatSummarizer: Send + Sync {
+    /// Summarize `messages`, oldest first. A previous summary may be among them.
+    async fn summarize(&self, messages: &[SerializableMessage]) -> Result<String>;
+}
+
+#[async_trait]
+impl<F> ChatSummarizer for F
+where
+    F: Fn(&[SerializableMessage]) -> String + Send + Sync,
+{
+    async fn summarize(&self, messages: &[SerializableMessage]) -> Result<String> {
+        Ok(self(messages))
+    }
+}
+
+/// When and how far to compact a chat history.
+#[derive(Clone)]
+pub struct CompactionPolicy {
+    /// Compact once the history is estimated above this many tokens
+    pub trigger_tokens: usize,
+    /// Keep the newest messages up to this many tokens (pinned messages included)
+    pub retain_tokens: usize,
+    pub estimator: Arc<dyn TokenEstimator>,
+    /// Text placed before the summary in the message that replaces old messages
+    pub summary_prefix: String,
+}
+
+impl std::fmt::Debug for CompactionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompactionPolicy")
+            .field("trigger_tokens", &self.trigger_tokens)
+            .field("retain_tokens", &self.retain_tokens)
+            .field("summary_prefix", &self.summary_prefix)
+            .finish()
+    }
+}
+
+impl CompactionPolicy {
+    /// Compact above `trigger_tokens`, keeping about `retain_tokens` of recent history.
+    pub fn new(trigger_tokens: usize, retain_tokens: usize) -> Self {
+        Self {
+            trigger_tokens,
+            retain_tokens,
+            estimator: Arc::new(ApproxTokenEstimator::default()),
+            summary_prefix: "Summary of the earlier conversation:".to_string(),
+        }
+    }
+
+    pub fn with_estimator(mut self, estimator: Arc<dyn TokenEstimator>) -> Self {
+        self.estimator = estimator;
+        self
+    }
+
+    pub fn with_summary_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.summary_prefix = prefix.into();
+        self
+    }
+}
+
+/// Summarizes chat history by prompting a rig agent (or anything implementing
+/// [`rig::completion::Prompt`]).
+#[cfg(feature = "rig")]
+pub struct PromptSummarizer<P> {
+    agent: P,
+    instructions: String,
+}
+
+#[cfg(feature = "rig")]
+impl<P: rig::completion::Prompt> PromptSummarizer<P> {
+    pub fn new(agent: P) -> Self {
+        Self {
+            agent,
+            instructions: "Summarize the following conversation so it can replace it as \
+                context for continuing the conversation. Keep names, numbers, decisions \
+                and open questions."
+                .to_string(),
+        }
+    }
+
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+}
+
+#[cfg(feature = "rig")]
+#[async_trait]
+impl<P: rig::completion::Prompt> ChatSummarizer for PromptSummarizer<P> {
+    async fn summarize(&self, messages: &[SerializableMessage]) -> Result<String> {
+        use crate::context::MessageRole;
+
+        let transcript = messages
+            .iter()
+            .map(|message| {
+                let role = match message.role {
+                    MessageRole::User => "User",
+                    MessageRole::Assistant => "Assistant",
+                    MessageRole::System => "System",
+                    MessageRole::Tool => "Tool",
+                };
+                format!("{role}: {}", message.content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.agent
+            .prompt(format!("{}\n\n{transcript}", self.instructions))
+            .await
+            .map_err(|e| {
+                crate::GraphError::TaskExecutionFailed(format!("Summarization failed: {e}"))
+            })
+    }
+}
@@ -0,0 +1,72 @@
+//! Saga-style compensation of completed steps.
+//!
+//! A task with side effects outside the context (a claim record created, a
+//! notification sent) can undo them in [`Task::compensate`](crate::Task::compensate),
+//! which does nothing by default. Every successful task run is appended to
+//! [`Session::compensable_steps`](crate::Session::compensable_steps).
+//!
+//! [`Graph::compensate_session`](crate::Graph::compensate_session) calls the
+//! compensations in reverse order and writes the outcome to the context under
+//! [`COMPENSATION_KEY`]. A [`FlowRunner`](crate::FlowRunner) created
+//! [`with_compensation`](crate::FlowRunner::with_compensation) does so when a step
+//! fails, and [`FlowRunner::abort`](crate::FlowRunner::abort) does so on request.
+//! A compensated session does not run again.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use async_trait::async_trait;
+//! use graph_flow::{Context, NextAction, Task, TaskResult};
+//!
+//! struct CreateClaimRecord;
+//!
+//! #[async_trait]
+//! impl Task for CreateClaimRecord {
+//!     fn id(&self) -> &str {
+//!         "create_claim_record"
+//!     }
+//!
+//!     async fn run(&self, context: Context) -> graph_flow::Result<TaskResult> {
+//!         context.set("claim_record_id", "CLM-1").await;
+//!         Ok(TaskResult::new(None, NextAction::Continue))
+//!     }
+//!
+//!     async fn compensate(&self, context: Context) -> graph_flow::Result<()> {
+//!         let _record: Option<String> = context.get("claim_record_id").await;
+//!         // delete the record in the claims system
+//!         Ok(())
+//!     }
+//! }
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Context key holding the [`Compensation`] of a rolled back session.
+pub const COMPENSATION_KEY: &str = "graph_flow.compensation";
+
+/// Outcome of compensating a session, written under [`COMPENSATION_KEY`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Compensation {
+    /// Why the session was rolled back: the step's error, or the abort reason
+    pub reason: String,
+    /// One record per compensated step, in the order they ran (newest step first)
+    pub steps: Vec<CompensationRecord>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl Compensation {
+    /// Whether every compensation succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.steps.iter().all(|step| step.error.is_none())
+    }
+}
+
+/// Outcome of one [`Task::compensate`](crate::Task::compensate) call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompensationRecord {
+    pub task_id: String,
+    /// Error of a failed compensation; `None` if it succeeded
+    pub error: Option<String>,
+    pub compensated_at: DateTime<Utc>,
+}
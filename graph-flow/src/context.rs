@@ -99,10 +99,17 @@
 //! ```
 
 use chrono::{DateTime, Utc};
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::{Arc, RwLock};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::blob_store::{BlobOffload, BlobRef, BlobStore};
+use crate::compaction::{ChatSummarizer, CompactionPolicy, TokenEstimator};
+use crate::error::GraphError;
+use crate::message::ContentPart;
 
 #[cfg(feature = "rig")]
 use rig::completion::Message;
@@ -111,6 +118,7 @@ use rig::completion::Message;
 ///
 /// Used in chat history to distinguish between different types of messages.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub enum MessageRole {
     /// Message from a user/human
     User,
@@ -118,6 +126,8 @@ pub enum MessageRole {
     Assistant,
     /// System message (instructions, status updates, etc.)
     System,
+    /// Result of a tool call, see [`ToolResult`](crate::ToolResult)
+    Tool,
 }
 
 /// A serializable message that can be converted to/from rig::completion::Message.
@@ -140,6 +150,7 @@ pub enum MessageRole {
 /// assert_eq!(user_msg.content, "Hello!");
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct SerializableMessage {
     /// The role of the message sender
     pub role: MessageRole,
@@ -147,6 +158,25 @@ pub struct SerializableMessage {
     pub content: String,
     /// When the message was created
     pub timestamp: DateTime<Utc>,
+    /// Pinned messages are never evicted by pruning, windowing or compaction
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Provider-assigned message id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Name of the participant, e.g. to tell several users or agents apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Id of the task that produced the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// Application-defined data attached to the message
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, Value>,
+    /// Structured content, in order. Empty for plain text messages, whose text is
+    /// in `content`; see [`ContentPart`](crate::ContentPart).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<ContentPart>,
 }
 
 impl SerializableMessage {
@@ -158,6 +188,12 @@ impl SerializableMessage {
             role,
             content,
             timestamp: Utc::now(),
+            pinned: false,
+            id: None,
+            name: None,
+            task_id: None,
+            metadata: serde_json::Map::new(),
+            parts: Vec::new(),
         }
     }
 
@@ -199,6 +235,21 @@ impl SerializableMessage {
     pub fn system(content: String) -> Self {
         Self::new(MessageRole::System, content)
     }
+
+    /// Mark the message as pinned so it is never evicted from the history.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::SerializableMessage;
+    ///
+    /// let msg = SerializableMessage::system("You are a claims assistant".to_string()).pin();
+    /// assert!(msg.pinned);
+    /// ```
+    pub fn pin(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
 }
 
 /// Container for managing chat history with serialization support.
@@ -220,16 +271,21 @@ impl SerializableMessage {
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ChatHistory {
-    messages: Vec<SerializableMessage>,
+    /// Shared copy-on-write, so cloning a history (e.g. for a fork) is cheap
+    messages: Arc<Vec<SerializableMessage>>,
     max_messages: Option<usize>,
+    /// Bumped whenever messages are removed or replaced, i.e. on anything but an append
+    #[serde(skip)]
+    revision: u64,
 }
 
 impl ChatHistory {
     /// Create a new empty chat history with a default limit of 1000 messages.
     pub fn new() -> Self {
         Self {
-            messages: Vec::new(),
+            messages: Arc::default(),
             max_messages: Some(1000), // Default limit to prevent unbounded growth
+            revision: 0,
         }
     }
 
@@ -254,8 +310,9 @@ impl ChatHistory {
     /// ```
     pub fn with_max_messages(max: usize) -> Self {
         Self {
-            messages: Vec::new(),
+            messages: Arc::default(),
             max_messages: Some(max),
+            revision: 0,
         }
     }
 
@@ -274,20 +331,39 @@ impl ChatHistory {
         self.add_message(SerializableMessage::system(content));
     }
 
+    /// Add a system message that is never evicted from the history.
+    pub fn add_pinned_system_message(&mut self, content: String) {
+        self.add_message(SerializableMessage::system(content).pin());
+    }
+
     /// Add a message to the chat history, respecting max_messages limit.
-    fn add_message(&mut self, message: SerializableMessage) {
-        self.messages.push(message);
+    pub fn add_message(&mut self, message: SerializableMessage) {
+        Arc::make_mut(&mut self.messages).push(message);
+        self.prune();
+    }
 
-        if let Some(max) = self.max_messages {
-            if self.messages.len() > max {
-                self.messages.drain(0..(self.messages.len() - max));
-            }
+    /// Drop the oldest unpinned messages until the history is within max_messages.
+    fn prune(&mut self) {
+        if let Some(max) = self.max_messages
+            && self.messages.len() > max
+        {
+            let mut excess = self.messages.len() - max;
+            Arc::make_mut(&mut self.messages).retain(|message| {
+                if excess > 0 && !message.pinned {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+            self.revision += 1;
         }
     }
 
     /// Clear all messages from the chat history.
     pub fn clear(&mut self) {
-        self.messages.clear();
+        self.messages = Arc::default();
+        self.revision += 1;
     }
 
     /// Get the number of messages in the chat history.
@@ -332,12 +408,288 @@ impl ChatHistory {
         };
         &self.messages[start..]
     }
+
+    /// Estimated token count of the whole history.
+    pub fn estimate_tokens(&self, estimator: &dyn TokenEstimator) -> usize {
+        self.messages.iter().map(|m| estimator.estimate(m)).sum()
+    }
+
+    /// Get the newest messages that fit within `budget` tokens, oldest first.
+    ///
+    /// Pinned messages are always included and count against the budget; the
+    /// remainder is filled with the most recent unpinned messages, stopping at
+    /// the first one that doesn't fit so the window has no gaps.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{ApproxTokenEstimator, ChatHistory, TokenEstimator};
+    ///
+    /// let mut history = ChatHistory::new();
+    /// history.add_pinned_system_message("Be brief".to_string());
+    /// for i in 0..100 {
+    ///     history.add_user_message(format!("Message {}", i));
+    /// }
+    ///
+    /// let estimator = ApproxTokenEstimator::default();
+    /// let window = history.window(50, &estimator);
+    /// assert_eq!(window[0].content, "Be brief");
+    /// assert_eq!(window.last().unwrap().content, "Message 99");
+    /// assert!(window.iter().map(|m| estimator.estimate(m)).sum::<usize>() <= 50);
+    /// ```
+    pub fn window(
+        &self,
+        budget: usize,
+        estimator: &dyn TokenEstimator,
+    ) -> Vec<SerializableMessage> {
+        let keep = self.window_indices(budget, estimator);
+        self.messages
+            .iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(message, _)| message.clone())
+            .collect()
+    }
+
+    fn window_indices(&self, budget: usize, estimator: &dyn TokenEstimator) -> Vec<bool> {
+        let mut keep: Vec<bool> = self.messages.iter().map(|m| m.pinned).collect();
+        let pinned_cost: usize = self
+            .messages
+            .iter()
+            .filter(|m| m.pinned)
+            .map(|m| estimator.estimate(m))
+            .sum();
+
+        let mut remaining = budget.saturating_sub(pinned_cost);
+        for (index, message) in self.messages.iter().enumerate().rev() {
+            if message.pinned {
+                continue;
+            }
+            let cost = estimator.estimate(message);
+            if cost > remaining {
+                break;
+            }
+            remaining -= cost;
+            keep[index] = true;
+        }
+        keep
+    }
+
+    /// Indices of the messages compaction would replace, if the policy triggers.
+    fn compaction_candidates(&self, policy: &CompactionPolicy) -> Option<Vec<usize>> {
+        let estimator = policy.estimator.as_ref();
+        if self.estimate_tokens(estimator) <= policy.trigger_tokens {
+            return None;
+        }
+        let evicted: Vec<usize> = self
+            .window_indices(policy.retain_tokens, estimator)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, keep)| (!keep).then_some(index))
+            .collect();
+        (!evicted.is_empty()).then_some(evicted)
+    }
+
+    /// Replace the messages at `evicted` (ascending) with `summary`, placed where
+    /// the first of them was.
+    fn replace_with_summary(&mut self, evicted: &[usize], summary: SerializableMessage) {
+        let mut evicted = evicted.iter().copied().peekable();
+        let mut summary = Some(summary);
+        let mut messages = Vec::with_capacity(self.messages.len());
+        for (index, message) in self.messages.iter().enumerate() {
+            if evicted.peek() == Some(&index) {
+                evicted.next();
+                if let Some(summary) = summary.take() {
+                    messages.push(summary);
+                }
+            } else {
+                messages.push(message.clone());
+            }
+        }
+        self.messages = Arc::new(messages);
+        self.revision += 1;
+    }
+}
+
+/// A typed handle for a context entry.
+///
+/// A `ContextKey<T>` ties a key name to the type stored under it, so that
+/// reads and writes through [`Context::get_key`] and [`Context::set_key`]
+/// are checked by the compiler. Keys are usually declared once as constants
+/// and shared by every task that touches the entry.
+///
+/// # Examples
+///
+/// ```rust
+/// use graph_flow::{Context, ContextKey};
+///
+/// const USER_NAME: ContextKey<String> = ContextKey::new("user_name");
+/// const VISITS: ContextKey<u32> = ContextKey::new("visits");
+///
+/// # #[tokio::main]
+/// # async fn main() -> graph_flow::Result<()> {
+/// let context = Context::new();
+/// context.set_key(USER_NAME, "Alice".to_string()).await;
+/// context.set_key(VISITS, 3).await;
+///
+/// let name = context.get_key(USER_NAME).await?;
+/// assert_eq!(name, Some("Alice".to_string()));
+///
+/// // Typed keys share storage with string keys
+/// let visits: Option<u32> = context.get("visits").await;
+/// assert_eq!(visits, Some(3));
+/// # Ok(())
+/// # }
+/// ```
+pub struct ContextKey<T> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ContextKey<T> {
+    /// Declare a key with the given name.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The name under which the value is stored in the context.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for ContextKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ContextKey<T> {}
+
+impl<T> std::fmt::Debug for ContextKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextKey")
+            .field("name", &self.name)
+            .field("type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// Custom reducer used by [`MergeStrategy::Custom`].
+///
+/// Called once for every key the child changed with the key, the parent's
+/// current value and the child's value (`None` means the key is absent).
+/// The returned value is stored in the parent; `None` removes the key.
+pub type MergeReducer =
+    Arc<dyn Fn(&str, Option<&Value>, Option<&Value>) -> Option<Value> + Send + Sync>;
+
+/// How [`Context::merge`] brings a child's changes back into its parent.
+///
+/// Only keys the child set or removed after [`Context::fork`] are considered;
+/// keys it merely inherited from the parent are left alone.
+#[derive(Clone)]
+pub enum MergeStrategy {
+    /// Child changes overwrite whatever the parent holds.
+    LastWriterWins,
+    /// Fail without touching the parent if a key changed by the child was also
+    /// changed in the parent since the fork (to a different value).
+    ErrorOnConflict,
+    /// Store child writes under `<prefix>.<key>` instead of `<key>`.
+    ///
+    /// Removals made by the child are not propagated.
+    Namespaced(String),
+    /// Resolve every changed key with a [`MergeReducer`].
+    Custom(MergeReducer),
+}
+
+impl std::fmt::Debug for MergeStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LastWriterWins => write!(f, "LastWriterWins"),
+            Self::ErrorOnConflict => write!(f, "ErrorOnConflict"),
+            Self::Namespaced(prefix) => f.debug_tuple("Namespaced").field(prefix).finish(),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Marker returned by [`Context::checkpoint`].
+///
+/// Pass it to [`Context::diff_since`] to see what changed after it was taken.
+/// Checkpoints are only meaningful for the context (or its clones) that
+/// created them and are not persisted with the context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    version: u64,
+    messages_appended: u64,
+}
+
+/// Changes made to a context since a [`Checkpoint`].
+///
+/// # Examples
+///
+/// ```rust
+/// use graph_flow::Context;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let context = Context::new();
+/// context.set("kept", 1).await;
+/// context.set("dropped", 2).await;
+///
+/// let checkpoint = context.checkpoint();
+/// context.set("kept", 10).await;
+/// context.remove("dropped").await;
+/// context.add_user_message("Hi".to_string()).await;
+///
+/// let diff = context.diff_since(&checkpoint);
+/// assert_eq!(diff.set.get("kept"), Some(&serde_json::json!(10)));
+/// assert!(diff.removed.contains("dropped"));
+/// assert_eq!(diff.messages.len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextDiff {
+    /// Keys written since the checkpoint, with their current values
+    pub set: BTreeMap<String, Value>,
+    /// Keys removed since the checkpoint
+    pub removed: BTreeSet<String>,
+    /// Chat messages appended since the checkpoint (pruned messages excluded)
+    pub messages: Vec<SerializableMessage>,
+}
+
+impl ContextDiff {
+    /// Whether nothing changed since the checkpoint.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.removed.is_empty() && self.messages.is_empty()
+    }
+}
+
+/// Records the version at which each key was last touched.
+#[derive(Debug, Default)]
+struct ChangeTracker {
+    version: u64,
+    touched: HashMap<String, u64>,
+    messages_appended: u64,
+}
+
+/// The parent a forked context reads through to, captured by [`Context::fork`].
+#[derive(Debug)]
+struct ForkBase {
+    parent: Context,
+    /// Parent's change version at the fork, to find keys it changed since
+    parent_version: u64,
+    chat_len: usize,
+    chat_revision: u64,
 }
 
 /// Helper struct for serializing/deserializing Context
 #[derive(Serialize, Deserialize)]
 struct ContextData {
-    data: std::collections::HashMap<String, Value>,
+    data: HashMap<String, Value>,
     chat_history: ChatHistory,
 }
 
@@ -389,8 +741,16 @@ struct ContextData {
 /// ```
 #[derive(Clone, Debug)]
 pub struct Context {
+    /// Values set in this context; for a fork, only its local writes
     data: Arc<DashMap<String, Value>>,
+    /// Keys a fork removed while its parent still has them
+    removed: Arc<DashSet<String>>,
     chat_history: Arc<RwLock<ChatHistory>>,
+    fork_base: Option<Arc<ForkBase>>,
+    changes: Arc<Mutex<ChangeTracker>>,
+    blobs: Arc<RwLock<Option<BlobOffload>>>,
+    /// Blobs whose values were overwritten or removed, deleted once that is saved
+    superseded: Arc<Mutex<Vec<BlobRef>>>,
 }
 
 impl Context {
@@ -398,7 +758,12 @@ impl Context {
     pub fn new() -> Self {
         Self {
             data: Arc::new(DashMap::new()),
+            removed: Arc::new(DashSet::new()),
             chat_history: Arc::new(RwLock::new(ChatHistory::new())),
+            fork_base: None,
+            changes: Arc::new(Mutex::new(ChangeTracker::default())),
+            blobs: Arc::new(RwLock::new(None)),
+            superseded: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -426,7 +791,12 @@ impl Context {
     pub fn with_max_chat_messages(max: usize) -> Self {
         Self {
             data: Arc::new(DashMap::new()),
+            removed: Arc::new(DashSet::new()),
             chat_history: Arc::new(RwLock::new(ChatHistory::with_max_messages(max))),
+            fork_base: None,
+            changes: Arc::new(Mutex::new(ChangeTracker::default())),
+            blobs: Arc::new(RwLock::new(None)),
+            superseded: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -463,8 +833,12 @@ impl Context {
     /// # }
     /// ```
     pub async fn set(&self, key: impl Into<String>, value: impl serde::Serialize) {
-        let value = serde_json::to_value(value).expect("Failed to serialize value");
-        self.data.insert(key.into(), value);
+        let key = key.into();
+        let mut value = serde_json::to_value(value).expect("Failed to serialize value");
+        if let Some(blobs) = self.blob_offload() {
+            value = blobs.offload(value).await;
+        }
+        self.write(key, value);
     }
 
     /// Get a value from the context.
@@ -489,9 +863,7 @@ impl Context {
     /// # }
     /// ```
     pub async fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
-        self.data
-            .get(key)
-            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        self.try_get(key).await.ok().flatten()
     }
 
     /// Remove a value from the context.
@@ -516,7 +888,7 @@ impl Context {
     /// # }
     /// ```
     pub async fn remove(&self, key: &str) -> Option<Value> {
-        self.data.remove(key).map(|(_, v)| v)
+        self.remove_sync(key)
     }
 
     /// Clear all regular context data (does not affect chat history).
@@ -544,7 +916,17 @@ impl Context {
     /// # }
     /// ```
     pub async fn clear(&self) {
+        let keys: Vec<String> = self.entries().into_keys().collect();
+        for entry in self.data.iter() {
+            self.supersede(entry.value());
+        }
         self.data.clear();
+        for key in keys {
+            if self.fork_base.is_some() {
+                self.removed.insert(key.clone());
+            }
+            self.touch(&key);
+        }
     }
 
     /// Synchronous version of get for use in edge conditions.
@@ -573,13 +955,13 @@ impl Context {
     /// # }
     /// ```
     pub fn get_sync<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
-        self.data
-            .get(key)
-            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        self.try_get_sync(key).ok().flatten()
     }
 
     /// Synchronous version of set for use when async is not available.
     ///
+    /// Values set this way are always stored inline, even if a blob store is attached.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -592,8 +974,208 @@ impl Context {
     /// assert_eq!(value, Some("value".to_string()));
     /// ```
     pub fn set_sync(&self, key: impl Into<String>, value: impl serde::Serialize) {
+        let key = key.into();
         let value = serde_json::to_value(value).expect("Failed to serialize value");
-        self.data.insert(key.into(), value);
+        self.write(key, value);
+    }
+
+    /// Get a value from the context, distinguishing a missing key from a type mismatch.
+    ///
+    /// Returns `Ok(None)` if the key doesn't exist and a
+    /// [`GraphError::ContextError`] if the stored value cannot be deserialized as `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::Context;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let context = Context::new();
+    /// context.set("count", "not a number".to_string()).await;
+    ///
+    /// assert!(context.try_get::<i32>("count").await.is_err());
+    /// assert_eq!(context.try_get::<i32>("missing").await.unwrap(), None);
+    /// # }
+    /// ```
+    pub async fn try_get<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> crate::Result<Option<T>> {
+        let Some(value) = self.lookup(key) else {
+            return Ok(None);
+        };
+        let value = self.resolve(value).await?;
+        Self::decode(key, value).map(Some)
+    }
+
+    /// Synchronous version of [`Context::try_get`].
+    ///
+    /// Offloaded values can only be read this way once they have been loaded by an
+    /// async read (or were set through this context); otherwise an error is returned.
+    pub fn try_get_sync<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> crate::Result<Option<T>> {
+        let Some(value) = self.lookup(key) else {
+            return Ok(None);
+        };
+        let value = match BlobRef::from_value(&value) {
+            Some(blob) => self
+                .blob_offload()
+                .and_then(|blobs| blobs.cached(&blob))
+                .ok_or_else(|| {
+                    GraphError::ContextError(format!(
+                        "Context key '{}' is offloaded to blob {} and not loaded yet",
+                        key, blob.id
+                    ))
+                })?,
+            None => value,
+        };
+        Self::decode(key, value).map(Some)
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(key: &str, value: Value) -> crate::Result<T> {
+        serde_json::from_value(value).map_err(|e| {
+            GraphError::ContextError(format!(
+                "Failed to deserialize context key '{}' as {}: {}",
+                key,
+                std::any::type_name::<T>(),
+                e
+            ))
+        })
+    }
+
+    /// Replace a blob reference with the value it points to.
+    async fn resolve(&self, value: Value) -> crate::Result<Value> {
+        let Some(blob) = BlobRef::from_value(&value) else {
+            return Ok(value);
+        };
+        match self.blob_offload() {
+            Some(blobs) => blobs.load(&blob).await,
+            None => Err(GraphError::ContextError(format!(
+                "Value is offloaded to blob {} but no blob store is attached",
+                blob.id
+            ))),
+        }
+    }
+
+    // Blob store methods
+
+    /// Store values whose JSON encoding exceeds `threshold` bytes in `store`.
+    ///
+    /// The context keeps a [`BlobRef`] in place of each offloaded value and loads
+    /// it back on the first read. Attach the same store again after loading a
+    /// session from storage. See the [`blob_store`](crate::blob_store) module.
+    pub fn set_blob_store(&self, store: Arc<dyn BlobStore>, threshold: usize) {
+        if let Ok(mut blobs) = self.blobs.write() {
+            *blobs = Some(BlobOffload::new(store, threshold));
+        }
+    }
+
+    fn blob_offload(&self) -> Option<BlobOffload> {
+        self.blobs.read().ok().and_then(|blobs| blobs.clone())
+    }
+
+    /// Load every offloaded value, so synchronous reads such as
+    /// [`Context::get_sync`] in edge conditions can see them.
+    pub async fn load_blobs(&self) -> crate::Result<()> {
+        for value in self.entries().into_values() {
+            if BlobRef::from_value(&value).is_some() {
+                self.resolve(value).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the blobs of values that were overwritten or removed.
+    ///
+    /// Call this once the context has been saved without them; until then the
+    /// stored session may still reference them. [`FlowRunner`](crate::FlowRunner)
+    /// does this after every save. Returns the number of blobs deleted.
+    pub async fn delete_superseded_blobs(&self) -> crate::Result<usize> {
+        let superseded = self
+            .superseded
+            .lock()
+            .map(|mut superseded| std::mem::take(&mut *superseded))
+            .unwrap_or_default();
+        let Some(blobs) = self.blob_offload() else {
+            return Ok(0);
+        };
+        let count = superseded.len();
+        for (index, blob) in superseded.iter().enumerate() {
+            if let Err(e) = blobs.delete(blob).await {
+                // Keep the rest for the next attempt
+                if let Ok(mut pending) = self.superseded.lock() {
+                    pending.extend(superseded[index..].iter().cloned());
+                }
+                return Err(e);
+            }
+        }
+        Ok(count)
+    }
+
+    /// Delete every blob this context references, e.g. when its session is deleted.
+    pub async fn delete_blobs(&self) -> crate::Result<()> {
+        let Some(blobs) = self.blob_offload() else {
+            return Ok(());
+        };
+        for value in self.data.iter().map(|entry| entry.value().clone()) {
+            if let Some(blob) = BlobRef::from_value(&value) {
+                blobs.delete(&blob).await?;
+            }
+        }
+        self.delete_superseded_blobs().await?;
+        Ok(())
+    }
+
+    fn supersede(&self, previous: &Value) {
+        if let Some(blob) = BlobRef::from_value(previous)
+            && let Ok(mut superseded) = self.superseded.lock()
+        {
+            superseded.push(blob);
+        }
+    }
+
+    // Typed key methods
+
+    /// Set a value through a typed [`ContextKey`].
+    pub async fn set_key<T: Serialize>(&self, key: ContextKey<T>, value: T) {
+        self.set(key.name(), value).await;
+    }
+
+    /// Get a value through a typed [`ContextKey`].
+    ///
+    /// Behaves like [`Context::try_get`]: a missing key yields `Ok(None)` while a
+    /// value of the wrong shape yields a [`GraphError::ContextError`].
+    pub async fn get_key<T: serde::de::DeserializeOwned>(
+        &self,
+        key: ContextKey<T>,
+    ) -> crate::Result<Option<T>> {
+        self.try_get(key.name()).await
+    }
+
+    /// Synchronous version of [`Context::get_key`] for use in edge conditions.
+    pub fn get_key_sync<T: serde::de::DeserializeOwned>(
+        &self,
+        key: ContextKey<T>,
+    ) -> crate::Result<Option<T>> {
+        self.try_get_sync(key.name())
+    }
+
+    /// Remove a value through a typed [`ContextKey`], returning it if it existed.
+    ///
+    /// The value is read before it is removed: one that cannot be read as `T`
+    /// yields a [`GraphError::ContextError`] and the context is left unchanged.
+    pub async fn remove_key<T: serde::de::DeserializeOwned>(
+        &self,
+        key: ContextKey<T>,
+    ) -> crate::Result<Option<T>> {
+        let value = self.try_get(key.name()).await?;
+        if value.is_some() {
+            self.remove(key.name()).await;
+        }
+        Ok(value)
     }
 
     // Chat history methods
@@ -615,6 +1197,7 @@ impl Context {
         if let Ok(mut history) = self.chat_history.write() {
             history.add_user_message(content);
         }
+        self.touch_messages(1);
     }
 
     /// Add an assistant message to the chat history.
@@ -634,6 +1217,7 @@ impl Context {
         if let Ok(mut history) = self.chat_history.write() {
             history.add_assistant_message(content);
         }
+        self.touch_messages(1);
     }
 
     /// Add a system message to the chat history.
@@ -653,6 +1237,41 @@ impl Context {
         if let Ok(mut history) = self.chat_history.write() {
             history.add_system_message(content);
         }
+        self.touch_messages(1);
+    }
+
+    /// Add a message to the chat history, e.g. one with tool calls or attachments.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{Context, SerializableMessage};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let context = Context::new();
+    /// context
+    ///     .add_message(SerializableMessage::tool_result("call_1", "42").with_task_id("calculator"))
+    ///     .await;
+    /// assert_eq!(context.get_all_messages().await[0].task_id.as_deref(), Some("calculator"));
+    /// # }
+    /// ```
+    pub async fn add_message(&self, message: SerializableMessage) {
+        if let Ok(mut history) = self.chat_history.write() {
+            history.add_message(message);
+        }
+        self.touch_messages(1);
+    }
+
+    /// Add a system message that is never evicted by pruning, windowing or compaction.
+    ///
+    /// Use it for instructions that must stay in front of the model for the
+    /// whole conversation.
+    pub async fn add_pinned_system_message(&self, content: String) {
+        if let Ok(mut history) = self.chat_history.write() {
+            history.add_pinned_system_message(content);
+        }
+        self.touch_messages(1);
     }
 
     /// Get a clone of the current chat history.
@@ -773,6 +1392,423 @@ impl Context {
         }
     }
 
+    /// Get the newest messages that fit within `budget` tokens, plus all pinned messages.
+    ///
+    /// See [`ChatHistory::window`].
+    pub async fn get_messages_within_budget(
+        &self,
+        budget: usize,
+        estimator: &dyn TokenEstimator,
+    ) -> Vec<SerializableMessage> {
+        if let Ok(history) = self.chat_history.read() {
+            history.window(budget, estimator)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Replace older messages with a summary once the history exceeds the policy's
+    /// token trigger.
+    ///
+    /// The newest messages within `policy.retain_tokens` and all pinned messages
+    /// are kept; the rest are passed to `summarizer` and replaced by one system
+    /// message holding the summary. Returns whether the history was compacted.
+    /// Compaction is skipped (returning `false`) if messages were pruned or
+    /// cleared while the summary was being produced.
+    ///
+    /// See the [`compaction`](crate::compaction) module for an example.
+    pub async fn compact_chat_history(
+        &self,
+        policy: &CompactionPolicy,
+        summarizer: &dyn ChatSummarizer,
+    ) -> crate::Result<bool> {
+        let (revision, evicted, messages) = {
+            let Ok(history) = self.chat_history.read() else {
+                return Ok(false);
+            };
+            let Some(evicted) = history.compaction_candidates(policy) else {
+                return Ok(false);
+            };
+            let messages: Vec<SerializableMessage> = evicted
+                .iter()
+                .map(|&index| history.messages[index].clone())
+                .collect();
+            (history.revision, evicted, messages)
+        };
+
+        let summary = summarizer.summarize(&messages).await?;
+        let summary =
+            SerializableMessage::system(format!("{}\n{}", policy.summary_prefix, summary));
+
+        let Ok(mut history) = self.chat_history.write() else {
+            return Ok(false);
+        };
+        // Appends don't move existing messages, anything else invalidates the indices
+        if history.revision != revision {
+            return Ok(false);
+        }
+        history.replace_with_summary(&evicted, summary);
+        Ok(true)
+    }
+
+    // Fork / merge methods
+
+    /// Create an isolated child scope of this context.
+    ///
+    /// The child is an overlay: it keeps its own writes and removals and reads
+    /// every other key through to the parent, so forking copies no data and the
+    /// child sees parent writes made after the fork. The chat history is shared
+    /// copy-on-write. Writes made through the child are invisible to the parent
+    /// (and to other forks) until they are brought back with [`Context::merge`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{Context, MergeStrategy};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> graph_flow::Result<()> {
+    /// let context = Context::new();
+    /// context.set("topic", "rust".to_string()).await;
+    ///
+    /// let child = context.fork();
+    /// child.set("summary", "A systems language".to_string()).await;
+    /// assert_eq!(context.get::<String>("summary").await, None);
+    ///
+    /// context.merge(&child, MergeStrategy::LastWriterWins).await?;
+    /// assert_eq!(
+    ///     context.get::<String>("summary").await,
+    ///     Some("A systems language".to_string())
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fork(&self) -> Context {
+        let mut history = if let Ok(history) = self.chat_history.read() {
+            history.clone()
+        } else {
+            ChatHistory::new()
+        };
+        let chat_len = history.len();
+        let chat_revision = history.revision;
+        // The child never prunes, so unless it rewrites its history (changing the
+        // revision), messages past `chat_len` are exactly the ones it added
+        history.max_messages = None;
+
+        Context {
+            data: Arc::new(DashMap::new()),
+            removed: Arc::new(DashSet::new()),
+            chat_history: Arc::new(RwLock::new(history)),
+            fork_base: Some(Arc::new(ForkBase {
+                parent: self.clone(),
+                parent_version: self.checkpoint().version,
+                chat_len,
+                chat_revision,
+            })),
+            changes: Arc::new(Mutex::new(ChangeTracker::default())),
+            blobs: Arc::new(RwLock::new(self.blob_offload())),
+            superseded: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Merge the changes made in `child` back into this context.
+    ///
+    /// Only the child's local writes, the keys it set or removed since
+    /// [`Context::fork`], are applied
+    /// according to `strategy`, and chat messages the child added are appended
+    /// to this context's history. If the child cleared or compacted its history,
+    /// its history replaces this one, followed by any messages this context
+    /// gained since the fork. A context that was not created by `fork` is
+    /// treated as a fork of an empty context.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::ContextError`] when `strategy` is
+    /// [`MergeStrategy::ErrorOnConflict`] and a conflicting key is found. In that
+    /// case nothing is merged.
+    pub async fn merge(&self, child: &Context, strategy: MergeStrategy) -> crate::Result<()> {
+        let (chat_len, chat_revision) = match &child.fork_base {
+            Some(fork) => (fork.chat_len, fork.chat_revision),
+            None => (0, 0),
+        };
+
+        // The child's local writes: keys it set or removed since the fork
+        let mut changes: Vec<(String, Option<Value>)> = child
+            .data
+            .iter()
+            .map(|entry| (entry.key().clone(), Some(entry.value().clone())))
+            .collect();
+        changes.extend(child.removed.iter().map(|key| (key.key().clone(), None)));
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        match strategy {
+            MergeStrategy::LastWriterWins => {
+                for (key, value) in changes {
+                    self.apply_merged(key, value);
+                }
+            }
+            MergeStrategy::ErrorOnConflict => {
+                let conflicts: Vec<&str> = changes
+                    .iter()
+                    .filter(|(key, value)| {
+                        self.changed_since_fork(key, child) && self.lookup(key) != *value
+                    })
+                    .map(|(key, _)| key.as_str())
+                    .collect();
+                if !conflicts.is_empty() {
+                    return Err(GraphError::ContextError(format!(
+                        "Merge conflict on key(s): {}",
+                        conflicts.join(", ")
+                    )));
+                }
+                for (key, value) in changes {
+                    self.apply_merged(key, value);
+                }
+            }
+            MergeStrategy::Namespaced(prefix) => {
+                for (key, value) in changes {
+                    if let Some(value) = value {
+                        self.apply_merged(format!("{}.{}", prefix, key), Some(value));
+                    }
+                }
+            }
+            MergeStrategy::Custom(reducer) => {
+                for (key, value) in changes {
+                    let current = self.lookup(&key);
+                    let merged = reducer(&key, current.as_ref(), value.as_ref());
+                    self.apply_merged(key, merged);
+                }
+            }
+        }
+
+        // Blobs the child created and overwrote itself are superseded here too
+        let child_superseded = child
+            .superseded
+            .lock()
+            .map(|mut superseded| std::mem::take(&mut *superseded))
+            .unwrap_or_default();
+        if let Ok(mut superseded) = self.superseded.lock() {
+            superseded.extend(child_superseded);
+        }
+
+        let rewritten = match child.chat_history.read() {
+            Ok(history) if history.revision != chat_revision => Some(history.messages.clone()),
+            _ => None,
+        };
+        if let Some(messages) = rewritten {
+            if let Ok(mut history) = self.chat_history.write() {
+                let split = chat_len.min(history.len());
+                let gained = Arc::make_mut(&mut history.messages).split_off(split);
+                history.messages = messages;
+                history.revision += 1;
+                Arc::make_mut(&mut history.messages).extend(gained);
+                history.prune();
+            }
+            return Ok(());
+        }
+
+        let appended = if let Ok(history) = child.chat_history.read() {
+            history
+                .messages()
+                .get(chat_len..)
+                .unwrap_or_default()
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+        let appended_count = appended.len() as u64;
+        if !appended.is_empty()
+            && let Ok(mut history) = self.chat_history.write()
+        {
+            for message in appended {
+                history.add_message(message);
+            }
+        }
+        self.touch_messages(appended_count);
+
+        Ok(())
+    }
+
+    fn apply_merged(&self, key: String, value: Option<Value>) {
+        match value {
+            Some(value) => self.write(key, value),
+            None => {
+                self.remove_sync(&key);
+            }
+        }
+    }
+
+    /// Whether `key` changed in this context after `child` was forked from it.
+    ///
+    /// For a child forked from another context (or not forked at all), whether
+    /// this context's value differs from the one the child inherits.
+    fn changed_since_fork(&self, key: &str, child: &Context) -> bool {
+        match &child.fork_base {
+            Some(fork) if Arc::ptr_eq(&fork.parent.data, &self.data) => self
+                .changes
+                .lock()
+                .map(|changes| {
+                    changes
+                        .touched
+                        .get(key)
+                        .is_some_and(|version| *version > fork.parent_version)
+                })
+                .unwrap_or(false),
+            Some(fork) => self.lookup(key) != fork.parent.lookup(key),
+            None => self.lookup(key).is_some(),
+        }
+    }
+
+    // Overlay methods
+
+    /// The raw value of `key`, reading through to the parent of a fork.
+    fn lookup(&self, key: &str) -> Option<Value> {
+        if let Some(value) = self.data.get(key) {
+            return Some(value.value().clone());
+        }
+        self.inherited(key)
+    }
+
+    /// The value a fork inherits for `key` from its parent, unless it removed it.
+    fn inherited(&self, key: &str) -> Option<Value> {
+        match &self.fork_base {
+            Some(fork) if !self.removed.contains(key) => fork.parent.lookup(key),
+            _ => None,
+        }
+    }
+
+    /// All visible entries, local writes shadowing the parent's.
+    fn entries(&self) -> HashMap<String, Value> {
+        let mut entries = match &self.fork_base {
+            Some(fork) => {
+                let mut entries = fork.parent.entries();
+                entries.retain(|key, _| !self.removed.contains(key));
+                entries
+            }
+            None => HashMap::new(),
+        };
+        for entry in self.data.iter() {
+            entries.insert(entry.key().clone(), entry.value().clone());
+        }
+        entries
+    }
+
+    fn write(&self, key: String, value: Value) {
+        self.removed.remove(&key);
+        self.touch(&key);
+        if let Some(previous) = self.data.insert(key, value) {
+            self.supersede(&previous);
+        }
+    }
+
+    fn remove_sync(&self, key: &str) -> Option<Value> {
+        let local = self.data.remove(key).map(|(_, v)| v);
+        if let Some(previous) = &local {
+            self.supersede(previous);
+        }
+        let inherited = self.inherited(key);
+        if inherited.is_some() {
+            self.removed.insert(key.to_string());
+        }
+        let removed = local.or(inherited);
+        if removed.is_some() {
+            self.touch(key);
+        }
+        removed
+    }
+
+    /// Drop a fork's local write or removal of `key`, so merging the fork leaves
+    /// the parent's value alone.
+    pub(crate) fn discard_local(&self, key: &str) {
+        if let Some((_, previous)) = self.data.remove(key) {
+            self.supersede(&previous);
+        }
+        self.removed.remove(key);
+    }
+
+    // Change tracking methods
+
+    /// Take a checkpoint to later ask which changes happened after it.
+    ///
+    /// See [`ContextDiff`] for an example.
+    pub fn checkpoint(&self) -> Checkpoint {
+        if let Ok(changes) = self.changes.lock() {
+            Checkpoint {
+                version: changes.version,
+                messages_appended: changes.messages_appended,
+            }
+        } else {
+            Checkpoint {
+                version: 0,
+                messages_appended: 0,
+            }
+        }
+    }
+
+    /// Collect the keys set or removed and the chat messages appended since `checkpoint`.
+    ///
+    /// A key that was written and later removed is reported as removed; a key that
+    /// was removed and written again is reported as set with its current value.
+    pub fn diff_since(&self, checkpoint: &Checkpoint) -> ContextDiff {
+        let (keys, appended) = if let Ok(changes) = self.changes.lock() {
+            let keys: Vec<String> = changes
+                .touched
+                .iter()
+                .filter(|(_, version)| **version > checkpoint.version)
+                .map(|(key, _)| key.clone())
+                .collect();
+            (
+                keys,
+                changes
+                    .messages_appended
+                    .saturating_sub(checkpoint.messages_appended),
+            )
+        } else {
+            (Vec::new(), 0)
+        };
+
+        let mut diff = ContextDiff::default();
+        for key in keys {
+            match self.lookup(&key) {
+                Some(value) => {
+                    diff.set.insert(key, value);
+                }
+                None => {
+                    diff.removed.insert(key);
+                }
+            }
+        }
+        if appended > 0 {
+            let n = usize::try_from(appended).unwrap_or(usize::MAX);
+            diff.messages = self.get_last_messages_sync(n);
+        }
+        diff
+    }
+
+    fn touch(&self, key: &str) {
+        if let Ok(mut changes) = self.changes.lock() {
+            changes.version += 1;
+            let version = changes.version;
+            changes.touched.insert(key.to_string(), version);
+        }
+    }
+
+    fn touch_messages(&self, count: u64) {
+        if count > 0
+            && let Ok(mut changes) = self.changes.lock()
+        {
+            changes.messages_appended += count;
+        }
+    }
+
+    fn get_last_messages_sync(&self, n: usize) -> Vec<SerializableMessage> {
+        if let Ok(history) = self.chat_history.read() {
+            history.last_messages(n).to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
     // Rig integration methods (only available when rig feature is enabled)
 
     #[cfg(feature = "rig")]
@@ -838,16 +1874,52 @@ impl Context {
             .collect()
     }
 
+    #[cfg(feature = "rig")]
+    /// Get the messages that fit within `budget` tokens in rig format.
+    ///
+    /// This method is only available when the "rig" feature is enabled.
+    /// See [`ChatHistory::window`] for which messages are selected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "rig")]
+    /// # {
+    /// use graph_flow::{ApproxTokenEstimator, Context};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let context = Context::new();
+    /// context.add_pinned_system_message("You are helpful".to_string()).await;
+    /// for i in 0..100 {
+    ///     context.add_user_message(format!("Message {}", i)).await;
+    /// }
+    ///
+    /// let messages = context
+    ///     .get_rig_messages_within_budget(200, &ApproxTokenEstimator::default())
+    ///     .await;
+    /// assert!(messages.len() < 101);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn get_rig_messages_within_budget(
+        &self,
+        budget: usize,
+        estimator: &dyn TokenEstimator,
+    ) -> Vec<Message> {
+        let messages = self.get_messages_within_budget(budget, estimator).await;
+        messages
+            .iter()
+            .map(|msg| self.to_rig_message(msg))
+            .collect()
+    }
+
     #[cfg(feature = "rig")]
     /// Convert a SerializableMessage to a rig::completion::Message.
     ///
     /// This method is only available when the "rig" feature is enabled.
     fn to_rig_message(&self, msg: &SerializableMessage) -> Message {
-        match msg.role {
-            MessageRole::User => Message::user(msg.content.clone()),
-            MessageRole::Assistant => Message::assistant(msg.content.clone()),
-            MessageRole::System => Message::system(msg.content.clone()),
-        }
+        Message::from(msg)
     }
 }
 
@@ -863,12 +1935,8 @@ impl Serialize for Context {
     where
         S: serde::Serializer,
     {
-        // Convert DashMap to HashMap for serialization
-        let data: std::collections::HashMap<String, Value> = self
-            .data
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
-            .collect();
+        // Flatten a fork's overlay into a plain map for serialization
+        let data = self.entries();
 
         let chat_history = if let Ok(history) = self.chat_history.read() {
             history.clone()
@@ -895,7 +1963,15 @@ impl<'de> Deserialize<'de> for Context {
 
         let chat_history = Arc::new(RwLock::new(context_data.chat_history));
 
-        Ok(Context { data, chat_history })
+        Ok(Context {
+            data,
+            removed: Arc::new(DashSet::new()),
+            chat_history,
+            fork_base: None,
+            changes: Arc::new(Mutex::new(ChangeTracker::default())),
+            blobs: Arc::new(RwLock::new(None)),
+            superseded: Arc::new(Mutex::new(Vec::new())),
+        })
     }
 }
 
@@ -912,6 +1988,248 @@ mod tests {
         assert_eq!(value, Some("value".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_typed_keys() {
+        const COUNT: ContextKey<i32> = ContextKey::new("count");
+        const NAME: ContextKey<String> = ContextKey::new("name");
+
+        let context = Context::new();
+        assert_eq!(context.get_key(COUNT).await.unwrap(), None);
+
+        context.set_key(COUNT, 7).await;
+        assert_eq!(context.get_key(COUNT).await.unwrap(), Some(7));
+        assert_eq!(context.get_key_sync(COUNT).unwrap(), Some(7));
+
+        // A key of the wrong type is reported instead of looking missing
+        context.set("name", 42).await;
+        let err = context.get_key(NAME).await.unwrap_err();
+        assert!(matches!(err, GraphError::ContextError(msg) if msg.contains("'name'")));
+
+        assert_eq!(context.remove_key(COUNT).await.unwrap(), Some(7));
+        assert_eq!(context.get_key(COUNT).await.unwrap(), None);
+        assert_eq!(context.remove_key(COUNT).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_typed_key_mismatch_keeps_value() {
+        const FLAG: ContextKey<bool> = ContextKey::new("flag");
+
+        let context = Context::new();
+        context.set("flag", "yes").await;
+        assert!(context.get_key_sync(FLAG).is_err());
+        assert!(context.remove_key(FLAG).await.is_err());
+        assert_eq!(context.get::<String>("flag").await, Some("yes".to_string()));
+
+        // Untyped reads still treat a mismatch as missing
+        assert_eq!(context.get::<bool>("flag").await, None);
+        assert!(context.try_get::<bool>("flag").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_typed_keys_share_storage_with_string_keys() {
+        const ITEMS: ContextKey<Vec<String>> = ContextKey::new("items");
+        assert_eq!(ITEMS.name(), "items");
+        assert!(format!("{:?}", ITEMS).contains("\"items\""));
+
+        let context = Context::new();
+        context.set("items", vec!["a", "b"]).await;
+        assert_eq!(
+            context.get_key(ITEMS).await.unwrap(),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+
+        context.set_key(ITEMS, vec!["c".to_string()]).await;
+        let raw: Option<Vec<String>> = context.get_sync("items");
+        assert_eq!(raw, Some(vec!["c".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_fork_is_isolated_and_merges_back() {
+        let parent = Context::new();
+        parent.set("shared", 1).await;
+        parent.set("doomed", true).await;
+        parent.add_user_message("before fork".to_string()).await;
+
+        let child = parent.fork();
+        child.set("shared", 2).await;
+        child.set("new", "value").await;
+        child.remove("doomed").await;
+        child.add_assistant_message("from child".to_string()).await;
+
+        // Parent is untouched until merge
+        assert_eq!(parent.get::<i32>("shared").await, Some(1));
+        assert_eq!(parent.get::<String>("new").await, None);
+        assert_eq!(parent.chat_history_len().await, 1);
+
+        parent
+            .merge(&child, MergeStrategy::LastWriterWins)
+            .await
+            .unwrap();
+        assert_eq!(parent.get::<i32>("shared").await, Some(2));
+        assert_eq!(parent.get::<String>("new").await, Some("value".to_string()));
+        assert_eq!(parent.get::<bool>("doomed").await, None);
+
+        let messages = parent.get_all_messages().await;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].content, "from child");
+    }
+
+    #[tokio::test]
+    async fn test_fork_reads_through_to_parent() {
+        let parent = Context::new();
+        parent.set("kept", 1).await;
+        parent.set("dropped", 2).await;
+        parent.add_user_message("before fork".to_string()).await;
+
+        let child = parent.fork();
+        child.remove("dropped").await;
+        child.set("local", 3).await;
+
+        // Later parent writes show through unless the child shadowed the key
+        parent.set("kept", 10).await;
+        parent.set("dropped", 20).await;
+        parent.set("late", 4).await;
+        assert_eq!(child.get::<i32>("kept").await, Some(10));
+        assert_eq!(child.get::<i32>("late").await, Some(4));
+        assert_eq!(child.get::<i32>("dropped").await, None);
+        assert_eq!(child.chat_history_len().await, 1);
+
+        let flattened: HashMap<String, Value> =
+            serde_json::from_value(serde_json::to_value(&child).unwrap()["data"].clone()).unwrap();
+        assert_eq!(flattened.len(), 3);
+
+        // Only the child's own writes are merged back
+        parent
+            .merge(&child, MergeStrategy::LastWriterWins)
+            .await
+            .unwrap();
+        assert_eq!(parent.get::<i32>("kept").await, Some(10));
+        assert_eq!(parent.get::<i32>("late").await, Some(4));
+        assert_eq!(parent.get::<i32>("local").await, Some(3));
+        assert_eq!(parent.get::<i32>("dropped").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_conflicts_with_parent_writes_after_fork() {
+        let parent = Context::new();
+        let child = parent.fork();
+        child.set("key", 1).await;
+        parent.set("key", 2).await;
+
+        let err = parent
+            .merge(&child, MergeStrategy::ErrorOnConflict)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::ContextError(msg) if msg.contains("key")));
+        assert_eq!(parent.get::<i32>("key").await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_merge_error_on_conflict() {
+        let parent = Context::new();
+        parent.set("key", 0).await;
+
+        let a = parent.fork();
+        let b = parent.fork();
+        a.set("key", 1).await;
+        b.set("key", 2).await;
+        b.set("other", 3).await;
+
+        parent
+            .merge(&a, MergeStrategy::ErrorOnConflict)
+            .await
+            .unwrap();
+        let err = parent
+            .merge(&b, MergeStrategy::ErrorOnConflict)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::ContextError(msg) if msg.contains("key")));
+
+        // A failed merge applies nothing
+        assert_eq!(parent.get::<i32>("key").await, Some(1));
+        assert_eq!(parent.get::<i32>("other").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_namespaced_and_custom() {
+        let parent = Context::new();
+        parent.set("count", 10).await;
+
+        let child = parent.fork();
+        child.set("count", 5).await;
+        child.set("label", "x").await;
+
+        parent
+            .merge(&child, MergeStrategy::Namespaced("branch".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(parent.get::<i32>("count").await, Some(10));
+        assert_eq!(parent.get::<i32>("branch.count").await, Some(5));
+        assert_eq!(
+            parent.get::<String>("branch.label").await,
+            Some("x".to_string())
+        );
+
+        let sum: MergeReducer = Arc::new(|_, current, incoming| {
+            let a = current.and_then(Value::as_i64).unwrap_or(0);
+            let b = incoming.and_then(Value::as_i64).unwrap_or(0);
+            Some(Value::from(a + b))
+        });
+        let child = parent.fork();
+        child.set("count", 5).await;
+        parent
+            .merge(&child, MergeStrategy::Custom(sum))
+            .await
+            .unwrap();
+        assert_eq!(parent.get::<i32>("count").await, Some(15));
+    }
+
+    #[tokio::test]
+    async fn test_diff_since_checkpoint() {
+        let context = Context::with_max_chat_messages(2);
+        context.set("a", 1).await;
+        context.set("b", 2).await;
+        context.add_user_message("old".to_string()).await;
+
+        let checkpoint = context.checkpoint();
+        assert!(context.diff_since(&checkpoint).is_empty());
+
+        context.set("a", 10).await;
+        context.remove("b").await;
+        context.remove("never_existed").await;
+        context.set("c", 3).await;
+        context.remove("c").await;
+        for i in 0..3 {
+            context.add_user_message(format!("new {}", i)).await;
+        }
+
+        let diff = context.diff_since(&checkpoint);
+        assert_eq!(diff.set.len(), 1);
+        assert_eq!(diff.set.get("a"), Some(&Value::from(10)));
+        assert_eq!(diff.removed.iter().collect::<Vec<_>>(), vec!["b", "c"]);
+        // Only the messages still in the (pruned) history are reported
+        let contents: Vec<_> = diff.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["new 1", "new 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_is_tracked_in_parent() {
+        let parent = Context::new();
+        let checkpoint = parent.checkpoint();
+
+        let child = parent.fork();
+        child.set("from_child", true).await;
+        child.add_assistant_message("hello".to_string()).await;
+        parent
+            .merge(&child, MergeStrategy::LastWriterWins)
+            .await
+            .unwrap();
+
+        let diff = parent.diff_since(&checkpoint);
+        assert!(diff.set.contains_key("from_child"));
+        assert_eq!(diff.messages.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_chat_history_operations() {
         let context = Context::new();
@@ -1058,4 +2376,122 @@ mod tests {
             last_two[1]
         );
     }
+
+    #[test]
+    fn test_pruning_keeps_pinned_messages() {
+        let mut history = ChatHistory::with_max_messages(3);
+        history.add_pinned_system_message("rules".to_string());
+        for i in 0..5 {
+            history.add_user_message(format!("Message {}", i));
+        }
+
+        let contents: Vec<&str> = history
+            .messages()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["rules", "Message 3", "Message 4"]);
+    }
+
+    #[test]
+    fn test_window_respects_budget() {
+        let mut history = ChatHistory::new();
+        history.add_pinned_system_message("rules".to_string());
+        for i in 0..10 {
+            history.add_user_message(format!("Message {}", i));
+        }
+
+        // Every message costs one token
+        let estimator = |_: &str| 1;
+        let window = history.window(4, &estimator);
+        let contents: Vec<&str> = window.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["rules", "Message 7", "Message 8", "Message 9"]
+        );
+
+        // Pinned messages are returned even when they exceed the budget
+        assert_eq!(history.window(0, &estimator).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_replaces_old_messages() {
+        let context = Context::new();
+        context.add_user_message("Message 0".to_string()).await;
+        context.add_pinned_system_message("rules".to_string()).await;
+        for i in 1..10 {
+            context.add_user_message(format!("Message {}", i)).await;
+        }
+
+        let policy = CompactionPolicy::new(5, 3).with_estimator(Arc::new(|_: &str| 1));
+        let summarize = |messages: &[SerializableMessage]| format!("{} messages", messages.len());
+        assert!(
+            context
+                .compact_chat_history(&policy, &summarize)
+                .await
+                .unwrap()
+        );
+
+        let contents: Vec<String> = context
+            .get_all_messages()
+            .await
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                "Summary of the earlier conversation:\n8 messages",
+                "rules",
+                "Message 8",
+                "Message 9",
+            ]
+        );
+
+        // Below the trigger nothing happens
+        assert!(
+            !context
+                .compact_chat_history(&policy, &summarize)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compaction_in_fork_is_merged() {
+        let context = Context::new();
+        for i in 0..6 {
+            context.add_user_message(format!("Message {}", i)).await;
+        }
+
+        let child = context.fork();
+        let policy = CompactionPolicy::new(3, 2).with_estimator(Arc::new(|_: &str| 1));
+        let summarize = |_: &[SerializableMessage]| "earlier".to_string();
+        assert!(
+            child
+                .compact_chat_history(&policy, &summarize)
+                .await
+                .unwrap()
+        );
+        child.add_assistant_message("Reply".to_string()).await;
+        // Added to the parent while the child was running
+        context.add_user_message("Message 6".to_string()).await;
+
+        context
+            .merge(&child, MergeStrategy::LastWriterWins)
+            .await
+            .unwrap();
+        let contents: Vec<String> = context
+            .get_all_messages()
+            .await
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents.len(), 5);
+        assert!(contents[0].ends_with("earlier"));
+        assert_eq!(
+            &contents[1..],
+            ["Message 4", "Message 5", "Reply", "Message 6"]
+        );
+    }
 }
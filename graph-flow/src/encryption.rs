@@ -0,0 +1,674 @@
+//! Encryption of context values at rest.
+//!
+//! A [`ContextCipher`] encrypts a serialized [`Context`] before a storage backend
+//! writes it, and decrypts it again on load. It can encrypt the whole context
+//! (data and chat history) or only selected keys, leaving the rest queryable.
+//! Values are sealed with AES-256-GCM using keys supplied by a [`KeyProvider`].
+//!
+//! Every encrypted value records the id of the key it was sealed with, so keys
+//! can be rotated: make a new key current and keep the old one available for
+//! decryption. Values are re-encrypted with the current key the next time the
+//! session is saved, or eagerly with
+//! [`PostgresSessionStorage::rotate_keys`](crate::PostgresSessionStorage::rotate_keys).
+//!
+//! Each value is bound to the session id and, for per-key encryption, the
+//! context key as AES-GCM associated data, so a sealed value copied into
+//! another session or key fails to decrypt. Plaintext values written before
+//! encryption was enabled are still read, as are values sealed before they were
+//! bound; both are re-sealed on the next save.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{Context, ContextCipher, StaticKeyProvider};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let provider = StaticKeyProvider::new("2024-06", [7u8; 32]);
+//! let cipher = ContextCipher::for_keys(Arc::new(provider), ["patient_notes"]);
+//!
+//! let context = Context::new();
+//! context.set("patient_notes", "confidential").await;
+//! context.set("step", 3).await;
+//!
+//! let stored = cipher.encrypt_context("session-1", &context).await?;
+//! assert!(!stored.to_string().contains("confidential"));
+//! assert_eq!(stored["data"]["step"], 3);
+//!
+//! // Sealed values only open in the session they were written for
+//! assert!(cipher.decrypt_context("session-2", stored.clone()).await.is_err());
+//! let restored = cipher.decrypt_context("session-1", stored).await?;
+//! let notes: Option<String> = restored.get("patient_notes").await;
+//! assert_eq!(notes.as_deref(), Some("confidential"));
+//! # Ok(())
+//! # }
+//! ```
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::blob_store::BlobStore;
+use crate::context::Context;
+use crate::error::{GraphError, Result};
+
+/// JSON field that marks a value as encrypted
+const ENCRYPTED_MARKER: &str = "$graph_flow_encrypted";
+/// Prefix of encrypted blobs, followed by the key id length, key id, nonce and ciphertext
+const BLOB_MAGIC: &[u8] = b"GFE1";
+const NONCE_LEN: usize = 12;
+
+/// Prefix of the associated data values are bound with
+const AAD_DOMAIN: &str = "graph_flow.context.v1";
+/// Length of an encryption key in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Raw AES-256 key material
+pub type EncryptionKey = [u8; KEY_LEN];
+
+/// Source of encryption keys, e.g. a KMS or secrets manager.
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+    /// Id and material of the key new values are encrypted with.
+    async fn current_key(&self) -> Result<(String, EncryptionKey)>;
+
+    /// Look up a key by id, including retired keys still needed for decryption.
+    async fn key(&self, id: &str) -> Result<Option<EncryptionKey>>;
+}
+
+/// Key provider holding keys in memory, loaded from code, the environment or a file.
+///
+/// The text format used by [`from_env`](Self::from_env) and
+/// [`from_file`](Self::from_file) is a list of `id:base64-key` entries separated by
+/// commas or newlines. The first entry is the current key; the others are retired
+/// keys kept for decrypting older values.
+#[derive(Clone)]
+pub struct StaticKeyProvider {
+    current: String,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl std::fmt::Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print key material
+        let mut ids: Vec<_> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("StaticKeyProvider")
+            .field("current", &self.current)
+            .field("keys", &ids)
+            .finish()
+    }
+}
+
+impl StaticKeyProvider {
+    pub fn new(id: impl Into<String>, key: EncryptionKey) -> Self {
+        let id = id.into();
+        Self {
+            keys: HashMap::from([(id.clone(), key)]),
+            current: id,
+        }
+    }
+
+    /// Keep a previous key available for decryption.
+    pub fn with_retired_key(mut self, id: impl Into<String>, key: EncryptionKey) -> Self {
+        self.keys.entry(id.into()).or_insert(key);
+        self
+    }
+
+    /// Parse keys from `id:base64-key` entries, the first being current.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut entries = spec
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+            .map(Self::parse_entry);
+
+        let (id, key) = entries.next().ok_or_else(|| {
+            GraphError::CryptoError("No encryption keys configured".to_string())
+        })??;
+        let mut provider = Self::new(id, key);
+        for entry in entries {
+            let (id, key) = entry?;
+            provider = provider.with_retired_key(id, key);
+        }
+        Ok(provider)
+    }
+
+    /// Read keys from the environment variable `var`.
+    pub fn from_env(var: &str) -> Result<Self> {
+        let spec = std::env::var(var).map_err(|e| {
+            GraphError::CryptoError(format!("Failed to read encryption keys from {var}: {e}"))
+        })?;
+        Self::parse(&spec)
+    }
+
+    /// Read keys from a file, one entry per line.
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let spec = tokio::fs::read_to_string(path).await.map_err(|e| {
+            GraphError::CryptoError(format!(
+                "Failed to read encryption keys from {}: {e}",
+                path.display()
+            ))
+        })?;
+        Self::parse(&spec)
+    }
+
+    fn parse_entry(entry: &str) -> Result<(String, EncryptionKey)> {
+        let (id, encoded) = entry.split_once(':').ok_or_else(|| {
+            GraphError::CryptoError("Encryption keys must be given as id:base64-key".to_string())
+        })?;
+        let bytes = BASE64.decode(encoded.trim()).map_err(|e| {
+            GraphError::CryptoError(format!("Encryption key '{id}' is not valid base64: {e}"))
+        })?;
+        let key = EncryptionKey::try_from(bytes.as_slice()).map_err(|_| {
+            GraphError::CryptoError(format!(
+                "Encryption key '{id}' must be {KEY_LEN} bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+        Ok((id.trim().to_string(), key))
+    }
+}
+
+#[async_trait]
+impl KeyProvider for StaticKeyProvider {
+    async fn current_key(&self) -> Result<(String, EncryptionKey)> {
+        Ok((self.current.clone(), self.keys[&self.current]))
+    }
+
+    async fn key(&self, id: &str) -> Result<Option<EncryptionKey>> {
+        Ok(self.keys.get(id).copied())
+    }
+}
+
+/// Which parts of a context are encrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionScope {
+    /// Data and chat history, stored as a single encrypted value
+    Context,
+    /// Only the values of these data keys
+    Keys(BTreeSet<String>),
+}
+
+/// Encrypted value as stored in place of the plaintext
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    kid: String,
+    nonce: String,
+    ciphertext: String,
+    /// Sealed with the session id and key as associated data; unset on older values
+    #[serde(default)]
+    bound: bool,
+}
+
+/// Encrypts and decrypts contexts for storage backends.
+#[derive(Clone)]
+pub struct ContextCipher {
+    provider: Arc<dyn KeyProvider>,
+    scope: EncryptionScope,
+}
+
+impl std::fmt::Debug for ContextCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextCipher")
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+impl ContextCipher {
+    /// Encrypt the whole context.
+    pub fn new(provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            provider,
+            scope: EncryptionScope::Context,
+        }
+    }
+
+    /// Encrypt only the given data keys. Chat history is left in plaintext.
+    pub fn for_keys<I, S>(provider: Arc<dyn KeyProvider>, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            provider,
+            scope: EncryptionScope::Keys(keys.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    pub fn scope(&self) -> &EncryptionScope {
+        &self.scope
+    }
+
+    /// Serialize the context of session `session_id` for storage, encrypting
+    /// the configured scope.
+    pub async fn encrypt_context(&self, session_id: &str, context: &Context) -> Result<Value> {
+        let mut value = serde_json::to_value(context)
+            .map_err(|e| GraphError::ContextError(format!("Context serialization failed: {e}")))?;
+
+        match &self.scope {
+            EncryptionScope::Context => {
+                self.encrypt_value(&value, &Self::associated_data(session_id, None))
+                    .await
+            }
+            EncryptionScope::Keys(keys) => {
+                if let Some(data) = value.get_mut("data").and_then(Value::as_object_mut) {
+                    for (key, entry) in data.iter_mut() {
+                        if keys.contains(key) {
+                            let aad = Self::associated_data(session_id, Some(key));
+                            *entry = self.encrypt_value(entry, &aad).await?;
+                        }
+                    }
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    /// Restore a context written by [`encrypt_context`](Self::encrypt_context).
+    ///
+    /// Accepts values written under either scope, and plaintext contexts. Fails
+    /// with [`GraphError::CryptoError`] if a value was sealed for another session
+    /// or key.
+    pub async fn decrypt_context(&self, session_id: &str, value: Value) -> Result<Context> {
+        let mut value = self
+            .decrypt_value(value, &Self::associated_data(session_id, None))
+            .await?;
+        if let Some(data) = value.get_mut("data").and_then(Value::as_object_mut) {
+            for (key, entry) in data.iter_mut() {
+                if Self::envelope(entry).is_some() {
+                    let aad = Self::associated_data(session_id, Some(key));
+                    *entry = self.decrypt_value(entry.take(), &aad).await?;
+                }
+            }
+        }
+        serde_json::from_value(value)
+            .map_err(|e| GraphError::ContextError(format!("Context deserialization failed: {e}")))
+    }
+
+    /// Whether a stored context contains plaintext in the configured scope, or
+    /// values sealed with a key other than the current one or before values
+    /// were bound to their session.
+    pub async fn needs_rotation(&self, stored: &Value) -> Result<bool> {
+        let (current, _) = self.provider.current_key().await?;
+        // Values sealed before they were bound count as plaintext
+        let sealed_with = |value: &Value| {
+            Self::envelope(value)
+                .filter(|envelope| envelope.bound)
+                .map(|envelope| envelope.kid)
+        };
+
+        Ok(match &self.scope {
+            EncryptionScope::Context => sealed_with(stored) != Some(current),
+            EncryptionScope::Keys(keys) => {
+                if Self::envelope(stored).is_some() {
+                    return Ok(true);
+                }
+                stored
+                    .get("data")
+                    .and_then(Value::as_object)
+                    .is_some_and(|data| {
+                        data.iter().any(|(key, value)| {
+                            let kid = sealed_with(value);
+                            let unbound = kid.is_none() && Self::envelope(value).is_some();
+                            (keys.contains(key) && kid.as_ref() != Some(&current))
+                                || kid.is_some_and(|kid| kid != current)
+                                || unbound
+                        })
+                    })
+            }
+        })
+    }
+
+    /// Encrypt a single JSON value into an envelope, bound to `aad` (see
+    /// [`associated_data`](Self::associated_data)).
+    pub async fn encrypt_value(&self, value: &Value, aad: &[u8]) -> Result<Value> {
+        let plaintext = serde_json::to_vec(value)
+            .map_err(|e| GraphError::ContextError(format!("Value serialization failed: {e}")))?;
+        let (kid, nonce, ciphertext) = self.seal(&plaintext, aad).await?;
+        let envelope = Envelope {
+            kid,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+            bound: true,
+        };
+        Ok(serde_json::json!({ ENCRYPTED_MARKER: envelope }))
+    }
+
+    /// Decrypt an envelope sealed with `aad`. Values that are not encrypted are
+    /// returned unchanged.
+    pub async fn decrypt_value(&self, value: Value, aad: &[u8]) -> Result<Value> {
+        let Some(envelope) = Self::envelope(&value) else {
+            return Ok(value);
+        };
+        let decode = |field: &str| {
+            BASE64
+                .decode(field)
+                .map_err(|e| GraphError::CryptoError(format!("Malformed encrypted value: {e}")))
+        };
+        let aad = if envelope.bound { aad } else { &[] };
+        let plaintext = self
+            .open(
+                &envelope.kid,
+                &decode(&envelope.nonce)?,
+                &decode(&envelope.ciphertext)?,
+                aad,
+            )
+            .await?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| GraphError::CryptoError(format!("Failed to decode decrypted value: {e}")))
+    }
+
+    /// Encrypt raw bytes, e.g. blob contents.
+    pub async fn encrypt_bytes(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let (kid, nonce, ciphertext) = self.seal(plaintext, &[]).await?;
+        let kid_len = u8::try_from(kid.len()).map_err(|_| {
+            GraphError::CryptoError(format!("Encryption key id '{kid}' is too long"))
+        })?;
+
+        let mut out =
+            Vec::with_capacity(BLOB_MAGIC.len() + 1 + kid.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(BLOB_MAGIC);
+        out.push(kid_len);
+        out.extend_from_slice(kid.as_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Decrypt bytes from [`encrypt_bytes`](Self::encrypt_bytes). Unencrypted
+    /// input is returned unchanged.
+    pub async fn decrypt_bytes(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let Some(rest) = data.strip_prefix(BLOB_MAGIC) else {
+            return Ok(data.to_vec());
+        };
+        let malformed = || GraphError::CryptoError("Malformed encrypted blob".to_string());
+
+        let (&kid_len, rest) = rest.split_first().ok_or_else(malformed)?;
+        let (kid, rest) = rest
+            .split_at_checked(kid_len as usize)
+            .ok_or_else(malformed)?;
+        let (nonce, ciphertext) = rest.split_at_checked(NONCE_LEN).ok_or_else(malformed)?;
+        let kid = std::str::from_utf8(kid).map_err(|_| malformed())?;
+        self.open(kid, nonce, ciphertext, &[]).await
+    }
+
+    /// Associated data binding a value to session `session_id` and, when only
+    /// some keys are encrypted, to the context `key` it is stored under.
+    pub fn associated_data(session_id: &str, key: Option<&str>) -> Vec<u8> {
+        let mut aad = format!("{AAD_DOMAIN}\0{session_id}").into_bytes();
+        if let Some(key) = key {
+            aad.push(0);
+            aad.extend_from_slice(key.as_bytes());
+        }
+        aad
+    }
+
+    async fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<(String, Vec<u8>, Vec<u8>)> {
+        let (kid, key) = self.provider.current_key().await?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| GraphError::CryptoError("Encryption failed".to_string()))?;
+        Ok((kid, nonce.to_vec(), ciphertext))
+    }
+
+    async fn open(
+        &self,
+        kid: &str,
+        nonce: &[u8],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>> {
+        let key =
+            self.provider.key(kid).await?.ok_or_else(|| {
+                GraphError::CryptoError(format!("Unknown encryption key '{kid}'"))
+            })?;
+        if nonce.len() != NONCE_LEN {
+            return Err(GraphError::CryptoError(
+                "Malformed encrypted value: bad nonce length".to_string(),
+            ));
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| {
+                GraphError::CryptoError(format!(
+                    "Failed to decrypt value with key '{kid}': wrong key, session or corrupted data"
+                ))
+            })
+    }
+
+    fn envelope(value: &Value) -> Option<Envelope> {
+        let object = value.as_object()?;
+        if object.len() != 1 {
+            return None;
+        }
+        serde_json::from_value(object.get(ENCRYPTED_MARKER)?.clone()).ok()
+    }
+}
+
+/// Blob store wrapper that encrypts blob contents before passing them on.
+///
+/// Offloaded context values bypass session encryption, so wrap the blob store
+/// when large values may be sensitive:
+///
+/// ```rust,no_run
+/// # use graph_flow::{ContextCipher, EncryptedBlobStore, FileBlobStore, StaticKeyProvider};
+/// # use std::sync::Arc;
+/// # async fn example() -> graph_flow::Result<()> {
+/// let cipher = ContextCipher::new(Arc::new(StaticKeyProvider::from_env("CONTEXT_KEYS")?));
+/// let blobs = EncryptedBlobStore::new(Arc::new(FileBlobStore::new("blobs").await?), cipher);
+/// # Ok(())
+/// # }
+/// ```
+pub struct EncryptedBlobStore {
+    inner: Arc<dyn BlobStore>,
+    cipher: ContextCipher,
+}
+
+impl EncryptedBlobStore {
+    pub fn new(inner: Arc<dyn BlobStore>, cipher: ContextCipher) -> Self {
+        Self { inner, cipher }
+    }
+}
+
+#[async_trait]
+impl BlobStore for EncryptedBlobStore {
+    async fn put(&self, data: Vec<u8>) -> Result<String> {
+        let sealed = self.cipher.encrypt_bytes(&data).await?;
+        self.inner.put(sealed).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        match self.inner.get(id).await? {
+            Some(data) => Ok(Some(self.cipher.decrypt_bytes(&data).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        self.inner.delete(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryBlobStore;
+
+    fn provider(id: &str, byte: u8) -> StaticKeyProvider {
+        StaticKeyProvider::new(id, [byte; KEY_LEN])
+    }
+
+    #[tokio::test]
+    async fn whole_context_round_trip() {
+        let cipher = ContextCipher::new(Arc::new(provider("k1", 1)));
+        let context = Context::new();
+        context.set("diagnosis", "confidential").await;
+        context.add_user_message("my symptoms".to_string()).await;
+
+        let stored = cipher.encrypt_context("s1", &context).await.unwrap();
+        let text = stored.to_string();
+        assert!(!text.contains("confidential") && !text.contains("symptoms"));
+
+        let restored = cipher.decrypt_context("s1", stored).await.unwrap();
+        assert_eq!(
+            restored.get::<String>("diagnosis").await.as_deref(),
+            Some("confidential")
+        );
+        assert_eq!(restored.chat_history_len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn plaintext_contexts_are_still_readable() {
+        let cipher = ContextCipher::new(Arc::new(provider("k1", 1)));
+        let context = Context::new();
+        context.set("legacy", 1).await;
+
+        let plaintext = serde_json::to_value(&context).unwrap();
+        assert!(cipher.needs_rotation(&plaintext).await.unwrap());
+        let restored = cipher.decrypt_context("s1", plaintext).await.unwrap();
+        assert_eq!(restored.get::<i32>("legacy").await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn rotation_keeps_old_values_readable() {
+        let old = ContextCipher::for_keys(Arc::new(provider("k1", 1)), ["secret"]);
+        let context = Context::new();
+        context.set("secret", "s3cr3t").await;
+        let stored = old.encrypt_context("s1", &context).await.unwrap();
+
+        let rotated = ContextCipher::for_keys(
+            Arc::new(provider("k2", 2).with_retired_key("k1", [1; KEY_LEN])),
+            ["secret"],
+        );
+        assert!(rotated.needs_rotation(&stored).await.unwrap());
+
+        let restored = rotated.decrypt_context("s1", stored).await.unwrap();
+        let resealed = rotated.encrypt_context("s1", &restored).await.unwrap();
+        assert_eq!(resealed["data"]["secret"][ENCRYPTED_MARKER]["kid"], "k2");
+        assert!(!rotated.needs_rotation(&resealed).await.unwrap());
+
+        // Without the retired key the old value can no longer be read
+        let forgotten = ContextCipher::new(Arc::new(provider("k2", 2)));
+        let context = old.encrypt_context("s1", &context).await.unwrap();
+        let err = forgotten.decrypt_context("s1", context).await.unwrap_err();
+        assert!(
+            matches!(err, GraphError::CryptoError(msg) if msg.contains("Unknown encryption key 'k1'"))
+        );
+    }
+
+    #[tokio::test]
+    async fn values_are_bound_to_their_session_and_key() {
+        let cipher = ContextCipher::for_keys(Arc::new(provider("k1", 1)), ["ssn", "notes"]);
+        let context = Context::new();
+        context.set("ssn", "123-45-6789").await;
+        context.set("notes", "harmless").await;
+        let stored = cipher.encrypt_context("s1", &context).await.unwrap();
+
+        // Replayed into another session
+        let err = cipher
+            .decrypt_context("s2", stored.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::CryptoError(_)));
+
+        // Moved to another key of the same session
+        let mut swapped = stored.clone();
+        swapped["data"]["notes"] = stored["data"]["ssn"].clone();
+        let err = cipher.decrypt_context("s1", swapped).await.unwrap_err();
+        assert!(matches!(err, GraphError::CryptoError(_)));
+    }
+
+    #[tokio::test]
+    async fn unbound_values_are_read_and_rotated() {
+        let cipher = ContextCipher::for_keys(Arc::new(provider("k1", 1)), ["secret"]);
+        // Sealed before values were bound: no associated data, no `bound` flag
+        let (kid, nonce, ciphertext) = cipher.seal(b"\"old\"", &[]).await.unwrap();
+        let stored = serde_json::json!({
+            "data": {
+                "secret": {
+                    ENCRYPTED_MARKER: {
+                        "kid": kid,
+                        "nonce": BASE64.encode(nonce),
+                        "ciphertext": BASE64.encode(ciphertext),
+                    }
+                }
+            },
+            "chat_history": { "messages": [], "max_messages": 1000 },
+        });
+
+        assert!(cipher.needs_rotation(&stored).await.unwrap());
+        let restored = cipher.decrypt_context("s1", stored).await.unwrap();
+        assert_eq!(
+            restored.get::<String>("secret").await.as_deref(),
+            Some("old")
+        );
+        let resealed = cipher.encrypt_context("s1", &restored).await.unwrap();
+        assert!(!cipher.needs_rotation(&resealed).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn wrong_key_material_is_rejected() {
+        let cipher = ContextCipher::new(Arc::new(provider("k1", 1)));
+        let imposter = ContextCipher::new(Arc::new(provider("k1", 9)));
+        let stored = cipher
+            .encrypt_value(&Value::from("x"), b"s1")
+            .await
+            .unwrap();
+        assert!(imposter.decrypt_value(stored, b"s1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn encrypted_blob_store_round_trip() {
+        let inner = Arc::new(InMemoryBlobStore::new());
+        let cipher = ContextCipher::new(Arc::new(provider("k1", 1)));
+        let store = EncryptedBlobStore::new(inner.clone(), cipher);
+
+        let id = store.put(b"patient text".to_vec()).await.unwrap();
+        let raw = inner.get(&id).await.unwrap().unwrap();
+        assert!(raw.starts_with(BLOB_MAGIC));
+        assert!(!raw.windows(7).any(|w| w == b"patient"));
+        assert_eq!(
+            store.get(&id).await.unwrap(),
+            Some(b"patient text".to_vec())
+        );
+    }
+
+    #[test]
+    fn parse_key_spec() {
+        let k1 = BASE64.encode([1u8; KEY_LEN]);
+        let k2 = BASE64.encode([2u8; KEY_LEN]);
+        let provider = StaticKeyProvider::parse(&format!("new:{k2}\n# retired\nold:{k1}")).unwrap();
+        assert_eq!(provider.current, "new");
+        assert_eq!(provider.keys.len(), 2);
+        assert!(!format!("{provider:?}").contains(&k1));
+
+        assert!(StaticKeyProvider::parse("").is_err());
+        assert!(StaticKeyProvider::parse("short:AAAA").is_err());
+    }
+}
@@ -20,11 +20,61 @@ pub enum GraphError {
     #[error("Storage error: {0}")]
     StorageError(String),
 
+    #[error("Encryption error: {0}")]
+    CryptoError(String),
+
     #[error("Session not found: {0}")]
     SessionNotFound(String),
 
+    #[error("Session is not waiting for input: {0}")]
+    NotWaitingForInput(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Not authorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Session was rolled back: {0}")]
+    SessionCompensated(String),
+
+    #[error("Session was saved by someone else since it was loaded: {0}")]
+    SessionConflict(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl GraphError {
+    /// Short, stable name of the variant, e.g. `"task_execution_failed"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GraphError::TaskExecutionFailed(_) => "task_execution_failed",
+            GraphError::GraphNotFound(_) => "graph_not_found",
+            GraphError::InvalidEdge(_) => "invalid_edge",
+            GraphError::TaskNotFound(_) => "task_not_found",
+            GraphError::ContextError(_) => "context_error",
+            GraphError::StorageError(_) => "storage_error",
+            GraphError::CryptoError(_) => "crypto_error",
+            GraphError::SessionNotFound(_) => "session_not_found",
+            GraphError::NotWaitingForInput(_) => "not_waiting_for_input",
+            GraphError::InvalidInput(_) => "invalid_input",
+            GraphError::Unauthorized(_) => "unauthorized",
+            GraphError::SessionCompensated(_) => "session_compensated",
+            GraphError::SessionConflict(_) => "session_conflict",
+            GraphError::Other(_) => "other",
+        }
+    }
+
+    /// Whether the error rejects what the caller sent (a decision from the wrong
+    /// reviewer, malformed input) rather than reporting a failed task. Tasks
+    /// returning one leave the session where it was, waiting for a valid caller.
+    pub(crate) fn is_caller_error(&self) -> bool {
+        matches!(
+            self,
+            GraphError::Unauthorized(_) | GraphError::InvalidInput(_)
+        )
+    }
+}
+
 pub type Result<T> = std::result::Result<T, GraphError>;
@@ -17,7 +17,18 @@
 //! - By default, all children share the same `Context` (concurrent writes must be
 //!   coordinated by the user). To avoid key collisions, you can set a prefix so that
 //!   each child’s output is stored under `"<prefix>.<child_id>.*"`.
-//! - Error policy is conservative: if any child fails, `FanOutTask` fails.
+//! - Alternatively, `with_merge_strategy` runs each child on its own `Context::fork()`
+//!   and merges the forks back in declaration order once every child has succeeded,
+//!   so the outcome no longer depends on scheduling.
+//! - Error policy is conservative by default: every child runs to completion and
+//!   `FanOutTask` fails, reporting every child error, if any of them failed.
+//!   `with_failure_policy` selects fail-fast, continue-on-error or quorum behaviour
+//!   instead; see [`FailurePolicy`].
+//! - `with_max_concurrency` caps how many children run at once, and
+//!   `with_child_timeout` fails a child that runs too long.
+//! - Besides the per-child keys, a [`FanOutResults`] record describing every child
+//!   (status, response, error, duration and `NextAction`) is stored under
+//!   `"<prefix>.results"`. `with_reducer` folds that record into a single typed value.
 //!
 //! Example:
 //! ```rust
@@ -55,23 +66,164 @@
 //! let _ = fan.run(ctx.clone()).await?;
 //! // Aggregated entries under prefix:
 //! // fanout.child_a.response, fanout.child_b.response
+//! let results: graph_flow::FanOutResults = ctx.get(&fan.results_key()).await.unwrap();
+//! assert_eq!(results.succeeded().count(), 2);
 //! # Ok(())
 //! # }
 //! ```
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 
-use crate::{Context, Result, Task, TaskResult, NextAction, GraphError};
+use crate::{Context, ContextKey, GraphError, MergeStrategy, NextAction, Result, Task, TaskResult};
+
+/// What a [`FanOutTask`] does when a child fails.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Fail at the first child error and abort the children still running
+    FailFast,
+    /// Let every child finish, then fail with all child errors if any occurred
+    #[default]
+    CollectAll,
+    /// Never fail; each failed child's error is stored under `<prefix>.<child_id>.error`
+    ContinueOnError,
+    /// Succeed as soon as this many children have succeeded, aborting the rest.
+    /// Fails once that many successes are no longer possible.
+    Quorum(usize),
+}
+
+impl FailurePolicy {
+    /// Whether the outcome is already decided, so unfinished children can be aborted.
+    pub(crate) fn settled(&self, succeeded: usize, failed: usize, outstanding: usize) -> bool {
+        match *self {
+            Self::FailFast => failed > 0,
+            Self::CollectAll | Self::ContinueOnError => false,
+            Self::Quorum(required) => succeeded >= required || succeeded + outstanding < required,
+        }
+    }
+
+    /// Whether the run as a whole failed.
+    pub(crate) fn failed(&self, succeeded: usize, failed: usize) -> bool {
+        match *self {
+            Self::FailFast | Self::CollectAll => failed > 0,
+            Self::ContinueOnError => false,
+            Self::Quorum(required) => succeeded < required,
+        }
+    }
+}
+
+/// Run time of a child and either its result or its failure status and message.
+pub(crate) type ChildOutcome = (
+    Duration,
+    std::result::Result<TaskResult, (ChildStatus, String)>,
+);
+
+/// Run `child` on `ctx`, failing it with [`ChildStatus::TimedOut`] after `timeout`.
+pub(crate) async fn run_child(
+    child: Arc<dyn Task>,
+    ctx: Context,
+    timeout: Option<Duration>,
+) -> ChildOutcome {
+    let started = Instant::now();
+    let outcome = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, child.run(ctx)).await {
+            Ok(outcome) => outcome.map_err(|e| (ChildStatus::Failed, e.to_string())),
+            Err(_) => Err((
+                ChildStatus::TimedOut,
+                GraphError::TaskExecutionFailed(format!("timed out after {:?}", limit)).to_string(),
+            )),
+        },
+        None => child
+            .run(ctx)
+            .await
+            .map_err(|e| (ChildStatus::Failed, e.to_string())),
+    };
+    (started.elapsed(), outcome)
+}
+
+/// How a single child of a [`FanOutTask`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildStatus {
+    /// The child returned a `TaskResult`
+    Succeeded,
+    /// The child returned an error
+    Failed,
+    /// The child exceeded the timeout set with [`FanOutTask::with_child_timeout`]
+    TimedOut,
+    /// The child was aborted, or never started, because the [`FailurePolicy`]
+    /// had already decided the outcome
+    Cancelled,
+}
+
+/// Outcome of one child of a [`FanOutTask`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChildResult {
+    pub child_id: String,
+    pub status: ChildStatus,
+    pub response: Option<String>,
+    pub status_message: Option<String>,
+    pub error: Option<String>,
+    /// Wall-clock run time; `None` for cancelled children
+    pub duration_ms: Option<u64>,
+    /// The `NextAction` the child returned (ignored by the fan-out itself)
+    pub next_action: Option<NextAction>,
+}
+
+impl ChildResult {
+    pub fn is_success(&self) -> bool {
+        self.status == ChildStatus::Succeeded
+    }
+}
+
+/// Per-child results of a [`FanOutTask`] run, in child declaration order.
+///
+/// Stored in the context under [`FanOutTask::results_key`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FanOutResults {
+    pub fanout_id: String,
+    pub children: Vec<ChildResult>,
+}
+
+impl FanOutResults {
+    /// The result of the child with the given id.
+    pub fn get(&self, child_id: &str) -> Option<&ChildResult> {
+        self.children.iter().find(|c| c.child_id == child_id)
+    }
+
+    /// Children that succeeded.
+    pub fn succeeded(&self) -> impl Iterator<Item = &ChildResult> {
+        self.children.iter().filter(|c| c.is_success())
+    }
+
+    /// Children that failed or timed out.
+    pub fn failed(&self) -> impl Iterator<Item = &ChildResult> {
+        self.children
+            .iter()
+            .filter(|c| matches!(c.status, ChildStatus::Failed | ChildStatus::TimedOut))
+    }
+}
+
+/// Type-erased reducer installed with [`FanOutTask::with_reducer`].
+type FanOutReducer = Arc<dyn Fn(&FanOutResults) -> Result<Value> + Send + Sync>;
 
 /// Composite task that executes multiple child tasks concurrently and aggregates results.
 #[derive(Clone)]
 pub struct FanOutTask {
     id: String,
-    children: Vec<Arc<dyn Task>>, // executed in parallel
-    prefix: Option<String>,        // context aggregation prefix
-    next_action: NextAction,       // default: Continue
+    children: Vec<Arc<dyn Task>>,          // executed in parallel
+    prefix: Option<String>,                // context aggregation prefix
+    next_action: NextAction,               // default: Continue
+    merge_strategy: Option<MergeStrategy>, // isolate children when set
+    max_concurrency: Option<usize>,        // default: all children at once
+    child_timeout: Option<Duration>,       // default: no timeout
+    failure_policy: FailurePolicy,         // default: CollectAll
+    reducer: Option<(&'static str, FanOutReducer)>, // output key and reducer
 }
 
 impl FanOutTask {
@@ -82,6 +234,11 @@ impl FanOutTask {
             children,
             prefix: None,
             next_action: NextAction::Continue,
+            merge_strategy: None,
+            max_concurrency: None,
+            child_timeout: None,
+            failure_policy: FailurePolicy::default(),
+            reducer: None,
         })
     }
 
@@ -99,6 +256,57 @@ impl FanOutTask {
         self
     }
 
+    /// Run each child on an isolated fork of the context and merge the forks back
+    /// with `strategy`, in child declaration order, after all children succeed.
+    ///
+    /// If any child fails, none of the children's context writes are applied.
+    pub fn with_merge_strategy(mut self: Arc<Self>, strategy: MergeStrategy) -> Arc<Self> {
+        Arc::make_mut(&mut self).merge_strategy = Some(strategy);
+        self
+    }
+
+    /// Run at most `limit` children at a time; the rest start, in declaration
+    /// order, as running ones finish.
+    pub fn with_max_concurrency(mut self: Arc<Self>, limit: usize) -> Arc<Self> {
+        Arc::make_mut(&mut self).max_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// Fail a child that hasn't finished within `timeout`.
+    pub fn with_child_timeout(mut self: Arc<Self>, timeout: Duration) -> Arc<Self> {
+        Arc::make_mut(&mut self).child_timeout = Some(timeout);
+        self
+    }
+
+    /// Choose how child failures are handled (default: [`FailurePolicy::CollectAll`]).
+    pub fn with_failure_policy(mut self: Arc<Self>, policy: FailurePolicy) -> Arc<Self> {
+        Arc::make_mut(&mut self).failure_policy = policy;
+        self
+    }
+
+    /// Combine the [`FanOutResults`] into a single value stored under `key`.
+    ///
+    /// The reducer runs after the children's context writes have been applied,
+    /// only when the fan-out as a whole succeeds. A reducer error fails the fan-out.
+    pub fn with_reducer<T, F>(mut self: Arc<Self>, key: ContextKey<T>, reducer: F) -> Arc<Self>
+    where
+        T: Serialize + 'static,
+        F: Fn(&FanOutResults) -> Result<T> + Send + Sync + 'static,
+    {
+        let reducer: FanOutReducer = Arc::new(move |results| {
+            serde_json::to_value(reducer(results)?).map_err(|e| {
+                GraphError::ContextError(format!("failed to serialize reduced value: {e}"))
+            })
+        });
+        Arc::make_mut(&mut self).reducer = Some((key.name(), reducer));
+        self
+    }
+
+    /// Context key under which the [`FanOutResults`] are stored: `<prefix>.results`.
+    pub fn results_key(&self) -> String {
+        format!("{}.results", self.prefix.as_deref().unwrap_or("fanout"))
+    }
+
     fn key(&self, child_id: &str, field: &str) -> String {
         if let Some(p) = &self.prefix {
             format!("{}.{}.{}", p, child_id, field)
@@ -110,64 +318,179 @@ impl FanOutTask {
 
 #[async_trait]
 impl Task for FanOutTask {
-    fn id(&self) -> &str { &self.id }
+    fn id(&self) -> &str {
+        &self.id
+    }
 
     async fn run(&self, context: Context) -> Result<TaskResult> {
-        let mut set = JoinSet::new();
+        // Each child runs on a fork when isolation is requested
+        let forks: Vec<Context> = self
+            .children
+            .iter()
+            .map(|_| match self.merge_strategy {
+                Some(_) => context.fork(),
+                None => context.clone(),
+            })
+            .collect();
 
-        // Spawn children concurrently
-        for child in &self.children {
+        let mut pending = self.children.iter().zip(forks.iter()).enumerate();
+        let mut set = JoinSet::new();
+        let mut running: HashMap<tokio::task::Id, usize> = HashMap::new();
+        let mut spawn_next = |set: &mut JoinSet<_>, running: &mut HashMap<_, _>| {
+            let Some((index, (child, ctx))) = pending.next() else {
+                return;
+            };
             let child = child.clone();
-            let ctx = context.clone();
-            set.spawn(async move {
-                let cid = child.id().to_string();
-                let res = child.run(ctx.clone()).await;
-                (cid, res)
-            });
+            let ctx = ctx.clone();
+            let child_timeout = self.child_timeout;
+            let handle = set.spawn(run_child(child, ctx, child_timeout));
+            running.insert(handle.id(), index);
+        };
+
+        let limit = self.max_concurrency.unwrap_or(self.children.len());
+        for _ in 0..limit {
+            spawn_next(&mut set, &mut running);
         }
 
-        let mut had_error = None;
-        let mut completed = 0usize;
+        let mut results: Vec<(usize, TaskResult)> = Vec::new();
+        let mut errors: Vec<(usize, String)> = Vec::new();
+        let mut records: Vec<Option<ChildResult>> = vec![None; self.children.len()];
 
-        while let Some(joined) = set.join_next().await {
-            match joined {
-                Err(join_err) => {
-                    had_error = Some(GraphError::TaskExecutionFailed(format!(
-                        "FanOut child join error: {}", join_err
-                    )));
+        while let Some(joined) = set.join_next_with_id().await {
+            let (task_id, elapsed, outcome) = match joined {
+                Ok((task_id, (elapsed, outcome))) => (task_id, Some(elapsed), outcome),
+                Err(join_err) => (
+                    join_err.id(),
+                    None,
+                    Err((ChildStatus::Failed, format!("join error: {}", join_err))),
+                ),
+            };
+            let index = running
+                .remove(&task_id)
+                .expect("every spawned child is tracked");
+            let mut record = ChildResult {
+                child_id: self.children[index].id().to_string(),
+                status: ChildStatus::Succeeded,
+                response: None,
+                status_message: None,
+                error: None,
+                duration_ms: elapsed.map(|d| d.as_millis() as u64),
+                next_action: None,
+            };
+            match outcome {
+                Ok(tr) => {
+                    record.response = tr.response.clone();
+                    record.status_message = tr.status_message.clone();
+                    record.next_action = Some(tr.next_action.clone());
+                    results.push((index, tr));
                 }
-                Ok((child_id, outcome)) => match outcome {
-                    Err(e) => {
-                        had_error = Some(GraphError::TaskExecutionFailed(format!(
-                            "FanOut child '{}' failed: {}", child_id, e
-                        )));
-                    }
-                    Ok(tr) => {
-                        // Store child outputs under prefixed keys
-                        if let Some(resp) = tr.response.clone() {
-                            context.set(self.key(&child_id, "response"), resp).await;
-                        }
-                        if let Some(status) = tr.status_message.clone() {
-                            context.set(self.key(&child_id, "status"), status).await;
-                        }
-                        // Always store the reported next_action for diagnostics
-                        context
-                            .set(self.key(&child_id, "next_action"), format!("{:?}", tr.next_action))
-                            .await;
-                        completed += 1;
-                    }
-                },
+                Err((status, message)) => {
+                    record.status = status;
+                    record.error = Some(message.clone());
+                    errors.push((index, message));
+                }
+            }
+            records[index] = Some(record);
+
+            let outstanding = self.children.len() - results.len() - errors.len();
+            if self
+                .failure_policy
+                .settled(results.len(), errors.len(), outstanding)
+            {
+                // Make sure aborted children have stopped before touching the context
+                set.abort_all();
+                while set.join_next().await.is_some() {}
+                break;
+            }
+            spawn_next(&mut set, &mut running);
+        }
+
+        // Children without a record were aborted or never started
+        let report = FanOutResults {
+            fanout_id: self.id.clone(),
+            children: records
+                .into_iter()
+                .zip(&self.children)
+                .map(|(record, child)| {
+                    record.unwrap_or_else(|| ChildResult {
+                        child_id: child.id().to_string(),
+                        status: ChildStatus::Cancelled,
+                        response: None,
+                        status_message: None,
+                        error: None,
+                        duration_ms: None,
+                        next_action: None,
+                    })
+                })
+                .collect(),
+        };
+        context.set(self.results_key(), &report).await;
+
+        if self.failure_policy.failed(results.len(), errors.len()) {
+            errors.sort_by_key(|(index, _)| *index);
+            let details = errors
+                .iter()
+                .map(|(index, message)| {
+                    format!("child '{}' failed: {}", self.children[*index].id(), message)
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "FanOut '{}' failed ({} of {} child task(s) succeeded): {}",
+                self.id,
+                results.len(),
+                self.children.len(),
+                details
+            )));
+        }
+
+        // Merge and report in child declaration order
+        results.sort_by_key(|(index, _)| *index);
+        errors.sort_by_key(|(index, _)| *index);
+
+        if let Some(strategy) = &self.merge_strategy {
+            // Merge into a staging fork first so a conflict leaves the context untouched
+            let staging = context.fork();
+            for (index, _) in &results {
+                staging.merge(&forks[*index], strategy.clone()).await?;
+            }
+            context
+                .merge(&staging, MergeStrategy::LastWriterWins)
+                .await?;
+        }
+
+        let completed = results.len();
+        for (index, tr) in results {
+            let child_id = self.children[index].id();
+            // Store child outputs under prefixed keys
+            if let Some(resp) = tr.response {
+                context.set(self.key(child_id, "response"), resp).await;
+            }
+            if let Some(status) = tr.status_message {
+                context.set(self.key(child_id, "status"), status).await;
             }
+            // Always store the reported next_action for diagnostics
+            context
+                .set(self.key(child_id, "next_action"), &tr.next_action)
+                .await;
+        }
+        for (index, message) in &errors {
+            context
+                .set(self.key(self.children[*index].id(), "error"), message)
+                .await;
         }
 
-        if let Some(err) = had_error {
-            return Err(err);
+        if let Some((key, reducer)) = &self.reducer {
+            context.set(*key, reducer(&report)?).await;
         }
 
-        let summary = format!(
+        let mut summary = format!(
             "FanOutTask '{}' completed {} child task(s)",
             self.id, completed
         );
+        if !errors.is_empty() {
+            summary.push_str(&format!(", {} failed", errors.len()));
+        }
 
         Ok(TaskResult::new_with_status(
             Some(summary.clone()),
@@ -181,26 +504,41 @@ impl Task for FanOutTask {
 mod tests {
     use super::*;
     use async_trait::async_trait;
-    use tokio::time::{sleep, Duration};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::{Duration, sleep};
 
-    struct OkTask { name: &'static str }
-    struct FailingTask { name: &'static str }
+    struct OkTask {
+        name: &'static str,
+    }
+    struct FailingTask {
+        name: &'static str,
+    }
 
     #[async_trait]
     impl Task for OkTask {
-        fn id(&self) -> &str { self.name }
+        fn id(&self) -> &str {
+            self.name
+        }
         async fn run(&self, ctx: Context) -> Result<TaskResult> {
             ctx.set(format!("out.{}", self.name), true).await;
             sleep(Duration::from_millis(10)).await;
-            Ok(TaskResult::new(Some(format!("{} ok", self.name)), NextAction::End))
+            Ok(TaskResult::new(
+                Some(format!("{} ok", self.name)),
+                NextAction::End,
+            ))
         }
     }
 
     #[async_trait]
     impl Task for FailingTask {
-        fn id(&self) -> &str { self.name }
+        fn id(&self) -> &str {
+            self.name
+        }
         async fn run(&self, _ctx: Context) -> Result<TaskResult> {
-            Err(GraphError::TaskExecutionFailed(format!("{} failed", self.name)))
+            Err(GraphError::TaskExecutionFailed(format!(
+                "{} failed",
+                self.name
+            )))
         }
     }
 
@@ -221,8 +559,63 @@ mod tests {
         assert_eq!(br, Some("b ok".to_string()));
 
         // also store next_action diagnostic
-        let an: Option<String> = ctx.get("agg.a.next_action").await;
-        assert_eq!(an, Some(format!("{:?}", NextAction::End)));
+        let an: Option<NextAction> = ctx.get("agg.a.next_action").await;
+        assert_eq!(an, Some(NextAction::End));
+    }
+
+    struct WriterTask {
+        name: &'static str,
+        delay_ms: u64,
+    }
+
+    #[async_trait]
+    impl Task for WriterTask {
+        fn id(&self) -> &str {
+            self.name
+        }
+        async fn run(&self, ctx: Context) -> Result<TaskResult> {
+            sleep(Duration::from_millis(self.delay_ms)).await;
+            ctx.set("winner", self.name).await;
+            Ok(TaskResult::new(None, NextAction::End))
+        }
+    }
+
+    #[tokio::test]
+    async fn fanout_isolated_children_merge_in_order() {
+        // "a" finishes last but is declared first, so "b" is merged last and wins
+        let a: Arc<dyn Task> = Arc::new(WriterTask {
+            name: "a",
+            delay_ms: 30,
+        });
+        let b: Arc<dyn Task> = Arc::new(WriterTask {
+            name: "b",
+            delay_ms: 0,
+        });
+        let fan =
+            FanOutTask::new("fan", vec![a, b]).with_merge_strategy(MergeStrategy::LastWriterWins);
+
+        let ctx = Context::new();
+        fan.run(ctx.clone()).await.unwrap();
+        assert_eq!(ctx.get::<String>("winner").await, Some("b".to_string()));
+    }
+
+    #[tokio::test]
+    async fn fanout_isolated_conflict_fails_without_writes() {
+        let a: Arc<dyn Task> = Arc::new(WriterTask {
+            name: "a",
+            delay_ms: 0,
+        });
+        let b: Arc<dyn Task> = Arc::new(WriterTask {
+            name: "b",
+            delay_ms: 0,
+        });
+        let fan =
+            FanOutTask::new("fan", vec![a, b]).with_merge_strategy(MergeStrategy::ErrorOnConflict);
+
+        let ctx = Context::new();
+        let err = fan.run(ctx.clone()).await.err().unwrap();
+        assert!(matches!(err, GraphError::ContextError(_)));
+        assert_eq!(ctx.get::<String>("winner").await, None);
     }
 
     #[tokio::test]
@@ -238,4 +631,210 @@ mod tests {
             other => panic!("Unexpected error variant: {other:?}"),
         }
     }
+
+    struct SlowTask {
+        name: &'static str,
+        delay_ms: u64,
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Task for SlowTask {
+        fn id(&self) -> &str {
+            self.name
+        }
+        async fn run(&self, ctx: Context) -> Result<TaskResult> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            sleep(Duration::from_millis(self.delay_ms)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            ctx.set(format!("out.{}", self.name), true).await;
+            Ok(TaskResult::new(
+                Some(format!("{} ok", self.name)),
+                NextAction::End,
+            ))
+        }
+    }
+
+    fn slow_children(delays: &[(&'static str, u64)]) -> (Vec<Arc<dyn Task>>, Arc<AtomicUsize>) {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let children = delays
+            .iter()
+            .map(|&(name, delay_ms)| {
+                Arc::new(SlowTask {
+                    name,
+                    delay_ms,
+                    running: running.clone(),
+                    peak: peak.clone(),
+                }) as Arc<dyn Task>
+            })
+            .collect();
+        (children, peak)
+    }
+
+    #[tokio::test]
+    async fn fanout_respects_max_concurrency() {
+        let (children, peak) = slow_children(&[("a", 20), ("b", 20), ("c", 20), ("d", 20)]);
+        let fan = FanOutTask::new("fan", children).with_max_concurrency(2);
+
+        let ctx = Context::new();
+        fan.run(ctx.clone()).await.unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        for name in ["a", "b", "c", "d"] {
+            assert_eq!(ctx.get::<bool>(&format!("out.{name}")).await, Some(true));
+        }
+    }
+
+    #[tokio::test]
+    async fn fanout_collects_every_error_and_times_out_children() {
+        let (mut children, _) = slow_children(&[("slow", 1_000)]);
+        children.push(Arc::new(FailingTask { name: "bad" }));
+        children.push(Arc::new(OkTask { name: "a" }));
+        let fan = FanOutTask::new("fan", children).with_child_timeout(Duration::from_millis(50));
+
+        let err = fan.run(Context::new()).await.err().unwrap();
+        let GraphError::TaskExecutionFailed(msg) = err else {
+            panic!("unexpected error: {err:?}")
+        };
+        assert!(msg.contains("1 of 3"), "{msg}");
+        assert!(
+            msg.contains("child 'slow' failed") && msg.contains("timed out after 50ms"),
+            "{msg}"
+        );
+        assert!(msg.contains("child 'bad' failed"), "{msg}");
+    }
+
+    #[tokio::test]
+    async fn fanout_fail_fast_aborts_siblings() {
+        let (mut children, _) = slow_children(&[("slow", 100)]);
+        children.push(Arc::new(FailingTask { name: "bad" }));
+        let fan = FanOutTask::new("fan", children).with_failure_policy(FailurePolicy::FailFast);
+
+        let ctx = Context::new();
+        assert!(fan.run(ctx.clone()).await.is_err());
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(ctx.get::<bool>("out.slow").await, None);
+    }
+
+    #[tokio::test]
+    async fn fanout_continue_on_error_records_failures() {
+        let a: Arc<dyn Task> = Arc::new(OkTask { name: "a" });
+        let f: Arc<dyn Task> = Arc::new(FailingTask { name: "bad" });
+        let fan = FanOutTask::new("fan", vec![a, f])
+            .with_prefix("agg")
+            .with_failure_policy(FailurePolicy::ContinueOnError);
+
+        let ctx = Context::new();
+        let res = fan.run(ctx.clone()).await.unwrap();
+        assert!(res.response.unwrap().contains("1 failed"));
+        assert_eq!(
+            ctx.get::<String>("agg.a.response").await,
+            Some("a ok".to_string())
+        );
+        let error: String = ctx.get("agg.bad.error").await.unwrap();
+        assert!(error.contains("bad failed"));
+    }
+
+    #[tokio::test]
+    async fn fanout_quorum_stops_early_or_fails_when_unreachable() {
+        let (mut children, _) = slow_children(&[("a", 0), ("b", 10), ("slow", 1_000)]);
+        children.push(Arc::new(FailingTask { name: "bad" }));
+        let fan =
+            FanOutTask::new("fan", children.clone()).with_failure_policy(FailurePolicy::Quorum(2));
+
+        let ctx = Context::new();
+        let started = std::time::Instant::now();
+        fan.run(ctx.clone()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(ctx.get::<bool>("out.b").await, Some(true));
+        assert_eq!(ctx.get::<String>("fanout.slow.response").await, None);
+
+        let fan = FanOutTask::new("fan", children)
+            .with_child_timeout(Duration::from_millis(50))
+            .with_failure_policy(FailurePolicy::Quorum(3));
+        let err = fan.run(Context::new()).await.err().unwrap();
+        assert!(err.to_string().contains("2 of 4"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn fanout_stores_structured_results() {
+        let (mut children, _) = slow_children(&[("slow", 1_000)]);
+        children.push(Arc::new(FailingTask { name: "bad" }));
+        children.push(Arc::new(OkTask { name: "a" }));
+        children.push(Arc::new(OkTask { name: "b" }));
+        let fan = FanOutTask::new("fan", children)
+            .with_prefix("agg")
+            .with_failure_policy(FailurePolicy::Quorum(2));
+
+        let ctx = Context::new();
+        fan.run(ctx.clone()).await.unwrap();
+
+        let results: FanOutResults = ctx.get(&fan.results_key()).await.unwrap();
+        assert_eq!(fan.results_key(), "agg.results");
+        assert_eq!(results.fanout_id, "fan");
+        let ids: Vec<_> = results
+            .children
+            .iter()
+            .map(|c| c.child_id.as_str())
+            .collect();
+        assert_eq!(ids, ["slow", "bad", "a", "b"]);
+
+        let a = results.get("a").unwrap();
+        assert_eq!(a.status, ChildStatus::Succeeded);
+        assert_eq!(a.response.as_deref(), Some("a ok"));
+        assert_eq!(a.next_action, Some(NextAction::End));
+        assert!(a.duration_ms.unwrap() >= 10);
+
+        let bad = results.get("bad").unwrap();
+        assert_eq!(bad.status, ChildStatus::Failed);
+        assert!(bad.error.as_deref().unwrap().contains("bad failed"));
+        assert_eq!(results.get("slow").unwrap().status, ChildStatus::Cancelled);
+        assert_eq!(results.succeeded().count(), 2);
+        assert_eq!(results.failed().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn fanout_results_are_stored_on_failure() {
+        let (mut children, _) = slow_children(&[("slow", 1_000)]);
+        children.push(Arc::new(OkTask { name: "a" }));
+        let fan = FanOutTask::new("fan", children).with_child_timeout(Duration::from_millis(20));
+
+        let ctx = Context::new();
+        assert!(fan.run(ctx.clone()).await.is_err());
+        let results: FanOutResults = ctx.get("fanout.results").await.unwrap();
+        assert_eq!(results.get("slow").unwrap().status, ChildStatus::TimedOut);
+        assert_eq!(results.get("a").unwrap().status, ChildStatus::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn fanout_reducer_combines_child_outputs() {
+        const ANSWERS: ContextKey<Vec<String>> = ContextKey::new("answers");
+        let a: Arc<dyn Task> = Arc::new(OkTask { name: "a" });
+        let f: Arc<dyn Task> = Arc::new(FailingTask { name: "bad" });
+        let b: Arc<dyn Task> = Arc::new(OkTask { name: "b" });
+        let fan = FanOutTask::new("fan", vec![a, f, b])
+            .with_failure_policy(FailurePolicy::ContinueOnError)
+            .with_reducer(ANSWERS, |results| {
+                Ok(results
+                    .succeeded()
+                    .filter_map(|c| c.response.clone())
+                    .collect())
+            });
+
+        let ctx = Context::new();
+        fan.run(ctx.clone()).await.unwrap();
+        assert_eq!(
+            ctx.get_key(ANSWERS).await.unwrap(),
+            Some(vec!["a ok".to_string(), "b ok".to_string()])
+        );
+
+        let failing = FanOutTask::new("fan", vec![Arc::new(OkTask { name: "a" }) as Arc<dyn Task>])
+            .with_reducer(ANSWERS, |_| -> Result<Vec<String>> {
+                Err(GraphError::TaskExecutionFailed("cannot reduce".into()))
+            });
+        let err = failing.run(Context::new()).await.err().unwrap();
+        assert!(err.to_string().contains("cannot reduce"));
+    }
 }
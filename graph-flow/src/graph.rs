@@ -1,15 +1,64 @@
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 
 use crate::{
-    context::Context,
+    compensation::{COMPENSATION_KEY, Compensation, CompensationRecord},
+    context::{Checkpoint, Context, MergeStrategy},
     error::{GraphError, Result},
-    storage::Session,
+    input::InputRequest,
+    storage::{
+        self, BranchState, BranchStatus, Breakpoint, ParallelState, PendingInput, Session,
+        SessionTimer, StepWriteSet,
+    },
     task::{NextAction, Task, TaskResult},
 };
 
+/// Context key set to `true` while a task runs because its session's timer fired
+/// (see [`NextAction::WaitUntil`] and [`NextAction::Sleep`]).
+pub const TIMER_FIRED_KEY: &str = "graph_flow.timer_fired";
+
+/// Context key holding the input delivered by [`Graph::resume_session`] or
+/// [`FlowRunner::resume`](crate::FlowRunner::resume).
+pub const INPUT_KEY: &str = "graph_flow.input";
+
+/// Context key holding the [`TaskFailure`] handed to an error edge or to the
+/// graph's error handler.
+pub const FAILURE_KEY: &str = "graph_flow.failure";
+
+/// Most error routes followed without a task succeeding in between
+const MAX_ERROR_ROUTES: usize = 16;
+
+/// Most failures in a row of one task that are routed, counted by
+/// [`TaskFailure::attempts`] across calls
+const MAX_ROUTED_ATTEMPTS: u32 = 16;
+
+/// A failed task, written under [`FAILURE_KEY`] before execution moves on to the
+/// task's error edge or the graph's error handler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskFailure {
+    /// Task that failed
+    pub task_id: String,
+    /// [`GraphError::kind`] of the task's error, or `"timeout"`
+    pub kind: String,
+    pub message: String,
+    /// Consecutive failures of this task, including this one
+    pub attempts: u32,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// A failed step, before it is reported as a [`GraphError`].
+struct StepFailure {
+    kind: &'static str,
+    message: String,
+    error: GraphError,
+}
+
 /// Type alias for edge condition functions
 pub type EdgeCondition = Arc<dyn Fn(&Context) -> bool + Send + Sync>;
 
@@ -21,11 +70,26 @@ pub struct Edge {
     pub condition: Option<EdgeCondition>,
 }
 
+/// How many branches of a fork must reach a join node before it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinPolicy {
+    /// Wait until no branch is still running or waiting for input
+    All,
+    /// Run once this many branches have arrived; the others are cancelled
+    AtLeast(usize),
+}
+
 /// A graph of tasks that can be executed
 pub struct Graph {
     pub id: String,
     tasks: DashMap<String, Arc<dyn Task>>,
     edges: Mutex<Vec<Edge>>,
+    forks: Mutex<HashMap<String, Vec<String>>>,
+    joins: Mutex<HashMap<String, JoinPolicy>>,
+    branch_merge: Mutex<MergeStrategy>,
+    breakpoints: Mutex<Vec<Breakpoint>>,
+    error_edges: Mutex<HashMap<String, String>>,
+    error_handler: Mutex<Option<String>>,
     start_task_id: Mutex<Option<String>>,
     task_timeout: Duration,
 }
@@ -36,11 +100,17 @@ impl Graph {
             id: id.into(),
             tasks: DashMap::new(),
             edges: Mutex::new(Vec::new()),
+            forks: Mutex::new(HashMap::new()),
+            joins: Mutex::new(HashMap::new()),
+            branch_merge: Mutex::new(MergeStrategy::ErrorOnConflict),
+            breakpoints: Mutex::new(Vec::new()),
+            error_edges: Mutex::new(HashMap::new()),
+            error_handler: Mutex::new(None),
             start_task_id: Mutex::new(None),
             task_timeout: Duration::from_secs(300), // Default 5 minute timeout
         }
     }
-    
+
     /// Set the timeout duration for task execution
     pub fn set_task_timeout(&mut self, timeout: Duration) {
         self.task_timeout = timeout;
@@ -116,20 +186,586 @@ impl Graph {
         self
     }
 
+    /// Start a parallel branch at each of `targets` when `from` continues.
+    ///
+    /// Takes the place of `from`'s outgoing edges. Each branch follows the normal
+    /// edges from its target until it reaches a join node (see [`Graph::add_join`]),
+    /// ends, or runs out of edges. Branches cannot fork again.
+    pub fn add_fork<I, S>(&self, from: impl Into<String>, targets: I) -> &Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let targets = targets.into_iter().map(Into::into).collect();
+        self.forks.lock().unwrap().insert(from.into(), targets);
+        self
+    }
+
+    /// Make `task_id` a join node: branches that reach it stop there, and it
+    /// runs once `policy` is satisfied.
+    pub fn add_join(&self, task_id: impl Into<String>, policy: JoinPolicy) -> &Self {
+        self.joins.lock().unwrap().insert(task_id.into(), policy);
+        self
+    }
+
+    /// Choose how a branch step's context changes are merged back into the
+    /// session context (default: [`MergeStrategy::ErrorOnConflict`], which fails
+    /// the step when another branch changed the same key first).
+    pub fn set_branch_merge_strategy(&self, strategy: MergeStrategy) -> &Self {
+        *self.branch_merge.lock().unwrap() = strategy;
+        self
+    }
+
+    /// When `from` fails, run `to` instead of returning the error.
+    ///
+    /// The failure is written to the context under [`FAILURE_KEY`] as a
+    /// [`TaskFailure`]. Error edges apply outside of parallel branches.
+    pub fn add_error_edge(&self, from: impl Into<String>, to: impl Into<String>) -> &Self {
+        self.error_edges
+            .lock()
+            .unwrap()
+            .insert(from.into(), to.into());
+        self
+    }
+
+    /// Run `task_id` when a task without an error edge fails, like
+    /// [`add_error_edge`](Self::add_error_edge) from every task but the handler.
+    pub fn set_error_handler(&self, task_id: impl Into<String>) -> &Self {
+        *self.error_handler.lock().unwrap() = Some(task_id.into());
+        self
+    }
+
+    /// Stop every session before `task_id` runs.
+    ///
+    /// Breakpoints apply outside of parallel branches, and not to a task that
+    /// receives the input it waited for.
+    pub fn interrupt_before(&self, task_id: impl Into<String>) -> &Self {
+        self.breakpoints
+            .lock()
+            .unwrap()
+            .push(Breakpoint::before(task_id));
+        self
+    }
+
+    /// Stop every session after `task_id` has run, even if it asked to
+    /// `ContinueAndExecute`.
+    pub fn interrupt_after(&self, task_id: impl Into<String>) -> &Self {
+        self.breakpoints
+            .lock()
+            .unwrap()
+            .push(Breakpoint::after(task_id));
+        self
+    }
+
+    /// Whether `breakpoint` is set on the graph or on `session`.
+    fn has_breakpoint(&self, session: &Session, breakpoint: &Breakpoint) -> bool {
+        session.breakpoints.contains(breakpoint)
+            || self.breakpoints.lock().unwrap().contains(breakpoint)
+    }
+
     /// Execute the graph with session management
     /// This method manages the session state and returns a simple status
     pub async fn execute_session(&self, session: &mut Session) -> Result<ExecutionResult> {
+        self.run_session(session, &mut Vec::new()).await
+    }
+
+    /// [`Graph::execute_session`], reached through the error routes of the tasks
+    /// in `failed`.
+    async fn run_session(
+        &self,
+        session: &mut Session,
+        failed: &mut Vec<String>,
+    ) -> Result<ExecutionResult> {
         tracing::info!(
             graph_id = %self.id,
             session_id = %session.id,
             current_task = %session.current_task_id,
             "Starting graph execution"
         );
-        
+
+        if let Some(compensation) = session.context.get::<Compensation>(COMPENSATION_KEY).await {
+            return Err(GraphError::SessionCompensated(compensation.reason));
+        }
+
+        let resumed = session.interrupted.take();
+        let pending_input = session.pending_input.take();
+        if pending_input.is_none()
+            && let Some(result) = self.interrupt_before_current(session, resumed.as_ref())
+        {
+            return Ok(result);
+        }
+        let result = self.execute_due(session, failed).await;
+        match &result {
+            Ok(ExecutionResult {
+                status: ExecutionStatus::WaitingForInput,
+                input_request,
+                ..
+            }) if session.pending_input.is_none() => {
+                session.pending_input = Some(PendingInput {
+                    task_id: session.current_task_id.clone(),
+                    since: Utc::now(),
+                    request: input_request.clone(),
+                    branch_id: None,
+                });
+            }
+            Ok(_) => {}
+            // Nothing was saved; the session still waits where it did
+            Err(_) => {
+                session.pending_input = pending_input;
+                session.interrupted = resumed;
+            }
+        }
+        result
+    }
+
+    /// Execute exactly one task of the session, then stop after it even if it
+    /// asked to `ContinueAndExecute`. A breakpoint before the task is ignored.
+    pub async fn step_session(&self, session: &mut Session) -> Result<ExecutionResult> {
+        let task_id = session.current_task_id.clone();
+        if session.pending_input.is_none() {
+            session.interrupted = Some(Breakpoint::before(task_id.clone()));
+        }
+        let step = Breakpoint::after(task_id);
+        let added = !session.breakpoints.contains(&step);
+        if added {
+            session.breakpoints.push(step.clone());
+        }
+        let result = self.execute_session(session).await;
+        if added {
+            session.breakpoints.retain(|breakpoint| *breakpoint != step);
+        }
+        result
+    }
+
+    /// Stop before the current task if it has a breakpoint the session is not
+    /// continuing from.
+    fn interrupt_before_current(
+        &self,
+        session: &mut Session,
+        resumed: Option<&Breakpoint>,
+    ) -> Option<ExecutionResult> {
+        let sleeping = session
+            .timer
+            .as_ref()
+            .is_some_and(|timer| timer.wake_at > Utc::now());
+        if session.parallel.is_some() || sleeping {
+            return None;
+        }
+        let breakpoint = Breakpoint::before(session.current_task_id.clone());
+        if resumed == Some(&breakpoint) || !self.has_breakpoint(session, &breakpoint) {
+            return None;
+        }
+        tracing::debug!(session_id = %session.id, task_id = %breakpoint.task_id, "Stopped at breakpoint");
+        session.interrupted = Some(breakpoint.clone());
+        Some(ExecutionResult {
+            response: None,
+            status: ExecutionStatus::PausedAtBreakpoint {
+                next_task_id: session.current_task_id.clone(),
+                breakpoint,
+            },
+            input_request: None,
+        })
+    }
+
+    /// Deliver `input` to the task the session waits on and execute it.
+    ///
+    /// The input is written to the context under [`INPUT_KEY`], where it stays
+    /// until the next resume replaces it. Fails with
+    /// [`GraphError::NotWaitingForInput`] unless the session is waiting for input,
+    /// and with [`GraphError::InvalidInput`] if the input does not match the
+    /// task's [`InputRequest`]; nothing runs in either case.
+    ///
+    /// While parallel branches are in progress the input goes to the branch the
+    /// session reported as waiting, as with [`resume_branch`](Self::resume_branch).
+    pub async fn resume_session(
+        &self,
+        session: &mut Session,
+        input: serde_json::Value,
+    ) -> Result<ExecutionResult> {
+        let Some(pending) = &session.pending_input else {
+            return Err(GraphError::NotWaitingForInput(session.id.clone()));
+        };
+        if let Some(parallel) = &session.parallel {
+            // Sessions saved before inputs were tracked per branch only know the task
+            let waiting = |b: &&BranchState| b.status == BranchStatus::WaitingForInput;
+            let branch_id = pending.branch_id.clone().or_else(|| {
+                parallel
+                    .branches
+                    .iter()
+                    .filter(waiting)
+                    .find(|b| b.current_task_id == pending.task_id)
+                    .or_else(|| parallel.branches.iter().find(waiting))
+                    .map(|b| b.id.clone())
+            });
+            let Some(branch_id) = branch_id else {
+                return Err(GraphError::NotWaitingForInput(session.id.clone()));
+            };
+            return self.resume_branch(session, &branch_id, input).await;
+        }
+        tracing::debug!(session_id = %session.id, task_id = %pending.task_id, "Resuming session with input");
+        if let Some(request) = &pending.request {
+            request.validate(&input)?;
+        }
+
+        let previous: Option<serde_json::Value> = session.context.get(INPUT_KEY).await;
+        session.context.set(INPUT_KEY, input).await;
+        let result = self.execute_session(session).await;
+        if result.is_err() {
+            // Leave the context as it was, so a retry sees no trace of this input
+            match previous {
+                Some(previous) => session.context.set(INPUT_KEY, previous).await,
+                None => {
+                    session.context.remove(INPUT_KEY).await;
+                }
+            }
+        }
+        result
+    }
+
+    /// Deliver `input` to the parallel branch `branch_id` and execute the session.
+    ///
+    /// Each waiting branch keeps its own input, which its next step sees under
+    /// [`INPUT_KEY`] without it reaching the session context. That step runs
+    /// alongside the active branches and every other branch that already has
+    /// input. Fails with [`GraphError::NotWaitingForInput`] unless the branch is
+    /// waiting for input it has not received yet, and with
+    /// [`GraphError::InvalidInput`] if the input does not match its
+    /// [`InputRequest`]; nothing runs in either case.
+    pub async fn resume_branch(
+        &self,
+        session: &mut Session,
+        branch_id: &str,
+        input: serde_json::Value,
+    ) -> Result<ExecutionResult> {
+        let Some(branch) = session.parallel.as_mut().and_then(|parallel| {
+            parallel.branches.iter_mut().find(|b| {
+                b.id == branch_id && b.status == BranchStatus::WaitingForInput && b.input.is_none()
+            })
+        }) else {
+            return Err(GraphError::NotWaitingForInput(session.id.clone()));
+        };
+        // Sessions saved before requests were kept per branch only have the pending one
+        let request = branch.input_request.as_ref().or_else(|| {
+            session
+                .pending_input
+                .as_ref()
+                .filter(|pending| pending.branch_id.as_deref() == Some(branch_id))
+                .and_then(|pending| pending.request.as_ref())
+        });
+        if let Some(request) = request {
+            request.validate(&input)?;
+        }
+        tracing::debug!(session_id = %session.id, branch = %branch_id, "Resuming branch with input");
+        branch.input = Some(input);
+
+        let result = self.execute_session(session).await;
+        if result.is_err()
+            && let Some(branch) = session
+                .parallel
+                .as_mut()
+                .and_then(|parallel| parallel.branches.iter_mut().find(|b| b.id == branch_id))
+        {
+            // As in `resume_session`, a retry sees no trace of this input
+            branch.input = None;
+        }
+        result
+    }
+
+    /// Handle the session's timer, if any, then execute the current step.
+    async fn execute_due(
+        &self,
+        session: &mut Session,
+        failed: &mut Vec<String>,
+    ) -> Result<ExecutionResult> {
+        let Some(timer) = session.timer.take() else {
+            return self.execute_step(session, failed).await;
+        };
+        if timer.wake_at > Utc::now() {
+            if timer.interruptible {
+                // Resumed (e.g. with input) before the deadline: the timer is cancelled
+                return self.execute_step(session, failed).await;
+            }
+            let until = timer.wake_at;
+            session.timer = Some(timer);
+            return Ok(ExecutionResult {
+                response: None,
+                status: ExecutionStatus::Sleeping {
+                    next_task_id: session.current_task_id.clone(),
+                    until,
+                },
+                input_request: None,
+            });
+        }
+
+        tracing::debug!(session_id = %session.id, wake_at = %timer.wake_at, "Session timer fired");
+        session.context.set(TIMER_FIRED_KEY, true).await;
+        let result = self.execute_step(session, failed).await;
+        session.context.remove(TIMER_FIRED_KEY).await;
+        result
+    }
+
+    /// Execute the current step of a session whose timer, if any, has been handled.
+    async fn execute_step(
+        &self,
+        session: &mut Session,
+        failed: &mut Vec<String>,
+    ) -> Result<ExecutionResult> {
+        if session.parallel.is_some() {
+            return self.execute_branches(session).await;
+        }
+
         // Execute ONLY the current task (not the full recursive chain)
-        let result = self
+        let checkpoint = session.context.checkpoint();
+        let mut result = match self
             .execute_single_task(&session.current_task_id, session.context.clone())
-            .await?;
+            .await
+        {
+            Ok(result) => result,
+            Err(failure) => {
+                return self
+                    .route_failure(session, &checkpoint, failure, failed)
+                    .await;
+            }
+        };
+
+        // A success ends the task's run of failures
+        if session
+            .context
+            .get::<TaskFailure>(FAILURE_KEY)
+            .await
+            .is_some_and(|failure| failure.task_id == result.task_id)
+        {
+            session.context.remove(FAILURE_KEY).await;
+        }
+
+        // Record which keys this step wrote
+        let diff = session.context.diff_since(&checkpoint);
+        session.record_write_set(StepWriteSet::from_diff(&result.task_id, &diff));
+        session.compensable_steps.push(result.task_id.clone());
+
+        // A breakpoint after the task turns moving on into a pause
+        let breakpoint = Breakpoint::after(result.task_id.clone());
+        if !self.has_breakpoint(session, &breakpoint) {
+            return self.apply_result(session, result).await;
+        }
+        if result.next_action == NextAction::ContinueAndExecute {
+            result.next_action = NextAction::Continue;
+        }
+        let outcome = self.apply_result(session, result).await?;
+        let ExecutionStatus::Paused { next_task_id, .. } = outcome.status else {
+            return Ok(outcome);
+        };
+        tracing::debug!(session_id = %session.id, task_id = %breakpoint.task_id, "Stopped at breakpoint");
+        session.interrupted = Some(breakpoint.clone());
+        Ok(ExecutionResult {
+            response: outcome.response,
+            status: ExecutionStatus::PausedAtBreakpoint {
+                next_task_id,
+                breakpoint,
+            },
+            input_request: None,
+        })
+    }
+
+    /// Send the session from its failed current task to the task's error edge or
+    /// the graph's error handler, and run it. Without either the error is returned,
+    /// as are errors that reject the caller: the task stays put for a valid one.
+    ///
+    /// `failed` holds the tasks whose error routes led here. A route back to one of
+    /// them, or more than [`MAX_ERROR_ROUTES`] routes in a row, fails instead. So
+    /// does a task failing more than [`MAX_ROUTED_ATTEMPTS`] times before it
+    /// succeeds, e.g. when a handler keeps sending the session back to it.
+    async fn route_failure(
+        &self,
+        session: &mut Session,
+        checkpoint: &Checkpoint,
+        failure: StepFailure,
+        failed: &mut Vec<String>,
+    ) -> Result<ExecutionResult> {
+        if failure.error.is_caller_error() {
+            return Err(failure.error);
+        }
+        let task_id = session.current_task_id.clone();
+        let target = self
+            .error_edges
+            .lock()
+            .unwrap()
+            .get(&task_id)
+            .cloned()
+            .or_else(|| {
+                let handler = self.error_handler.lock().unwrap().clone();
+                handler.filter(|handler| *handler != task_id)
+            });
+        let Some(target) = target else {
+            return Err(failure.error);
+        };
+        if !self.tasks.contains_key(&target) {
+            return Err(GraphError::TaskNotFound(target));
+        }
+        failed.push(task_id.clone());
+        if failed.contains(&target) {
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "error route from '{}' leads back to '{}', which already failed ({}): {}",
+                task_id,
+                target,
+                failed.join(" -> "),
+                failure.error
+            )));
+        }
+        if failed.len() > MAX_ERROR_ROUTES {
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "gave up after {} error routes in a row ({}): {}",
+                MAX_ERROR_ROUTES,
+                failed.join(" -> "),
+                failure.error
+            )));
+        }
+
+        let attempts = match session.context.get::<TaskFailure>(FAILURE_KEY).await {
+            Some(previous) if previous.task_id == task_id => previous.attempts + 1,
+            _ => 1,
+        };
+        if attempts > MAX_ROUTED_ATTEMPTS {
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "gave up routing task '{}' after {} failures in a row: {}",
+                task_id, MAX_ROUTED_ATTEMPTS, failure.error
+            )));
+        }
+        tracing::warn!(
+            session_id = %session.id,
+            task_id = %task_id,
+            handler = %target,
+            attempts,
+            error = %failure.error,
+            "Task failed, routing to error handler"
+        );
+        let failure = TaskFailure {
+            task_id: task_id.clone(),
+            kind: failure.kind.to_string(),
+            message: failure.message,
+            attempts,
+            failed_at: Utc::now(),
+        };
+        session.context.set(FAILURE_KEY, &failure).await;
+        let diff = session.context.diff_since(checkpoint);
+        session.record_write_set(StepWriteSet::from_diff(&task_id, &diff));
+
+        session.status_message = Some(format!(
+            "Task '{}' failed, handled by '{}'",
+            task_id, target
+        ));
+        session.current_task_id = target;
+        Box::pin(self.run_session(session, failed)).await
+    }
+
+    /// Roll the session back: compensate its recorded steps, most recent first, and
+    /// write the outcome to the context under [`COMPENSATION_KEY`].
+    ///
+    /// A failed compensation is recorded and the others still run. The session's
+    /// timer and pending input are cleared, and executing it afterwards fails with
+    /// [`GraphError::SessionCompensated`], as does compensating it again.
+    pub async fn compensate_session(
+        &self,
+        session: &mut Session,
+        reason: impl Into<String>,
+    ) -> Result<Compensation> {
+        if let Some(compensation) = session.context.get::<Compensation>(COMPENSATION_KEY).await {
+            return Err(GraphError::SessionCompensated(compensation.reason));
+        }
+        let reason = reason.into();
+        tracing::warn!(
+            session_id = %session.id,
+            steps = session.compensable_steps.len(),
+            reason = %reason,
+            "Compensating session"
+        );
+
+        let mut steps = Vec::new();
+        for task_id in std::mem::take(&mut session.compensable_steps)
+            .into_iter()
+            .rev()
+        {
+            let outcome = match self.get_task(&task_id) {
+                Some(task) => timeout(self.task_timeout, task.compensate(session.context.clone()))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(GraphError::TaskExecutionFailed(format!(
+                            "Compensation timed out after {:?}",
+                            self.task_timeout
+                        )))
+                    }),
+                None => Err(GraphError::TaskNotFound(task_id.clone())),
+            };
+            if let Err(e) = &outcome {
+                tracing::error!(
+                    session_id = %session.id,
+                    task_id = %task_id,
+                    error = %e,
+                    "Compensation failed"
+                );
+            }
+            steps.push(CompensationRecord {
+                task_id,
+                error: outcome.err().map(|e| e.to_string()),
+                compensated_at: Utc::now(),
+            });
+        }
+
+        let compensation = Compensation {
+            reason,
+            steps,
+            completed_at: Utc::now(),
+        };
+        session.context.set(COMPENSATION_KEY, &compensation).await;
+        session.timer = None;
+        session.pending_input = None;
+        session.status_message = Some(format!("Rolled back: {}", compensation.reason));
+        Ok(compensation)
+    }
+
+    /// Move the session on according to the result of its current task.
+    async fn apply_result(
+        &self,
+        session: &mut Session,
+        result: TaskResult,
+    ) -> Result<ExecutionResult> {
+        // A fork replaces the outgoing edges with one branch per target
+        let forks_to = match result.next_action {
+            NextAction::Continue | NextAction::ContinueAndExecute => {
+                self.forks.lock().unwrap().get(&result.task_id).cloned()
+            }
+            _ => None,
+        };
+        if let Some(targets) = forks_to {
+            session.status_message = result.status_message.clone();
+            session.parallel = Some(ParallelState {
+                fork_task_id: result.task_id.clone(),
+                join_task_id: None,
+                branches: targets
+                    .iter()
+                    .map(|target| BranchState {
+                        id: target.clone(),
+                        current_task_id: target.clone(),
+                        status: BranchStatus::Active,
+                        input: None,
+                        input_request: None,
+                    })
+                    .collect(),
+            });
+            let first = targets.first().cloned().unwrap_or_default();
+            session.current_task_id = first.clone();
+            if result.next_action == NextAction::ContinueAndExecute {
+                return Box::pin(self.execute_session(session)).await;
+            }
+            return Ok(ExecutionResult {
+                response: result.response,
+                status: ExecutionStatus::Paused {
+                    next_task_id: first,
+                    reason: format!("Forked into {} parallel branches", targets.len()),
+                },
+                input_request: None,
+            });
+        }
 
         // Handle next action at the session level
         match &result.next_action {
@@ -138,24 +774,29 @@ impl Graph {
                 session.status_message = result.status_message.clone();
 
                 // Find the next task but don't execute it
-                if let Some(next_task_id) = self.find_next_task(&result.task_id, &session.context) {
+                if let Some(next_task_id) = self
+                    .resolve_next_task(&result.task_id, &session.context)
+                    .await?
+                {
                     session.current_task_id = next_task_id.clone();
                     Ok(ExecutionResult {
                         response: result.response,
-                        status: ExecutionStatus::Paused { 
+                        status: ExecutionStatus::Paused {
                             next_task_id,
                             reason: "Task completed, continuing to next task".to_string(),
                         },
+                        input_request: None,
                     })
                 } else {
                     // No next task found, stay at current task
                     session.current_task_id = result.task_id.clone();
                     Ok(ExecutionResult {
                         response: result.response,
-                        status: ExecutionStatus::Paused { 
+                        status: ExecutionStatus::Paused {
                             next_task_id: result.task_id.clone(),
                             reason: "No outgoing edge found from current task".to_string(),
                         },
+                        input_request: None,
                     })
                 }
             }
@@ -164,7 +805,10 @@ impl Graph {
                 session.status_message = result.status_message.clone();
 
                 // Find the next task and execute it immediately (recursive behavior)
-                if let Some(next_task_id) = self.find_next_task(&result.task_id, &session.context) {
+                if let Some(next_task_id) = self
+                    .resolve_next_task(&result.task_id, &session.context)
+                    .await?
+                {
                     // Instead of using the old execute method that clones context,
                     // continue executing in session mode to preserve context updates
                     session.current_task_id = next_task_id;
@@ -176,10 +820,11 @@ impl Graph {
                     session.current_task_id = result.task_id.clone();
                     Ok(ExecutionResult {
                         response: result.response,
-                        status: ExecutionStatus::Paused { 
+                        status: ExecutionStatus::Paused {
                             next_task_id: result.task_id.clone(),
                             reason: "No outgoing edge found from current task".to_string(),
                         },
+                        input_request: None,
                     })
                 }
             }
@@ -191,6 +836,7 @@ impl Graph {
                 Ok(ExecutionResult {
                     response: result.response,
                     status: ExecutionStatus::WaitingForInput,
+                    input_request: result.input_request,
                 })
             }
             NextAction::End => {
@@ -200,6 +846,7 @@ impl Graph {
                 Ok(ExecutionResult {
                     response: result.response,
                     status: ExecutionStatus::Completed,
+                    input_request: None,
                 })
             }
             NextAction::GoTo(target_id) => {
@@ -209,10 +856,11 @@ impl Graph {
                     session.current_task_id = target_id.clone();
                     Ok(ExecutionResult {
                         response: result.response,
-                        status: ExecutionStatus::Paused { 
+                        status: ExecutionStatus::Paused {
                             next_task_id: target_id.clone(),
                             reason: "Task requested jump to specific task".to_string(),
                         },
+                        input_request: None,
                     })
                 } else {
                     Err(GraphError::TaskNotFound(target_id.clone()))
@@ -226,41 +874,442 @@ impl Graph {
                 Ok(ExecutionResult {
                     response: result.response,
                     status: ExecutionStatus::WaitingForInput,
+                    input_request: None,
+                })
+            }
+            NextAction::WaitUntil(wake_at) => {
+                // Update session status message if provided
+                session.status_message = result.status_message.clone();
+                // Stay at the current task until input arrives or the deadline passes
+                session.current_task_id = result.task_id.clone();
+                session.timer = Some(SessionTimer {
+                    wake_at: *wake_at,
+                    interruptible: true,
+                    attempts: 0,
+                });
+                Ok(ExecutionResult {
+                    response: result.response,
+                    status: ExecutionStatus::WaitingForInput,
+                    input_request: result.input_request,
+                })
+            }
+            NextAction::Sleep(duration) => {
+                // Update session status message if provided
+                session.status_message = result.status_message.clone();
+                // Move on now, but don't run the next task before the timer fires
+                let next_task_id = self
+                    .resolve_next_task(&result.task_id, &session.context)
+                    .await?
+                    .unwrap_or_else(|| result.task_id.clone());
+                let until = storage::after(Utc::now(), *duration);
+                session.current_task_id = next_task_id.clone();
+                session.timer = Some(SessionTimer {
+                    wake_at: until,
+                    interruptible: false,
+                    attempts: 0,
+                });
+                Ok(ExecutionResult {
+                    response: result.response,
+                    status: ExecutionStatus::Sleeping {
+                        next_task_id,
+                        until,
+                    },
+                    input_request: None,
                 })
             }
         }
     }
 
     /// Execute a single task without following Continue actions
-    async fn execute_single_task(&self, task_id: &str, context: Context) -> Result<TaskResult> {
+    async fn execute_single_task(
+        &self,
+        task_id: &str,
+        context: Context,
+    ) -> std::result::Result<TaskResult, StepFailure> {
         tracing::debug!(
             task_id = %task_id,
             "Executing single task"
         );
-        
-        let task = self
-            .tasks
-            .get(task_id)
-            .ok_or_else(|| GraphError::TaskNotFound(task_id.to_string()))?;
 
+        let task = self.tasks.get(task_id).ok_or_else(|| {
+            let error = GraphError::TaskNotFound(task_id.to_string());
+            StepFailure {
+                kind: error.kind(),
+                message: error.to_string(),
+                error,
+            }
+        })?;
+
+        Self::try_run_step(
+            task.value().clone(),
+            task_id.to_string(),
+            context,
+            self.task_timeout,
+        )
+        .await
+    }
+
+    /// Run a task with a timeout, tagging the result with its task id.
+    async fn run_step(
+        task: Arc<dyn Task>,
+        task_id: String,
+        context: Context,
+        task_timeout: Duration,
+    ) -> Result<TaskResult> {
+        Self::try_run_step(task, task_id, context, task_timeout)
+            .await
+            .map_err(|failure| failure.error)
+    }
+
+    /// [`run_step`](Self::run_step), keeping what went wrong for error routing.
+    async fn try_run_step(
+        task: Arc<dyn Task>,
+        task_id: String,
+        context: Context,
+        task_timeout: Duration,
+    ) -> std::result::Result<TaskResult, StepFailure> {
         // Execute task with timeout
-        let task_future = task.run(context);
-        let mut result = match timeout(self.task_timeout, task_future).await {
+        let task_future = Self::run_task(&task, &context);
+        let mut result = match timeout(task_timeout, task_future).await {
             Ok(Ok(result)) => result,
-            Ok(Err(e)) => return Err(GraphError::TaskExecutionFailed(
-                format!("Task '{}' failed: {}", task_id, e)
-            )),
-            Err(_) => return Err(GraphError::TaskExecutionFailed(
-                format!("Task '{}' timed out after {:?}", task_id, self.task_timeout)
-            )),
+            Ok(Err(e)) => {
+                let kind = e.kind();
+                let message = e.to_string();
+                // The caller needs to know it was turned away, not that the task broke
+                let error = if e.is_caller_error() {
+                    e
+                } else {
+                    GraphError::TaskExecutionFailed(format!("Task '{}' failed: {}", task_id, e))
+                };
+                return Err(StepFailure {
+                    kind,
+                    message,
+                    error,
+                });
+            }
+            Err(_) => {
+                return Err(StepFailure {
+                    kind: "timeout",
+                    message: format!("timed out after {:?}", task_timeout),
+                    error: GraphError::TaskExecutionFailed(format!(
+                        "Task '{}' timed out after {:?}",
+                        task_id, task_timeout
+                    )),
+                });
+            }
         };
 
         // Set the task_id in the result to track which task generated it
-        result.task_id = task_id.to_string();
+        result.task_id = task_id;
 
         Ok(result)
     }
 
+    /// Run one step of every active branch, plus the first branch waiting for
+    /// input (the caller's input is meant for it), then check the join.
+    ///
+    /// Branches that return `ContinueAndExecute` keep running within the call.
+    /// Each step runs on a fork of the session context that is merged back, in
+    /// branch order, once the step succeeds. A failed step leaves its branch
+    /// where it was; the other branches keep their progress, which
+    /// [`FlowRunner`](crate::FlowRunner) saves before returning the error.
+    async fn execute_branches(&self, session: &mut Session) -> Result<ExecutionResult> {
+        let mut parallel = session
+            .parallel
+            .clone()
+            .expect("execute_branches requires branches in progress");
+        let mut round: Vec<usize> = (0..parallel.branches.len())
+            .filter(|&i| {
+                let branch = &parallel.branches[i];
+                branch.status == BranchStatus::Active
+                    || (branch.status == BranchStatus::WaitingForInput && branch.input.is_some())
+            })
+            .collect();
+        let strategy = self.branch_merge.lock().unwrap().clone();
+
+        let mut responses = Vec::new();
+        let mut first_error: Option<(String, GraphError)> = None;
+        // Whether each branch that arrived during this call asked to keep executing
+        let mut arrivals_execute = Vec::new();
+
+        while !round.is_empty() {
+            let mut set = JoinSet::new();
+            let mut running = HashMap::new();
+            for &index in &round {
+                let task_id = parallel.branches[index].current_task_id.clone();
+                let Some(task) = self.get_task(&task_id) else {
+                    first_error.get_or_insert((task_id.clone(), GraphError::TaskNotFound(task_id)));
+                    continue;
+                };
+                let scope = session.context.fork();
+                let input = parallel.branches[index].input.take();
+                if let Some(input) = &input {
+                    scope.set(INPUT_KEY, input).await;
+                }
+                let task_timeout = self.task_timeout;
+                let handle = set.spawn(async move {
+                    let checkpoint = scope.checkpoint();
+                    let result = Self::run_step(task, task_id, scope.clone(), task_timeout).await;
+                    if input.is_some() {
+                        scope.discard_local(INPUT_KEY);
+                    }
+                    let result = result?;
+                    let diff = scope.diff_since(&checkpoint);
+                    Ok::<_, GraphError>((result, scope, diff))
+                });
+                running.insert(handle.id(), index);
+            }
+
+            let mut finished = Vec::new();
+            while let Some(joined) = set.join_next_with_id().await {
+                match joined {
+                    Ok((id, outcome)) => finished.push((running[&id], outcome)),
+                    Err(e) => finished.push((
+                        running[&e.id()],
+                        Err(GraphError::TaskExecutionFailed(format!(
+                            "Branch task panicked: {e}"
+                        ))),
+                    )),
+                }
+            }
+            finished.sort_by_key(|(index, _)| *index);
+
+            round.clear();
+            for (index, outcome) in finished {
+                let task_id = parallel.branches[index].current_task_id.clone();
+                let step = match outcome {
+                    Ok((result, scope, diff)) => session
+                        .context
+                        .merge(&scope, strategy.clone())
+                        .await
+                        .map(|_| (result, diff))
+                        .map_err(|e| match e {
+                            GraphError::ContextError(message) => GraphError::ContextError(format!(
+                                "Branch '{}': {}",
+                                parallel.branches[index].id, message
+                            )),
+                            e => e,
+                        }),
+                    Err(e) => Err(e),
+                };
+                let (result, diff) = match step {
+                    Ok(step) => step,
+                    Err(e) => {
+                        first_error.get_or_insert((task_id, e));
+                        continue;
+                    }
+                };
+                session.record_write_set(StepWriteSet::from_diff(&task_id, &diff));
+                session.compensable_steps.push(task_id.clone());
+                parallel.branches[index].input_request = None;
+                responses.extend(result.response);
+                if result.status_message.is_some() {
+                    session.status_message = result.status_message;
+                }
+
+                let execute = result.next_action == NextAction::ContinueAndExecute;
+                let next = match result.next_action {
+                    NextAction::Continue | NextAction::ContinueAndExecute => {
+                        if self.forks.lock().unwrap().contains_key(&task_id) {
+                            let message = format!(
+                                "Task '{}' forks inside branch '{}'; nested forks are not supported",
+                                task_id, parallel.branches[index].id
+                            );
+                            first_error.get_or_insert((task_id, GraphError::InvalidEdge(message)));
+                            continue;
+                        }
+                        match self.resolve_next_task(&task_id, &session.context).await {
+                            Ok(next) => next,
+                            Err(e) => {
+                                first_error.get_or_insert((task_id, e));
+                                continue;
+                            }
+                        }
+                    }
+                    NextAction::GoTo(target) => {
+                        if !self.tasks.contains_key(&target) {
+                            first_error.get_or_insert((task_id, GraphError::TaskNotFound(target)));
+                            continue;
+                        }
+                        Some(target)
+                    }
+                    NextAction::WaitForInput | NextAction::GoBack => {
+                        let branch = &mut parallel.branches[index];
+                        branch.status = BranchStatus::WaitingForInput;
+                        branch.input_request = result.input_request;
+                        continue;
+                    }
+                    NextAction::End => None,
+                    NextAction::WaitUntil(_) | NextAction::Sleep(_) => {
+                        let message = format!(
+                            "Task '{}' in branch '{}' set a timer; timers are not supported inside parallel branches",
+                            task_id, parallel.branches[index].id
+                        );
+                        first_error
+                            .get_or_insert((task_id, GraphError::TaskExecutionFailed(message)));
+                        continue;
+                    }
+                };
+
+                let is_join = next
+                    .as_ref()
+                    .is_some_and(|next| self.joins.lock().unwrap().contains_key(next));
+                let branch = &mut parallel.branches[index];
+                match next {
+                    None => branch.status = BranchStatus::Ended,
+                    Some(next) if is_join => {
+                        if let Some(join) =
+                            parallel.join_task_id.as_ref().filter(|join| **join != next)
+                        {
+                            let message = format!(
+                                "Branches of fork '{}' lead to different joins '{}' and '{}'",
+                                parallel.fork_task_id, join, next
+                            );
+                            first_error.get_or_insert((task_id, GraphError::InvalidEdge(message)));
+                            continue;
+                        }
+                        parallel.join_task_id = Some(next.clone());
+                        branch.current_task_id = next;
+                        branch.status = BranchStatus::Arrived;
+                        arrivals_execute.push(execute);
+                    }
+                    Some(next) => {
+                        branch.current_task_id = next;
+                        branch.status = BranchStatus::Active;
+                        if execute {
+                            round.push(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((task_id, e)) = first_error {
+            session.current_task_id = task_id;
+            session.parallel = Some(parallel);
+            return Err(e);
+        }
+
+        let response = (!responses.is_empty()).then(|| responses.join("\n"));
+        let unfinished = parallel
+            .branches
+            .iter()
+            .filter(|b| {
+                matches!(
+                    b.status,
+                    BranchStatus::Active | BranchStatus::WaitingForInput
+                )
+            })
+            .count();
+        let arrived = parallel
+            .branches
+            .iter()
+            .filter(|b| b.status == BranchStatus::Arrived)
+            .count();
+        let policy = parallel
+            .join_task_id
+            .as_ref()
+            .and_then(|join| self.joins.lock().unwrap().get(join).cloned());
+        let joined = match policy {
+            Some(JoinPolicy::All) => unfinished == 0,
+            Some(JoinPolicy::AtLeast(required)) => {
+                if arrived < required && unfinished == 0 {
+                    let message = format!(
+                        "Join '{}' needs {} branch(es) but only {} arrived",
+                        parallel.join_task_id.as_deref().unwrap_or_default(),
+                        required,
+                        arrived
+                    );
+                    session.parallel = Some(parallel);
+                    return Err(GraphError::TaskExecutionFailed(message));
+                }
+                arrived >= required
+            }
+            None => false,
+        };
+
+        if joined {
+            let join = parallel.join_task_id.clone().unwrap_or_default();
+            for branch in &mut parallel.branches {
+                if matches!(
+                    branch.status,
+                    BranchStatus::Active | BranchStatus::WaitingForInput
+                ) {
+                    branch.status = BranchStatus::Cancelled;
+                }
+            }
+            tracing::debug!(
+                fork = %parallel.fork_task_id,
+                join = %join,
+                arrived,
+                "Parallel branches joined"
+            );
+            session.parallel = None;
+            session.current_task_id = join.clone();
+            if !arrivals_execute.is_empty() && arrivals_execute.iter().all(|execute| *execute) {
+                return Box::pin(self.execute_session(session)).await;
+            }
+            return Ok(ExecutionResult {
+                response,
+                status: ExecutionStatus::Paused {
+                    next_task_id: join,
+                    reason: "Parallel branches joined".to_string(),
+                },
+                input_request: None,
+            });
+        }
+
+        if unfinished == 0 {
+            // Every branch ended without reaching a join
+            session.parallel = None;
+            return Ok(ExecutionResult {
+                response,
+                status: ExecutionStatus::Completed,
+                input_request: None,
+            });
+        }
+
+        // Input is requested while any branch waits; the next call also steps the active ones
+        let waiting = parallel
+            .branches
+            .iter()
+            .position(|b| b.status == BranchStatus::WaitingForInput && b.input.is_none());
+        let mut input_request = None;
+        let status = match waiting {
+            Some(index) => {
+                let branch = &parallel.branches[index];
+                session.current_task_id = branch.current_task_id.clone();
+                input_request = branch.input_request.clone();
+                session.pending_input = Some(PendingInput {
+                    task_id: branch.current_task_id.clone(),
+                    since: Utc::now(),
+                    request: input_request.clone(),
+                    branch_id: Some(branch.id.clone()),
+                });
+                ExecutionStatus::WaitingForInput
+            }
+            None => {
+                let next = parallel
+                    .branches
+                    .iter()
+                    .find(|b| b.status == BranchStatus::Active)
+                    .map(|b| b.current_task_id.clone())
+                    .unwrap_or_default();
+                session.current_task_id = next.clone();
+                ExecutionStatus::Paused {
+                    next_task_id: next,
+                    reason: format!("{} parallel branch(es) in progress", unfinished),
+                }
+            }
+        };
+        session.parallel = Some(parallel);
+        Ok(ExecutionResult {
+            response,
+            status,
+            input_request,
+        })
+    }
+
     /// Execute the graph starting from a specific task
     pub async fn execute(&self, task_id: &str, context: Context) -> Result<TaskResult> {
         let task = self
@@ -268,7 +1317,7 @@ impl Graph {
             .get(task_id)
             .ok_or_else(|| GraphError::TaskNotFound(task_id.to_string()))?;
 
-        let mut result = task.run(context.clone()).await?;
+        let mut result = Self::run_task(task.value(), &context).await?;
 
         // Set the task_id in the result to track which task generated it
         result.task_id = task_id.to_string();
@@ -282,7 +1331,7 @@ impl Graph {
                     Ok(result)
                 } else {
                     // Find the next task based on edges
-                    if let Some(next_task_id) = self.find_next_task(task_id, &context) {
+                    if let Some(next_task_id) = self.resolve_next_task(task_id, &context).await? {
                         Box::pin(self.execute(&next_task_id, context)).await
                     } else {
                         Ok(result)
@@ -300,6 +1349,22 @@ impl Graph {
         }
     }
 
+    /// Run a task, committing its context writes only if it succeeds.
+    ///
+    /// Transactional tasks run against a fork of `context` that is merged back
+    /// on `Ok`. If the task fails, or its future is dropped (e.g. on timeout),
+    /// the fork is discarded and `context` is left as it was.
+    async fn run_task(task: &Arc<dyn Task>, context: &Context) -> Result<TaskResult> {
+        if !task.transactional() {
+            return task.run(context.clone()).await;
+        }
+
+        let scope = context.fork();
+        let result = task.run(scope.clone()).await?;
+        context.merge(&scope, MergeStrategy::LastWriterWins).await?;
+        Ok(result)
+    }
+
     /// Find the next task based on edges and conditions
     pub fn find_next_task(&self, current_task_id: &str, context: &Context) -> Option<String> {
         let edges = self.edges.lock().unwrap();
@@ -315,6 +1380,27 @@ impl Graph {
         fallback
     }
 
+    /// Async version of [`Graph::find_next_task`] used while executing.
+    ///
+    /// If `current_task_id` has conditional edges, offloaded context values are
+    /// loaded first, so conditions reading them synchronously see the value.
+    pub async fn resolve_next_task(
+        &self,
+        current_task_id: &str,
+        context: &Context,
+    ) -> Result<Option<String>> {
+        let conditional = self
+            .edges
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|e| e.from == current_task_id && e.condition.is_some());
+        if conditional {
+            context.load_blobs().await?;
+        }
+        Ok(self.find_next_task(current_task_id, context))
+    }
+
     /// Get the start task ID
     pub fn start_task_id(&self) -> Option<String> {
         self.start_task_id.lock().unwrap().clone()
@@ -362,35 +1448,83 @@ impl GraphBuilder {
         self
     }
 
+    pub fn add_fork<I, S>(self, from: impl Into<String>, targets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.graph.add_fork(from, targets);
+        self
+    }
+
+    pub fn add_join(self, task_id: impl Into<String>, policy: JoinPolicy) -> Self {
+        self.graph.add_join(task_id, policy);
+        self
+    }
+
+    pub fn set_branch_merge_strategy(self, strategy: MergeStrategy) -> Self {
+        self.graph.set_branch_merge_strategy(strategy);
+        self
+    }
+
     pub fn set_start_task(self, task_id: impl Into<String>) -> Self {
         self.graph.set_start_task(task_id);
         self
     }
 
+    pub fn add_error_edge(self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.graph.add_error_edge(from, to);
+        self
+    }
+
+    pub fn set_error_handler(self, task_id: impl Into<String>) -> Self {
+        self.graph.set_error_handler(task_id);
+        self
+    }
+
+    pub fn interrupt_before(self, task_id: impl Into<String>) -> Self {
+        self.graph.interrupt_before(task_id);
+        self
+    }
+
+    pub fn interrupt_after(self, task_id: impl Into<String>) -> Self {
+        self.graph.interrupt_after(task_id);
+        self
+    }
+
     pub fn build(self) -> Graph {
         // Validate the graph before returning
         if self.graph.tasks.is_empty() {
             tracing::warn!("Building graph with no tasks");
         }
-        
+
         // Check for orphaned tasks (tasks with no incoming or outgoing edges)
         let task_count = self.graph.tasks.len();
         if task_count > 1 {
             // Collect task IDs first
-            let all_task_ids: Vec<String> = self.graph.tasks.iter()
-                .map(|t| t.key().clone())
-                .collect();
-            
+            let all_task_ids: Vec<String> =
+                self.graph.tasks.iter().map(|t| t.key().clone()).collect();
+
             // Then check edges
             let edges = self.graph.edges.lock().unwrap();
             let mut connected_tasks = std::collections::HashSet::new();
-            
+
             for edge in edges.iter() {
                 connected_tasks.insert(edge.from.clone());
                 connected_tasks.insert(edge.to.clone());
             }
             drop(edges); // Explicitly drop the lock
-            
+
+            for (from, targets) in self.graph.forks.lock().unwrap().iter() {
+                connected_tasks.insert(from.clone());
+                connected_tasks.extend(targets.iter().cloned());
+            }
+            for (from, to) in self.graph.error_edges.lock().unwrap().iter() {
+                connected_tasks.insert(from.clone());
+                connected_tasks.insert(to.clone());
+            }
+            connected_tasks.extend(self.graph.error_handler.lock().unwrap().clone());
+
             // Now check for orphaned tasks
             for task_id in all_task_ids {
                 if !connected_tasks.contains(&task_id) {
@@ -401,29 +1535,506 @@ impl GraphBuilder {
                 }
             }
         }
-        
+
         self.graph
     }
 }
 
 /// Status of graph execution
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ExecutionResult {
     pub response: Option<String>,
     pub status: ExecutionStatus,
+    /// What the task expects, when `status` is `WaitingForInput` and the task said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_request: Option<InputRequest>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum ExecutionStatus {
     /// Paused, will continue automatically to the specified next task
-    Paused { 
+    Paused {
         next_task_id: String,
         reason: String,
     },
+    /// Stopped at a breakpoint; executing the session again continues with `next_task_id`
+    PausedAtBreakpoint {
+        next_task_id: String,
+        breakpoint: Breakpoint,
+    },
     /// Waiting for user input to continue
     WaitingForInput,
+    /// Sleeping until `until`; a `Scheduler` then continues with `next_task_id`
+    Sleeping {
+        next_task_id: String,
+        until: DateTime<Utc>,
+    },
     /// Workflow completed successfully
     Completed,
     /// Error occurred during execution
     Error(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::BreakpointPosition;
+    use crate::testing::{MockTask, Scenario};
+    use crate::{ApprovalDecision, ApprovalInput, ApprovalTask, Reviewer};
+    use serde_json::json;
+
+    fn mock(id: &str) -> MockTask {
+        MockTask::new(id).writes(format!("ran.{id}"), json!(true))
+    }
+
+    fn fork_graph(a: MockTask, policy: JoinPolicy) -> Graph {
+        GraphBuilder::new("parallel")
+            .add_task(Arc::new(mock("start")))
+            .add_task(Arc::new(a))
+            .add_task(Arc::new(mock("a2")))
+            .add_task(Arc::new(mock("b")))
+            .add_task(Arc::new(
+                mock("join").then(TaskResult::new(None, NextAction::End)),
+            ))
+            .add_fork("start", ["a", "b"])
+            .add_edge("a", "a2")
+            .add_edge("a2", "join")
+            .add_edge("b", "join")
+            .add_join("join", policy)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn fork_runs_every_branch_before_the_join() {
+        let outcome = Scenario::new(fork_graph(mock("a"), JoinPolicy::All))
+            .run()
+            .await;
+
+        assert!(outcome.is_completed(), "{:?}", outcome.status);
+        outcome.assert_path(&["start", "a", "b", "a2", "join"]);
+        for id in ["a", "a2", "b", "join"] {
+            outcome
+                .assert_context(&format!("ran.{id}"), json!(true))
+                .await;
+        }
+        assert!(outcome.session.parallel.is_none());
+    }
+
+    #[tokio::test]
+    async fn branch_waiting_for_input_resumes_from_a_persisted_session() {
+        let a = mock("a")
+            .then(TaskResult::new(
+                Some("Which one?".into()),
+                NextAction::WaitForInput,
+            ))
+            .then(TaskResult::move_to_next_direct());
+        let graph = fork_graph(a, JoinPolicy::All);
+        let mut session = Session::new_from_task("s".to_string(), "start");
+
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        assert_eq!(result.response.as_deref(), Some("Which one?"));
+        let parallel = session.parallel.as_ref().unwrap();
+        assert_eq!(parallel.join_task_id.as_deref(), Some("join"));
+        let statuses: Vec<_> = parallel.branches.iter().map(|b| b.status).collect();
+        assert_eq!(
+            statuses,
+            [BranchStatus::WaitingForInput, BranchStatus::Arrived]
+        );
+
+        // Round-trip through storage before resuming
+        let mut session: Session =
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert_eq!(
+            session
+                .pending_input
+                .as_ref()
+                .and_then(|p| p.branch_id.as_deref()),
+            Some("a")
+        );
+        let result = graph
+            .resume_session(&mut session, json!("the first"))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert!(session.parallel.is_none());
+        assert_eq!(session.current_task_id, "join");
+        assert_eq!(session.context.get::<bool>("ran.a2").await, Some(true));
+    }
+
+    /// Waits for input, then records what it received under `input.<id>`
+    struct AsksOnce(&'static str);
+
+    #[async_trait::async_trait]
+    impl Task for AsksOnce {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            match context.get::<serde_json::Value>(INPUT_KEY).await {
+                Some(input) => {
+                    context.set(format!("input.{}", self.0), input).await;
+                    Ok(TaskResult::move_to_next_direct())
+                }
+                None => Ok(TaskResult::request_input(InputRequest::text(format!(
+                    "{}?",
+                    self.0
+                )))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn each_waiting_branch_receives_its_own_input() {
+        let graph = GraphBuilder::new("parallel")
+            .add_task(Arc::new(mock("start")))
+            .add_task(Arc::new(AsksOnce("a")))
+            .add_task(Arc::new(AsksOnce("b")))
+            .add_task(Arc::new(
+                mock("join").then(TaskResult::new(None, NextAction::End)),
+            ))
+            .add_fork("start", ["a", "b"])
+            .add_edge("a", "join")
+            .add_edge("b", "join")
+            .add_join("join", JoinPolicy::All)
+            .build();
+        let mut session = Session::new_from_task("s".to_string(), "start");
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+
+        assert_eq!(
+            result.input_request.map(|r| r.prompt).as_deref(),
+            Some("a?")
+        );
+
+        // Answer the second branch first; the first keeps waiting with its request
+        let result = graph
+            .resume_branch(&mut session, "b", json!("for b"))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        assert_eq!(
+            result.input_request.map(|r| r.prompt).as_deref(),
+            Some("a?")
+        );
+        let branches = &session.parallel.as_ref().unwrap().branches;
+        assert_eq!(branches[0].input_request, Some(InputRequest::text("a?")));
+        assert_eq!(branches[1].input_request, None);
+        assert_eq!(
+            session
+                .pending_input
+                .as_ref()
+                .and_then(|p| p.branch_id.as_deref()),
+            Some("a")
+        );
+        assert!(matches!(
+            graph.resume_branch(&mut session, "a", json!(42)).await,
+            Err(GraphError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            graph.resume_branch(&mut session, "b", json!("again")).await,
+            Err(GraphError::NotWaitingForInput(_))
+        ));
+
+        let result = graph
+            .resume_session(&mut session, json!("for a"))
+            .await
+            .unwrap();
+        assert!(
+            matches!(result.status, ExecutionStatus::Completed),
+            "{:?}",
+            result.status
+        );
+        assert_eq!(
+            session.context.get::<String>("input.a").await.as_deref(),
+            Some("for a")
+        );
+        assert_eq!(
+            session.context.get::<String>("input.b").await.as_deref(),
+            Some("for b")
+        );
+        assert_eq!(
+            session.context.get::<serde_json::Value>(INPUT_KEY).await,
+            None
+        );
+    }
+
+    fn conflicting_graph(builder: GraphBuilder) -> Graph {
+        builder
+            .add_task(Arc::new(mock("start")))
+            .add_task(Arc::new(mock("a").writes("shared", json!("a"))))
+            .add_task(Arc::new(mock("b").writes("shared", json!("b"))))
+            .add_task(Arc::new(
+                mock("join").then(TaskResult::new(None, NextAction::End)),
+            ))
+            .add_fork("start", ["a", "b"])
+            .add_edge("a", "join")
+            .add_edge("b", "join")
+            .add_join("join", JoinPolicy::All)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn conflicting_branch_writes_are_reported() {
+        let graph = conflicting_graph(GraphBuilder::new("parallel"));
+        let mut session = Session::new_from_task("s".to_string(), "start");
+
+        // "start" continues straight into the branches
+        let err = graph.execute_session(&mut session).await.unwrap_err();
+        assert!(
+            matches!(&err, GraphError::ContextError(message) if message.contains("Branch 'b'") && message.contains("shared")),
+            "{err}"
+        );
+        assert_eq!(
+            session.context.get::<String>("shared").await.as_deref(),
+            Some("a")
+        );
+        assert_eq!(
+            session.parallel.as_ref().unwrap().branches[1].status,
+            BranchStatus::Active
+        );
+    }
+
+    #[tokio::test]
+    async fn branch_merge_strategy_can_be_chosen() {
+        let graph = conflicting_graph(
+            GraphBuilder::new("parallel").set_branch_merge_strategy(MergeStrategy::LastWriterWins),
+        );
+        let outcome = Scenario::new(graph).run().await;
+
+        assert!(outcome.is_completed(), "{:?}", outcome.status);
+        outcome.assert_context("shared", json!("b")).await;
+    }
+
+    #[tokio::test]
+    async fn join_at_least_cancels_remaining_branches() {
+        let a = mock("a").then(TaskResult::move_to_next());
+        let outcome = Scenario::new(fork_graph(a, JoinPolicy::AtLeast(1)))
+            .run()
+            .await;
+
+        assert!(outcome.is_completed(), "{:?}", outcome.status);
+        outcome.assert_path(&["start", "a", "b", "join"]);
+        assert_eq!(outcome.context().get::<bool>("ran.a2").await, None);
+    }
+
+    #[tokio::test]
+    async fn failing_branch_keeps_sibling_progress() {
+        let a = mock("a")
+            .then_fail("boom")
+            .then(TaskResult::move_to_next_direct());
+        let graph = fork_graph(a, JoinPolicy::All);
+        let mut session = Session::new_from_task("s".to_string(), "start");
+
+        // "start" continues straight into the branches
+        assert!(graph.execute_session(&mut session).await.is_err());
+        assert_eq!(session.current_task_id, "a");
+        assert_eq!(session.context.get::<bool>("ran.a").await, None);
+        let parallel = session.parallel.as_ref().unwrap();
+        assert_eq!(parallel.branches[1].status, BranchStatus::Arrived);
+
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+    }
+
+    fn chain(builder: GraphBuilder) -> Graph {
+        builder
+            .add_task(Arc::new(mock("a")))
+            .add_task(Arc::new(mock("b")))
+            .add_task(Arc::new(
+                mock("c").then(TaskResult::new(None, NextAction::End)),
+            ))
+            .add_edge("a", "b")
+            .add_edge("b", "c")
+            .build()
+    }
+
+    #[tokio::test]
+    async fn graph_breakpoint_stops_before_task_until_continued() {
+        let graph = chain(GraphBuilder::new("chain").interrupt_before("b"));
+        let mut session = Session::new_from_task("s".to_string(), "a");
+
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(
+            result.status,
+            ExecutionStatus::PausedAtBreakpoint { ref next_task_id, ref breakpoint }
+                if next_task_id == "b" && *breakpoint == Breakpoint::before("b")
+        ));
+        assert_eq!(session.context.get::<bool>("ran.b").await, None);
+
+        // Persisted and continued: the breakpoint is passed, not hit again
+        let mut session: Session =
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        session.context.set("edited", true).await;
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert!(session.interrupted.is_none());
+        assert_eq!(session.context.get::<bool>("ran.c").await, Some(true));
+    }
+
+    #[tokio::test]
+    async fn session_breakpoint_after_task_and_single_step() {
+        let graph = chain(GraphBuilder::new("chain"));
+        let mut session = Session::new_from_task("s".to_string(), "a");
+        session.interrupt_after("a");
+
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(
+            result.status,
+            ExecutionStatus::PausedAtBreakpoint { ref next_task_id, ref breakpoint }
+                if next_task_id == "b" && breakpoint.position == BreakpointPosition::After
+        ));
+
+        let result = graph.step_session(&mut session).await.unwrap();
+        assert!(matches!(
+            result.status,
+            ExecutionStatus::PausedAtBreakpoint { ref next_task_id, .. } if next_task_id == "c"
+        ));
+        assert_eq!(session.context.get::<bool>("ran.b").await, Some(true));
+        assert_eq!(session.context.get::<bool>("ran.c").await, None);
+        assert_eq!(session.breakpoints, [Breakpoint::after("a")]);
+
+        let result = graph.execute_session(&mut session).await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+    }
+
+    #[tokio::test]
+    async fn error_edge_hands_a_failed_task_to_a_human() {
+        let graph = GraphBuilder::new("payments")
+            .add_task(Arc::new(MockTask::new("charge").then_fail("card declined")))
+            .add_task(Arc::new(MockTask::new("human").then(TaskResult::new(
+                Some("An agent will call you".into()),
+                NextAction::WaitForInput,
+            ))))
+            .add_task(Arc::new(mock("receipt")))
+            .add_edge("charge", "receipt")
+            .add_error_edge("charge", "human")
+            .build();
+
+        let outcome = Scenario::new(graph).run().await;
+        outcome.assert_path(&["charge", "human"]);
+        assert!(matches!(outcome.status, ExecutionStatus::WaitingForInput));
+        assert_eq!(outcome.responses, ["An agent will call you"]);
+        let failure: TaskFailure = outcome.context().get(FAILURE_KEY).await.unwrap();
+        assert_eq!(failure.task_id, "charge");
+        assert_eq!(failure.kind, "task_execution_failed");
+        assert!(
+            failure.message.contains("card declined"),
+            "{}",
+            failure.message
+        );
+        assert_eq!(failure.attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn error_handler_counts_attempts_until_the_task_succeeds() {
+        let retry = || TaskResult::new(None, NextAction::GoTo("flaky".into()));
+        let handler = MockTask::new("handler")
+            .then(retry())
+            .then(retry())
+            .then_fail("handler down");
+        let graph = GraphBuilder::new("retries")
+            .add_task(Arc::new(
+                MockTask::new("flaky")
+                    .then_fail("timeout talking to API")
+                    .then_fail("timeout talking to API")
+                    .then(TaskResult::new(None, NextAction::Continue))
+                    .then_fail("down again"),
+            ))
+            .add_task(Arc::new(handler))
+            .set_error_handler("handler")
+            .build();
+        let mut session = Session::new_from_task("s".to_string(), "flaky");
+
+        for attempts in [1, 2] {
+            let result = graph.execute_session(&mut session).await.unwrap();
+            assert!(matches!(
+                result.status,
+                ExecutionStatus::Paused { ref next_task_id, .. } if next_task_id == "flaky"
+            ));
+            let failure: TaskFailure = session.context.get(FAILURE_KEY).await.unwrap();
+            assert_eq!(failure.attempts, attempts);
+        }
+
+        // Success clears the failure; a later failure starts counting again
+        graph.execute_session(&mut session).await.unwrap();
+        assert_eq!(session.context.get::<TaskFailure>(FAILURE_KEY).await, None);
+
+        // The handler's own failure is not routed to itself
+        let err = graph.execute_session(&mut session).await.unwrap_err();
+        assert!(err.to_string().contains("handler down"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn handlers_that_keep_retrying_give_up_eventually() {
+        let graph = GraphBuilder::new("retries")
+            .add_task(Arc::new(MockTask::new("flaky").then_fail("API down")))
+            .add_task(Arc::new(
+                MockTask::new("handler")
+                    .then(TaskResult::new(None, NextAction::GoTo("flaky".into()))),
+            ))
+            .set_error_handler("handler")
+            .build();
+        let mut session = Session::new_from_task("s".to_string(), "flaky");
+
+        // Every call is a new route from the task to the handler and back
+        for attempts in 1..=MAX_ROUTED_ATTEMPTS {
+            graph.execute_session(&mut session).await.unwrap();
+            let failure: TaskFailure = session.context.get(FAILURE_KEY).await.unwrap();
+            assert_eq!(failure.attempts, attempts);
+        }
+        let err = graph.execute_session(&mut session).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("16 failures in a row"), "{message}");
+        assert!(message.contains("API down"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn rejected_callers_are_not_routed_to_error_handlers() {
+        let graph = GraphBuilder::new("approvals")
+            .add_task(ApprovalTask::new("approval", "Approve?").with_required_roles(["manager"]))
+            .add_task(Arc::new(mock("escalate")))
+            .add_task(Arc::new(mock("handler")))
+            .add_error_edge("approval", "escalate")
+            .set_error_handler("handler")
+            .build();
+        let mut session = Session::new_from_task("s".to_string(), "approval");
+        graph.execute_session(&mut session).await.unwrap();
+
+        let input = ApprovalInput {
+            reviewer: Reviewer::new("carol", ["clerk"]),
+            decision: ApprovalDecision::Approve,
+            comment: None,
+        };
+        let err = graph
+            .resume_session(&mut session, serde_json::to_value(input).unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::Unauthorized(_)), "{err}");
+        assert_eq!(session.current_task_id, "approval");
+        assert!(session.pending_input.is_some());
+        assert_eq!(session.context.get::<TaskFailure>(FAILURE_KEY).await, None);
+    }
+
+    #[tokio::test]
+    async fn error_routes_that_loop_back_fail() {
+        let graph = GraphBuilder::new("cycle")
+            .add_task(Arc::new(MockTask::new("charge").then_fail("card declined")))
+            .add_task(Arc::new(
+                MockTask::new("refund").then_fail("refund declined"),
+            ))
+            .add_error_edge("charge", "refund")
+            .add_error_edge("refund", "charge")
+            .build();
+        let mut session = Session::new_from_task("s".to_string(), "charge");
+
+        let err = graph.execute_session(&mut session).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("leads back to 'charge'"), "{message}");
+        assert!(message.contains("charge -> refund"), "{message}");
+        assert!(message.contains("refund declined"), "{message}");
+    }
+}
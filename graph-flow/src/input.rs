@@ -0,0 +1,183 @@
+//! Typed input requests for tasks that wait for input.
+//!
+//! A task returning [`NextAction::WaitForInput`](crate::NextAction::WaitForInput)
+//! can describe what it expects with an [`InputRequest`]: a prompt, a JSON schema
+//! and, for choices, the allowed values. The request is returned to the caller in
+//! [`ExecutionResult::input_request`](crate::ExecutionResult::input_request),
+//! stored with the session in [`PendingInput`](crate::PendingInput), and
+//! [`FlowRunner::resume`](crate::FlowRunner::resume) rejects input that does not
+//! match it before anything runs.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{InputRequest, TaskResult};
+//! use serde_json::json;
+//!
+//! let request = InputRequest::choice("How should we proceed?", ["approve", "reject"]);
+//! assert!(request.validate(&json!("approve")).is_ok());
+//! assert!(request.validate(&json!("maybe")).is_err());
+//!
+//! // In a task: reply with the prompt and wait for a valid answer
+//! let result = TaskResult::request_input(InputRequest::confirm("Submit the claim?"));
+//! assert_eq!(result.response.as_deref(), Some("Submit the claim?"));
+//! ```
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::{GraphError, Result};
+
+/// What a task waiting for input expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputRequest {
+    /// Question shown to the user
+    pub prompt: String,
+    /// JSON schema the input must match, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    /// Allowed values, for requests made with [`InputRequest::choice`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<Value>,
+}
+
+impl InputRequest {
+    /// Accept any input.
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            schema: None,
+            choices: Vec::new(),
+        }
+    }
+
+    /// Free text.
+    pub fn text(prompt: impl Into<String>) -> Self {
+        Self::new(prompt).with_schema(json!({ "type": "string" }))
+    }
+
+    /// A yes/no answer, submitted as `true` or `false`.
+    pub fn confirm(prompt: impl Into<String>) -> Self {
+        Self::new(prompt).with_schema(json!({ "type": "boolean" }))
+    }
+
+    /// A number.
+    pub fn number(prompt: impl Into<String>) -> Self {
+        Self::new(prompt).with_schema(json!({ "type": "number" }))
+    }
+
+    /// One of `choices`.
+    pub fn choice<T: Serialize>(
+        prompt: impl Into<String>,
+        choices: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let choices: Vec<Value> = choices
+            .into_iter()
+            .map(|choice| serde_json::to_value(choice).unwrap_or(Value::Null))
+            .collect();
+        let mut request = Self::new(prompt).with_schema(json!({ "enum": choices }));
+        request.choices = choices;
+        request
+    }
+
+    /// A value of type `T`, described by its derived JSON schema.
+    pub fn typed<T: JsonSchema>(prompt: impl Into<String>) -> Self {
+        Self::new(prompt).with_schema(schemars::schema_for!(T).to_value())
+    }
+
+    /// Require the input to match `schema`.
+    pub fn with_schema(mut self, schema: Value) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Check `input` against the schema.
+    ///
+    /// Fails with [`GraphError::InvalidInput`] describing every violation, or if
+    /// the schema itself is invalid.
+    pub fn validate(&self, input: &Value) -> Result<()> {
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+        let errors = schema_errors(schema, input)
+            .map_err(|e| GraphError::InvalidInput(format!("Invalid input schema: {e}")))?;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(GraphError::InvalidInput(errors.join("; ")))
+        }
+    }
+}
+
+/// Every violation of `schema` by `value`, prefixed with its location.
+///
+/// Fails with the compile error if `schema` is not a valid JSON schema.
+pub(crate) fn schema_errors(
+    schema: &Value,
+    value: &Value,
+) -> std::result::Result<Vec<String>, String> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| e.to_string())?;
+    Ok(validator
+        .iter_errors(value)
+        .map(|error| match error.instance_path.to_string() {
+            path if path.is_empty() => error.to_string(),
+            path => format!("{path}: {error}"),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Review {
+        approved: bool,
+        comment: Option<String>,
+    }
+
+    #[test]
+    fn builders_constrain_the_input_type() {
+        assert!(
+            InputRequest::new("Anything?")
+                .validate(&json!({"a": 1}))
+                .is_ok()
+        );
+        assert!(InputRequest::text("Name?").validate(&json!("Ada")).is_ok());
+        assert!(InputRequest::text("Name?").validate(&json!(42)).is_err());
+        assert!(
+            InputRequest::confirm("Sure?")
+                .validate(&json!(true))
+                .is_ok()
+        );
+        assert!(
+            InputRequest::confirm("Sure?")
+                .validate(&json!("yes"))
+                .is_err()
+        );
+        assert!(
+            InputRequest::number("Amount?")
+                .validate(&json!(12.5))
+                .is_ok()
+        );
+
+        let choice = InputRequest::choice("Pick one", ["car", "apartment"]);
+        assert_eq!(choice.choices, [json!("car"), json!("apartment")]);
+        assert!(choice.validate(&json!("boat")).is_err());
+    }
+
+    #[test]
+    fn typed_requests_report_where_the_input_is_wrong() {
+        let request = InputRequest::typed::<Review>("Your review");
+        assert!(request.validate(&json!({"approved": true})).is_ok());
+
+        let err = request
+            .validate(&json!({"approved": "yes"}))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("/approved"), "{err}");
+        assert!(request.validate(&json!({"comment": "ok"})).is_err());
+    }
+}
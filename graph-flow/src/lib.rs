@@ -166,26 +166,76 @@
 //! - [`InMemorySessionStorage`]: For development and testing
 //! - [`PostgresSessionStorage`]: For production use with PostgreSQL
 
+pub mod approval;
+pub mod blob_store;
+pub mod cassette;
+pub mod compaction;
+pub mod compensation;
 pub mod context;
+pub mod encryption;
 pub mod error;
+pub mod fanout;
 pub mod graph;
+pub mod input;
+pub mod llm;
+pub mod map;
+pub mod message;
+pub mod queue;
+#[cfg(feature = "rig")]
+pub mod rig_agent;
 pub mod runner;
+pub mod scheduler;
 pub mod storage;
 pub mod storage_postgres;
+pub mod structured;
 pub mod task;
-pub mod fanout;
+pub mod template;
+pub mod testing;
+pub mod worker;
 
 // Re-export commonly used types
-pub use context::{ChatHistory, Context, MessageRole, SerializableMessage};
+pub use approval::{ApprovalDecision, ApprovalInput, ApprovalRecord, ApprovalTask, Reviewer};
+pub use blob_store::{BlobRef, BlobStore, FileBlobStore, InMemoryBlobStore};
+pub use cassette::{Cassette, CassetteLlmClient, CassetteMode, RequestNormalizer};
+#[cfg(feature = "rig")]
+pub use compaction::PromptSummarizer;
+pub use compaction::{ApproxTokenEstimator, ChatSummarizer, CompactionPolicy, TokenEstimator};
+pub use compensation::{COMPENSATION_KEY, Compensation, CompensationRecord};
+pub use context::{
+    ChatHistory, Checkpoint, Context, ContextDiff, ContextKey, MergeReducer, MergeStrategy,
+    MessageRole, SerializableMessage,
+};
+pub use encryption::{
+    ContextCipher, EncryptedBlobStore, EncryptionKey, EncryptionScope, KeyProvider,
+    StaticKeyProvider,
+};
 pub use error::{GraphError, Result};
-pub use graph::{ExecutionResult, ExecutionStatus, Graph, GraphBuilder};
+pub use fanout::{ChildResult, ChildStatus, FailurePolicy, FanOutResults, FanOutTask};
+pub use graph::{
+    ExecutionResult, ExecutionStatus, FAILURE_KEY, Graph, GraphBuilder, INPUT_KEY, JoinPolicy,
+    TIMER_FIRED_KEY, TaskFailure,
+};
+pub use input::InputRequest;
+#[cfg(feature = "rig")]
+pub use llm::{LlmAgent, RigLlmClient};
+pub use llm::{LlmClient, LlmRequest, MockLlmClient};
+pub use map::{MapItemResult, MapTask};
+pub use message::{ContentPart, MediaPart, MediaSource, ToolCall, ToolResult};
+pub use queue::{DEFAULT_MAX_ATTEMPTS, InMemoryJobQueue, Job, JobQueue, JobStatus};
+#[cfg(feature = "rig")]
+pub use rig_agent::{AgentReply, HistoryMode, NextActionPolicy, OutputParser, RigAgentTask};
 pub use runner::FlowRunner;
+pub use scheduler::{Scheduler, WAKE_FAILURE_KEY, WakeOutcome};
 pub use storage::{
-    GraphStorage, InMemoryGraphStorage, InMemorySessionStorage, Session, SessionStorage,
+    BranchState, BranchStatus, Breakpoint, BreakpointPosition, GraphStorage, InMemoryGraphStorage,
+    InMemorySessionStorage, ParallelState, PendingInput, Session, SessionStorage, SessionTimer,
+    StepWriteSet,
 };
-pub use storage_postgres::PostgresSessionStorage;
+pub use storage_postgres::{PostgresBlobStore, PostgresJobQueue, PostgresSessionStorage};
+pub use structured::{Extraction, OutputValidator, StructuredOutput};
 pub use task::{NextAction, Task, TaskResult};
-pub use fanout::FanOutTask;
+pub use template::PromptTemplate;
+pub use worker::{WorkerPool, WorkerPoolHandle};
 
 #[cfg(test)]
 mod tests {
@@ -234,6 +284,141 @@ mod tests {
         assert_eq!(output, "Processed: Hello, World!");
     }
 
+    struct PartialWriteTask {
+        id: &'static str,
+        transactional: bool,
+    }
+
+    #[async_trait]
+    impl Task for PartialWriteTask {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn transactional(&self) -> bool {
+            self.transactional
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            context.set("partial", true).await;
+            context.add_user_message("partial".to_string()).await;
+            Err(GraphError::TaskExecutionFailed("boom".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_task_writes_are_rolled_back() {
+        let task = Arc::new(PartialWriteTask {
+            id: "partial",
+            transactional: true,
+        });
+        let graph = GraphBuilder::new("tx").add_task(task).build();
+
+        let storage = Arc::new(InMemorySessionStorage::new());
+        let session = Session::new_from_task("s".to_string(), "partial");
+        session.context.set("existing", 1).await;
+        storage.save(session).await.unwrap();
+
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone());
+        assert!(runner.run("s").await.is_err());
+
+        let session = storage.get("s").await.unwrap().unwrap();
+        assert_eq!(session.context.get::<bool>("partial").await, None);
+        assert_eq!(session.context.get::<i32>("existing").await, Some(1));
+        assert!(session.context.is_chat_history_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_non_transactional_task_keeps_partial_writes() {
+        let task = Arc::new(PartialWriteTask {
+            id: "partial",
+            transactional: false,
+        });
+        let graph = GraphBuilder::new("tx").add_task(task).build();
+
+        let context = Context::new();
+        assert!(graph.execute("partial", context.clone()).await.is_err());
+        assert_eq!(context.get::<bool>("partial").await, Some(true));
+    }
+
+    struct SlowWriterTask;
+
+    #[async_trait]
+    impl Task for SlowWriterTask {
+        fn id(&self) -> &str {
+            "slow"
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            context.set("partial", true).await;
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok(TaskResult::new(None, NextAction::End))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_task_writes_are_rolled_back() {
+        let mut graph = Graph::new("tx");
+        graph.set_task_timeout(std::time::Duration::from_millis(20));
+        graph.add_task(Arc::new(SlowWriterTask));
+
+        let mut session = Session::new_from_task("s".to_string(), "slow");
+        assert!(graph.execute_session(&mut session).await.is_err());
+        assert_eq!(session.context.get::<bool>("partial").await, None);
+    }
+
+    struct WriterTask {
+        id: &'static str,
+        key: &'static str,
+    }
+
+    #[async_trait]
+    impl Task for WriterTask {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            context.set(self.key, self.id).await;
+            context
+                .add_assistant_message(format!("{} ran", self.id))
+                .await;
+            Ok(TaskResult::new(None, NextAction::ContinueAndExecute))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_steps_record_write_sets() {
+        let first = Arc::new(WriterTask {
+            id: "first",
+            key: "document",
+        });
+        let second = Arc::new(WriterTask {
+            id: "second",
+            key: "document",
+        });
+        let graph = GraphBuilder::new("writes")
+            .add_task(first)
+            .add_task(second)
+            .add_edge("first", "second")
+            .build();
+
+        let mut session = Session::new_from_task("s".to_string(), "first");
+        graph.execute_session(&mut session).await.unwrap();
+
+        assert_eq!(session.write_sets.len(), 2);
+        assert_eq!(session.write_sets[0].task_id, "first");
+        assert_eq!(session.write_sets[0].set, vec!["document".to_string()]);
+        assert_eq!(session.write_sets[0].messages_appended, 1);
+        assert_eq!(session.last_writer("document"), Some("second"));
+        assert_eq!(session.last_writer("missing"), None);
+
+        // Write sets survive a serialization round trip
+        let json = serde_json::to_string(&session).unwrap();
+        let restored: Session = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.write_sets.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_storage() {
         let graph_storage = InMemoryGraphStorage::new();
@@ -254,6 +439,14 @@ mod tests {
             current_task_id: "task1".to_string(),
             status_message: None,
             context: Context::new(),
+            write_sets: Vec::new(),
+            parallel: None,
+            timer: None,
+            pending_input: None,
+            breakpoints: Vec::new(),
+            interrupted: None,
+            compensable_steps: Vec::new(),
+            version: 0,
         };
 
         session_storage.save(session.clone()).await.unwrap();
@@ -0,0 +1,532 @@
+//! Provider-agnostic LLM access.
+//!
+//! Tasks that depend on an [`LlmClient`] instead of a concrete provider can be
+//! run against a real model in production and a [`MockLlmClient`] in tests,
+//! so whole workflows can be exercised offline.
+//!
+//! - [`LlmRequest`]: a prompt with optional preamble, history and model
+//!   settings, built from the crate's own [`SerializableMessage`] type
+//! - [`MockLlmClient`]: canned responses, either queued or matched by rule,
+//!   with every request recorded for assertions
+//! - `RigLlmClient` (with the `rig` feature): sends requests through any rig
+//!   provider client
+//! - `LlmAgent` (with the `rig` feature): exposes an `LlmClient` as a rig
+//!   `Chat`/`Prompt` agent, so it plugs into [`RigAgentTask`],
+//!   [`StructuredOutput::extract`] and [`PromptSummarizer`]
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{LlmClient, LlmRequest, MockLlmClient};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let llm = MockLlmClient::new()
+//!     .when_prompt_contains("classify", r#"{"insurance_type": "car"}"#)
+//!     .with_response("Hello! How can I help with your claim?");
+//!
+//! let reply = llm.complete(LlmRequest::new("Hi")).await?;
+//! assert_eq!(reply, "Hello! How can I help with your claim?");
+//!
+//! let reply = llm
+//!     .complete(LlmRequest::new("Please classify: I hit a tree").with_preamble("You are..."))
+//!     .await?;
+//! assert_eq!(reply, r#"{"insurance_type": "car"}"#);
+//! assert_eq!(llm.requests().len(), 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::context::SerializableMessage;
+use crate::error::{GraphError, Result};
+
+#[cfg(doc)]
+use crate::StructuredOutput;
+#[cfg(all(doc, feature = "rig"))]
+use crate::{PromptSummarizer, RigAgentTask};
+
+/// A single completion request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmRequest {
+    /// Model to use; `None` lets the client pick its default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// System prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<String>,
+    /// Conversation before the prompt, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<SerializableMessage>,
+    pub prompt: SerializableMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+}
+
+impl LlmRequest {
+    /// A request with a plain-text user prompt.
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self::from_message(SerializableMessage::user(prompt.into()))
+    }
+
+    /// A request whose prompt is an arbitrary message, e.g. one with image parts.
+    pub fn from_message(prompt: SerializableMessage) -> Self {
+        Self {
+            model: None,
+            preamble: None,
+            history: Vec::new(),
+            prompt,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn with_preamble(mut self, preamble: impl Into<String>) -> Self {
+        self.preamble = Some(preamble.into());
+        self
+    }
+
+    pub fn with_history(mut self, history: Vec<SerializableMessage>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+}
+
+/// Anything that can turn an [`LlmRequest`] into a text reply.
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    async fn complete(&self, request: LlmRequest) -> Result<String>;
+}
+
+type MockRule = (
+    Arc<dyn Fn(&LlmRequest) -> bool + Send + Sync>,
+    Arc<dyn Fn(&LlmRequest) -> String + Send + Sync>,
+);
+
+/// Scripted [`LlmClient`] for tests and offline runs.
+///
+/// Rules are checked first, in the order they were added, and can match any
+/// number of requests. Otherwise the next queued response is used. A request
+/// that matches no rule when the queue is empty fails with
+/// [`GraphError::TaskExecutionFailed`].
+#[derive(Default)]
+pub struct MockLlmClient {
+    rules: Vec<MockRule>,
+    responses: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<LlmRequest>>,
+}
+
+impl MockLlmClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a response for the next request not matched by a rule.
+    pub fn with_response(self, response: impl Into<String>) -> Self {
+        self.push_response(response);
+        self
+    }
+
+    /// Queue responses in order.
+    pub fn with_responses<I, S>(self, responses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for response in responses {
+            self.push_response(response);
+        }
+        self
+    }
+
+    /// Answer every request matching `matches` with the output of `respond`.
+    pub fn with_rule<M, R>(mut self, matches: M, respond: R) -> Self
+    where
+        M: Fn(&LlmRequest) -> bool + Send + Sync + 'static,
+        R: Fn(&LlmRequest) -> String + Send + Sync + 'static,
+    {
+        self.rules.push((Arc::new(matches), Arc::new(respond)));
+        self
+    }
+
+    /// Answer every request whose prompt or preamble contains `needle` with `response`.
+    pub fn when_prompt_contains(
+        self,
+        needle: impl Into<String>,
+        response: impl Into<String>,
+    ) -> Self {
+        let needle = needle.into();
+        let response = response.into();
+        self.with_rule(
+            move |request| {
+                request.prompt.content.contains(&needle)
+                    || request
+                        .preamble
+                        .as_deref()
+                        .is_some_and(|preamble| preamble.contains(&needle))
+            },
+            move |_| response.clone(),
+        )
+    }
+
+    /// Queue a response on a client that is already shared.
+    pub fn push_response(&self, response: impl Into<String>) {
+        self.responses.lock().unwrap().push_back(response.into());
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<LlmRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LlmClient for MockLlmClient {
+    async fn complete(&self, request: LlmRequest) -> Result<String> {
+        self.requests.lock().unwrap().push(request.clone());
+
+        if let Some((_, respond)) = self.rules.iter().find(|(matches, _)| matches(&request)) {
+            return Ok(respond(&request));
+        }
+        self.responses.lock().unwrap().pop_front().ok_or_else(|| {
+            GraphError::TaskExecutionFailed(format!(
+                "MockLlmClient has no response for prompt: {}",
+                request.prompt.content
+            ))
+        })
+    }
+}
+
+#[cfg(feature = "rig")]
+pub use self::rig_client::{LlmAgent, RigLlmClient};
+
+#[cfg(feature = "rig")]
+mod rig_client {
+    use rig::client::CompletionClient;
+    use rig::completion::{Chat, CompletionError, Message, Prompt, PromptError};
+    use rig::wasm_compat::WasmCompatSend;
+    use std::sync::Arc;
+
+    use super::{LlmClient, LlmRequest};
+    use crate::context::SerializableMessage;
+    use crate::error::{GraphError, Result};
+
+    /// [`LlmClient`] backed by a rig provider client, e.g. `openrouter::Client`.
+    ///
+    /// Each request builds an agent for the requested model (or the default)
+    /// with the request's preamble and settings.
+    pub struct RigLlmClient<C> {
+        client: C,
+        default_model: String,
+    }
+
+    impl<C: CompletionClient> RigLlmClient<C> {
+        pub fn new(client: C, default_model: impl Into<String>) -> Self {
+            Self {
+                client,
+                default_model: default_model.into(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl<C> LlmClient for RigLlmClient<C>
+    where
+        C: CompletionClient + Send + Sync,
+        C::CompletionModel: 'static,
+    {
+        async fn complete(&self, request: LlmRequest) -> Result<String> {
+            let model = request.model.as_deref().unwrap_or(&self.default_model);
+            let mut builder = self.client.agent(model);
+            if let Some(preamble) = &request.preamble {
+                builder = builder.preamble(preamble);
+            }
+            if let Some(temperature) = request.temperature {
+                builder = builder.temperature(temperature);
+            }
+            if let Some(max_tokens) = request.max_tokens {
+                builder = builder.max_tokens(max_tokens);
+            }
+            let agent = builder.build();
+
+            let history: Vec<Message> = request.history.iter().map(Message::from).collect();
+            agent
+                .chat(Message::from(request.prompt), history)
+                .await
+                .map_err(|e| GraphError::TaskExecutionFailed(format!("LLM request failed: {e}")))
+        }
+    }
+
+    /// A rig [`Chat`] and [`Prompt`] agent that sends its requests through an
+    /// [`LlmClient`].
+    #[derive(Clone)]
+    pub struct LlmAgent {
+        client: Arc<dyn LlmClient>,
+        model: Option<String>,
+        preamble: Option<String>,
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
+    }
+
+    impl LlmAgent {
+        pub fn new(client: Arc<dyn LlmClient>) -> Self {
+            Self {
+                client,
+                model: None,
+                preamble: None,
+                temperature: None,
+                max_tokens: None,
+            }
+        }
+
+        pub fn with_model(mut self, model: impl Into<String>) -> Self {
+            self.model = Some(model.into());
+            self
+        }
+
+        pub fn with_preamble(mut self, preamble: impl Into<String>) -> Self {
+            self.preamble = Some(preamble.into());
+            self
+        }
+
+        pub fn with_temperature(mut self, temperature: f64) -> Self {
+            self.temperature = Some(temperature);
+            self
+        }
+
+        pub fn with_max_tokens(mut self, max_tokens: u64) -> Self {
+            self.max_tokens = Some(max_tokens);
+            self
+        }
+
+        fn request(&self, prompt: Message, history: Vec<SerializableMessage>) -> LlmRequest {
+            LlmRequest {
+                model: self.model.clone(),
+                preamble: self.preamble.clone(),
+                history,
+                prompt: SerializableMessage::from(prompt),
+                temperature: self.temperature,
+                max_tokens: self.max_tokens,
+            }
+        }
+
+        async fn send(&self, request: LlmRequest) -> std::result::Result<String, PromptError> {
+            self.client.complete(request).await.map_err(|e| {
+                PromptError::CompletionError(CompletionError::ProviderError(e.to_string()))
+            })
+        }
+    }
+
+    impl Chat for LlmAgent {
+        #[allow(refining_impl_trait)]
+        fn chat<I, T>(
+            &self,
+            prompt: impl Into<Message> + WasmCompatSend,
+            chat_history: I,
+        ) -> impl std::future::Future<Output = std::result::Result<String, PromptError>> + WasmCompatSend
+        where
+            I: IntoIterator<Item = T> + WasmCompatSend,
+            T: Into<Message>,
+        {
+            let history = chat_history
+                .into_iter()
+                .map(|message| SerializableMessage::from(message.into()))
+                .collect();
+            let request = self.request(prompt.into(), history);
+            async move { self.send(request).await }
+        }
+    }
+
+    impl Prompt for LlmAgent {
+        #[allow(refining_impl_trait)]
+        fn prompt(
+            &self,
+            prompt: impl Into<Message> + WasmCompatSend,
+        ) -> impl std::future::Future<Output = std::result::Result<String, PromptError>> + WasmCompatSend
+        {
+            let request = self.request(prompt.into(), Vec::new());
+            async move { self.send(request).await }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rules_take_precedence_and_empty_queue_errors() {
+        let llm = MockLlmClient::new()
+            .with_responses(["first", "second"])
+            .with_rule(
+                |request| request.model.as_deref() == Some("vision"),
+                |_| "page text".to_string(),
+            );
+
+        let vision = LlmRequest::new("read this").with_model("vision");
+        assert_eq!(llm.complete(vision.clone()).await.unwrap(), "page text");
+        assert_eq!(llm.complete(LlmRequest::new("a")).await.unwrap(), "first");
+        assert_eq!(llm.complete(vision).await.unwrap(), "page text");
+        assert_eq!(llm.complete(LlmRequest::new("b")).await.unwrap(), "second");
+
+        let err = llm.complete(LlmRequest::new("c")).await.unwrap_err();
+        assert!(matches!(err, GraphError::TaskExecutionFailed(msg) if msg.contains("c")));
+        assert_eq!(llm.requests().len(), 5);
+    }
+
+    #[cfg(feature = "rig")]
+    #[tokio::test]
+    async fn llm_agent_drives_rig_agent_task_offline() {
+        use crate::context::{Context, MessageRole};
+        use crate::task::{NextAction, Task};
+        use crate::{Extraction, RigAgentTask, StructuredOutput};
+
+        let llm =
+            Arc::new(MockLlmClient::new().with_responses(["refined query", r#"{"ok": true}"#]));
+        let agent = LlmAgent::new(llm.clone())
+            .with_preamble("You refine queries")
+            .with_model("small");
+
+        let context = Context::new();
+        context.set("query", "space films").await;
+        context.add_user_message("earlier".to_string()).await;
+
+        let task = RigAgentTask::new("refine", agent.clone(), "Refine {{query}}")
+            .with_output_key("refined");
+        let result = task.run(context.clone()).await.unwrap();
+        assert_eq!(result.next_action, NextAction::ContinueAndExecute);
+        assert_eq!(
+            context.get::<String>("refined").await.unwrap(),
+            "refined query"
+        );
+
+        let extraction = StructuredOutput::<serde_json::Value>::new()
+            .extract(&agent, "Check", Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            extraction,
+            Extraction::Value(serde_json::json!({ "ok": true }))
+        );
+
+        let requests = llm.requests();
+        assert_eq!(requests[0].preamble.as_deref(), Some("You refine queries"));
+        assert_eq!(requests[0].model.as_deref(), Some("small"));
+        assert_eq!(requests[0].prompt.content, "Refine space films");
+        assert_eq!(requests[0].history.len(), 1);
+        assert_eq!(requests[0].history[0].role, MessageRole::User);
+    }
+
+    /// Serves one OpenRouter chat completion on a local port and returns the
+    /// request body it received.
+    #[cfg(feature = "rig")]
+    async fn fake_openrouter(reply: &str) -> (String, tokio::task::JoinHandle<serde_json::Value>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let body = serde_json::json!({
+            "id": "gen-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "openai/gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": reply },
+                "finish_reason": "stop"
+            }]
+        })
+        .to_string();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 8192];
+            let (head_len, content_length) = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .and_then(|value| value.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    break (end + 4, length);
+                }
+            };
+            while request.len() < head_len + content_length {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            serde_json::from_slice(&request[head_len..]).unwrap()
+        });
+        (base_url, server)
+    }
+
+    #[cfg(feature = "rig")]
+    #[tokio::test]
+    async fn base64_image_is_sent_through_openrouter() {
+        use crate::message::{ContentPart, MediaPart};
+        use rig::providers::openrouter;
+
+        let (base_url, server) = fake_openrouter("A dented rear bumper").await;
+        let client = openrouter::Client::builder()
+            .api_key("test-key")
+            .base_url(&base_url)
+            .build()
+            .unwrap();
+        let llm = RigLlmClient::new(client, "openai/gpt-4o-mini");
+
+        let image = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk";
+        let prompt = SerializableMessage::user_parts(vec![
+            ContentPart::text("What does this photo show?"),
+            ContentPart::Image(MediaPart::base64(image).with_media_type("image/png")),
+        ]);
+        let reply = llm
+            .complete(LlmRequest::from_message(prompt))
+            .await
+            .unwrap();
+        assert_eq!(reply, "A dented rear bumper");
+
+        let body = server.await.unwrap();
+        let content = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|message| message["role"] == "user")
+            .map(|message| message["content"].to_string())
+            .unwrap();
+        assert!(content.contains("What does this photo show?"), "{content}");
+        assert!(
+            content.contains(&format!("data:image/png;base64,{image}")),
+            "{content}"
+        );
+    }
+}
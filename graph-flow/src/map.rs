@@ -0,0 +1,479 @@
+//! MapTask – run one task template once per element of a context collection
+//!
+//! Where [`FanOutTask`](crate::FanOutTask) runs a fixed set of children known at
+//! graph build time, `MapTask` decides the amount of work at run time: it reads a
+//! JSON array from a context key and runs the same template task once per element.
+//!
+//! Each element runs on its own `Context::fork()` (its *scope*) in which the element
+//! is stored under `"item"` and its position under `"item_index"`. Writes made in a
+//! scope are discarded; what the template produces for an element is either its
+//! response or, with `with_output_key`, the value it wrote under that key in its
+//! scope. The outputs are written back, in collection order, as a
+//! `Vec<MapItemResult>` under `"<id>.results"`.
+//!
+//! Concurrency, per-item timeouts and failure handling work like `FanOutTask`:
+//! `with_max_concurrency`, `with_item_timeout` and `with_failure_policy`.
+//!
+//! Example:
+//! ```rust
+//! use graph_flow::{Context, MapItemResult, MapTask, NextAction, Task, TaskResult};
+//! use async_trait::async_trait;
+//! use std::sync::Arc;
+//!
+//! struct Shout;
+//!
+//! #[async_trait]
+//! impl Task for Shout {
+//!     fn id(&self) -> &str { "shout" }
+//!     async fn run(&self, ctx: Context) -> graph_flow::Result<TaskResult> {
+//!         let word: String = ctx.get("item").await.unwrap_or_default();
+//!         Ok(TaskResult::new(Some(word.to_uppercase()), NextAction::Continue))
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let map = MapTask::new("shout_all", "words", Arc::new(Shout)).with_max_concurrency(2);
+//! let ctx = Context::new();
+//! ctx.set("words", vec!["a", "b", "c"]).await;
+//! map.run(ctx.clone()).await?;
+//!
+//! let results: Vec<MapItemResult> = ctx.get("shout_all.results").await.unwrap();
+//! let shouted: Vec<String> = results
+//!     .iter()
+//!     .filter_map(|r| r.parse_output().ok().flatten())
+//!     .collect();
+//! assert_eq!(shouted, ["A", "B", "C"]);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::fanout::{ChildStatus, run_child};
+use crate::{Context, FailurePolicy, GraphError, NextAction, Result, Task, TaskResult};
+
+/// Outcome of the template run for one element of a [`MapTask`] collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapItemResult {
+    /// Position of the element in the collection
+    pub index: usize,
+    pub status: ChildStatus,
+    /// What the template produced; `None` unless the item succeeded
+    pub output: Option<Value>,
+    pub error: Option<String>,
+    /// Wall-clock run time; `None` for cancelled items
+    pub duration_ms: Option<u64>,
+}
+
+impl MapItemResult {
+    pub fn is_success(&self) -> bool {
+        self.status == ChildStatus::Succeeded
+    }
+
+    /// Deserialize the output into `T`.
+    pub fn parse_output<T: serde::de::DeserializeOwned>(&self) -> Result<Option<T>> {
+        self.output
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| {
+                GraphError::ContextError(format!(
+                    "failed to parse output of item {}: {e}",
+                    self.index
+                ))
+            })
+    }
+}
+
+/// Composite task that runs a template task once per element of a context collection.
+#[derive(Clone)]
+pub struct MapTask {
+    id: String,
+    items_key: String,              // context key holding the JSON array
+    template: Arc<dyn Task>,        // run once per element
+    item_key: String,               // default: "item"
+    index_key: String,              // default: "item_index"
+    output_key: Option<String>,     // default: the template's response
+    results_key: Option<String>,    // default: "<id>.results"
+    next_action: NextAction,        // default: Continue
+    max_concurrency: Option<usize>, // default: all elements at once
+    item_timeout: Option<Duration>, // default: no timeout
+    failure_policy: FailurePolicy,  // default: CollectAll
+}
+
+impl MapTask {
+    /// Create a `MapTask` running `template` over the array stored under `items_key`.
+    pub fn new(
+        id: impl Into<String>,
+        items_key: impl Into<String>,
+        template: Arc<dyn Task>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            id: id.into(),
+            items_key: items_key.into(),
+            template,
+            item_key: "item".to_string(),
+            index_key: "item_index".to_string(),
+            output_key: None,
+            results_key: None,
+            next_action: NextAction::Continue,
+            max_concurrency: None,
+            item_timeout: None,
+            failure_policy: FailurePolicy::default(),
+        })
+    }
+
+    /// Store the element (and its index) under `item_key` (and `index_key`) in each scope.
+    pub fn with_item_keys(
+        mut self: Arc<Self>,
+        item_key: impl Into<String>,
+        index_key: impl Into<String>,
+    ) -> Arc<Self> {
+        let this = Arc::make_mut(&mut self);
+        this.item_key = item_key.into();
+        this.index_key = index_key.into();
+        self
+    }
+
+    /// Use the value the template writes under `key` in its scope as the item's
+    /// output, instead of its response.
+    pub fn with_output_key(mut self: Arc<Self>, key: impl Into<String>) -> Arc<Self> {
+        Arc::make_mut(&mut self).output_key = Some(key.into());
+        self
+    }
+
+    /// Write the results array under `key` instead of `<id>.results`.
+    pub fn with_results_key(mut self: Arc<Self>, key: impl Into<String>) -> Arc<Self> {
+        Arc::make_mut(&mut self).results_key = Some(key.into());
+        self
+    }
+
+    /// Override the `NextAction` returned by the `MapTask` (default: `Continue`).
+    pub fn with_next_action(mut self: Arc<Self>, next: NextAction) -> Arc<Self> {
+        Arc::make_mut(&mut self).next_action = next;
+        self
+    }
+
+    /// Run the template for at most `limit` elements at a time.
+    pub fn with_max_concurrency(mut self: Arc<Self>, limit: usize) -> Arc<Self> {
+        Arc::make_mut(&mut self).max_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// Fail an element whose template run hasn't finished within `timeout`.
+    pub fn with_item_timeout(mut self: Arc<Self>, timeout: Duration) -> Arc<Self> {
+        Arc::make_mut(&mut self).item_timeout = Some(timeout);
+        self
+    }
+
+    /// Choose how failed elements are handled (default: [`FailurePolicy::CollectAll`]).
+    pub fn with_failure_policy(mut self: Arc<Self>, policy: FailurePolicy) -> Arc<Self> {
+        Arc::make_mut(&mut self).failure_policy = policy;
+        self
+    }
+
+    /// Context key under which the `Vec<MapItemResult>` is stored.
+    pub fn results_key(&self) -> String {
+        self.results_key
+            .clone()
+            .unwrap_or_else(|| format!("{}.results", self.id))
+    }
+
+    async fn output(&self, scope: &Context, result: TaskResult) -> Option<Value> {
+        match &self.output_key {
+            Some(key) => scope.get::<Value>(key).await,
+            None => result.response.map(Value::String),
+        }
+    }
+}
+
+#[async_trait]
+impl Task for MapTask {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn run(&self, context: Context) -> Result<TaskResult> {
+        let items: Vec<Value> = context.try_get(&self.items_key).await?.ok_or_else(|| {
+            GraphError::ContextError(format!(
+                "MapTask '{}': no collection under '{}'",
+                self.id, self.items_key
+            ))
+        })?;
+
+        let total = items.len();
+        // Scopes are forked as each element starts, so a large collection under a
+        // concurrency limit only holds copies of the context for running elements
+        let mut scopes: Vec<Option<Context>> = vec![None; total];
+        let mut pending = items.into_iter().enumerate();
+        let mut set = JoinSet::new();
+        let mut running: HashMap<tokio::task::Id, usize> = HashMap::new();
+        let mut spawn_next = |set: &mut JoinSet<_>,
+                              running: &mut HashMap<_, _>,
+                              scopes: &mut Vec<Option<Context>>| {
+            let Some((index, item)) = pending.next() else {
+                return;
+            };
+            let scope = context.fork();
+            scopes[index] = Some(scope.clone());
+            let (template, timeout) = (self.template.clone(), self.item_timeout);
+            let (item_key, index_key) = (self.item_key.clone(), self.index_key.clone());
+            let handle = set.spawn(async move {
+                scope.set(item_key, item).await;
+                scope.set(index_key, index).await;
+                run_child(template, scope, timeout).await
+            });
+            running.insert(handle.id(), index);
+        };
+
+        let limit = self.max_concurrency.unwrap_or(total);
+        for _ in 0..limit {
+            spawn_next(&mut set, &mut running, &mut scopes);
+        }
+
+        let mut records: Vec<Option<MapItemResult>> = vec![None; total];
+        let (mut succeeded, mut failed) = (0, 0);
+
+        while let Some(joined) = set.join_next_with_id().await {
+            let (task_id, elapsed, outcome) = match joined {
+                Ok((task_id, (elapsed, outcome))) => (task_id, Some(elapsed), outcome),
+                Err(join_err) => (
+                    join_err.id(),
+                    None,
+                    Err((ChildStatus::Failed, format!("join error: {}", join_err))),
+                ),
+            };
+            let index = running
+                .remove(&task_id)
+                .expect("every spawned item is tracked");
+            let scope = scopes[index]
+                .take()
+                .expect("every spawned item has a scope");
+            let mut record = MapItemResult {
+                index,
+                status: ChildStatus::Succeeded,
+                output: None,
+                error: None,
+                duration_ms: elapsed.map(|d| d.as_millis() as u64),
+            };
+            match outcome {
+                Ok(tr) => {
+                    record.output = self.output(&scope, tr).await;
+                    succeeded += 1;
+                }
+                Err((status, message)) => {
+                    record.status = status;
+                    record.error = Some(message);
+                    failed += 1;
+                }
+            }
+            records[index] = Some(record);
+
+            let outstanding = total - succeeded - failed;
+            if self.failure_policy.settled(succeeded, failed, outstanding) {
+                set.abort_all();
+                while set.join_next().await.is_some() {}
+                break;
+            }
+            spawn_next(&mut set, &mut running, &mut scopes);
+        }
+
+        // Items without a record were aborted or never started
+        let results: Vec<MapItemResult> = records
+            .into_iter()
+            .enumerate()
+            .map(|(index, record)| {
+                record.unwrap_or(MapItemResult {
+                    index,
+                    status: ChildStatus::Cancelled,
+                    output: None,
+                    error: None,
+                    duration_ms: None,
+                })
+            })
+            .collect();
+
+        if self.failure_policy.failed(succeeded, failed) {
+            let details = results
+                .iter()
+                .filter_map(|r| {
+                    r.error
+                        .as_ref()
+                        .map(|error| format!("item {} failed: {}", r.index, error))
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            context.set(self.results_key(), &results).await;
+            return Err(GraphError::TaskExecutionFailed(format!(
+                "MapTask '{}' failed ({} of {} item(s) succeeded): {}",
+                self.id,
+                succeeded,
+                results.len(),
+                details
+            )));
+        }
+
+        let mut summary = format!(
+            "MapTask '{}' completed {} of {} item(s)",
+            self.id,
+            succeeded,
+            results.len()
+        );
+        if failed > 0 {
+            summary.push_str(&format!(", {} failed", failed));
+        }
+        context.set(self.results_key(), &results).await;
+
+        Ok(TaskResult::new_with_status(
+            Some(summary.clone()),
+            self.next_action.clone(),
+            Some(summary),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::sleep;
+
+    /// Doubles its item after a delay that shrinks with the index, so later items
+    /// finish first.
+    struct Double {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Task for Double {
+        fn id(&self) -> &str {
+            "double"
+        }
+        async fn run(&self, ctx: Context) -> Result<TaskResult> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            let item: i64 = ctx.get("item").await.unwrap();
+            let index: usize = ctx.get("item_index").await.unwrap();
+            sleep(Duration::from_millis(40 - 10 * index as u64)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            if item < 0 {
+                return Err(GraphError::TaskExecutionFailed(format!(
+                    "negative item {item}"
+                )));
+            }
+            ctx.set("doubled", item * 2).await;
+            ctx.set("leaked", true).await;
+            Ok(TaskResult::new(
+                Some(format!("{item} doubled")),
+                NextAction::End,
+            ))
+        }
+    }
+
+    fn double() -> (Arc<dyn Task>, Arc<AtomicUsize>) {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let task = Arc::new(Double {
+            running: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        });
+        (task, peak)
+    }
+
+    #[tokio::test]
+    async fn map_writes_ordered_outputs_with_scoped_context() {
+        let (template, peak) = double();
+        let map = MapTask::new("map", "numbers", template)
+            .with_output_key("doubled")
+            .with_max_concurrency(2);
+
+        let ctx = Context::new();
+        ctx.set("numbers", vec![1, 2, 3, 4]).await;
+        map.run(ctx.clone()).await.unwrap();
+
+        let results: Vec<MapItemResult> = ctx.get("map.results").await.unwrap();
+        let outputs: Vec<i64> = results
+            .iter()
+            .map(|r| r.parse_output().unwrap().unwrap())
+            .collect();
+        assert_eq!(outputs, [2, 4, 6, 8]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(ctx.get::<bool>("leaked").await, None);
+        assert_eq!(ctx.get::<i64>("item").await, None);
+    }
+
+    #[tokio::test]
+    async fn map_defaults_to_responses_and_handles_empty_collections() {
+        let (template, _) = double();
+        let map = MapTask::new("map", "numbers", template).with_results_key("doubled_all");
+
+        let ctx = Context::new();
+        ctx.set("numbers", vec![1, 3]).await;
+        map.run(ctx.clone()).await.unwrap();
+        let results: Vec<MapItemResult> = ctx.get("doubled_all").await.unwrap();
+        assert_eq!(
+            results[1].output,
+            Some(Value::String("3 doubled".to_string()))
+        );
+
+        ctx.set("numbers", Vec::<i64>::new()).await;
+        map.run(ctx.clone()).await.unwrap();
+        let results: Vec<MapItemResult> = ctx.get("doubled_all").await.unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn map_failures_follow_the_failure_policy() {
+        let (template, _) = double();
+        let ctx = Context::new();
+        ctx.set("numbers", vec![1, -2, 3]).await;
+
+        let err = MapTask::new("map", "numbers", template.clone())
+            .run(ctx.clone())
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("2 of 3 item(s) succeeded"),
+            "{err}"
+        );
+        assert!(err.to_string().contains("item 1 failed"), "{err}");
+
+        let map = MapTask::new("map", "numbers", template)
+            .with_failure_policy(FailurePolicy::ContinueOnError);
+        map.run(ctx.clone()).await.unwrap();
+        let results: Vec<MapItemResult> = ctx.get("map.results").await.unwrap();
+        let statuses: Vec<_> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                ChildStatus::Succeeded,
+                ChildStatus::Failed,
+                ChildStatus::Succeeded
+            ]
+        );
+        assert!(
+            results[1]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("negative item -2")
+        );
+    }
+
+    #[tokio::test]
+    async fn map_requires_a_collection() {
+        let (template, _) = double();
+        let map = MapTask::new("map", "numbers", template);
+
+        let err = map.run(Context::new()).await.err().unwrap();
+        assert!(matches!(err, GraphError::ContextError(_)));
+    }
+}
@@ -0,0 +1,704 @@
+//! Structured content for chat messages.
+//!
+//! A plain [`SerializableMessage`] carries its text in `content`. Messages that
+//! hold more than a single text block (images, documents, tool calls, tool
+//! results) list their content in order as [`ContentPart`]s, and `content` holds
+//! the text parts joined for display and token estimation.
+//!
+//! With the `rig` feature, messages convert to and from `rig::completion::Message`
+//! without losing content, so history read back from storage is what the model saw.
+//! Fields rig has no place for (name, task id, metadata, timestamp, pinning) are
+//! kept on the graph-flow side only.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{ContentPart, MediaPart, MessageRole, SerializableMessage, ToolCall};
+//! use serde_json::json;
+//!
+//! let question = SerializableMessage::user_parts(vec![
+//!     ContentPart::text("What does this scan show?"),
+//!     ContentPart::Image(MediaPart::url("https://example.com/scan.png").with_media_type("image/png")),
+//! ])
+//! .with_task_id("triage");
+//! assert_eq!(question.content, "What does this scan show?");
+//!
+//! let call = SerializableMessage::assistant_tool_calls(vec![ToolCall::new(
+//!     "call_1",
+//!     "lookup_policy",
+//!     json!({ "policy_id": "P-42" }),
+//! )]);
+//! assert_eq!(call.tool_calls().count(), 1);
+//!
+//! let result = SerializableMessage::tool_result("call_1", "Policy P-42 is active");
+//! assert_eq!(result.role, MessageRole::Tool);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::{MessageRole, SerializableMessage};
+
+/// One block of message content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    Image(MediaPart),
+    Audio(MediaPart),
+    Video(MediaPart),
+    Document(MediaPart),
+    /// A tool invocation requested by the assistant
+    ToolCall(ToolCall),
+    /// The output of a tool invocation
+    ToolResult(ToolResult),
+    /// Provider reasoning, kept as the provider's own JSON representation
+    Reasoning {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        content: Value,
+    },
+}
+
+impl ContentPart {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+}
+
+/// Where the data of a media part comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum MediaSource {
+    Url(String),
+    Base64(String),
+    Raw(Vec<u8>),
+    /// Inline text, e.g. the contents of a plain-text document
+    Text(String),
+    Unknown,
+}
+
+/// An image, audio clip, video or document attached to a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct MediaPart {
+    pub source: MediaSource,
+    /// MIME type, e.g. `image/png` or `application/pdf`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    /// Requested image detail (`low`, `high`, `auto`); images only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Provider-specific parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Value>,
+}
+
+impl MediaPart {
+    pub fn new(source: MediaSource) -> Self {
+        Self {
+            source,
+            media_type: None,
+            detail: None,
+            extra: None,
+        }
+    }
+
+    pub fn url(url: impl Into<String>) -> Self {
+        Self::new(MediaSource::Url(url.into()))
+    }
+
+    pub fn base64(data: impl Into<String>) -> Self {
+        Self::new(MediaSource::Base64(data.into()))
+    }
+
+    pub fn with_media_type(mut self, media_type: impl Into<String>) -> Self {
+        self.media_type = Some(media_type.into());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// A tool invocation requested by the assistant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ToolCall {
+    pub id: String,
+    /// Provider call id, when it differs from `id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,
+    pub name: String,
+    pub arguments: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Provider-specific parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Value>,
+}
+
+impl ToolCall {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, arguments: Value) -> Self {
+        Self {
+            id: id.into(),
+            call_id: None,
+            name: name.into(),
+            arguments,
+            signature: None,
+            extra: None,
+        }
+    }
+}
+
+/// The output of a tool invocation, answering the [`ToolCall`] with the same id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ToolResult {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,
+    /// Text and image parts
+    pub content: Vec<ContentPart>,
+}
+
+impl ToolResult {
+    pub fn text(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            call_id: None,
+            content: vec![ContentPart::text(text)],
+        }
+    }
+}
+
+impl SerializableMessage {
+    /// Create a message from content parts. `content` is set to the text parts.
+    pub fn from_parts(role: MessageRole, parts: Vec<ContentPart>) -> Self {
+        let mut message = Self::new(role, joined_text(&parts));
+        message.parts = parts;
+        message
+    }
+
+    /// Create a user message with several content parts, e.g. text and an image.
+    pub fn user_parts(parts: Vec<ContentPart>) -> Self {
+        Self::from_parts(MessageRole::User, parts)
+    }
+
+    /// Create an assistant message requesting tool calls.
+    pub fn assistant_tool_calls(calls: Vec<ToolCall>) -> Self {
+        Self::from_parts(
+            MessageRole::Assistant,
+            calls.into_iter().map(ContentPart::ToolCall).collect(),
+        )
+    }
+
+    /// Create a tool message with a text result for the call `id`.
+    pub fn tool_result(id: impl Into<String>, output: impl Into<String>) -> Self {
+        let output = output.into();
+        let result = ToolResult::text(id, output.clone());
+        let mut message =
+            Self::from_parts(MessageRole::Tool, vec![ContentPart::ToolResult(result)]);
+        // The result has no top-level text parts; keep the output readable in `content`
+        message.content = output;
+        message
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Record which task produced the message.
+    pub fn with_task_id(mut self, task_id: impl Into<String>) -> Self {
+        self.task_id = Some(task_id.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Tool calls requested in this message.
+    pub fn tool_calls(&self) -> impl Iterator<Item = &ToolCall> {
+        self.parts.iter().filter_map(|part| match part {
+            ContentPart::ToolCall(call) => Some(call),
+            _ => None,
+        })
+    }
+
+    /// Tool results carried by this message.
+    pub fn tool_results(&self) -> impl Iterator<Item = &ToolResult> {
+        self.parts.iter().filter_map(|part| match part {
+            ContentPart::ToolResult(result) => Some(result),
+            _ => None,
+        })
+    }
+}
+
+fn joined_text(parts: &[ContentPart]) -> String {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "rig")]
+mod rig_conversion {
+    use super::*;
+    use rig::OneOrMany;
+    use rig::completion::Message;
+    use rig::message::{
+        AssistantContent, Audio, Document, DocumentSourceKind, Image, ImageDetail, ImageMediaType,
+        MimeType, Reasoning, ToolFunction, ToolResultContent, UserContent, Video,
+    };
+
+    impl From<&SerializableMessage> for Message {
+        fn from(msg: &SerializableMessage) -> Self {
+            match msg.role {
+                MessageRole::System => Message::System {
+                    content: msg.content.clone(),
+                },
+                MessageRole::User | MessageRole::Tool => {
+                    let content = msg.parts.iter().filter_map(user_content).collect();
+                    Message::User {
+                        content: one_or_many(content, || UserContent::text(msg.content.clone())),
+                    }
+                }
+                MessageRole::Assistant => {
+                    let content = msg.parts.iter().filter_map(assistant_content).collect();
+                    Message::Assistant {
+                        id: msg.id.clone(),
+                        content: one_or_many(content, || {
+                            AssistantContent::text(msg.content.clone())
+                        }),
+                    }
+                }
+            }
+        }
+    }
+
+    impl From<SerializableMessage> for Message {
+        fn from(msg: SerializableMessage) -> Self {
+            Message::from(&msg)
+        }
+    }
+
+    impl From<Message> for SerializableMessage {
+        fn from(message: Message) -> Self {
+            match message {
+                Message::System { content } => SerializableMessage::system(content),
+                Message::User { content } => {
+                    if content.len() == 1
+                        && let UserContent::Text(text) = content.first_ref()
+                    {
+                        return SerializableMessage::user(text.text.clone());
+                    }
+                    let parts: Vec<ContentPart> = content.into_iter().map(user_part).collect();
+                    let role = if parts
+                        .iter()
+                        .all(|p| matches!(p, ContentPart::ToolResult(_)))
+                    {
+                        MessageRole::Tool
+                    } else {
+                        MessageRole::User
+                    };
+                    let mut msg = SerializableMessage::from_parts(role, parts);
+                    if msg.role == MessageRole::Tool {
+                        msg.content = msg
+                            .tool_results()
+                            .map(|r| joined_text(&r.content))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                    }
+                    msg
+                }
+                Message::Assistant { id, content } => {
+                    let mut msg = match content.first_ref() {
+                        AssistantContent::Text(text) if content.len() == 1 => {
+                            SerializableMessage::assistant(text.text.clone())
+                        }
+                        _ => SerializableMessage::from_parts(
+                            MessageRole::Assistant,
+                            content.into_iter().map(assistant_part).collect(),
+                        ),
+                    };
+                    msg.id = id;
+                    msg
+                }
+            }
+        }
+    }
+
+    fn one_or_many<T: Clone>(items: Vec<T>, fallback: impl FnOnce() -> T) -> OneOrMany<T> {
+        OneOrMany::many(items).unwrap_or_else(|_| OneOrMany::one(fallback()))
+    }
+
+    fn user_content(part: &ContentPart) -> Option<UserContent> {
+        Some(match part {
+            ContentPart::Text { text } => UserContent::text(text.clone()),
+            ContentPart::Image(media) => match to_image(media) {
+                Ok(image) => UserContent::Image(image),
+                Err(placeholder) => UserContent::text(placeholder),
+            },
+            ContentPart::Audio(media) => match mime(media) {
+                Ok(media_type) => UserContent::Audio(Audio {
+                    data: to_source(&media.source),
+                    media_type,
+                    additional_params: media.extra.clone(),
+                }),
+                Err(placeholder) => UserContent::text(placeholder),
+            },
+            ContentPart::Video(media) => match mime(media) {
+                Ok(media_type) => UserContent::Video(Video {
+                    data: to_source(&media.source),
+                    media_type,
+                    additional_params: media.extra.clone(),
+                }),
+                Err(placeholder) => UserContent::text(placeholder),
+            },
+            ContentPart::Document(media) => match mime(media) {
+                Ok(media_type) => UserContent::Document(Document {
+                    data: to_source(&media.source),
+                    media_type,
+                    additional_params: media.extra.clone(),
+                }),
+                Err(placeholder) => UserContent::text(placeholder),
+            },
+            ContentPart::ToolResult(result) => {
+                let content = result
+                    .content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(ToolResultContent::text(text.clone())),
+                        ContentPart::Image(media) => Some(match to_image(media) {
+                            Ok(image) => ToolResultContent::Image(image),
+                            Err(placeholder) => ToolResultContent::text(placeholder),
+                        }),
+                        // Tool results only carry text and images
+                        ContentPart::Audio(media)
+                        | ContentPart::Video(media)
+                        | ContentPart::Document(media) => {
+                            Some(ToolResultContent::text(placeholder(media)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                UserContent::ToolResult(rig::message::ToolResult {
+                    id: result.id.clone(),
+                    call_id: result.call_id.clone(),
+                    content: one_or_many(content, || ToolResultContent::text(String::new())),
+                })
+            }
+            // Not valid in user turns
+            ContentPart::ToolCall(_) | ContentPart::Reasoning { .. } => return None,
+        })
+    }
+
+    fn assistant_content(part: &ContentPart) -> Option<AssistantContent> {
+        Some(match part {
+            ContentPart::Text { text } => AssistantContent::text(text.clone()),
+            ContentPart::Image(media) => match to_image(media) {
+                Ok(image) => AssistantContent::Image(image),
+                Err(placeholder) => AssistantContent::text(placeholder),
+            },
+            ContentPart::ToolCall(call) => {
+                let mut tool_call = rig::message::ToolCall::new(
+                    call.id.clone(),
+                    ToolFunction::new(call.name.clone(), call.arguments.clone()),
+                )
+                .with_signature(call.signature.clone())
+                .with_additional_params(call.extra.clone());
+                tool_call.call_id = call.call_id.clone();
+                AssistantContent::ToolCall(tool_call)
+            }
+            ContentPart::Reasoning { id, content } => {
+                let reasoning: Reasoning =
+                    serde_json::from_value(serde_json::json!({ "id": id, "content": content }))
+                        .ok()?;
+                AssistantContent::Reasoning(reasoning)
+            }
+            // Not valid in assistant turns
+            ContentPart::Audio(_)
+            | ContentPart::Video(_)
+            | ContentPart::Document(_)
+            | ContentPart::ToolResult(_) => return None,
+        })
+    }
+
+    fn user_part(content: UserContent) -> ContentPart {
+        match content {
+            UserContent::Text(text) => ContentPart::text(text.text),
+            UserContent::Image(image) => ContentPart::Image(from_image(image)),
+            UserContent::Audio(audio) => ContentPart::Audio(from_media(
+                audio.data,
+                audio.media_type.map(|m| m.to_mime_type()),
+                audio.additional_params,
+            )),
+            UserContent::Video(video) => ContentPart::Video(from_media(
+                video.data,
+                video.media_type.map(|m| m.to_mime_type()),
+                video.additional_params,
+            )),
+            UserContent::Document(document) => ContentPart::Document(from_media(
+                document.data,
+                document.media_type.map(|m| m.to_mime_type()),
+                document.additional_params,
+            )),
+            UserContent::ToolResult(result) => ContentPart::ToolResult(ToolResult {
+                id: result.id,
+                call_id: result.call_id,
+                content: result
+                    .content
+                    .into_iter()
+                    .map(|content| match content {
+                        ToolResultContent::Text(text) => ContentPart::text(text.text),
+                        ToolResultContent::Image(image) => ContentPart::Image(from_image(image)),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn assistant_part(content: AssistantContent) -> ContentPart {
+        match content {
+            AssistantContent::Text(text) => ContentPart::text(text.text),
+            AssistantContent::Image(image) => ContentPart::Image(from_image(image)),
+            AssistantContent::ToolCall(call) => ContentPart::ToolCall(ToolCall {
+                id: call.id,
+                call_id: call.call_id,
+                name: call.function.name,
+                arguments: call.function.arguments,
+                signature: call.signature,
+                extra: call.additional_params,
+            }),
+            AssistantContent::Reasoning(reasoning) => ContentPart::Reasoning {
+                id: reasoning.id.clone(),
+                content: serde_json::to_value(&reasoning.content).unwrap_or(Value::Null),
+            },
+        }
+    }
+
+    fn to_image(media: &MediaPart) -> Result<Image, String> {
+        Ok(Image {
+            data: to_source(&media.source),
+            media_type: mime::<ImageMediaType>(media)?,
+            detail: media
+                .detail
+                .as_deref()
+                .and_then(|detail| detail.parse::<ImageDetail>().ok()),
+            additional_params: media.extra.clone(),
+        })
+    }
+
+    fn from_image(image: Image) -> MediaPart {
+        let mut media = from_media(
+            image.data,
+            image.media_type.map(|m| m.to_mime_type()),
+            image.additional_params,
+        );
+        media.detail = image.detail.map(|detail| {
+            match detail {
+                ImageDetail::Low => "low",
+                ImageDetail::High => "high",
+                ImageDetail::Auto => "auto",
+            }
+            .to_string()
+        });
+        media
+    }
+
+    fn from_media(
+        data: DocumentSourceKind,
+        media_type: Option<&'static str>,
+        extra: Option<Value>,
+    ) -> MediaPart {
+        MediaPart {
+            source: match data {
+                DocumentSourceKind::Url(url) => MediaSource::Url(url),
+                DocumentSourceKind::Base64(data) => MediaSource::Base64(data),
+                DocumentSourceKind::Raw(bytes) => MediaSource::Raw(bytes),
+                DocumentSourceKind::String(text) => MediaSource::Text(text),
+                _ => MediaSource::Unknown,
+            },
+            media_type: media_type.map(str::to_string),
+            detail: None,
+            extra,
+        }
+    }
+
+    fn to_source(source: &MediaSource) -> DocumentSourceKind {
+        match source {
+            MediaSource::Url(url) => DocumentSourceKind::Url(url.clone()),
+            MediaSource::Base64(data) => DocumentSourceKind::Base64(data.clone()),
+            MediaSource::Raw(bytes) => DocumentSourceKind::Raw(bytes.clone()),
+            MediaSource::Text(text) => DocumentSourceKind::String(text.clone()),
+            MediaSource::Unknown => DocumentSourceKind::Unknown,
+        }
+    }
+
+    /// Map a part's MIME type to rig's media type enum.
+    ///
+    /// A type rig doesn't know would reach the model untyped, so the part is
+    /// replaced by the placeholder text returned as the error.
+    fn mime<T: MimeType>(media: &MediaPart) -> Result<Option<T>, String> {
+        match media.media_type.as_deref() {
+            None => Ok(None),
+            Some(media_type) => T::from_mime_type(media_type)
+                .map(Some)
+                .ok_or_else(|| placeholder(media)),
+        }
+    }
+
+    /// Text standing in for an attachment the model can't be sent.
+    fn placeholder(media: &MediaPart) -> String {
+        format!(
+            "[Attachment omitted: unsupported media type {}]",
+            media.media_type.as_deref().unwrap_or("unknown")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_messages_still_deserialize() {
+        let json = r#"{"role":"User","content":"Hello","timestamp":"2024-01-01T00:00:00Z"}"#;
+        let message: SerializableMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(message.content, "Hello");
+        assert!(message.parts.is_empty() && message.metadata.is_empty());
+        assert_eq!(message.name, None);
+
+        // New fields are omitted when unused, so plain messages keep the old shape
+        let value = serde_json::to_value(SerializableMessage::user("Hi".to_string())).unwrap();
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["content", "role", "timestamp"]);
+    }
+
+    #[test]
+    fn structured_messages_round_trip_through_json() {
+        let message = SerializableMessage::user_parts(vec![
+            ContentPart::text("See attached"),
+            ContentPart::Document(MediaPart::base64("JVBERi0=").with_media_type("application/pdf")),
+        ])
+        .with_name("alice")
+        .with_metadata("source", "upload");
+
+        let json = serde_json::to_string(&message).unwrap();
+        let restored: SerializableMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.parts, message.parts);
+        assert_eq!(restored.name.as_deref(), Some("alice"));
+        assert_eq!(restored.metadata["source"], "upload");
+    }
+
+    #[cfg(feature = "rig")]
+    #[test]
+    fn rig_messages_round_trip_losslessly() {
+        use rig::OneOrMany;
+        use rig::completion::Message;
+        use rig::message::{
+            AssistantContent, Document, DocumentMediaType, DocumentSourceKind, Image, ImageDetail,
+            ImageMediaType, Reasoning, ToolResultContent, UserContent,
+        };
+
+        let messages = vec![
+            Message::system("Be careful"),
+            Message::user("Plain question"),
+            Message::User {
+                content: OneOrMany::many(vec![
+                    UserContent::text("Compare these"),
+                    UserContent::Image(Image {
+                        data: DocumentSourceKind::Url("https://example.com/a.png".to_string()),
+                        media_type: Some(ImageMediaType::PNG),
+                        detail: Some(ImageDetail::High),
+                        additional_params: None,
+                    }),
+                    UserContent::Document(Document {
+                        data: DocumentSourceKind::Base64("JVBERi0=".to_string()),
+                        media_type: Some(DocumentMediaType::PDF),
+                        additional_params: None,
+                    }),
+                ])
+                .unwrap(),
+            },
+            Message::Assistant {
+                id: Some("msg_1".to_string()),
+                content: OneOrMany::many(vec![
+                    AssistantContent::Reasoning(Reasoning::new("thinking")),
+                    AssistantContent::tool_call(
+                        "call_1",
+                        "lookup",
+                        serde_json::json!({ "q": "rust" }),
+                    ),
+                ])
+                .unwrap(),
+            },
+            Message::User {
+                content: OneOrMany::one(UserContent::tool_result(
+                    "call_1",
+                    OneOrMany::one(ToolResultContent::text("found")),
+                )),
+            },
+            Message::Assistant {
+                id: None,
+                content: OneOrMany::one(AssistantContent::text("Done")),
+            },
+        ];
+
+        for original in messages {
+            let stored = SerializableMessage::from(original.clone());
+            let json = serde_json::to_string(&stored).unwrap();
+            let restored: SerializableMessage = serde_json::from_str(&json).unwrap();
+            assert_eq!(Message::from(&restored), original);
+        }
+
+        let tool = SerializableMessage::from(Message::User {
+            content: OneOrMany::one(UserContent::tool_result(
+                "call_1",
+                OneOrMany::one(ToolResultContent::text("found")),
+            )),
+        });
+        assert_eq!(tool.role, MessageRole::Tool);
+        assert_eq!(tool.content, "found");
+    }
+
+    #[cfg(feature = "rig")]
+    #[test]
+    fn unsupported_attachments_become_placeholders() {
+        use rig::completion::Message;
+        use rig::message::UserContent;
+
+        let message = SerializableMessage::user_parts(vec![
+            ContentPart::text("See attached"),
+            ContentPart::Document(MediaPart::base64("UEsDBA==").with_media_type("application/zip")),
+        ]);
+        let Message::User { content } = Message::from(&message) else {
+            panic!("expected a user message");
+        };
+        let content: Vec<UserContent> = content.into_iter().collect();
+        assert_eq!(content.len(), 2);
+        assert_eq!(
+            content[1],
+            UserContent::text("[Attachment omitted: unsupported media type application/zip]")
+        );
+    }
+}
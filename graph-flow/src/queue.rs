@@ -0,0 +1,515 @@
+//! Durable job queue for running sessions in the background.
+//!
+//! Instead of running a session inside the request that started it, a service
+//! enqueues a [`Job`] and returns immediately; a [`WorkerPool`](crate::WorkerPool)
+//! claims the job and drives the session until it needs input, sleeps or completes.
+//! Callers poll [`JobQueue::get`] for the outcome.
+//!
+//! Jobs carry the id of the graph that runs their session, and workers only claim
+//! jobs of their own graph, so services with different graphs can share a queue.
+//!
+//! A claimed job is leased to one worker, which extends the lease with heartbeats
+//! while it runs. If the worker dies, the lease runs out and another worker claims
+//! the job again, up to the job's `max_attempts`. Steps that finished before the
+//! crash were already saved with the session, so the retry picks up at the step
+//! that was interrupted; that step may therefore run more than once.
+//!
+//! [`InMemoryJobQueue`] is meant for development and tests;
+//! [`PostgresJobQueue`](crate::PostgresJobQueue) shares jobs between processes.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{InMemoryJobQueue, JobQueue, JobStatus};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let queue = InMemoryJobQueue::new();
+//! let job = queue.enqueue("ocr", "session-1", 3).await?;
+//!
+//! // Enqueueing again while the job is pending returns the same job
+//! assert_eq!(queue.enqueue("ocr", "session-1", 3).await?.id, job.id);
+//!
+//! let polled = queue.get(&job.id).await?.unwrap();
+//! assert_eq!(polled.status, JobStatus::Queued);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{error::Result, graph::ExecutionResult, storage::after};
+
+/// Attempts allowed per job unless the caller asks for something else
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Lifecycle of a [`Job`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a worker, possibly until `available_at` for a retry
+    Queued,
+    /// Leased to a worker
+    Running,
+    /// The session ran until it stopped; see [`Job::result`]
+    Succeeded,
+    /// Every attempt failed; see [`Job::last_error`]
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job will not change any more.
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn parse(status: &str) -> Option<Self> {
+        match status {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "succeeded" => Some(JobStatus::Succeeded),
+            "failed" => Some(JobStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A request to run a session in the background.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    /// Graph whose workers may claim the job
+    #[serde(default)]
+    pub graph_id: String,
+    pub session_id: String,
+    pub status: JobStatus,
+    /// Number of times the job has been claimed
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Worker holding the lease while `Running`
+    pub worker_id: Option<String>,
+    /// When the lease runs out unless renewed by a heartbeat
+    pub lease_until: Option<DateTime<Utc>>,
+    /// Earliest time a queued job may be claimed
+    pub available_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// Where the session stopped, once `Succeeded`
+    pub result: Option<ExecutionResult>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Job {
+    fn new(graph_id: &str, session_id: &str, max_attempts: u32, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            graph_id: graph_id.to_string(),
+            session_id: session_id.to_string(),
+            status: JobStatus::Queued,
+            attempts: 0,
+            max_attempts: max_attempts.max(1),
+            worker_id: None,
+            lease_until: None,
+            available_at: now,
+            last_error: None,
+            result: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn lease_expired(&self, now: DateTime<Utc>) -> bool {
+        self.status == JobStatus::Running && self.lease_until.is_some_and(|until| until <= now)
+    }
+
+    fn held_by(&self, worker_id: &str) -> bool {
+        self.status == JobStatus::Running && self.worker_id.as_deref() == Some(worker_id)
+    }
+}
+
+/// Error recorded for a job whose worker stopped sending heartbeats
+pub(crate) const LEASE_EXPIRED_ERROR: &str = "Worker lease expired before the job finished";
+
+/// Storage for background jobs, shared by the services that enqueue them and the
+/// workers that run them.
+///
+/// Methods that act on a claimed job take the claiming `worker_id` and return
+/// `false` when that worker no longer holds the lease, e.g. because it expired
+/// and the job was handed to another worker.
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    /// Queue a run of `session_id` by the workers of graph `graph_id`.
+    ///
+    /// If the session already has a queued or running job, that job is returned
+    /// instead, so a session is never run by two workers at once.
+    async fn enqueue(&self, graph_id: &str, session_id: &str, max_attempts: u32) -> Result<Job>;
+
+    /// Lease the oldest available job of graph `graph_id` to `worker_id` until
+    /// `now + lease`.
+    ///
+    /// Running jobs whose lease expired before `now` are available again; those
+    /// out of attempts are marked `Failed` instead.
+    async fn claim(
+        &self,
+        graph_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<Job>>;
+
+    /// Extend the lease of a running job to `now + lease`.
+    async fn heartbeat(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool>;
+
+    /// Mark a running job `Succeeded` with where the session stopped.
+    async fn complete(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        result: ExecutionResult,
+    ) -> Result<bool>;
+
+    /// Record a failed attempt. The job is queued again from `retry_at` if it has
+    /// attempts left, and marked `Failed` otherwise.
+    async fn fail(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        error: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool>;
+
+    async fn get(&self, job_id: &str) -> Result<Option<Job>>;
+}
+
+/// In-memory job queue, for development and tests.
+#[derive(Default)]
+pub struct InMemoryJobQueue {
+    jobs: Arc<DashMap<String, Job>>,
+    /// Serializes claims so a job is handed out once
+    claim_lock: Arc<Mutex<()>>,
+}
+
+impl InMemoryJobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl JobQueue for InMemoryJobQueue {
+    async fn enqueue(&self, graph_id: &str, session_id: &str, max_attempts: u32) -> Result<Job> {
+        let _guard = self.claim_lock.lock().unwrap();
+        let pending = self
+            .jobs
+            .iter()
+            .find(|entry| entry.session_id == session_id && !entry.status.is_finished());
+        if let Some(entry) = pending {
+            return Ok(entry.clone());
+        }
+
+        let job = Job::new(graph_id, session_id, max_attempts, Utc::now());
+        self.jobs.insert(job.id.clone(), job.clone());
+        Ok(job)
+    }
+
+    async fn claim(
+        &self,
+        graph_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<Job>> {
+        let _guard = self.claim_lock.lock().unwrap();
+
+        // Jobs whose worker vanished and that have no attempts left
+        for mut entry in self.jobs.iter_mut() {
+            if entry.graph_id == graph_id
+                && entry.lease_expired(now)
+                && entry.attempts >= entry.max_attempts
+            {
+                entry.status = JobStatus::Failed;
+                entry.last_error = Some(LEASE_EXPIRED_ERROR.to_string());
+                entry.worker_id = None;
+                entry.lease_until = None;
+                entry.updated_at = now;
+            }
+        }
+
+        let next = self
+            .jobs
+            .iter()
+            .filter(|entry| {
+                entry.graph_id == graph_id
+                    && ((entry.status == JobStatus::Queued && entry.available_at <= now)
+                        || entry.lease_expired(now))
+            })
+            .min_by_key(|entry| (entry.available_at, entry.created_at))
+            .map(|entry| entry.id.clone());
+        let Some(id) = next else {
+            return Ok(None);
+        };
+
+        let mut job = self
+            .jobs
+            .get_mut(&id)
+            .expect("job exists while claim lock is held");
+        if job.status == JobStatus::Running {
+            job.last_error = Some(LEASE_EXPIRED_ERROR.to_string());
+        }
+        job.status = JobStatus::Running;
+        job.attempts += 1;
+        job.worker_id = Some(worker_id.to_string());
+        job.lease_until = Some(after(now, lease));
+        job.updated_at = now;
+        Ok(Some(job.clone()))
+    }
+
+    async fn heartbeat(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool> {
+        match self.jobs.get_mut(job_id) {
+            Some(mut job) if job.held_by(worker_id) => {
+                job.lease_until = Some(after(now, lease));
+                job.updated_at = now;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn complete(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        result: ExecutionResult,
+    ) -> Result<bool> {
+        match self.jobs.get_mut(job_id) {
+            Some(mut job) if job.held_by(worker_id) => {
+                job.status = JobStatus::Succeeded;
+                job.result = Some(result);
+                job.worker_id = None;
+                job.lease_until = None;
+                job.updated_at = Utc::now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn fail(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        error: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        match self.jobs.get_mut(job_id) {
+            Some(mut job) if job.held_by(worker_id) => {
+                job.status = if job.attempts < job.max_attempts {
+                    JobStatus::Queued
+                } else {
+                    JobStatus::Failed
+                };
+                job.last_error = Some(error.to_string());
+                job.available_at = retry_at;
+                job.worker_id = None;
+                job.lease_until = None;
+                job.updated_at = Utc::now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn get(&self, job_id: &str) -> Result<Option<Job>> {
+        Ok(self.jobs.get(job_id).map(|entry| entry.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutionStatus;
+    use chrono::Duration as TimeDelta;
+
+    const LEASE: Duration = Duration::from_secs(30);
+
+    #[tokio::test]
+    async fn claims_hand_out_each_job_once() {
+        let queue = InMemoryJobQueue::new();
+        let job = queue.enqueue("g", "s1", 3).await.unwrap();
+        let now = Utc::now();
+
+        let claimed = queue.claim("g", "w1", now, LEASE).await.unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+        assert_eq!(claimed.attempts, 1);
+        assert_eq!(claimed.worker_id.as_deref(), Some("w1"));
+        assert!(queue.claim("g", "w2", now, LEASE).await.unwrap().is_none());
+
+        // Only the lease holder can finish the job
+        let result = ExecutionResult {
+            response: None,
+            status: ExecutionStatus::Completed,
+            input_request: None,
+        };
+        assert!(!queue.complete(&job.id, "w2", result.clone()).await.unwrap());
+        assert!(queue.complete(&job.id, "w1", result).await.unwrap());
+        let done = queue.get(&job.id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert!(matches!(
+            done.result.unwrap().status,
+            ExecutionStatus::Completed
+        ));
+
+        // A finished job no longer blocks a new run of the session
+        assert_ne!(queue.enqueue("g", "s1", 3).await.unwrap().id, job.id);
+    }
+
+    #[tokio::test]
+    async fn workers_only_claim_jobs_of_their_graph() {
+        let queue = InMemoryJobQueue::new();
+        let job = queue.enqueue("ocr", "s1", 3).await.unwrap();
+        assert_eq!(job.graph_id, "ocr");
+        let now = Utc::now();
+
+        assert!(
+            queue
+                .claim("billing", "w1", now, LEASE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let claimed = queue.claim("ocr", "w2", now, LEASE).await.unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+
+        // An expired lease is only reclaimed by the job's own graph as well
+        let expired = now + TimeDelta::seconds(60);
+        assert!(
+            queue
+                .claim("billing", "w1", expired, LEASE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            queue
+                .get(&job.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .worker_id
+                .as_deref(),
+            Some("w2")
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_leases_are_reclaimed_until_attempts_run_out() {
+        let queue = InMemoryJobQueue::new();
+        let job = queue.enqueue("g", "s1", 2).await.unwrap();
+        let now = Utc::now();
+        queue.claim("g", "w1", now, LEASE).await.unwrap().unwrap();
+
+        // Heartbeats keep the job away from other workers
+        let later = now + TimeDelta::seconds(20);
+        assert!(queue.heartbeat(&job.id, "w1", later, LEASE).await.unwrap());
+        let before_expiry = now + TimeDelta::seconds(40);
+        assert!(
+            queue
+                .claim("g", "w2", before_expiry, LEASE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // w1 crashed: w2 takes over and w1 loses the job
+        let expired = now + TimeDelta::seconds(60);
+        let retried = queue
+            .claim("g", "w2", expired, LEASE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retried.attempts, 2);
+        assert!(retried.last_error.unwrap().contains("lease expired"));
+        assert!(
+            !queue
+                .heartbeat(&job.id, "w1", expired, LEASE)
+                .await
+                .unwrap()
+        );
+
+        // w2 crashed too, with no attempts left
+        let expired_again = expired + TimeDelta::seconds(60);
+        assert!(
+            queue
+                .claim("g", "w3", expired_again, LEASE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let failed = queue.get(&job.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.worker_id, None);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_are_retried_after_the_delay() {
+        let queue = InMemoryJobQueue::new();
+        let job = queue.enqueue("g", "s1", 2).await.unwrap();
+        let now = Utc::now();
+
+        queue.claim("g", "w1", now, LEASE).await.unwrap().unwrap();
+        let retry_at = now + TimeDelta::seconds(5);
+        assert!(
+            queue
+                .fail(&job.id, "w1", "OCR timed out", retry_at)
+                .await
+                .unwrap()
+        );
+        let queued = queue.get(&job.id).await.unwrap().unwrap();
+        assert_eq!(queued.status, JobStatus::Queued);
+        assert_eq!(queued.last_error.as_deref(), Some("OCR timed out"));
+
+        assert!(queue.claim("g", "w1", now, LEASE).await.unwrap().is_none());
+        queue
+            .claim("g", "w1", retry_at, LEASE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            queue
+                .fail(&job.id, "w1", "OCR timed out", retry_at)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            queue.get(&job.id).await.unwrap().unwrap().status,
+            JobStatus::Failed
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! A configurable task that prompts a rig agent.
+//!
+//! Most LLM tasks follow the same steps: read some context values, build a
+//! prompt, call the agent with the chat history, record the exchange, store the
+//! answer and decide where to go next. [`RigAgentTask`] does all of that from
+//! configuration:
+//!
+//! - the prompt is a [`PromptTemplate`] rendered from the context
+//! - chat history can be sent in full, within a token budget, or not at all
+//! - the reply is parsed (plain text by default, or JSON) and stored under an
+//!   output key
+//! - a policy maps the reply to a [`NextAction`]
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use graph_flow::{NextAction, RigAgentTask};
+//! # fn build(agent: impl rig::completion::Chat) {
+//! let task = RigAgentTask::new(
+//!     "refine_query",
+//!     agent,
+//!     "Rewrite this request as a search query: {{user_query}}",
+//! )
+//! .with_history_budget(4_000)
+//! .with_output_key("search_query")
+//! .with_next_action(|_| NextAction::Continue);
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use rig::completion::Chat;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::compaction::ApproxTokenEstimator;
+use crate::context::{Context, SerializableMessage};
+use crate::error::{GraphError, Result};
+use crate::structured::extract_json;
+use crate::task::{NextAction, Task, TaskResult};
+use crate::template::PromptTemplate;
+
+/// Turns the agent's raw reply into a value, or `None` if it can't be used.
+pub type OutputParser = Arc<dyn Fn(&str) -> Option<Value> + Send + Sync>;
+
+/// Chooses the next action from the agent's reply.
+pub type NextActionPolicy = Arc<dyn Fn(&AgentReply) -> NextAction + Send + Sync>;
+
+/// The agent's reply, as seen by a [`NextActionPolicy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentReply {
+    /// Raw text returned by the agent
+    pub text: String,
+    /// Result of the output parser
+    pub parsed: Option<Value>,
+}
+
+/// How much chat history is sent with the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryMode {
+    None,
+    Full,
+    /// The newest messages that fit this many tokens
+    Budget(usize),
+}
+
+/// Task that renders a prompt from the context and sends it to a rig agent.
+///
+/// By default the whole chat history is sent, the exchange is recorded in the
+/// history, the trimmed reply text is the parsed output, and the task continues
+/// with [`NextAction::ContinueAndExecute`] when the reply parses and waits for
+/// input otherwise.
+pub struct RigAgentTask<A> {
+    id: String,
+    agent: A,
+    prompt: PromptTemplate,
+    history: HistoryMode,
+    record_history: bool,
+    output_key: Option<String>,
+    parser: OutputParser,
+    next_action: NextActionPolicy,
+    status_message: Option<String>,
+}
+
+impl<A: Chat> RigAgentTask<A> {
+    pub fn new(id: impl Into<String>, agent: A, prompt: impl Into<PromptTemplate>) -> Self {
+        Self {
+            id: id.into(),
+            agent,
+            prompt: prompt.into(),
+            history: HistoryMode::Full,
+            record_history: true,
+            output_key: None,
+            parser: Arc::new(|text: &str| Some(Value::String(text.trim().to_string()))),
+            next_action: Arc::new(|reply: &AgentReply| {
+                if reply.parsed.is_some() {
+                    NextAction::ContinueAndExecute
+                } else {
+                    NextAction::WaitForInput
+                }
+            }),
+            status_message: None,
+        }
+    }
+
+    /// Send the full chat history (`true`) or none of it (`false`).
+    pub fn with_history(mut self, enabled: bool) -> Self {
+        self.history = if enabled {
+            HistoryMode::Full
+        } else {
+            HistoryMode::None
+        };
+        self
+    }
+
+    /// Send only the newest history that fits `tokens`, estimated with
+    /// [`ApproxTokenEstimator`].
+    pub fn with_history_budget(mut self, tokens: usize) -> Self {
+        self.history = HistoryMode::Budget(tokens);
+        self
+    }
+
+    /// Whether to append the prompt and reply to the chat history (default `true`).
+    pub fn with_record_history(mut self, record: bool) -> Self {
+        self.record_history = record;
+        self
+    }
+
+    /// Store the parsed output under `key`.
+    pub fn with_output_key(mut self, key: impl Into<String>) -> Self {
+        self.output_key = Some(key.into());
+        self
+    }
+
+    /// Parse the reply as JSON, tolerating a surrounding code fence or prose.
+    ///
+    /// Use [`StructuredOutput`](crate::StructuredOutput) for typed, validated output.
+    pub fn with_json_output(mut self) -> Self {
+        self.parser = Arc::new(extract_json);
+        self
+    }
+
+    pub fn with_output_parser<F>(mut self, parser: F) -> Self
+    where
+        F: Fn(&str) -> Option<Value> + Send + Sync + 'static,
+    {
+        self.parser = Arc::new(parser);
+        self
+    }
+
+    pub fn with_next_action<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&AgentReply) -> NextAction + Send + Sync + 'static,
+    {
+        self.next_action = Arc::new(policy);
+        self
+    }
+
+    pub fn with_status_message(mut self, message: impl Into<String>) -> Self {
+        self.status_message = Some(message.into());
+        self
+    }
+}
+
+#[async_trait]
+impl<A: Chat> Task for RigAgentTask<A> {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn run(&self, context: Context) -> Result<TaskResult> {
+        let prompt = self.prompt.render(&context).await?;
+
+        let history = match self.history {
+            HistoryMode::None => Vec::new(),
+            HistoryMode::Full => context.get_rig_messages().await,
+            HistoryMode::Budget(tokens) => {
+                context
+                    .get_rig_messages_within_budget(tokens, &ApproxTokenEstimator::default())
+                    .await
+            }
+        };
+
+        let text = self
+            .agent
+            .chat(prompt.clone(), history)
+            .await
+            .map_err(|e| {
+                GraphError::TaskExecutionFailed(format!("Agent call in '{}' failed: {e}", self.id))
+            })?;
+
+        if self.record_history {
+            context
+                .add_message(SerializableMessage::user(prompt).with_task_id(&self.id))
+                .await;
+            context
+                .add_message(SerializableMessage::assistant(text.clone()).with_task_id(&self.id))
+                .await;
+        }
+
+        let parsed = (self.parser)(&text);
+        if let (Some(key), Some(value)) = (&self.output_key, &parsed) {
+            context.set(key, value.clone()).await;
+        }
+
+        let reply = AgentReply { text, parsed };
+        let next_action = (self.next_action)(&reply);
+        Ok(TaskResult::new_with_status(
+            Some(reply.text),
+            next_action,
+            self.status_message.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::MessageRole;
+    use rig::completion::{Message, PromptError};
+    use rig::wasm_compat::WasmCompatSend;
+    use std::sync::Mutex;
+
+    /// Replies with a fixed answer and records what it was sent.
+    struct ScriptedAgent {
+        reply: String,
+        calls: Mutex<Vec<(Message, usize)>>,
+    }
+
+    impl ScriptedAgent {
+        fn new(reply: &str) -> Self {
+            Self {
+                reply: reply.to_string(),
+                calls: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Chat for ScriptedAgent {
+        #[allow(refining_impl_trait)]
+        fn chat<I, T>(
+            &self,
+            prompt: impl Into<Message> + WasmCompatSend,
+            chat_history: I,
+        ) -> impl std::future::Future<Output = std::result::Result<String, PromptError>> + WasmCompatSend
+        where
+            I: IntoIterator<Item = T> + WasmCompatSend,
+            T: Into<Message>,
+        {
+            let history = chat_history.into_iter().count();
+            self.calls.lock().unwrap().push((prompt.into(), history));
+            let reply = self.reply.clone();
+            async move { Ok(reply) }
+        }
+    }
+
+    #[tokio::test]
+    async fn renders_prompt_records_history_and_stores_output() {
+        let context = Context::new();
+        context.set("user_query", "films like Alien").await;
+        context.add_user_message("earlier".to_string()).await;
+
+        let task = RigAgentTask::new(
+            "refine",
+            ScriptedAgent::new("  scary space movies \n"),
+            "Refine: {{user_query}}",
+        )
+        .with_output_key("search_query");
+
+        let result = task.run(context.clone()).await.unwrap();
+        assert_eq!(result.next_action, NextAction::ContinueAndExecute);
+        assert_eq!(
+            context.get::<String>("search_query").await.unwrap(),
+            "scary space movies"
+        );
+
+        assert_eq!(
+            task.agent.calls.lock().unwrap()[0],
+            (Message::user("Refine: films like Alien"), 1)
+        );
+
+        let messages = context.get_all_messages().await;
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, MessageRole::User);
+        assert_eq!(messages[2].task_id.as_deref(), Some("refine"));
+    }
+
+    #[tokio::test]
+    async fn json_output_feeds_next_action_policy() {
+        let context = Context::new();
+        context
+            .set("claim", serde_json::json!({ "type": "car" }))
+            .await;
+
+        let task = RigAgentTask::new(
+            "classify",
+            ScriptedAgent::new("```json\n{\"insurance_type\": \"car\"}\n```"),
+            "Classify {{claim.type}}",
+        )
+        .with_history(false)
+        .with_record_history(false)
+        .with_json_output()
+        .with_output_key("classification")
+        .with_next_action(|reply| match &reply.parsed {
+            Some(value) => NextAction::GoTo(value["insurance_type"].as_str().unwrap().to_string()),
+            None => NextAction::WaitForInput,
+        });
+
+        let result = task.run(context.clone()).await.unwrap();
+        assert_eq!(result.next_action, NextAction::GoTo("car".to_string()));
+        assert!(context.get_all_messages().await.is_empty());
+
+        let unparseable =
+            RigAgentTask::new("classify", ScriptedAgent::new("not sure"), "x").with_json_output();
+        let result = unparseable.run(Context::new()).await.unwrap();
+        assert_eq!(result.next_action, NextAction::WaitForInput);
+        assert_eq!(result.response.as_deref(), Some("not sure"));
+    }
+}
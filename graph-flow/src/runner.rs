@@ -134,12 +134,17 @@
 //! }
 //! ```
 
+use chrono::Utc;
+use serde::Serialize;
 use std::sync::Arc;
 
 use crate::{
+    blob_store::BlobStore,
+    compaction::{ChatSummarizer, CompactionPolicy},
+    compensation::Compensation,
     error::{GraphError, Result},
     graph::{ExecutionResult, Graph},
-    storage::SessionStorage,
+    storage::{Session, SessionStorage},
 };
 
 /// High-level helper that orchestrates the common _load → execute → save_ pattern.
@@ -217,6 +222,9 @@ use crate::{
 pub struct FlowRunner {
     graph: Arc<Graph>,
     storage: Arc<dyn SessionStorage>,
+    blob_store: Option<(Arc<dyn BlobStore>, usize)>,
+    compaction: Option<(CompactionPolicy, Arc<dyn ChatSummarizer>)>,
+    compensation: bool,
 }
 
 impl FlowRunner {
@@ -255,7 +263,93 @@ impl FlowRunner {
     /// # }
     /// ```
     pub fn new(graph: Arc<Graph>, storage: Arc<dyn SessionStorage>) -> Self {
-        Self { graph, storage }
+        Self {
+            graph,
+            storage,
+            blob_store: None,
+            compaction: None,
+            compensation: false,
+        }
+    }
+
+    /// Offload context values larger than `threshold` bytes to `store`.
+    ///
+    /// The store is attached to every session context the runner loads, so
+    /// offloaded values are resolved transparently on read. See
+    /// [`Context::set_blob_store`](crate::Context::set_blob_store).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{FlowRunner, Graph, InMemoryBlobStore, InMemorySessionStorage};
+    /// use std::sync::Arc;
+    ///
+    /// let runner = FlowRunner::new(
+    ///     Arc::new(Graph::new("my_workflow")),
+    ///     Arc::new(InMemorySessionStorage::new()),
+    /// )
+    /// .with_blob_store(Arc::new(InMemoryBlobStore::new()), 64 * 1024);
+    /// ```
+    pub fn with_blob_store(mut self, store: Arc<dyn BlobStore>, threshold: usize) -> Self {
+        self.blob_store = Some((store, threshold));
+        self
+    }
+
+    /// Compact each session's chat history with `summarizer` before it is saved.
+    ///
+    /// Runs [`Context::compact_chat_history`](crate::Context::compact_chat_history)
+    /// with `policy` on every save, so long conversations stay within the budget
+    /// without each task compacting on its own. A failing summarizer is logged and
+    /// the session is saved uncompacted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{CompactionPolicy, FlowRunner, Graph, InMemorySessionStorage, SerializableMessage};
+    /// use std::sync::Arc;
+    ///
+    /// let summarize = |messages: &[SerializableMessage]| {
+    ///     format!("{} earlier messages", messages.len())
+    /// };
+    /// let runner = FlowRunner::new(
+    ///     Arc::new(Graph::new("my_workflow")),
+    ///     Arc::new(InMemorySessionStorage::new()),
+    /// )
+    /// .with_compaction(CompactionPolicy::new(4_000, 1_500), Arc::new(summarize));
+    /// ```
+    pub fn with_compaction(
+        mut self,
+        policy: CompactionPolicy,
+        summarizer: Arc<dyn ChatSummarizer>,
+    ) -> Self {
+        self.compaction = Some((policy, summarizer));
+        self
+    }
+
+    /// Roll sessions back when a step fails.
+    ///
+    /// When executing a session fails, the runner calls
+    /// [`Graph::compensate_session`](crate::Graph::compensate_session) before returning
+    /// the error, and saves the session together with the
+    /// [`Compensation`](crate::Compensation) recorded in its context; the session
+    /// cannot run again. Failures handled by an error edge or the error handler, and
+    /// input rejected by [`resume`](Self::resume), do not count.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{FlowRunner, Graph, InMemorySessionStorage};
+    /// use std::sync::Arc;
+    ///
+    /// let runner = FlowRunner::new(
+    ///     Arc::new(Graph::new("claims")),
+    ///     Arc::new(InMemorySessionStorage::new()),
+    /// )
+    /// .with_compensation();
+    /// ```
+    pub fn with_compensation(mut self) -> Self {
+        self.compensation = true;
+        self
     }
 
     /// Execute **exactly one** task for the given `session_id` and persist the updated session.
@@ -308,9 +402,16 @@ impl FlowRunner {
     ///     graph_flow::ExecutionStatus::Paused { next_task_id, reason } => {
     ///         println!("Paused, next task: {}, reason: {}", next_task_id, reason);
     ///     }
+    ///     graph_flow::ExecutionStatus::PausedAtBreakpoint { next_task_id, breakpoint } => {
+    ///         println!("Stopped at {:?}, next task: {}", breakpoint, next_task_id);
+    ///     }
+    ///     graph_flow::ExecutionStatus::Sleeping { next_task_id, until } => {
+    ///         println!("Sleeping until {}, then running {}", until, next_task_id);
+    ///     }
     ///     graph_flow::ExecutionStatus::Error(e) => {
     ///         eprintln!("Error: {}", e);
     ///     }
+    ///     status => println!("Stopped: {:?}", status),
     /// }
     /// # Ok(())
     /// # }
@@ -341,10 +442,19 @@ impl FlowRunner {
     ///             // Continue to next step
     ///             continue;
     ///         }
+    ///         ExecutionStatus::PausedAtBreakpoint { .. } => {
+    ///             // Inspect the session, then `run` or `step` again
+    ///             break;
+    ///         }
+    ///         ExecutionStatus::Sleeping { .. } => {
+    ///             // A Scheduler resumes the session later
+    ///             break;
+    ///         }
     ///         ExecutionStatus::Error(e) => {
     ///             eprintln!("Error: {}", e);
     ///             break;
     ///         }
+    ///         _ => break,
     ///     }
     /// }
     /// # Ok(())
@@ -377,18 +487,695 @@ impl FlowRunner {
     /// ```
     pub async fn run(&self, session_id: &str) -> Result<ExecutionResult> {
         // 1. Load session
-        let mut session = self
+        let mut session = self.load(session_id).await?;
+
+        // 2. Execute current task (exactly one step)
+        let result = match self.graph.execute_session(&mut session).await {
+            Ok(result) => result,
+            Err(e) => return self.fail(session, e).await,
+        };
+
+        // 3. Persist new state so the next call starts where we left off
+        self.save(session).await?;
+
+        Ok(result)
+    }
+
+    /// Run exactly one task of `session_id` and stop after it, even if it asked to
+    /// `ContinueAndExecute`; a breakpoint before the task is ignored.
+    ///
+    /// Use it to single-step a session stopped at a breakpoint
+    /// ([`ExecutionStatus::PausedAtBreakpoint`](crate::ExecutionStatus::PausedAtBreakpoint));
+    /// [`run`](Self::run) continues past the breakpoint instead. To inspect or edit the
+    /// context in between, load the session from storage, change it and save it.
+    pub async fn step(&self, session_id: &str) -> Result<ExecutionResult> {
+        let mut session = self.load(session_id).await?;
+        let result = match self.graph.step_session(&mut session).await {
+            Ok(result) => result,
+            Err(e) => return self.fail(session, e).await,
+        };
+        self.save(session).await?;
+        Ok(result)
+    }
+
+    /// Deliver `input` to the task `session_id` is waiting on and run it.
+    ///
+    /// The input is written to the context under [`INPUT_KEY`](crate::INPUT_KEY) and saved
+    /// together with the outcome of the step, so a failed step leaves no trace of it and the
+    /// call can be retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::NotWaitingForInput`] if the session did not stop with
+    /// `WaitingForInput` (or was marked with [`Session::wait_for_input`]), in addition to
+    /// the errors of [`run`](Self::run).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use graph_flow::{FlowRunner, Graph, InMemorySessionStorage, GraphError};
+    /// # use std::sync::Arc;
+    /// # #[tokio::main]
+    /// # async fn main() -> graph_flow::Result<()> {
+    /// # let graph = Arc::new(Graph::new("test"));
+    /// # let storage = Arc::new(InMemorySessionStorage::new());
+    /// # let runner = FlowRunner::new(graph, storage);
+    /// match runner.resume("session_id", "Please add the treatment plan").await {
+    ///     Ok(result) => println!("{:?}", result.status),
+    ///     Err(GraphError::NotWaitingForInput(id)) => eprintln!("{id} did not ask for input"),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn resume(&self, session_id: &str, input: impl Serialize) -> Result<ExecutionResult> {
+        let input = serde_json::to_value(input)
+            .map_err(|e| GraphError::ContextError(format!("Failed to serialize input: {e}")))?;
+        let mut session = self.load(session_id).await?;
+        let result = match self.graph.resume_session(&mut session, input).await {
+            Ok(result) => result,
+            Err(e) => return self.fail(session, e).await,
+        };
+        self.save(session).await?;
+        Ok(result)
+    }
+
+    /// Deliver `input` to the waiting parallel branch `branch_id` of `session_id`,
+    /// execute the session and save it.
+    ///
+    /// Like [`resume`](Self::resume), but for any waiting branch rather than the one
+    /// the session reported; see [`Graph::resume_branch`].
+    pub async fn resume_branch(
+        &self,
+        session_id: &str,
+        branch_id: &str,
+        input: impl Serialize,
+    ) -> Result<ExecutionResult> {
+        let input = serde_json::to_value(input)
+            .map_err(|e| GraphError::ContextError(format!("Failed to serialize input: {e}")))?;
+        let mut session = self.load(session_id).await?;
+        let result = match self
+            .graph
+            .resume_branch(&mut session, branch_id, input)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return self.fail(session, e).await,
+        };
+        self.save(session).await?;
+        Ok(result)
+    }
+
+    /// Execute one step for `session_id` only if its timer is due, as a
+    /// [`Scheduler`](crate::Scheduler) does.
+    ///
+    /// Returns `Ok(None)` without running or saving anything when the session has
+    /// no due timer, e.g. because user input resumed it after it was claimed. If
+    /// the session is saved by someone else while the step runs, the step is not
+    /// saved and [`GraphError::SessionConflict`] is returned.
+    pub async fn wake(&self, session_id: &str) -> Result<Option<ExecutionResult>> {
+        let mut session = self.load(session_id).await?;
+        let due = session
+            .timer
+            .as_ref()
+            .is_some_and(|timer| timer.wake_at <= Utc::now());
+        if !due {
+            return Ok(None);
+        }
+
+        let result = match self.graph.execute_session(&mut session).await {
+            Ok(result) => result,
+            Err(e) => return self.fail(session, e).await.map(Some),
+        };
+        self.save(session).await?;
+        Ok(Some(result))
+    }
+
+    /// Cancel `session_id`: compensate its completed steps in reverse order and save it.
+    ///
+    /// Works whether or not the runner was created
+    /// [`with_compensation`](Self::with_compensation). The returned
+    /// [`Compensation`] is also recorded in the session context under
+    /// [`COMPENSATION_KEY`](crate::COMPENSATION_KEY); check
+    /// [`Compensation::is_complete`] to see whether every step was undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::SessionCompensated`] if the session was already rolled
+    /// back, in addition to the storage errors of [`run`](Self::run).
+    pub async fn abort(&self, session_id: &str, reason: &str) -> Result<Compensation> {
+        let mut session = self.load(session_id).await?;
+        let compensation = self.graph.compensate_session(&mut session, reason).await?;
+        self.save(session).await?;
+        Ok(compensation)
+    }
+
+    /// Delete `session_id` along with the blobs its context offloaded to the
+    /// runner's blob store.
+    ///
+    /// Deleting a session that doesn't exist is not an error.
+    pub async fn delete(&self, session_id: &str) -> Result<()> {
+        if self.blob_store.is_some()
+            && let Some(session) = self.storage.get(session_id).await?
+        {
+            self.attach_blob_store(&session);
+            session.context.delete_blobs().await?;
+        }
+        self.storage.delete(session_id).await
+    }
+
+    pub(crate) fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    pub(crate) fn storage(&self) -> &Arc<dyn SessionStorage> {
+        &self.storage
+    }
+
+    /// Return `error`, first rolling the session back if the runner compensates
+    /// failures and the error came from executing it. Errors that reject the
+    /// caller, such as an unauthorized reviewer, leave the session as it was.
+    ///
+    /// A rollback that cannot be completed or saved is logged; `error` is still
+    /// what the caller gets.
+    async fn fail<T>(&self, mut session: Session, error: GraphError) -> Result<T> {
+        let rejected = error.is_caller_error()
+            || matches!(
+                error,
+                GraphError::NotWaitingForInput(_) | GraphError::SessionCompensated(_)
+            );
+        if self.compensation && !rejected {
+            let session_id = session.id.clone();
+            match self
+                .graph
+                .compensate_session(&mut session, error.to_string())
+                .await
+            {
+                Ok(_) => {
+                    if let Err(e) = self.save(session).await {
+                        tracing::error!(session_id = %session_id, error = %e, failure = %error, "Failed to save compensated session");
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(session_id = %session_id, error = %e, failure = %error, "Failed to compensate session");
+                }
+            }
+        } else if !rejected && session.parallel.is_some() {
+            // Branches whose step succeeded keep their progress
+            let session_id = session.id.clone();
+            if let Err(e) = self.save(session).await {
+                tracing::error!(session_id = %session_id, error = %e, failure = %error, "Failed to save branch progress");
+            }
+        }
+        Err(error)
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Session> {
+        let session = self
             .storage
             .get(session_id)
             .await?
             .ok_or_else(|| GraphError::SessionNotFound(session_id.to_string()))?;
+        self.attach_blob_store(&session);
+        Ok(session)
+    }
 
-        // 2. Execute current task (exactly one step)
-        let result = self.graph.execute_session(&mut session).await?;
+    fn attach_blob_store(&self, session: &Session) {
+        if let Some((store, threshold)) = &self.blob_store {
+            session.context.set_blob_store(store.clone(), *threshold);
+        }
+    }
 
-        // 3. Persist new state so the next call starts where we left off
+    /// Compact the chat history if configured, save `session`, then delete the
+    /// blobs of values it no longer holds.
+    async fn save(&self, session: Session) -> Result<()> {
+        let context = session.context.clone();
+        if let Some((policy, summarizer)) = &self.compaction
+            && let Err(e) = context
+                .compact_chat_history(policy, summarizer.as_ref())
+                .await
+        {
+            tracing::warn!(error = %e, session_id = %session.id, "Failed to compact chat history");
+        }
         self.storage.save(session).await?;
+        if let Err(e) = context.delete_superseded_blobs().await {
+            tracing::warn!(error = %e, "Failed to delete superseded blobs");
+        }
+        Ok(())
+    }
+}
 
-        Ok(result)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTask;
+    use crate::{
+        ApprovalDecision, ApprovalInput, ApprovalTask, Context, ExecutionStatus, GraphBuilder,
+        INPUT_KEY, InMemorySessionStorage, InputRequest, NextAction, Reviewer, Task, TaskResult,
+    };
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    async fn setup(review: MockTask) -> (FlowRunner, Arc<InMemorySessionStorage>) {
+        let graph = GraphBuilder::new("review")
+            .add_task(Arc::new(review))
+            .add_task(Arc::new(
+                MockTask::new("publish")
+                    .then(TaskResult::new(Some("done".into()), NextAction::End)),
+            ))
+            .add_edge("review", "publish")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "review"))
+            .await
+            .unwrap();
+        (FlowRunner::new(Arc::new(graph), storage.clone()), storage)
+    }
+
+    #[tokio::test]
+    async fn resume_delivers_input_to_the_waiting_task() {
+        let review = MockTask::new("review")
+            .then(TaskResult::new(
+                Some("Feedback?".into()),
+                NextAction::WaitForInput,
+            ))
+            .then(TaskResult::move_to_next_direct());
+        let (runner, storage) = setup(review).await;
+
+        // Not waiting yet
+        let err = runner.resume("s1", "too early").await.unwrap_err();
+        assert!(matches!(err, GraphError::NotWaitingForInput(id) if id == "s1"));
+
+        let result = runner.run("s1").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.pending_input.unwrap().task_id, "review");
+
+        let result = runner
+            .resume("s1", json!({"approved": true}))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_none());
+        assert_eq!(
+            session.context.get::<serde_json::Value>(INPUT_KEY).await,
+            Some(json!({"approved": true}))
+        );
+        assert!(runner.resume("s1", "again").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_resume_leaves_the_session_waiting() {
+        let review = MockTask::new("review")
+            .then(TaskResult::new(None, NextAction::WaitForInput))
+            .then_fail("reviewer service down")
+            .then(TaskResult::move_to_next_direct());
+        let (runner, storage) = setup(review).await;
+        runner.run("s1").await.unwrap();
+
+        assert!(runner.resume("s1", "first try").await.is_err());
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_some());
+        assert_eq!(session.context.get::<String>(INPUT_KEY).await, None);
+
+        runner.resume("s1", "second try").await.unwrap();
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(
+            session.context.get::<String>(INPUT_KEY).await.as_deref(),
+            Some("second try")
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_rejects_input_that_does_not_match_the_request() {
+        let request = InputRequest::choice("Approve?", ["yes", "no"]);
+        let review = MockTask::new("review")
+            .then(TaskResult::request_input(request.clone()))
+            .then(TaskResult::move_to_next_direct());
+        let (runner, storage) = setup(review).await;
+
+        let result = runner.run("s1").await.unwrap();
+        assert_eq!(result.response.as_deref(), Some("Approve?"));
+        assert_eq!(result.input_request.as_ref(), Some(&request));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.pending_input.unwrap().request, Some(request));
+
+        let err = runner.resume("s1", "maybe").await.unwrap_err();
+        assert!(matches!(err, GraphError::InvalidInput(_)));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_some());
+        assert_eq!(session.context.get::<String>(INPUT_KEY).await, None);
+
+        let result = runner.resume("s1", "yes").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert!(result.input_request.is_none());
+    }
+
+    /// Saves its own session while running, like a concurrent request would.
+    struct Interloper(Arc<InMemorySessionStorage>);
+
+    #[async_trait]
+    impl Task for Interloper {
+        fn id(&self) -> &str {
+            "review"
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            if context.get::<String>(INPUT_KEY).await.is_none() {
+                return Ok(TaskResult::new(None, NextAction::WaitForInput));
+            }
+            let mut session = self.0.get("s1").await?.unwrap();
+            session.status_message = Some("saved elsewhere".into());
+            self.0.save(session).await?;
+            Ok(TaskResult::new(None, NextAction::End))
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_saves_are_rejected() {
+        let storage = Arc::new(InMemorySessionStorage::new());
+        let graph = GraphBuilder::new("review")
+            .add_task(Arc::new(Interloper(storage.clone())))
+            .build();
+        storage
+            .save(Session::new_from_task("s1".to_string(), "review"))
+            .await
+            .unwrap();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone());
+        runner.run("s1").await.unwrap();
+
+        let err = runner.resume("s1", "approved").await.unwrap_err();
+        assert!(matches!(err, GraphError::SessionConflict(id) if id == "s1"));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.status_message.as_deref(), Some("saved elsewhere"));
+        assert!(session.pending_input.is_some());
+        assert_eq!(session.version, 3);
+    }
+
+    /// Adds a long user message on every run.
+    struct Chatty;
+
+    #[async_trait]
+    impl Task for Chatty {
+        fn id(&self) -> &str {
+            "chat"
+        }
+
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            context
+                .add_user_message("a fairly long message ".repeat(10))
+                .await;
+            Ok(TaskResult::new(None, NextAction::Continue))
+        }
+    }
+
+    #[tokio::test]
+    async fn runner_compacts_chat_history_before_saving() {
+        let graph = GraphBuilder::new("chat").add_task(Arc::new(Chatty)).build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "chat"))
+            .await
+            .unwrap();
+        let summarize =
+            |messages: &[crate::SerializableMessage]| format!("{} messages", messages.len());
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone())
+            .with_compaction(CompactionPolicy::new(200, 100), Arc::new(summarize));
+
+        for _ in 0..6 {
+            runner.run("s1").await.unwrap();
+        }
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        let messages = session.context.get_all_messages().await;
+        assert!(messages.len() < 6);
+        assert!(messages[0].content.contains("messages"));
+    }
+
+    #[tokio::test]
+    async fn new_sessions_can_start_by_waiting_for_input() {
+        let (runner, storage) = setup(MockTask::new("review")).await;
+        let mut session = storage.get("s1").await.unwrap().unwrap();
+        session.wait_for_input();
+        storage.save(session).await.unwrap();
+
+        let result = runner.resume("s1", "opening message").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+    }
+
+    /// Books something and logs its compensations; a failing one logs nothing.
+    struct Booking {
+        id: &'static str,
+        fail_compensation: bool,
+        compensated: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Task for Booking {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        async fn run(&self, _context: Context) -> Result<TaskResult> {
+            Ok(TaskResult::move_to_next_direct())
+        }
+
+        async fn compensate(&self, _context: Context) -> Result<()> {
+            if self.fail_compensation {
+                return Err(GraphError::TaskExecutionFailed(
+                    "refund service down".into(),
+                ));
+            }
+            self.compensated.lock().unwrap().push(self.id.to_string());
+            Ok(())
+        }
+    }
+
+    async fn saga(
+        last: MockTask,
+        compensated: &Arc<Mutex<Vec<String>>>,
+    ) -> (FlowRunner, Arc<InMemorySessionStorage>) {
+        let booking = |id, fail_compensation| {
+            Arc::new(Booking {
+                id,
+                fail_compensation,
+                compensated: compensated.clone(),
+            })
+        };
+        let graph = GraphBuilder::new("saga")
+            .add_task(booking("reserve", false))
+            .add_task(Arc::new(MockTask::new("log")))
+            .add_task(booking("charge", true))
+            .add_task(Arc::new(last))
+            .add_edge("reserve", "log")
+            .add_edge("log", "charge")
+            .add_edge("charge", "last")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "reserve"))
+            .await
+            .unwrap();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone()).with_compensation();
+        (runner, storage)
+    }
+
+    #[tokio::test]
+    async fn failed_step_compensates_completed_steps_in_reverse() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let last = MockTask::new("last").then_fail("notification failed");
+        let (runner, storage) = saga(last, &compensated).await;
+
+        let err = runner.run("s1").await.unwrap_err();
+        assert!(err.to_string().contains("notification failed"));
+        assert_eq!(*compensated.lock().unwrap(), vec!["reserve".to_string()]);
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.compensable_steps.is_empty());
+        let compensation: Compensation =
+            session.context.get(crate::COMPENSATION_KEY).await.unwrap();
+        assert!(compensation.reason.contains("notification failed"));
+        let steps: Vec<_> = compensation
+            .steps
+            .iter()
+            .map(|step| (step.task_id.as_str(), step.error.is_some()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![("charge", true), ("log", false), ("reserve", false)]
+        );
+        assert!(!compensation.is_complete());
+
+        let err = runner.run("s1").await.unwrap_err();
+        assert!(matches!(err, GraphError::SessionCompensated(_)));
+    }
+
+    #[tokio::test]
+    async fn unauthorized_reviewers_do_not_trigger_compensation() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let graph = GraphBuilder::new("saga")
+            .add_task(Arc::new(Booking {
+                id: "reserve",
+                fail_compensation: false,
+                compensated: compensated.clone(),
+            }))
+            .add_task(ApprovalTask::new("approval", "Approve?").with_required_roles(["manager"]))
+            .add_edge("reserve", "approval")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "reserve"))
+            .await
+            .unwrap();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone()).with_compensation();
+        runner.run("s1").await.unwrap();
+
+        let input = ApprovalInput {
+            reviewer: Reviewer::new("carol", ["clerk"]),
+            decision: ApprovalDecision::Approve,
+            comment: None,
+        };
+        let err = runner.resume("s1", input).await.unwrap_err();
+        assert!(matches!(err, GraphError::Unauthorized(_)), "{err}");
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_some());
+        assert_eq!(
+            session
+                .context
+                .get::<Compensation>(crate::COMPENSATION_KEY)
+                .await,
+            None
+        );
+        assert!(compensated.lock().unwrap().is_empty());
+    }
+
+    /// Saves its own session, then fails.
+    struct FailsAfterSave(Arc<InMemorySessionStorage>);
+
+    #[async_trait]
+    impl Task for FailsAfterSave {
+        fn id(&self) -> &str {
+            "notify"
+        }
+
+        async fn run(&self, _context: Context) -> Result<TaskResult> {
+            let session = self.0.get("s1").await?.unwrap();
+            self.0.save(session).await?;
+            Err(GraphError::TaskExecutionFailed(
+                "notification failed".into(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn failure_is_returned_when_the_rollback_cannot_be_saved() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let storage = Arc::new(InMemorySessionStorage::new());
+        let graph = GraphBuilder::new("saga")
+            .add_task(Arc::new(Booking {
+                id: "reserve",
+                fail_compensation: false,
+                compensated: compensated.clone(),
+            }))
+            .add_task(Arc::new(FailsAfterSave(storage.clone())))
+            .add_edge("reserve", "notify")
+            .build();
+        storage
+            .save(Session::new_from_task("s1".to_string(), "reserve"))
+            .await
+            .unwrap();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone()).with_compensation();
+
+        // The rollback ran but its save conflicted with the task's
+        let err = runner.run("s1").await.unwrap_err();
+        assert!(err.to_string().contains("notification failed"), "{err}");
+        assert_eq!(*compensated.lock().unwrap(), vec!["reserve".to_string()]);
+        // Saved on creation and by the task, not after the rollback
+        assert_eq!(storage.get("s1").await.unwrap().unwrap().version, 2);
+    }
+
+    #[tokio::test]
+    async fn failed_branch_keeps_sibling_progress_in_storage() {
+        let b = Arc::new(MockTask::new("b"));
+        let graph = GraphBuilder::new("parallel")
+            .add_task(Arc::new(MockTask::new("start")))
+            .add_task(Arc::new(
+                MockTask::new("a")
+                    .then_fail("boom")
+                    .then(TaskResult::move_to_next_direct()),
+            ))
+            .add_task(b.clone())
+            .add_task(Arc::new(
+                MockTask::new("join").then(TaskResult::new(None, NextAction::End)),
+            ))
+            .add_fork("start", ["a", "b"])
+            .add_edge("a", "join")
+            .add_edge("b", "join")
+            .add_join("join", crate::JoinPolicy::All)
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "start"))
+            .await
+            .unwrap();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone());
+
+        assert!(runner.run("s1").await.is_err());
+        let session = storage.get("s1").await.unwrap().unwrap();
+        let statuses: Vec<_> = session
+            .parallel
+            .as_ref()
+            .unwrap()
+            .branches
+            .iter()
+            .map(|branch| branch.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![crate::BranchStatus::Active, crate::BranchStatus::Arrived]
+        );
+
+        // Only the failed branch runs again
+        let result = runner.run("s1").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert_eq!(b.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn abort_compensates_a_waiting_session() {
+        let compensated = Arc::new(Mutex::new(Vec::new()));
+        let last = MockTask::new("last").then(TaskResult::new(
+            Some("Confirm?".into()),
+            NextAction::WaitForInput,
+        ));
+        let (runner, storage) = saga(last, &compensated).await;
+
+        let result = runner.run("s1").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(
+            session.compensable_steps,
+            ["reserve", "log", "charge", "last"]
+        );
+
+        let compensation = runner.abort("s1", "customer cancelled").await.unwrap();
+        assert_eq!(compensation.reason, "customer cancelled");
+        assert_eq!(compensation.steps.len(), 4);
+        assert_eq!(*compensated.lock().unwrap(), vec!["reserve".to_string()]);
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.pending_input.is_none());
+        assert!(matches!(
+            runner.run("s1").await,
+            Err(GraphError::SessionCompensated(_))
+        ));
+        assert!(matches!(
+            runner.abort("s1", "again").await,
+            Err(GraphError::SessionCompensated(_))
+        ));
     }
 }
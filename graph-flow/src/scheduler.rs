@@ -0,0 +1,523 @@
+//! Scheduler – resumes sessions whose timer is due.
+//!
+//! A task sets a timer by returning [`NextAction::WaitUntil`](crate::NextAction::WaitUntil)
+//! (wait for input, with a deadline) or [`NextAction::Sleep`](crate::NextAction::Sleep)
+//! (continue after a delay). The wake-up time is stored on the [`Session`](crate::Session),
+//! so it lives as long as the session storage does. A `Scheduler` polls the storage
+//! for sessions of its graph whose timer is due and runs one step for each of them
+//! through a [`FlowRunner`].
+//!
+//! Due sessions are claimed with a lease (see
+//! [`SessionStorage::claim_due_sessions`](crate::SessionStorage::claim_due_sessions)),
+//! so several scheduler instances can share a storage without running a session twice.
+//! If a wake-up fails, the session's timer is moved back with exponential backoff
+//! (see [`Scheduler::with_backoff`]). After [`Scheduler::with_max_attempts`] failures
+//! the timer is cleared and the last error is recorded under [`WAKE_FAILURE_KEY`], so
+//! the session is not picked up again.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use graph_flow::{FlowRunner, Graph, InMemorySessionStorage, Scheduler};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let graph = Arc::new(Graph::new("reminders"));
+//! let storage = Arc::new(InMemorySessionStorage::new());
+//! let runner = FlowRunner::new(graph, storage);
+//!
+//! // Poll every 5 seconds in the background
+//! let handle = Scheduler::new(runner)
+//!     .with_poll_interval(Duration::from_secs(5))
+//!     .spawn();
+//! # handle.abort();
+//! # }
+//! ```
+
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{
+    error::{GraphError, Result},
+    graph::{ExecutionResult, TaskFailure},
+    runner::FlowRunner,
+    storage,
+};
+
+/// Context key holding the [`TaskFailure`] of a session whose scheduled wake-up
+/// failed too often; its timer has been cleared.
+pub const WAKE_FAILURE_KEY: &str = "graph_flow.wake_failure";
+
+/// Result of waking one due session.
+#[derive(Debug)]
+pub struct WakeOutcome {
+    pub session_id: String,
+    /// `Ok(None)` if the session turned out not to be due any more
+    pub result: Result<Option<ExecutionResult>>,
+}
+
+/// Polls session storage and runs sessions whose timer is due.
+#[derive(Clone)]
+pub struct Scheduler {
+    runner: FlowRunner,
+    poll_interval: Duration,
+    lease: Duration,
+    batch_size: usize,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Scheduler {
+    /// Create a scheduler for the graph and storage of `runner`.
+    ///
+    /// Only sessions whose `graph_id` matches the graph's id are picked up.
+    pub fn new(runner: FlowRunner) -> Self {
+        Self {
+            runner,
+            poll_interval: Duration::from_secs(1),
+            lease: Duration::from_secs(300),
+            batch_size: 100,
+            max_attempts: 5,
+            backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(3600),
+        }
+    }
+
+    /// How long to wait between polls when nothing is due (default: 1 second).
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// How long a claimed session is hidden from other schedulers (default: 5 minutes).
+    ///
+    /// Should comfortably exceed the time one step takes.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Maximum number of sessions claimed per poll (default: 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Give up on a session after this many failed wake-ups (default: 5).
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Retry a failed wake-up after `initial`, doubling the delay on every
+    /// further failure up to `max` (default: 30 seconds up to 1 hour).
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Claim the sessions due now and run one step for each, in order of their wake-up time.
+    pub async fn run_due(&self) -> Result<Vec<WakeOutcome>> {
+        let due = self
+            .runner
+            .storage()
+            .claim_due_sessions(
+                &self.runner.graph().id,
+                Utc::now(),
+                self.lease,
+                self.batch_size,
+            )
+            .await?;
+
+        let mut outcomes = Vec::with_capacity(due.len());
+        for session_id in due {
+            let result = self.runner.wake(&session_id).await;
+            match &result {
+                // Someone else saved the session meanwhile; its timer is theirs now
+                Err(GraphError::SessionConflict(_) | GraphError::SessionNotFound(_)) => {}
+                Err(e) => {
+                    tracing::warn!(session_id = %session_id, error = %e, "Scheduled wake-up failed");
+                    if let Err(e) = self.record_failure(&session_id, e).await {
+                        tracing::error!(session_id = %session_id, error = %e, "Failed to record failed wake-up");
+                    }
+                }
+                Ok(_) => {}
+            }
+            outcomes.push(WakeOutcome { session_id, result });
+        }
+        Ok(outcomes)
+    }
+
+    /// Count a failed wake-up on the session's timer and move it back, or clear
+    /// it once the session has failed `max_attempts` times. A compensated session
+    /// never runs again, so its timer is cleared right away.
+    async fn record_failure(&self, session_id: &str, error: &GraphError) -> Result<()> {
+        let storage = self.runner.storage();
+        let Some(mut session) = storage.get(session_id).await? else {
+            return Ok(());
+        };
+        if matches!(error, GraphError::SessionCompensated(_)) {
+            if session.timer.take().is_none() {
+                return Ok(());
+            }
+            return storage.save(session).await;
+        }
+        let Some(timer) = session.timer.as_mut() else {
+            return Ok(());
+        };
+        timer.attempts += 1;
+        let attempts = timer.attempts;
+        if attempts < self.max_attempts {
+            let delay = self
+                .backoff
+                .checked_mul(1 << (attempts - 1).min(20))
+                .map_or(self.max_backoff, |delay| delay.min(self.max_backoff));
+            timer.wake_at = storage::after(Utc::now(), delay);
+        } else {
+            tracing::error!(session_id = %session_id, attempts, "Giving up on scheduled wake-up");
+            let failure = TaskFailure {
+                task_id: session.current_task_id.clone(),
+                kind: error.kind().to_string(),
+                message: error.to_string(),
+                attempts,
+                failed_at: Utc::now(),
+            };
+            session.timer = None;
+            session.status_message = Some(format!(
+                "Scheduled wake-up failed {} time(s): {}",
+                attempts, failure.message
+            ));
+            session.context.set(WAKE_FAILURE_KEY, &failure).await;
+        }
+        storage.save(session).await
+    }
+
+    /// Poll forever. A full batch is followed immediately by the next poll.
+    pub async fn run(self) {
+        loop {
+            match self.run_due().await {
+                Ok(outcomes) if outcomes.len() == self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!(error = %e, "Failed to claim due sessions"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Run [`Scheduler::run`] on a background task.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTask;
+    use crate::{
+        ExecutionStatus, GraphBuilder, InMemorySessionStorage, NextAction, Session, SessionStorage,
+        TIMER_FIRED_KEY, TaskResult,
+    };
+    use chrono::Duration as TimeDelta;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn setup(first: MockTask) -> (FlowRunner, Arc<InMemorySessionStorage>) {
+        let graph = GraphBuilder::new("timers")
+            .add_task(Arc::new(first))
+            .add_task(Arc::new(
+                MockTask::new("next").then(TaskResult::new(Some("done".into()), NextAction::End)),
+            ))
+            .add_edge("first", "next")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        (FlowRunner::new(Arc::new(graph), storage.clone()), storage)
+    }
+
+    async fn start(storage: &InMemorySessionStorage) {
+        let mut session = Session::new_from_task("s1".to_string(), "first");
+        session.graph_id = "timers".to_string();
+        storage.save(session).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sleep_defers_the_next_task_until_the_timer_fires() {
+        let (runner, storage) = setup(MockTask::new("first").then(TaskResult::new(
+            None,
+            NextAction::Sleep(Duration::from_millis(50)),
+        )));
+        start(&storage).await;
+
+        let result = runner.run("s1").await.unwrap();
+        assert!(
+            matches!(result.status, ExecutionStatus::Sleeping { ref next_task_id, .. } if next_task_id == "next")
+        );
+
+        // Too early: nothing runs and nothing is due
+        let early = runner.run("s1").await.unwrap();
+        assert!(matches!(early.status, ExecutionStatus::Sleeping { .. }));
+        let scheduler = Scheduler::new(runner.clone());
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let outcomes = scheduler.run_due().await.unwrap();
+        assert_eq!(outcomes.len(), 1);
+        let result = outcomes[0].result.as_ref().unwrap().as_ref().unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert!(storage.get("s1").await.unwrap().unwrap().timer.is_none());
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn wait_until_reruns_the_task_after_the_deadline() {
+        let deadline = Utc::now() - TimeDelta::seconds(1);
+        let first = MockTask::new("first")
+            .then(TaskResult::new(
+                Some("Any feedback?".into()),
+                NextAction::WaitUntil(deadline),
+            ))
+            .then(TaskResult::new(
+                Some("Reminder sent".into()),
+                NextAction::WaitForInput,
+            ));
+        let (runner, storage) = setup(first);
+        start(&storage).await;
+
+        let result = runner.run("s1").await.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::WaitingForInput));
+
+        // The deadline survives a reload from storage
+        let stored = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(stored.timer.as_ref().unwrap().wake_at, deadline);
+
+        let outcomes = Scheduler::new(runner).run_due().await.unwrap();
+        let result = outcomes[0].result.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(result.response.as_deref(), Some("Reminder sent"));
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.current_task_id, "first");
+        assert_eq!(session.last_writer(TIMER_FIRED_KEY), None);
+        assert_eq!(session.context.get::<bool>(TIMER_FIRED_KEY).await, None);
+    }
+
+    #[tokio::test]
+    async fn input_before_the_deadline_cancels_the_timer() {
+        let deadline = Utc::now() + TimeDelta::hours(48);
+        let first = MockTask::new("first")
+            .then(TaskResult::new(None, NextAction::WaitUntil(deadline)))
+            .then_writing(
+                [("feedback", json!("looks good"))],
+                TaskResult::move_to_next(),
+            );
+        let (runner, storage) = setup(first);
+        start(&storage).await;
+
+        runner.run("s1").await.unwrap();
+        runner.run("s1").await.unwrap();
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.timer.is_none());
+        assert_eq!(session.current_task_id, "next");
+    }
+
+    #[tokio::test]
+    async fn failed_wake_ups_back_off_then_give_up() {
+        let overdue = Utc::now() - TimeDelta::seconds(1);
+        let (runner, storage) = setup(
+            MockTask::new("first")
+                .then(TaskResult::new(None, NextAction::WaitUntil(overdue)))
+                .then_fail("reminder service down")
+                .then_fail("reminder service down"),
+        );
+        start(&storage).await;
+        runner.run("s1").await.unwrap();
+
+        let scheduler = Scheduler::new(runner.clone()).with_max_attempts(2);
+        let outcomes = scheduler.run_due().await.unwrap();
+        assert!(outcomes[0].result.is_err());
+        let timer = storage.get("s1").await.unwrap().unwrap().timer.unwrap();
+        assert_eq!(timer.attempts, 1);
+        assert!(timer.wake_at > Utc::now() + TimeDelta::seconds(29));
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+
+        // Due again after the backoff; the second failure is the last
+        let mut session = storage.get("s1").await.unwrap().unwrap();
+        session.timer.as_mut().unwrap().wake_at = overdue;
+        storage.save(session).await.unwrap();
+        assert!(scheduler.run_due().await.unwrap()[0].result.is_err());
+
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.timer.is_none());
+        let failure: TaskFailure = session.context.get(WAKE_FAILURE_KEY).await.unwrap();
+        assert_eq!((failure.task_id.as_str(), failure.attempts), ("first", 2));
+        assert!(failure.message.contains("reminder service down"));
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn compensated_sessions_lose_their_timer() {
+        let overdue = Utc::now() - TimeDelta::seconds(1);
+        let (runner, storage) = setup(
+            MockTask::new("first").then(TaskResult::new(None, NextAction::WaitUntil(overdue))),
+        );
+        start(&storage).await;
+        runner.run("s1").await.unwrap();
+        runner.abort("s1", "claim withdrawn").await.unwrap();
+
+        // A timer left behind, e.g. by a save that raced the abort
+        let mut session = storage.get("s1").await.unwrap().unwrap();
+        session.timer = Some(crate::SessionTimer {
+            wake_at: overdue,
+            interruptible: false,
+            attempts: 0,
+        });
+        storage.save(session).await.unwrap();
+
+        let scheduler = Scheduler::new(runner);
+        let outcomes = scheduler.run_due().await.unwrap();
+        assert!(matches!(
+            outcomes[0].result,
+            Err(GraphError::SessionCompensated(_))
+        ));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert!(session.timer.is_none());
+        assert_eq!(
+            session.context.get::<TaskFailure>(WAKE_FAILURE_KEY).await,
+            None
+        );
+        assert!(scheduler.run_due().await.unwrap().is_empty());
+    }
+
+    /// Answers its own session while the timer fires, like a concurrent resume.
+    struct Interloper(Arc<InMemorySessionStorage>);
+
+    #[async_trait::async_trait]
+    impl crate::Task for Interloper {
+        fn id(&self) -> &str {
+            "first"
+        }
+
+        async fn run(&self, context: crate::Context) -> Result<TaskResult> {
+            if context.get::<bool>(TIMER_FIRED_KEY).await.is_none() {
+                let overdue = Utc::now() - TimeDelta::seconds(1);
+                return Ok(TaskResult::new(None, NextAction::WaitUntil(overdue)));
+            }
+            let mut session = self.0.get("s1").await?.unwrap();
+            session.timer = None;
+            session.status_message = Some("answered".into());
+            self.0.save(session).await?;
+            Ok(TaskResult::new(None, NextAction::End))
+        }
+    }
+
+    #[tokio::test]
+    async fn wake_up_does_not_overwrite_a_concurrent_save() {
+        let storage = Arc::new(InMemorySessionStorage::new());
+        let graph = GraphBuilder::new("timers")
+            .add_task(Arc::new(Interloper(storage.clone())))
+            .build();
+        let runner = FlowRunner::new(Arc::new(graph), storage.clone());
+        start(&storage).await;
+        runner.run("s1").await.unwrap();
+
+        let outcomes = Scheduler::new(runner).run_due().await.unwrap();
+        assert!(matches!(
+            outcomes[0].result,
+            Err(GraphError::SessionConflict(_))
+        ));
+        let session = storage.get("s1").await.unwrap().unwrap();
+        assert_eq!(session.status_message.as_deref(), Some("answered"));
+        assert!(session.timer.is_none());
+        assert_eq!(
+            session.context.get::<TaskFailure>(WAKE_FAILURE_KEY).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn claimed_sessions_are_leased() {
+        let storage = InMemorySessionStorage::new();
+        start(&storage).await;
+        let mut session = storage.get("s1").await.unwrap().unwrap();
+        session.timer = Some(crate::SessionTimer {
+            wake_at: Utc::now() - TimeDelta::seconds(1),
+            interruptible: false,
+            attempts: 0,
+        });
+        storage.save(session).await.unwrap();
+
+        let lease = Duration::from_secs(60);
+        let now = Utc::now();
+        assert_eq!(
+            storage
+                .claim_due_sessions("timers", now, lease, 10)
+                .await
+                .unwrap(),
+            ["s1"]
+        );
+        assert!(
+            storage
+                .claim_due_sessions("timers", now, lease, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            storage
+                .claim_due_sessions("other", now, lease, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // The lease expires, and saving releases it
+        let later = now + TimeDelta::seconds(61);
+        assert_eq!(
+            storage
+                .claim_due_sessions("timers", later, lease, 10)
+                .await
+                .unwrap(),
+            ["s1"]
+        );
+        let session = storage.get("s1").await.unwrap().unwrap();
+        storage.save(session).await.unwrap();
+        assert_eq!(
+            storage
+                .claim_due_sessions("timers", now, lease, 10)
+                .await
+                .unwrap(),
+            ["s1"]
+        );
+    }
+
+    /// A storage that only implements the required methods.
+    struct Basic(InMemorySessionStorage);
+
+    #[async_trait::async_trait]
+    impl SessionStorage for Basic {
+        async fn save(&self, session: Session) -> Result<()> {
+            self.0.save(session).await
+        }
+        async fn get(&self, id: &str) -> Result<Option<Session>> {
+            self.0.get(id).await
+        }
+        async fn delete(&self, id: &str) -> Result<()> {
+            self.0.delete(id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn storages_without_claims_cannot_be_scheduled() {
+        let graph = GraphBuilder::new("timers")
+            .add_task(Arc::new(MockTask::new("first")))
+            .build();
+        let runner = FlowRunner::new(Arc::new(graph), Arc::new(Basic(Default::default())));
+
+        let err = Scheduler::new(runner).run_due().await.unwrap_err();
+        assert!(matches!(err, GraphError::StorageError(ref msg) if msg.contains("not supported")));
+    }
+}
@@ -1,12 +1,167 @@
 use async_trait::async_trait;
-use dashmap::DashMap;
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, mapref::entry::Entry};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{Context, error::Result, graph::Graph};
+use crate::{
+    Context,
+    context::ContextDiff,
+    error::{GraphError, Result},
+    graph::Graph,
+    input::InputRequest,
+};
+
+/// Maximum number of step write sets kept in a session (oldest are dropped first)
+const MAX_WRITE_SETS: usize = 1000;
+
+/// `at + duration`, saturating at the latest representable time.
+pub(crate) fn after(at: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|delta| at.checked_add_signed(delta))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// The context keys and messages written by one executed step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepWriteSet {
+    /// ID of the task that ran in this step
+    pub task_id: String,
+    /// Keys the task set
+    pub set: Vec<String>,
+    /// Keys the task removed
+    pub removed: Vec<String>,
+    /// Number of chat messages the task appended
+    pub messages_appended: usize,
+    /// When the step finished
+    pub completed_at: DateTime<Utc>,
+}
+
+impl StepWriteSet {
+    /// Build a write set for `task_id` from the diff of its step.
+    pub fn from_diff(task_id: impl Into<String>, diff: &ContextDiff) -> Self {
+        Self {
+            task_id: task_id.into(),
+            set: diff.set.keys().cloned().collect(),
+            removed: diff.removed.iter().cloned().collect(),
+            messages_appended: diff.messages.len(),
+            completed_at: Utc::now(),
+        }
+    }
+}
+
+/// Where a branch started by a fork stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchStatus {
+    /// Runs `current_task_id` on the next call
+    Active,
+    /// Paused at `current_task_id` until the next call brings input
+    WaitingForInput,
+    /// Reached the join node
+    Arrived,
+    /// Ended, or ran out of edges, without reaching a join node
+    Ended,
+    /// Stopped because the join no longer needed it
+    Cancelled,
+}
+
+/// Progress of one branch started by a fork.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchState {
+    /// ID of the task the branch started at
+    pub id: String,
+    /// Task the branch runs next, or last ran once it stopped
+    pub current_task_id: String,
+    pub status: BranchStatus,
+    /// Input delivered to the waiting branch, visible to its next step under
+    /// [`INPUT_KEY`](crate::INPUT_KEY)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<serde_json::Value>,
+    /// What the waiting branch expects, checked when input is delivered to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_request: Option<InputRequest>,
+}
+
+/// Branches of a fork that have not been joined yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParallelState {
+    /// ID of the task whose fork started the branches
+    pub fork_task_id: String,
+    /// Join node the branches lead to, known once the first branch arrives
+    pub join_task_id: Option<String>,
+    pub branches: Vec<BranchState>,
+}
+
+/// Wake-up time recorded on a session by `NextAction::WaitUntil` or `NextAction::Sleep`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTimer {
+    pub wake_at: DateTime<Utc>,
+    /// Whether executing the session before `wake_at` (e.g. with user input) runs
+    /// the current task and cancels the timer, rather than doing nothing
+    pub interruptible: bool,
+    /// Failed wake-ups so far, counted by the [`Scheduler`](crate::Scheduler)
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// Task a session is waiting on for input, set while it is `WaitingForInput`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingInput {
+    /// Task that receives the input on [`FlowRunner::resume`](crate::FlowRunner::resume)
+    pub task_id: String,
+    /// When the session started waiting
+    pub since: DateTime<Utc>,
+    /// What the task expects, checked by `resume`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<InputRequest>,
+    /// Parallel branch the task belongs to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_id: Option<String>,
+}
+
+/// Which side of a task a [`Breakpoint`] interrupts execution on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakpointPosition {
+    /// Before the task runs
+    Before,
+    /// After the task ran, before the next one
+    After,
+}
+
+/// Interrupts execution before or after a task, even one that would
+/// `ContinueAndExecute`. Set on a graph or on a single session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub task_id: String,
+    pub position: BreakpointPosition,
+}
+
+impl Breakpoint {
+    pub fn before(task_id: impl Into<String>) -> Self {
+        Self {
+            task_id: task_id.into(),
+            position: BreakpointPosition::Before,
+        }
+    }
+
+    pub fn after(task_id: impl Into<String>) -> Self {
+        Self {
+            task_id: task_id.into(),
+            position: BreakpointPosition::After,
+        }
+    }
+}
 
 /// Session information
+///
+/// Create sessions with [`Session::new_from_task`] and the `with_*` methods;
+/// fields may be added in minor releases.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Session {
     pub id: String,
     pub graph_id: String,
@@ -14,9 +169,36 @@ pub struct Session {
     /// Optional status message from the last executed task
     pub status_message: Option<String>,
     pub context: crate::context::Context,
+    /// Write sets of the executed steps, oldest first
+    #[serde(default)]
+    pub write_sets: Vec<StepWriteSet>,
+    /// Branches in progress while the session is between a fork and its join
+    #[serde(default)]
+    pub parallel: Option<ParallelState>,
+    /// Pending wake-up, picked up by a [`Scheduler`](crate::Scheduler)
+    #[serde(default)]
+    pub timer: Option<SessionTimer>,
+    /// Set while the session waits for input; only such sessions can be resumed
+    #[serde(default)]
+    pub pending_input: Option<PendingInput>,
+    /// Breakpoints of this session, on top of the graph's
+    #[serde(default)]
+    pub breakpoints: Vec<Breakpoint>,
+    /// Breakpoint the session is stopped at; the next execution continues past it
+    #[serde(default)]
+    pub interrupted: Option<Breakpoint>,
+    /// Successful task runs, oldest first, compensated in reverse on a rollback
+    #[serde(default)]
+    pub compensable_steps: Vec<String>,
+    /// Times the session had been saved when it was loaded, 0 for a new one.
+    /// Saving fails with [`GraphError::SessionConflict`] once the stored
+    /// session has moved past it.
+    #[serde(default)]
+    pub version: u64,
 }
 
 impl Session {
+    /// Create a session of the `"default"` graph that starts at `task_name`.
     pub fn new_from_task(sid: String, task_name: &str) -> Self {
         Self {
             id: sid,
@@ -24,8 +206,78 @@ impl Session {
             current_task_id: task_name.to_string(),
             status_message: None,
             context: Context::new(),
+            write_sets: Vec::new(),
+            parallel: None,
+            timer: None,
+            pending_input: None,
+            breakpoints: Vec::new(),
+            interrupted: None,
+            compensable_steps: Vec::new(),
+            version: 0,
+        }
+    }
+
+    /// Set the id of the graph this session runs on.
+    pub fn with_graph_id(mut self, graph_id: impl Into<String>) -> Self {
+        self.graph_id = graph_id.into();
+        self
+    }
+
+    /// Start the session with `context` instead of an empty one.
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Stop this session before `task_id` runs.
+    pub fn interrupt_before(&mut self, task_id: impl Into<String>) {
+        self.add_breakpoint(Breakpoint::before(task_id));
+    }
+
+    /// Stop this session after `task_id` has run.
+    pub fn interrupt_after(&mut self, task_id: impl Into<String>) {
+        self.add_breakpoint(Breakpoint::after(task_id));
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
         }
     }
+
+    /// Remove the session's own breakpoints; the graph's still apply.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Mark the session as waiting for input to its current task, e.g. for a
+    /// workflow whose first task needs the user's opening message.
+    pub fn wait_for_input(&mut self) {
+        self.pending_input = Some(PendingInput {
+            task_id: self.current_task_id.clone(),
+            since: Utc::now(),
+            request: None,
+            branch_id: None,
+        });
+    }
+
+    /// Append the write set of an executed step, keeping at most 1000 entries.
+    pub fn record_write_set(&mut self, write_set: StepWriteSet) {
+        self.write_sets.push(write_set);
+        if self.write_sets.len() > MAX_WRITE_SETS {
+            let excess = self.write_sets.len() - MAX_WRITE_SETS;
+            self.write_sets.drain(0..excess);
+        }
+    }
+
+    /// ID of the task that most recently set or removed `key`, if it is still recorded.
+    pub fn last_writer(&self, key: &str) -> Option<&str> {
+        self.write_sets
+            .iter()
+            .rev()
+            .find(|ws| ws.set.iter().chain(ws.removed.iter()).any(|k| k == key))
+            .map(|ws| ws.task_id.as_str())
+    }
 }
 
 /// Trait for storing and retrieving graphs
@@ -39,9 +291,38 @@ pub trait GraphStorage: Send + Sync {
 /// Trait for storing and retrieving sessions
 #[async_trait]
 pub trait SessionStorage: Send + Sync {
+    /// Store `session`, unless the stored copy was saved again after
+    /// `session` was loaded from it ([`Session::version`] differs), in which
+    /// case this fails with [`GraphError::SessionConflict`].
+    ///
+    /// This is a compare-and-swap: implementations must store the session
+    /// only if the stored version equals `session.version` (or nothing is
+    /// stored yet), and store it with the version incremented. Callers must
+    /// reload a session before saving it again, since a copy that has been
+    /// saved once is stale from then on.
     async fn save(&self, session: Session) -> Result<()>;
     async fn get(&self, id: &str) -> Result<Option<Session>>;
     async fn delete(&self, id: &str) -> Result<()>;
+
+    /// Claim up to `limit` sessions of `graph_id` whose timer is due at `now`,
+    /// returning their ids.
+    ///
+    /// A claimed session is not returned again until `now + lease` has passed or
+    /// it has been saved, so several schedulers can poll the same storage.
+    ///
+    /// Needed only by [`Scheduler`](crate::Scheduler); storages that do not
+    /// override it fail with [`GraphError::StorageError`].
+    async fn claim_due_sessions(
+        &self,
+        _graph_id: &str,
+        _now: DateTime<Utc>,
+        _lease: Duration,
+        _limit: usize,
+    ) -> Result<Vec<String>> {
+        Err(GraphError::StorageError(
+            "claim_due_sessions not supported".to_string(),
+        ))
+    }
 }
 
 /// In-memory implementation of GraphStorage
@@ -83,6 +364,8 @@ impl GraphStorage for InMemoryGraphStorage {
 /// In-memory implementation of SessionStorage
 pub struct InMemorySessionStorage {
     sessions: Arc<DashMap<String, Session>>,
+    /// Claims handed out by `claim_due_sessions`, until when they hold
+    leases: Arc<DashMap<String, DateTime<Utc>>>,
 }
 
 impl Default for InMemorySessionStorage {
@@ -95,14 +378,29 @@ impl InMemorySessionStorage {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(DashMap::new()),
+            leases: Arc::new(DashMap::new()),
         }
     }
 }
 
 #[async_trait]
 impl SessionStorage for InMemorySessionStorage {
-    async fn save(&self, session: Session) -> Result<()> {
-        self.sessions.insert(session.id.clone(), session);
+    async fn save(&self, mut session: Session) -> Result<()> {
+        let id = session.id.clone();
+        match self.sessions.entry(id.clone()) {
+            Entry::Occupied(mut stored) => {
+                if stored.get().version != session.version {
+                    return Err(GraphError::SessionConflict(id));
+                }
+                session.version += 1;
+                stored.insert(session);
+            }
+            Entry::Vacant(slot) => {
+                session.version += 1;
+                slot.insert(session);
+            }
+        }
+        self.leases.remove(&id);
         Ok(())
     }
 
@@ -111,7 +409,38 @@ impl SessionStorage for InMemorySessionStorage {
     }
 
     async fn delete(&self, id: &str) -> Result<()> {
+        self.leases.remove(id);
         self.sessions.remove(id);
         Ok(())
     }
+
+    async fn claim_due_sessions(
+        &self,
+        graph_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let mut due: Vec<(DateTime<Utc>, String)> = self
+            .sessions
+            .iter()
+            .filter(|entry| entry.graph_id == graph_id)
+            .filter_map(|entry| {
+                let wake_at = entry.timer.as_ref()?.wake_at;
+                let leased = self
+                    .leases
+                    .get(entry.key())
+                    .is_some_and(|until| *until > now);
+                (wake_at <= now && !leased).then(|| (wake_at, entry.key().clone()))
+            })
+            .collect();
+        due.sort();
+        due.truncate(limit);
+
+        let lease_until = after(now, lease);
+        for (_, id) in &due {
+            self.leases.insert(id.clone(), lease_until);
+        }
+        Ok(due.into_iter().map(|(_, id)| id).collect())
+    }
 }
@@ -1,12 +1,25 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde_json;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{Session, error::{Result, GraphError}, storage::SessionStorage};
+use uuid::Uuid;
+
+use crate::{
+    Session,
+    blob_store::BlobStore,
+    encryption::ContextCipher,
+    error::{GraphError, Result},
+    graph::ExecutionResult,
+    queue::{Job, JobQueue, JobStatus, LEASE_EXPIRED_ERROR},
+    storage::SessionStorage,
+};
 
 pub struct PostgresSessionStorage {
     pool: Arc<Pool<Postgres>>,
+    cipher: Option<ContextCipher>,
 }
 
 impl PostgresSessionStorage {
@@ -18,51 +31,266 @@ impl PostgresSessionStorage {
             .map_err(|e| GraphError::StorageError(format!("Failed to connect to Postgres: {e}")))?;
 
         Self::migrate(&pool).await?;
-        Ok(Self { pool: Arc::new(pool) })
+        Ok(Self {
+            pool: Arc::new(pool),
+            cipher: None,
+        })
+    }
+
+    /// The connection pool, for sharing with a [`PostgresBlobStore`] or
+    /// [`PostgresJobQueue`] on the same database.
+    pub fn pool(&self) -> Arc<Pool<Postgres>> {
+        self.pool.clone()
+    }
+
+    /// Encrypt session contexts at rest with `cipher`.
+    ///
+    /// Existing plaintext sessions remain readable and are encrypted when next saved.
+    pub fn with_encryption(mut self, cipher: ContextCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Re-encrypt every stored context that is not sealed with the current key,
+    /// returning the number of sessions updated.
+    ///
+    /// Run after making a new key current; retired keys can be dropped from the
+    /// key provider once this completes.
+    pub async fn rotate_keys(&self) -> Result<usize> {
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            GraphError::StorageError("Session encryption is not configured".to_string())
+        })?;
+
+        let ids = sqlx::query_as::<_, (String,)>("SELECT id::text FROM sessions")
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to list sessions: {e}")))?;
+
+        let mut rotated = 0;
+        for (id,) in ids {
+            let mut tx = self.pool.begin().await.map_err(|e| {
+                GraphError::StorageError(format!("Failed to start transaction: {e}"))
+            })?;
+
+            // Lock the row so a concurrent save is not overwritten with stale data
+            let row = sqlx::query_as::<_, (serde_json::Value,)>(
+                "SELECT context FROM sessions WHERE id = $1::uuid FOR UPDATE",
+            )
+            .bind(&id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to fetch session: {e}")))?;
+
+            let Some((stored,)) = row else { continue };
+            if !cipher.needs_rotation(&stored).await? {
+                continue;
+            }
+
+            let context = cipher.decrypt_context(&id, stored).await?;
+            let resealed = cipher.encrypt_context(&id, &context).await?;
+            sqlx::query("UPDATE sessions SET context = $2 WHERE id = $1::uuid")
+                .bind(&id)
+                .bind(&resealed)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| GraphError::StorageError(format!("Failed to save session: {e}")))?;
+
+            tx.commit().await.map_err(|e| {
+                GraphError::StorageError(format!("Failed to commit transaction: {e}"))
+            })?;
+            rotated += 1;
+        }
+        Ok(rotated)
     }
 
     async fn migrate(pool: &Pool<Postgres>) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS sessions (
-                id UUID PRIMARY KEY,
-                graph_id TEXT NOT NULL,
-                current_task_id TEXT NOT NULL,
-                status_message TEXT,
-                context JSONB NOT NULL,
-                created_at TIMESTAMPTZ DEFAULT NOW(),
-                updated_at TIMESTAMPTZ DEFAULT NOW()
-            );
-            "#,
+        run_migrations(pool, "sessions", SESSION_MIGRATIONS).await
+    }
+}
+
+/// Session id as Postgres prints it back, which encrypted contexts are bound to.
+fn canonical_id(id: &str) -> String {
+    Uuid::parse_str(id).map_or_else(|_| id.to_string(), |uuid| uuid.to_string())
+}
+
+/// Schema of the `sessions` table; entry `n` brings it to version `n + 1`.
+///
+/// Statements are idempotent, so databases created before migrations were
+/// versioned are brought up to date as well.
+const SESSION_MIGRATIONS: &[&[&str]] = &[
+    &[r#"
+    CREATE TABLE IF NOT EXISTS sessions (
+        id UUID PRIMARY KEY,
+        graph_id TEXT NOT NULL,
+        current_task_id TEXT NOT NULL,
+        status_message TEXT,
+        context JSONB NOT NULL,
+        created_at TIMESTAMPTZ DEFAULT NOW(),
+        updated_at TIMESTAMPTZ DEFAULT NOW()
+    )
+    "#],
+    // Execution state: write sets, branches, timers, input requests,
+    // breakpoints and compensation. `wake_at` mirrors the timer for polling;
+    // schedulers move it forward to claim a session
+    &[
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS write_sets JSONB NOT NULL DEFAULT '[]'::jsonb",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS parallel JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS timer JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS pending_input JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS breakpoints JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS interrupted JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS compensable_steps JSONB",
+        "ALTER TABLE sessions ADD COLUMN IF NOT EXISTS wake_at TIMESTAMPTZ",
+        "CREATE INDEX IF NOT EXISTS sessions_wake_at_idx ON sessions (graph_id, wake_at) WHERE wake_at IS NOT NULL",
+    ],
+    // Optimistic concurrency: a save must name the version it loaded
+    &["ALTER TABLE sessions ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 0"],
+];
+
+/// Apply the `migrations` of `component` that the database has not seen yet.
+///
+/// Applied versions are recorded in `graph_flow_migrations`; an advisory lock
+/// keeps concurrently starting processes from migrating twice.
+async fn run_migrations(
+    pool: &Pool<Postgres>,
+    component: &str,
+    migrations: &[&[&str]],
+) -> Result<()> {
+    let failed = |e: sqlx::Error| GraphError::StorageError(format!("Migration failed: {e}"));
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS graph_flow_migrations (
+            component TEXT NOT NULL,
+            version INT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (component, version)
         )
-        .execute(pool)
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(failed)?;
+
+    let mut tx = pool.begin().await.map_err(failed)?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('graph_flow_migrations'))")
+        .execute(&mut *tx)
         .await
-        .map_err(|e| GraphError::StorageError(format!("Migration failed: {e}")))?;
-        Ok(())
+        .map_err(failed)?;
+    let (current,) = sqlx::query_as::<_, (i32,)>(
+        "SELECT COALESCE(MAX(version), 0) FROM graph_flow_migrations WHERE component = $1",
+    )
+    .bind(component)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(failed)?;
+
+    for (index, statements) in migrations.iter().enumerate() {
+        let version = index as i32 + 1;
+        if version <= current {
+            continue;
+        }
+        for statement in *statements {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(failed)?;
+        }
+        sqlx::query("INSERT INTO graph_flow_migrations (component, version) VALUES ($1, $2)")
+            .bind(component)
+            .bind(version)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        tracing::info!(component, version, "Applied schema migration");
     }
+    tx.commit().await.map_err(failed)
 }
 
 #[async_trait]
 impl SessionStorage for PostgresSessionStorage {
     async fn save(&self, session: Session) -> Result<()> {
-        let context_json = serde_json::to_value(&session.context)
-            .map_err(|e| GraphError::StorageError(format!("Context serialization failed: {e}")))?;
+        let context_json = match &self.cipher {
+            Some(cipher) => {
+                cipher
+                    .encrypt_context(&canonical_id(&session.id), &session.context)
+                    .await?
+            }
+            None => serde_json::to_value(&session.context).map_err(|e| {
+                GraphError::StorageError(format!("Context serialization failed: {e}"))
+            })?,
+        };
+        let write_sets_json = serde_json::to_value(&session.write_sets).map_err(|e| {
+            GraphError::StorageError(format!("Write set serialization failed: {e}"))
+        })?;
+        let parallel_json = session
+            .parallel
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| {
+                GraphError::StorageError(format!("Branch state serialization failed: {e}"))
+            })?;
+        let timer_json = session
+            .timer
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| GraphError::StorageError(format!("Timer serialization failed: {e}")))?;
+        let wake_at = session
+            .timer
+            .as_ref()
+            .map(|timer| timer.wake_at.to_rfc3339());
+        let pending_input_json = session
+            .pending_input
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| {
+                GraphError::StorageError(format!("Pending input serialization failed: {e}"))
+            })?;
+        let breakpoints_json = serde_json::to_value(&session.breakpoints).map_err(|e| {
+            GraphError::StorageError(format!("Breakpoint serialization failed: {e}"))
+        })?;
+        let interrupted_json = session
+            .interrupted
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| {
+                GraphError::StorageError(format!("Breakpoint serialization failed: {e}"))
+            })?;
+        let compensable_steps_json =
+            serde_json::to_value(&session.compensable_steps).map_err(|e| {
+                GraphError::StorageError(format!("Compensable step serialization failed: {e}"))
+            })?;
 
         // Use a transaction to ensure atomicity
-        let mut tx = self.pool.begin().await
-            .map_err(|e| GraphError::StorageError(format!("Failed to start transaction: {e}")))?;
+        let mut tx =
+            self.pool.begin().await.map_err(|e| {
+                GraphError::StorageError(format!("Failed to start transaction: {e}"))
+            })?;
 
-        sqlx::query(
+        let saved = sqlx::query(
             r#"
-            INSERT INTO sessions (id, graph_id, current_task_id, status_message, context, updated_at)
-            VALUES ($1::uuid, $2, $3, $4, $5, NOW())
+            INSERT INTO sessions (id, graph_id, current_task_id, status_message, context, write_sets, parallel, timer, wake_at, pending_input, breakpoints, interrupted, compensable_steps, version, updated_at)
+            VALUES ($1::uuid, $2, $3, $4, $5, $6, $7, $8, $9::timestamptz, $10, $11, $12, $13, $14 + 1, NOW())
             ON CONFLICT (id) DO UPDATE
             SET graph_id = EXCLUDED.graph_id,
                 current_task_id = EXCLUDED.current_task_id,
                 status_message = EXCLUDED.status_message,
                 context = EXCLUDED.context,
+                write_sets = EXCLUDED.write_sets,
+                parallel = EXCLUDED.parallel,
+                timer = EXCLUDED.timer,
+                wake_at = EXCLUDED.wake_at,
+                pending_input = EXCLUDED.pending_input,
+                breakpoints = EXCLUDED.breakpoints,
+                interrupted = EXCLUDED.interrupted,
+                compensable_steps = EXCLUDED.compensable_steps,
+                version = sessions.version + 1,
                 updated_at = NOW()
-            WHERE sessions.updated_at <= EXCLUDED.updated_at  -- Prevent overwriting newer data
+            WHERE sessions.version = $14  -- Reject saves of a stale copy
             "#,
         )
         .bind(&session.id)
@@ -70,20 +298,33 @@ impl SessionStorage for PostgresSessionStorage {
         .bind(&session.current_task_id)
         .bind(&session.status_message)
         .bind(&context_json)
+        .bind(&write_sets_json)
+        .bind(&parallel_json)
+        .bind(&timer_json)
+        .bind(&wake_at)
+        .bind(&pending_input_json)
+        .bind(&breakpoints_json)
+        .bind(&interrupted_json)
+        .bind(&compensable_steps_json)
+        .bind(session.version as i64)
         .execute(&mut *tx)
         .await
         .map_err(|e| GraphError::StorageError(format!("Failed to save session: {e}")))?;
-        
-        tx.commit().await
+        if saved.rows_affected() == 0 {
+            return Err(GraphError::SessionConflict(session.id));
+        }
+
+        tx.commit()
+            .await
             .map_err(|e| GraphError::StorageError(format!("Failed to commit transaction: {e}")))?;
-        
+
         Ok(())
     }
 
     async fn get(&self, id: &str) -> Result<Option<Session>> {
-        let row = sqlx::query_as::<_, (String, String, String, Option<String>, serde_json::Value)>(
+        let row = sqlx::query_as::<_, (String, String, String, Option<String>, serde_json::Value, serde_json::Value, Option<serde_json::Value>, Option<serde_json::Value>, Option<serde_json::Value>, Option<serde_json::Value>, Option<serde_json::Value>, Option<serde_json::Value>, i64)>(
             r#"
-            SELECT id::text, graph_id, current_task_id, status_message, context
+            SELECT id::text, graph_id, current_task_id, status_message, context, write_sets, parallel, timer, pending_input, breakpoints, interrupted, compensable_steps, version
             FROM sessions
             WHERE id = $1::uuid
             "#,
@@ -93,15 +334,85 @@ impl SessionStorage for PostgresSessionStorage {
         .await
         .map_err(|e| GraphError::StorageError(format!("Failed to fetch session: {e}")))?;
 
-        if let Some((session_id, graph_id, current_task_id, status_message, context_json)) = row {
-            let context: crate::Context = serde_json::from_value(context_json)
-                .map_err(|e| GraphError::StorageError(format!("Context deserialization failed: {e}")))?;
+        if let Some((
+            session_id,
+            graph_id,
+            current_task_id,
+            status_message,
+            context_json,
+            write_sets_json,
+            parallel_json,
+            timer_json,
+            pending_input_json,
+            breakpoints_json,
+            interrupted_json,
+            compensable_steps_json,
+            version,
+        )) = row
+        {
+            let context: crate::Context = match &self.cipher {
+                Some(cipher) => cipher.decrypt_context(&session_id, context_json).await?,
+                None => serde_json::from_value(context_json).map_err(|e| {
+                    GraphError::StorageError(format!("Context deserialization failed: {e}"))
+                })?,
+            };
+            let write_sets = serde_json::from_value(write_sets_json).map_err(|e| {
+                GraphError::StorageError(format!("Write set deserialization failed: {e}"))
+            })?;
+            let parallel = parallel_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Branch state deserialization failed: {e}"))
+                })?;
+            let timer = timer_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Timer deserialization failed: {e}"))
+                })?;
+            let pending_input = pending_input_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Pending input deserialization failed: {e}"))
+                })?;
+            let breakpoints = breakpoints_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Breakpoint deserialization failed: {e}"))
+                })?
+                .unwrap_or_default();
+            let interrupted = interrupted_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Breakpoint deserialization failed: {e}"))
+                })?;
+            let compensable_steps = compensable_steps_json
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!(
+                        "Compensable step deserialization failed: {e}"
+                    ))
+                })?
+                .unwrap_or_default();
             Ok(Some(Session {
                 id: session_id,
                 graph_id,
                 current_task_id,
                 status_message,
                 context,
+                write_sets,
+                parallel,
+                timer,
+                pending_input,
+                breakpoints,
+                interrupted,
+                compensable_steps,
+                version: version as u64,
             }))
         } else {
             Ok(None)
@@ -120,4 +431,435 @@ impl SessionStorage for PostgresSessionStorage {
         .map_err(|e| GraphError::StorageError(format!("Failed to delete session: {e}")))?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn claim_due_sessions(
+        &self,
+        graph_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let lease_until = crate::storage::after(now, lease);
+        let rows = sqlx::query_as::<_, (String,)>(
+            r#"
+            UPDATE sessions SET wake_at = $3::timestamptz
+            WHERE id IN (
+                SELECT id FROM sessions
+                WHERE graph_id = $1 AND wake_at <= $2::timestamptz
+                ORDER BY wake_at
+                LIMIT $4
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id::text
+            "#,
+        )
+        .bind(graph_id)
+        .bind(now.to_rfc3339())
+        .bind(lease_until.to_rfc3339())
+        .bind(limit as i64)
+        .fetch_all(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to claim due sessions: {e}")))?;
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+/// PostgreSQL implementation of BlobStore backed by large objects.
+///
+/// Blob ids are the large object OIDs.
+pub struct PostgresBlobStore {
+    pool: Arc<Pool<Postgres>>,
+}
+
+impl PostgresBlobStore {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to connect to Postgres: {e}")))?;
+        Ok(Self::from_pool(Arc::new(pool)))
+    }
+
+    /// Use an existing connection pool, e.g. [`PostgresSessionStorage::pool`].
+    pub fn from_pool(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    fn oid(id: &str) -> Result<i64> {
+        id.parse()
+            .map_err(|_| GraphError::StorageError(format!("Invalid blob id: {id}")))
+    }
+}
+
+#[async_trait]
+impl BlobStore for PostgresBlobStore {
+    async fn put(&self, data: Vec<u8>) -> Result<String> {
+        let (oid,) = sqlx::query_as::<_, (i64,)>("SELECT lo_from_bytea(0, $1)::bigint")
+            .bind(data)
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to store blob: {e}")))?;
+        Ok(oid.to_string())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query_as::<_, (Vec<u8>,)>(
+            r#"
+            SELECT lo_get(oid) FROM pg_largeobject_metadata WHERE oid = $1::bigint::oid
+            "#,
+        )
+        .bind(Self::oid(id)?)
+        .fetch_optional(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to fetch blob: {e}")))?;
+        Ok(row.map(|(data,)| data))
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            SELECT lo_unlink(oid) FROM pg_largeobject_metadata WHERE oid = $1::bigint::oid
+            "#,
+        )
+        .bind(Self::oid(id)?)
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to delete blob: {e}")))?;
+        Ok(())
+    }
+}
+
+/// Schema of the `session_jobs` table, see [`SESSION_MIGRATIONS`].
+///
+/// The partial unique index keeps one pending job per session.
+const JOB_MIGRATIONS: &[&[&str]] = &[
+    &[
+        r#"
+        CREATE TABLE IF NOT EXISTS session_jobs (
+            id UUID PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            max_attempts INT NOT NULL,
+            worker_id TEXT,
+            lease_until TIMESTAMPTZ,
+            available_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            last_error TEXT,
+            result JSONB,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+        "CREATE UNIQUE INDEX IF NOT EXISTS session_jobs_pending_idx ON session_jobs (session_id) WHERE status IN ('queued', 'running')",
+        "CREATE INDEX IF NOT EXISTS session_jobs_available_idx ON session_jobs (status, available_at)",
+    ],
+    // Workers only claim jobs of their own graph; jobs queued before this
+    // take the graph of their session
+    &[
+        "ALTER TABLE session_jobs ADD COLUMN IF NOT EXISTS graph_id TEXT NOT NULL DEFAULT ''",
+        r#"
+        DO $$
+        BEGIN
+            IF to_regclass('sessions') IS NOT NULL THEN
+                UPDATE session_jobs SET graph_id = sessions.graph_id
+                FROM sessions
+                WHERE session_jobs.graph_id = '' AND sessions.id::text = session_jobs.session_id;
+            END IF;
+        END $$
+        "#,
+        "DROP INDEX IF EXISTS session_jobs_available_idx",
+        "CREATE INDEX IF NOT EXISTS session_jobs_graph_available_idx ON session_jobs (graph_id, status, available_at)",
+    ],
+];
+
+/// PostgreSQL implementation of [`JobQueue`].
+///
+/// Jobs are claimed with `FOR UPDATE SKIP LOCKED`, so any number of worker
+/// processes can share the table.
+pub struct PostgresJobQueue {
+    pool: Arc<Pool<Postgres>>,
+}
+
+/// Columns selected for a [`Job`]; timestamps as microseconds since the epoch
+const JOB_COLUMNS: &str = r#"
+    id::text, graph_id, session_id, status, attempts, max_attempts, worker_id,
+    (EXTRACT(EPOCH FROM lease_until) * 1000000)::bigint,
+    (EXTRACT(EPOCH FROM available_at) * 1000000)::bigint,
+    last_error, result,
+    (EXTRACT(EPOCH FROM created_at) * 1000000)::bigint,
+    (EXTRACT(EPOCH FROM updated_at) * 1000000)::bigint
+"#;
+
+type JobRow = (
+    String,
+    String,
+    String,
+    String,
+    i32,
+    i32,
+    Option<String>,
+    Option<i64>,
+    i64,
+    Option<String>,
+    Option<serde_json::Value>,
+    i64,
+    i64,
+);
+
+impl PostgresJobQueue {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to connect to Postgres: {e}")))?;
+
+        Self::from_pool(Arc::new(pool)).await
+    }
+
+    /// Use an existing connection pool, e.g. [`PostgresSessionStorage::pool`].
+    pub async fn from_pool(pool: Arc<Pool<Postgres>>) -> Result<Self> {
+        Self::migrate(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    async fn migrate(pool: &Pool<Postgres>) -> Result<()> {
+        run_migrations(pool, "session_jobs", JOB_MIGRATIONS).await
+    }
+
+    fn job_from_row(row: JobRow) -> Result<Job> {
+        let (
+            id,
+            graph_id,
+            session_id,
+            status,
+            attempts,
+            max_attempts,
+            worker_id,
+            lease_until,
+            available_at,
+            last_error,
+            result,
+            created_at,
+            updated_at,
+        ) = row;
+        let timestamp = |micros: i64| {
+            DateTime::from_timestamp_micros(micros)
+                .ok_or_else(|| GraphError::StorageError(format!("Invalid job timestamp: {micros}")))
+        };
+        Ok(Job {
+            status: JobStatus::parse(&status)
+                .ok_or_else(|| GraphError::StorageError(format!("Invalid job status: {status}")))?,
+            id,
+            graph_id,
+            session_id,
+            attempts: attempts.max(0) as u32,
+            max_attempts: max_attempts.max(0) as u32,
+            worker_id,
+            lease_until: lease_until.map(timestamp).transpose()?,
+            available_at: timestamp(available_at)?,
+            last_error,
+            result: result
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    GraphError::StorageError(format!("Failed to deserialize job result: {e}"))
+                })?,
+            created_at: timestamp(created_at)?,
+            updated_at: timestamp(updated_at)?,
+        })
+    }
+
+    /// Ids that are not UUIDs cannot name a job
+    fn job_uuid(id: &str) -> Option<Uuid> {
+        Uuid::parse_str(id).ok()
+    }
+}
+
+#[async_trait]
+impl JobQueue for PostgresJobQueue {
+    async fn enqueue(&self, graph_id: &str, session_id: &str, max_attempts: u32) -> Result<Job> {
+        // A pending job can finish between the insert and the select; try again then
+        for _ in 0..3 {
+            sqlx::query(
+                r#"
+                INSERT INTO session_jobs (id, graph_id, session_id, status, max_attempts)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (session_id) WHERE status IN ('queued', 'running') DO NOTHING
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(graph_id)
+            .bind(session_id)
+            .bind(JobStatus::Queued.as_str())
+            .bind(max_attempts.max(1) as i32)
+            .execute(&*self.pool)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to enqueue job: {e}")))?;
+
+            let row = sqlx::query_as::<_, JobRow>(&format!(
+                "SELECT {JOB_COLUMNS} FROM session_jobs WHERE session_id = $1 AND status IN ('queued', 'running')"
+            ))
+            .bind(session_id)
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(|e| GraphError::StorageError(format!("Failed to fetch job: {e}")))?;
+            if let Some(row) = row {
+                return Self::job_from_row(row);
+            }
+        }
+        Err(GraphError::StorageError(format!(
+            "Failed to enqueue job for session {session_id}"
+        )))
+    }
+
+    async fn claim(
+        &self,
+        graph_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<Job>> {
+        sqlx::query(
+            r#"
+            UPDATE session_jobs
+            SET status = 'failed', last_error = $2, worker_id = NULL, lease_until = NULL,
+                updated_at = $1::timestamptz
+            WHERE graph_id = $3 AND status = 'running' AND lease_until <= $1::timestamptz
+              AND attempts >= max_attempts
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .bind(LEASE_EXPIRED_ERROR)
+        .bind(graph_id)
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to expire jobs: {e}")))?;
+
+        let row = sqlx::query_as::<_, JobRow>(&format!(
+            r#"
+            UPDATE session_jobs
+            SET last_error = CASE WHEN status = 'running' THEN $4 ELSE last_error END,
+                status = 'running', attempts = attempts + 1, worker_id = $2,
+                lease_until = $3::timestamptz, updated_at = $1::timestamptz
+            WHERE id = (
+                SELECT id FROM session_jobs
+                WHERE graph_id = $5
+                  AND ((status = 'queued' AND available_at <= $1::timestamptz)
+                    OR (status = 'running' AND lease_until <= $1::timestamptz))
+                ORDER BY available_at, created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {JOB_COLUMNS}
+            "#
+        ))
+        .bind(now.to_rfc3339())
+        .bind(worker_id)
+        .bind(crate::storage::after(now, lease).to_rfc3339())
+        .bind(LEASE_EXPIRED_ERROR)
+        .bind(graph_id)
+        .fetch_optional(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to claim job: {e}")))?;
+        row.map(Self::job_from_row).transpose()
+    }
+
+    async fn heartbeat(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool> {
+        let Some(id) = Self::job_uuid(job_id) else {
+            return Ok(false);
+        };
+        let updated = sqlx::query(
+            r#"
+            UPDATE session_jobs SET lease_until = $3::timestamptz, updated_at = $4::timestamptz
+            WHERE id = $1 AND worker_id = $2 AND status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(crate::storage::after(now, lease).to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to renew job lease: {e}")))?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn complete(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        result: ExecutionResult,
+    ) -> Result<bool> {
+        let Some(id) = Self::job_uuid(job_id) else {
+            return Ok(false);
+        };
+        let result = serde_json::to_value(&result).map_err(|e| {
+            GraphError::StorageError(format!("Failed to serialize job result: {e}"))
+        })?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE session_jobs
+            SET status = 'succeeded', result = $3, worker_id = NULL, lease_until = NULL,
+                updated_at = NOW()
+            WHERE id = $1 AND worker_id = $2 AND status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(result)
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to complete job: {e}")))?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn fail(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        error: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let Some(id) = Self::job_uuid(job_id) else {
+            return Ok(false);
+        };
+        let updated = sqlx::query(
+            r#"
+            UPDATE session_jobs
+            SET status = CASE WHEN attempts < max_attempts THEN 'queued' ELSE 'failed' END,
+                last_error = $3, available_at = $4::timestamptz, worker_id = NULL,
+                lease_until = NULL, updated_at = NOW()
+            WHERE id = $1 AND worker_id = $2 AND status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(error)
+        .bind(retry_at.to_rfc3339())
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to record job failure: {e}")))?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn get(&self, job_id: &str) -> Result<Option<Job>> {
+        let Some(id) = Self::job_uuid(job_id) else {
+            return Ok(None);
+        };
+        let row = sqlx::query_as::<_, JobRow>(&format!(
+            "SELECT {JOB_COLUMNS} FROM session_jobs WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&*self.pool)
+        .await
+        .map_err(|e| GraphError::StorageError(format!("Failed to fetch job: {e}")))?;
+        row.map(Self::job_from_row).transpose()
+    }
+}
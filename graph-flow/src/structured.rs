@@ -0,0 +1,420 @@
+//! Typed, validated output from LLM replies.
+//!
+//! [`StructuredOutput`] describes the reply it expects with the JSON schema of
+//! a serde type, checks replies against that schema and by deserializing them
+//! (plus an optional custom validator), and with the `rig` feature drives a repair loop: an invalid
+//! reply is sent back to the model together with the validation error, up to a
+//! configurable number of times.
+//!
+//! A model that can't answer yet may reply with a plain-text question instead
+//! of JSON. That comes back as [`Extraction::Clarification`] so the task can
+//! wait for the user's answer rather than fail.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{Extraction, StructuredOutput};
+//! use schemars::JsonSchema;
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+//! #[serde(rename_all = "lowercase")]
+//! enum InsuranceType {
+//!     Car,
+//!     Apartment,
+//! }
+//!
+//! #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+//! struct Classification {
+//!     insurance_type: InsuranceType,
+//! }
+//!
+//! let output = StructuredOutput::<Classification>::new();
+//! assert!(output.instructions().contains("insurance_type"));
+//!
+//! let reply = "```json\n{\"insurance_type\": \"car\"}\n```";
+//! assert_eq!(
+//!     output.parse(reply).unwrap(),
+//!     Extraction::Value(Classification { insurance_type: InsuranceType::Car })
+//! );
+//!
+//! let question = "Is the damage to your car or your apartment?";
+//! assert!(matches!(output.parse(question), Ok(Extraction::Clarification(_))));
+//!
+//! // Values outside the schema are rejected with a message for the model
+//! assert!(output.parse(r#"{"insurance_type": "boat"}"#).is_err());
+//! ```
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(feature = "rig")]
+use crate::error::{GraphError, Result};
+use crate::input::schema_errors;
+
+/// Extra check applied after a reply deserializes; `Err` explains the problem to the model.
+pub type OutputValidator<T> = Arc<dyn Fn(&T) -> std::result::Result<(), String> + Send + Sync>;
+
+/// Outcome of a structured-output request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Extraction<T> {
+    /// The model returned a valid value
+    Value(T),
+    /// The model asked a question instead of answering
+    Clarification(String),
+}
+
+impl<T> Extraction<T> {
+    pub fn value(self) -> Option<T> {
+        match self {
+            Extraction::Value(value) => Some(value),
+            Extraction::Clarification(_) => None,
+        }
+    }
+}
+
+/// Expected shape of an LLM reply, described by the JSON schema of `T`.
+pub struct StructuredOutput<T> {
+    max_repairs: usize,
+    allow_clarification: bool,
+    validator: Option<OutputValidator<T>>,
+    _output: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for StructuredOutput<T> {
+    fn clone(&self) -> Self {
+        Self {
+            max_repairs: self.max_repairs,
+            allow_clarification: self.allow_clarification,
+            validator: self.validator.clone(),
+            _output: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned + JsonSchema> Default for StructuredOutput<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned + JsonSchema> StructuredOutput<T> {
+    /// Two repair attempts, clarifying questions allowed.
+    pub fn new() -> Self {
+        Self {
+            max_repairs: 2,
+            allow_clarification: true,
+            validator: None,
+            _output: PhantomData,
+        }
+    }
+
+    /// How many times an invalid reply is sent back to the model for correction.
+    pub fn with_max_repairs(mut self, max_repairs: usize) -> Self {
+        self.max_repairs = max_repairs;
+        self
+    }
+
+    /// Whether a plain-text reply counts as a clarifying question (default `true`).
+    /// When disabled it is treated as invalid output and repaired.
+    pub fn with_clarifications(mut self, allow: bool) -> Self {
+        self.allow_clarification = allow;
+        self
+    }
+
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&T) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// JSON schema of `T`.
+    pub fn schema(&self) -> Value {
+        schemars::schema_for!(T).to_value()
+    }
+
+    /// Instructions to append to a prompt so the model answers in the expected format.
+    pub fn instructions(&self) -> String {
+        let schema = serde_json::to_string_pretty(&self.schema()).unwrap_or_default();
+        let mut instructions = format!(
+            "Respond with only a JSON value that matches this JSON schema, with no other text:\n{schema}"
+        );
+        if self.allow_clarification {
+            instructions.push_str(
+                "\nIf you need more information before you can answer, reply with a single \
+                 clarifying question in plain text instead of JSON.",
+            );
+        }
+        instructions
+    }
+
+    /// Check a reply. `Err` holds a validation message suitable for sending back to the model.
+    pub fn parse(&self, reply: &str) -> std::result::Result<Extraction<T>, String> {
+        let Some(json) = extract_json(reply) else {
+            let text = reply.trim();
+            if self.allow_clarification && !text.is_empty() && !looks_like_json(text) {
+                return Ok(Extraction::Clarification(text.to_string()));
+            }
+            return Err("The reply is not valid JSON.".to_string());
+        };
+
+        // The schema can be stricter than serde (formats, ranges, lengths)
+        let errors = schema_errors(&self.schema(), &json)?;
+        if !errors.is_empty() {
+            return Err(format!(
+                "The JSON does not match the schema: {}",
+                errors.join("; ")
+            ));
+        }
+        let value: T = serde_json::from_value(json)
+            .map_err(|e| format!("The JSON does not match the schema: {e}"))?;
+        if let Some(validator) = &self.validator {
+            validator(&value)?;
+        }
+        Ok(Extraction::Value(value))
+    }
+
+    /// Prompt `agent` until it returns a valid `T` or a clarifying question.
+    ///
+    /// `prompt` is sent with [`instructions`](Self::instructions) appended and
+    /// `history` as the preceding conversation. Failed attempts and their
+    /// validation errors are added to the conversation for the next attempt,
+    /// but not to `history` itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::TaskExecutionFailed`] if the agent call fails or no
+    /// valid reply is produced within the repair budget.
+    #[cfg(feature = "rig")]
+    pub async fn extract<A: rig::completion::Chat>(
+        &self,
+        agent: &A,
+        prompt: impl Into<String>,
+        history: Vec<rig::completion::Message>,
+    ) -> Result<Extraction<T>> {
+        use rig::completion::Message;
+
+        let mut conversation = history;
+        let mut prompt = format!("{}\n\n{}", prompt.into(), self.instructions());
+        let mut attempt = 0;
+        loop {
+            let reply = agent
+                .chat(prompt.clone(), conversation.clone())
+                .await
+                .map_err(|e| GraphError::TaskExecutionFailed(format!("Agent call failed: {e}")))?;
+
+            let error = match self.parse(&reply) {
+                Ok(extraction) => return Ok(extraction),
+                Err(error) => error,
+            };
+            if attempt == self.max_repairs {
+                return Err(GraphError::TaskExecutionFailed(format!(
+                    "No valid structured output after {} attempts: {error}",
+                    attempt + 1
+                )));
+            }
+
+            tracing::debug!(attempt, %error, "Structured output invalid, asking model to repair");
+            conversation.push(Message::user(prompt));
+            conversation.push(Message::assistant(reply));
+            prompt = format!(
+                "{error}\nReply again with only the corrected JSON, following the schema given earlier."
+            );
+            attempt += 1;
+        }
+    }
+}
+
+fn looks_like_json(text: &str) -> bool {
+    text.starts_with('{') || text.starts_with('[') || text.starts_with("```")
+}
+
+/// Pull a JSON object or array out of a reply.
+///
+/// Accepts bare JSON, JSON inside a markdown code fence, or a single object or
+/// array surrounded by prose.
+pub(crate) fn extract_json(reply: &str) -> Option<Value> {
+    let text = reply.trim();
+    let unfenced = text
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|body| {
+            // Drop the language tag on the opening fence line
+            body.split_once('\n').map_or(body, |(_, code)| code).trim()
+        })
+        .unwrap_or(text);
+    if let Ok(value) = serde_json::from_str(unfenced) {
+        return Some(value);
+    }
+
+    let start = unfenced.find(['{', '['])?;
+    let close = if unfenced[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = unfenced.rfind(close)?;
+    (end > start)
+        .then(|| serde_json::from_str(&unfenced[start..=end]).ok())
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Queries {
+        queries: Vec<String>,
+    }
+
+    #[test]
+    fn extracts_json_from_prose_and_applies_validator() {
+        let output = StructuredOutput::<Queries>::new().with_validator(|q| {
+            if q.queries.len() == 2 {
+                Ok(())
+            } else {
+                Err(format!("Expected 2 queries, got {}", q.queries.len()))
+            }
+        });
+
+        let reply = r#"Here you go: {"queries": ["a", "b"]} Hope that helps"#;
+        assert_eq!(
+            output.parse(reply).unwrap().value().unwrap().queries,
+            vec!["a", "b"]
+        );
+
+        let err = output.parse(r#"{"queries": ["a"]}"#).unwrap_err();
+        assert_eq!(err, "Expected 2 queries, got 1");
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Estimate {
+        #[schemars(range(min = 0.0))]
+        amount: f64,
+        #[schemars(length(min = 1))]
+        items: Vec<String>,
+    }
+
+    #[test]
+    fn replies_are_validated_against_the_schema() {
+        let output = StructuredOutput::<Estimate>::new();
+        assert!(
+            output
+                .parse(r#"{"amount": 800, "items": ["bumper"]}"#)
+                .is_ok()
+        );
+
+        // Deserializes fine, but violates the schema's constraints
+        let err = output.parse(r#"{"amount": -5, "items": []}"#).unwrap_err();
+        assert!(
+            err.starts_with("The JSON does not match the schema"),
+            "{err}"
+        );
+        assert!(err.contains("/amount") && err.contains("/items"), "{err}");
+    }
+
+    #[test]
+    fn plain_text_is_clarification_only_when_allowed() {
+        let output = StructuredOutput::<Queries>::new();
+        assert_eq!(
+            output.parse("Which condition?").unwrap(),
+            Extraction::Clarification("Which condition?".to_string())
+        );
+        assert!(output.parse("{\"queries\": [").is_err());
+        assert!(
+            output
+                .with_clarifications(false)
+                .parse("Which condition?")
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "rig")]
+    mod repair {
+        use super::*;
+        use rig::completion::{Chat, Message, PromptError};
+        use rig::wasm_compat::WasmCompatSend;
+        use std::sync::Mutex;
+
+        /// Returns scripted replies in order, recording each prompt and history length.
+        struct ScriptedAgent {
+            replies: Mutex<Vec<&'static str>>,
+            calls: Mutex<Vec<(Message, usize)>>,
+        }
+
+        impl ScriptedAgent {
+            fn new(mut replies: Vec<&'static str>) -> Self {
+                replies.reverse();
+                Self {
+                    replies: Mutex::new(replies),
+                    calls: Mutex::new(Vec::new()),
+                }
+            }
+        }
+
+        impl Chat for ScriptedAgent {
+            #[allow(refining_impl_trait)]
+            fn chat<I, T>(
+                &self,
+                prompt: impl Into<Message> + WasmCompatSend,
+                chat_history: I,
+            ) -> impl std::future::Future<Output = std::result::Result<String, PromptError>>
+            + WasmCompatSend
+            where
+                I: IntoIterator<Item = T> + WasmCompatSend,
+                T: Into<Message>,
+            {
+                let history = chat_history.into_iter().count();
+                self.calls.lock().unwrap().push((prompt.into(), history));
+                let reply = self.replies.lock().unwrap().pop().unwrap_or_default();
+                async move { Ok(reply.to_string()) }
+            }
+        }
+
+        #[tokio::test]
+        async fn repairs_invalid_reply_with_validation_error() {
+            let agent = ScriptedAgent::new(vec![r#"{"queries": "a"}"#, r#"{"queries": ["a"]}"#]);
+            let output = StructuredOutput::<Queries>::new();
+
+            let result = output
+                .extract(&agent, "Find queries", vec![Message::user("earlier")])
+                .await
+                .unwrap();
+            assert_eq!(result.value().unwrap().queries, vec!["a"]);
+
+            let calls = agent.calls.lock().unwrap();
+            assert_eq!(calls.len(), 2);
+            assert_eq!(calls[1].1, 3);
+            let Message::User { content } = &calls[1].0 else {
+                panic!("expected user message");
+            };
+            let rig::message::UserContent::Text(text) = content.first_ref() else {
+                panic!("expected text");
+            };
+            assert!(text.text.contains("does not match the schema"));
+        }
+
+        #[tokio::test]
+        async fn gives_up_after_max_repairs() {
+            let agent = ScriptedAgent::new(vec!["[", "[", "["]);
+            let output = StructuredOutput::<Queries>::new().with_max_repairs(1);
+
+            let err = output
+                .extract(&agent, "Find queries", vec![])
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, GraphError::TaskExecutionFailed(msg) if msg.contains("2 attempts"))
+            );
+            assert_eq!(agent.calls.lock().unwrap().len(), 2);
+        }
+    }
+}
@@ -69,9 +69,11 @@
 //! ```
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-use crate::{context::Context, error::Result};
+use crate::{context::Context, error::Result, input::InputRequest};
 
 /// Result of a task execution.
 ///
@@ -110,6 +112,9 @@ pub struct TaskResult {
     pub task_id: String,
     /// Optional status message that describes the current state of the task
     pub status_message: Option<String>,
+    /// What the task expects when it waits for input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_request: Option<InputRequest>,
 }
 
 impl TaskResult {
@@ -133,6 +138,7 @@ impl TaskResult {
             next_action,
             task_id: String::new(),
             status_message: None,
+            input_request: None,
         }
     }
 
@@ -163,6 +169,7 @@ impl TaskResult {
             next_action,
             task_id: String::new(),
             status_message,
+            input_request: None,
         }
     }
 
@@ -179,6 +186,7 @@ impl TaskResult {
             next_action: NextAction::Continue,
             task_id: String::new(),
             status_message: None,
+            input_request: None,
         }
     }
 
@@ -195,8 +203,34 @@ impl TaskResult {
             next_action: NextAction::ContinueAndExecute,
             task_id: String::new(),
             status_message: None,
+            input_request: None,
         }
     }
+
+    /// Wait for input described by `request`, sending its prompt as the response.
+    ///
+    /// [`FlowRunner::resume`](crate::FlowRunner::resume) only accepts input that
+    /// matches the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use graph_flow::{InputRequest, NextAction, TaskResult};
+    ///
+    /// let result = TaskResult::request_input(InputRequest::number("Estimated repair cost?"));
+    /// assert_eq!(result.next_action, NextAction::WaitForInput);
+    /// ```
+    pub fn request_input(request: InputRequest) -> Self {
+        Self::new(Some(request.prompt.clone()), NextAction::WaitForInput)
+            .with_input_request(request)
+    }
+
+    /// Attach an input request to a result that waits for input
+    /// (`WaitForInput` or `WaitUntil`); ignored for other actions.
+    pub fn with_input_request(mut self, request: InputRequest) -> Self {
+        self.input_request = Some(request);
+        self
+    }
 }
 
 /// Defines what should happen after a task completes.
@@ -240,6 +274,7 @@ impl TaskResult {
 /// );
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum NextAction {
     /// Continue to the next task in the default path (step-by-step execution).
     ///
@@ -300,6 +335,24 @@ pub enum NextAction {
     ///
     /// Best for: Human-in-the-loop workflows, interactive applications
     WaitForInput,
+
+    /// Wait for user input, but run this task again at the given time if the
+    /// session hasn't been resumed by then.
+    ///
+    /// The deadline is stored on the session, where a [`Scheduler`](crate::Scheduler)
+    /// finds it, in this process or after a restart. During the run triggered by
+    /// the deadline the context holds [`TIMER_FIRED_KEY`](crate::TIMER_FIRED_KEY).
+    ///
+    /// Best for: Reminders and escalations when nobody answers
+    WaitUntil(DateTime<Utc>),
+
+    /// Continue to the next task once the duration has passed.
+    ///
+    /// Until then, executing the session runs nothing and reports
+    /// `ExecutionStatus::Sleeping`; a [`Scheduler`](crate::Scheduler) resumes it.
+    ///
+    /// Best for: Delays such as "wait 24 hours, then continue"
+    Sleep(Duration),
 }
 
 /// Core trait that all tasks must implement.
@@ -496,6 +549,53 @@ pub trait Task: Send + Sync {
     /// }
     /// ```
     async fn run(&self, context: Context) -> Result<TaskResult>;
+
+    /// Whether the graph should run this task transactionally.
+    ///
+    /// When `true` (the default), the task's context writes are buffered and only
+    /// committed once [`Task::run`] returns `Ok`. On error or timeout they are
+    /// discarded, so a failed task never leaves partial state behind.
+    ///
+    /// Override this to return `false` if the task must publish writes while it
+    /// is still running, or hands its `Context` to work that outlives `run`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use graph_flow::{Task, TaskResult, NextAction, Context};
+    /// # use async_trait::async_trait;
+    /// struct ProgressReporter;
+    ///
+    /// #[async_trait]
+    /// impl Task for ProgressReporter {
+    ///     fn id(&self) -> &str {
+    ///         "progress_reporter"
+    ///     }
+    ///
+    ///     fn transactional(&self) -> bool {
+    ///         false // progress must be visible even if a later step fails
+    ///     }
+    ///
+    ///     async fn run(&self, context: Context) -> graph_flow::Result<TaskResult> {
+    ///         context.set("progress", 50).await;
+    ///         Ok(TaskResult::new(None, NextAction::Continue))
+    ///     }
+    /// }
+    /// ```
+    fn transactional(&self) -> bool {
+        true
+    }
+
+    /// Undo the side effects of a successful [`run`](Task::run), e.g. delete a record it
+    /// created. Does nothing by default.
+    ///
+    /// Called by [`Graph::compensate_session`](crate::Graph::compensate_session) once per
+    /// run recorded in [`Session::compensable_steps`](crate::Session::compensable_steps),
+    /// most recent first, with the session context. An error is recorded and the
+    /// remaining compensations still run. See [`crate::compensation`].
+    async fn compensate(&self, _context: Context) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
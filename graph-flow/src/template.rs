@@ -0,0 +1,201 @@
+//! Prompt templates rendered from context values.
+//!
+//! Placeholders are written `{{key}}` and replaced with the value stored under
+//! `key` in the [`Context`]. Strings are inserted as-is and other values as
+//! compact JSON. Fields of structured values are reached with dots, e.g.
+//! `{{document.summary}}` or `{{items.0}}`; a key that itself contains dots is
+//! matched before its fields are.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::{Context, PromptTemplate};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let context = Context::new();
+//! context.set("user_query", "space movies").await;
+//! context.set("filters", serde_json::json!({ "year": 1999 })).await;
+//!
+//! let template = PromptTemplate::new("Rewrite for search: {{user_query}} ({{ filters.year }})");
+//! assert_eq!(template.keys(), vec!["user_query", "filters.year"]);
+//! assert_eq!(
+//!     template.render(&context).await?,
+//!     "Rewrite for search: space movies (1999)"
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::Value;
+
+use crate::context::Context;
+use crate::error::{GraphError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A prompt with `{{key}}` placeholders filled from the context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    /// Parse a template. An unclosed `{{` is kept as literal text.
+    pub fn new(template: impl AsRef<str>) -> Self {
+        let mut segments = Vec::new();
+        let mut rest = template.as_ref();
+
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let key = rest[start + 2..start + 2 + len].trim();
+            segments.push(Segment::Placeholder(key.to_string()));
+            rest = &rest[start + 2 + len + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Self { segments }
+    }
+
+    /// Placeholders referenced by the template, in order of appearance.
+    pub fn keys(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Placeholder(key) => Some(key.as_str()),
+                Segment::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /// Fill in the placeholders from `context`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GraphError::ContextError`] if a referenced key or field is missing.
+    pub async fn render(&self, context: &Context) -> Result<String> {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => output.push_str(text),
+                Segment::Placeholder(path) => {
+                    let value = Self::lookup(context, path).await?;
+                    match value {
+                        Value::String(text) => output.push_str(&text),
+                        other => output.push_str(&other.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    async fn lookup(context: &Context, path: &str) -> Result<Value> {
+        let missing = || {
+            GraphError::ContextError(format!(
+                "Prompt template references missing context value '{path}'"
+            ))
+        };
+
+        // Keys may contain dots themselves (e.g. `INPUT_KEY`), so the longest
+        // stored prefix of the path is the key and the rest are fields
+        let mut found = None;
+        for end in std::iter::once(path.len()).chain(path.rmatch_indices('.').map(|(i, _)| i)) {
+            if let Some(value) = context.try_get::<Value>(&path[..end]).await? {
+                found = Some((value, &path[end..]));
+                break;
+            }
+        }
+        let (mut value, fields) = found.ok_or_else(missing)?;
+        for field in fields.split('.').skip(1) {
+            value = match value {
+                Value::Object(mut map) => map.remove(field),
+                Value::Array(mut items) => field
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&index| index < items.len())
+                    .map(|index| items.swap_remove(index)),
+                _ => None,
+            }
+            .ok_or_else(missing)?;
+        }
+        Ok(value)
+    }
+}
+
+impl From<&str> for PromptTemplate {
+    fn from(template: &str) -> Self {
+        Self::new(template)
+    }
+}
+
+impl From<String> for PromptTemplate {
+    fn from(template: String) -> Self {
+        Self::new(template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn renders_nested_values_and_reports_missing_keys() {
+        let context = Context::new();
+        context
+            .set(
+                "document",
+                serde_json::json!({ "summary": "stable", "pages": [1, 2] }),
+            )
+            .await;
+
+        let template =
+            PromptTemplate::new("{{document.summary}} / {{document.pages.1}} / {{document.pages}}");
+        assert_eq!(
+            template.render(&context).await.unwrap(),
+            "stable / 2 / [1,2]"
+        );
+
+        let err = PromptTemplate::new("{{document.author}}")
+            .render(&context)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GraphError::ContextError(msg) if msg.contains("document.author")));
+    }
+
+    #[tokio::test]
+    async fn keys_may_contain_dots() {
+        let context = Context::new();
+        context.set(crate::INPUT_KEY, "hello").await;
+        context
+            .set("claim.details", serde_json::json!({ "amount": 800 }))
+            .await;
+
+        let template = PromptTemplate::new(format!(
+            "{{{{{}}}}} {{{{claim.details.amount}}}}",
+            crate::INPUT_KEY
+        ));
+        assert_eq!(template.render(&context).await.unwrap(), "hello 800");
+    }
+
+    #[test]
+    fn unclosed_placeholder_is_literal() {
+        let template = PromptTemplate::new("Hello {{name}}, {{ unclosed");
+        assert_eq!(template.keys(), vec!["name"]);
+        assert_eq!(
+            template.segments.last(),
+            Some(&Segment::Literal(", {{ unclosed".to_string()))
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Helpers for scripted workflow tests.
+//!
+//! - [`MockTask`]: a task that plays back scripted [`TaskResult`]s and context
+//!   writes, either in a test graph or in place of a real task
+//! - [`Scenario`]: drives a graph through a sequence of user inputs and
+//!   collects a [`ScenarioOutcome`] with the path of task ids taken, the final
+//!   status, the responses and the resulting context
+//!
+//! # Examples
+//!
+//! ```rust
+//! use graph_flow::testing::{MockTask, Scenario};
+//! use graph_flow::{GraphBuilder, NextAction, TaskResult};
+//! use serde_json::json;
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let ask = Arc::new(
+//!     MockTask::new("ask")
+//!         .then(TaskResult::new(Some("Car or apartment?".into()), NextAction::WaitForInput))
+//!         .then_writing([("insurance_type", json!("car"))], TaskResult::move_to_next_direct()),
+//! );
+//! let done = Arc::new(MockTask::new("done").then(TaskResult::new(None, NextAction::End)));
+//! let graph = GraphBuilder::new("claims")
+//!     .add_task(ask)
+//!     .add_task(done)
+//!     .add_edge("ask", "done")
+//!     .build();
+//!
+//! let outcome = Scenario::new(graph).inputs(["I had an accident", "car"]).run().await;
+//! outcome.assert_path(&["ask", "ask", "done"]);
+//! assert!(outcome.is_completed());
+//! outcome.assert_context("insurance_type", json!("car")).await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Stubbing a task of a production graph replaces it by id, so the rest of the
+//! graph runs for real:
+//!
+//! ```rust,ignore
+//! let outcome = Scenario::new(create_default_graph(llm))
+//!     .stub(MockTask::replacing::<InsuranceTypeClassifierTask>().then_writing(
+//!         [("insurance_type", json!("car"))],
+//!         TaskResult::move_to_next_direct(),
+//!     ))
+//!     .inputs(["My car was hit"])
+//!     .run()
+//!     .await;
+//! ```
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::context::Context;
+use crate::error::{GraphError, Result};
+use crate::graph::{ExecutionStatus, Graph};
+use crate::storage::Session;
+use crate::task::{Task, TaskResult};
+
+/// Context key [`Scenario`] writes each user input to, unless overridden.
+pub const DEFAULT_INPUT_KEY: &str = "user_input";
+
+/// Runs allowed per scenario before it is stopped, to catch routing loops.
+const DEFAULT_MAX_STEPS: usize = 100;
+
+#[derive(Clone)]
+struct MockStep {
+    writes: Vec<(String, Value)>,
+    outcome: std::result::Result<TaskResult, String>,
+}
+
+/// A task that plays back a script.
+///
+/// Each run writes the task's fixed values, then the next scripted step's
+/// values, and returns that step's result. Once the script is used up the last
+/// step repeats; with no script the task writes its values and continues
+/// straight to the next task.
+pub struct MockTask {
+    id: String,
+    writes: Vec<(String, Value)>,
+    steps: Vec<MockStep>,
+    calls: AtomicUsize,
+}
+
+impl MockTask {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            writes: Vec::new(),
+            steps: Vec::new(),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// A mock with the id of task type `T`, for use with [`Scenario::stub`].
+    pub fn replacing<T: Task>() -> Self {
+        Self::new(std::any::type_name::<T>())
+    }
+
+    /// Write `key` on every run.
+    pub fn writes(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        self.writes.push((key.into(), to_value(value)));
+        self
+    }
+
+    /// Return `result` on the next scripted run.
+    pub fn then(self, result: TaskResult) -> Self {
+        self.then_writing(Vec::<(String, Value)>::new(), result)
+    }
+
+    /// Write `writes` and return `result` on the next scripted run.
+    pub fn then_writing<K: Into<String>>(
+        mut self,
+        writes: impl IntoIterator<Item = (K, Value)>,
+        result: TaskResult,
+    ) -> Self {
+        self.steps.push(MockStep {
+            writes: writes.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+            outcome: Ok(result),
+        });
+        self
+    }
+
+    /// Fail with [`GraphError::TaskExecutionFailed`] on the next scripted run.
+    pub fn then_fail(mut self, message: impl Into<String>) -> Self {
+        self.steps.push(MockStep {
+            writes: Vec::new(),
+            outcome: Err(message.into()),
+        });
+        self
+    }
+
+    /// Number of times the task has run.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).expect("mock value must serialize to JSON")
+}
+
+#[async_trait]
+impl Task for MockTask {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn run(&self, context: Context) -> Result<TaskResult> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        let step = self.steps.get(call).or(self.steps.last());
+
+        for (key, value) in &self.writes {
+            context.set(key.clone(), value).await;
+        }
+        let Some(step) = step else {
+            return Ok(TaskResult::move_to_next_direct());
+        };
+        for (key, value) in &step.writes {
+            context.set(key.clone(), value).await;
+        }
+        step.outcome
+            .clone()
+            .map_err(GraphError::TaskExecutionFailed)
+    }
+}
+
+/// Drives a graph through scripted user inputs.
+///
+/// The first input is written before the first step and each following one
+/// whenever the graph waits for input. Paused steps are resumed without new
+/// input. The scenario stops when the graph completes, fails, waits for input
+/// with none left, or exceeds the step limit.
+pub struct Scenario {
+    graph: Arc<Graph>,
+    start_task_id: Option<String>,
+    input_key: String,
+    inputs: VecDeque<String>,
+    initial: Vec<(String, Value)>,
+    max_steps: usize,
+}
+
+impl Scenario {
+    pub fn new(graph: impl Into<Arc<Graph>>) -> Self {
+        Self {
+            graph: graph.into(),
+            start_task_id: None,
+            input_key: DEFAULT_INPUT_KEY.to_string(),
+            inputs: VecDeque::new(),
+            initial: Vec::new(),
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Start at `task_id` instead of the graph's start task.
+    pub fn starting_at(mut self, task_id: impl Into<String>) -> Self {
+        self.start_task_id = Some(task_id.into());
+        self
+    }
+
+    /// Context key the inputs are written to; `user_input` by default.
+    pub fn input_key(mut self, key: impl Into<String>) -> Self {
+        self.input_key = key.into();
+        self
+    }
+
+    /// User inputs, in the order they are fed to the graph.
+    pub fn inputs<S: Into<String>>(mut self, inputs: impl IntoIterator<Item = S>) -> Self {
+        self.inputs.extend(inputs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set `key` in the context before the first step.
+    pub fn with_value(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        self.initial.push((key.into(), to_value(value)));
+        self
+    }
+
+    /// Replace the graph's task with the same id as `task`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the graph has no task with that id, since the stub would
+    /// otherwise never run.
+    pub fn stub(self, task: impl Task + 'static) -> Self {
+        self.stub_shared(Arc::new(task))
+    }
+
+    /// Like [`stub`](Self::stub), keeping a handle to the task, e.g. to check
+    /// [`MockTask::calls`] afterwards.
+    pub fn stub_shared(self, task: Arc<dyn Task>) -> Self {
+        assert!(
+            self.graph.get_task(task.id()).is_some(),
+            "cannot stub '{}': no such task in graph '{}'",
+            task.id(),
+            self.graph.id
+        );
+        self.graph.add_task(task);
+        self
+    }
+
+    /// Maximum number of steps before the scenario is stopped; 100 by default.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Run the scenario. Task failures end up in the outcome's status rather
+    /// than being returned, so they can be asserted on.
+    pub async fn run(mut self) -> ScenarioOutcome {
+        let start = self
+            .start_task_id
+            .take()
+            .or_else(|| self.graph.start_task_id())
+            .unwrap_or_default();
+        let mut session = Session::new_from_task("scenario".to_string(), &start);
+        session.graph_id = self.graph.id.clone();
+        for (key, value) in &self.initial {
+            session.context.set(key.clone(), value).await;
+        }
+
+        let mut responses = Vec::new();
+        let mut failed_task = None;
+        let mut steps = 0;
+        let mut next_input = self.inputs.pop_front();
+        let status = loop {
+            if let Some(input) = next_input.take() {
+                session.context.set(self.input_key.clone(), input).await;
+            }
+            if steps == self.max_steps {
+                break ExecutionStatus::Error(format!(
+                    "Scenario stopped after {} steps",
+                    self.max_steps
+                ));
+            }
+            steps += 1;
+
+            match self.graph.execute_session(&mut session).await {
+                Ok(result) => {
+                    responses.extend(result.response);
+                    match result.status {
+                        ExecutionStatus::Paused { .. } => {}
+                        ExecutionStatus::WaitingForInput => match self.inputs.pop_front() {
+                            Some(input) => next_input = Some(input),
+                            None => break ExecutionStatus::WaitingForInput,
+                        },
+                        status => break status,
+                    }
+                }
+                Err(e) => {
+                    failed_task = Some(session.current_task_id.clone());
+                    break ExecutionStatus::Error(e.to_string());
+                }
+            }
+        };
+
+        let mut path: Vec<String> = session
+            .write_sets
+            .iter()
+            .map(|write_set| write_set.task_id.clone())
+            .collect();
+        path.extend(failed_task);
+
+        ScenarioOutcome {
+            path,
+            status,
+            responses,
+            remaining_inputs: self.inputs.into(),
+            session,
+        }
+    }
+}
+
+/// What a [`Scenario`] did.
+#[derive(Debug)]
+pub struct ScenarioOutcome {
+    /// Ids of the tasks that ran, in order, including a task that failed
+    pub path: Vec<String>,
+    pub status: ExecutionStatus,
+    /// Responses returned to the user, in order
+    pub responses: Vec<String>,
+    /// Inputs the graph never asked for
+    pub remaining_inputs: Vec<String>,
+    pub session: Session,
+}
+
+impl ScenarioOutcome {
+    pub fn context(&self) -> &Context {
+        &self.session.context
+    }
+
+    pub fn is_completed(&self) -> bool {
+        matches!(self.status, ExecutionStatus::Completed)
+    }
+
+    /// Error message if the scenario ended in a failure.
+    pub fn error(&self) -> Option<&str> {
+        match &self.status {
+            ExecutionStatus::Error(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Assert the exact sequence of task ids taken.
+    #[track_caller]
+    pub fn assert_path(&self, expected: &[&str]) {
+        assert_eq!(
+            self.path, expected,
+            "unexpected task path ({:?})",
+            self.status
+        );
+    }
+
+    /// Assert that `key` holds `expected` in the final context.
+    pub async fn assert_context(&self, key: &str, expected: impl Serialize) {
+        let expected = to_value(expected);
+        let actual: Option<Value> = self.session.context.get(key).await;
+        assert_eq!(
+            actual.as_ref(),
+            Some(&expected),
+            "unexpected value for context key '{key}'"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GraphBuilder;
+    use crate::task::NextAction;
+    use serde_json::json;
+
+    struct EchoTask;
+
+    #[async_trait]
+    impl Task for EchoTask {
+        async fn run(&self, context: Context) -> Result<TaskResult> {
+            let input: String = context.get(DEFAULT_INPUT_KEY).await.unwrap_or_default();
+            context.set("echo", input.clone()).await;
+            Ok(TaskResult::new(Some(input), NextAction::End))
+        }
+    }
+
+    fn routed_graph() -> Graph {
+        GraphBuilder::new("routed")
+            .add_task(Arc::new(
+                MockTask::new("classify")
+                    .then(TaskResult::new(
+                        Some("Which one?".to_string()),
+                        NextAction::WaitForInput,
+                    ))
+                    .then_writing([("kind", json!("b"))], TaskResult::move_to_next()),
+            ))
+            .add_task(Arc::new(MockTask::new("a")))
+            .add_task(Arc::new(EchoTask))
+            .add_conditional_edge(
+                "classify",
+                |ctx| ctx.get_sync::<String>("kind").as_deref() == Some("a"),
+                "a",
+                std::any::type_name::<EchoTask>(),
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn scenario_feeds_inputs_and_records_path() {
+        let outcome = Scenario::new(routed_graph())
+            .inputs(["hello", "the second one", "unused"])
+            .run()
+            .await;
+
+        outcome.assert_path(&["classify", "classify", std::any::type_name::<EchoTask>()]);
+        assert!(outcome.is_completed());
+        assert_eq!(outcome.responses, vec!["Which one?", "the second one"]);
+        assert_eq!(outcome.remaining_inputs, vec!["unused"]);
+        outcome.assert_context("kind", "b").await;
+        outcome.assert_context("echo", "the second one").await;
+    }
+
+    #[tokio::test]
+    async fn stubbed_task_replaces_real_one() {
+        let stub = Arc::new(MockTask::replacing::<EchoTask>().then_fail("service down"));
+        let outcome = Scenario::new(routed_graph())
+            .stub_shared(stub.clone())
+            .inputs(["hello", "b"])
+            .run()
+            .await;
+
+        assert_eq!(stub.calls(), 1);
+        assert_eq!(outcome.path.last().map(String::as_str), Some(stub.id()));
+        assert!(outcome.error().unwrap().contains("service down"));
+    }
+
+    #[tokio::test]
+    async fn waiting_without_inputs_stops_and_loops_are_capped() {
+        let outcome = Scenario::new(routed_graph()).run().await;
+        assert!(matches!(outcome.status, ExecutionStatus::WaitingForInput));
+        outcome.assert_path(&["classify"]);
+
+        let looping = GraphBuilder::new("loop")
+            .add_task(Arc::new(
+                MockTask::new("again")
+                    .then(TaskResult::new(None, NextAction::GoTo("again".into()))),
+            ))
+            .build();
+        let outcome = Scenario::new(looping).max_steps(5).run().await;
+        assert_eq!(outcome.path.len(), 5);
+        assert!(outcome.error().unwrap().contains("5 steps"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no such task")]
+    fn stubbing_unknown_task_panics() {
+        let _ = Scenario::new(routed_graph()).stub(MockTask::new("missing"));
+    }
+}
@@ -0,0 +1,390 @@
+//! Worker pool – runs queued sessions in the background.
+//!
+//! Each worker claims a [`Job`] from a [`JobQueue`], runs the job's session through
+//! a [`FlowRunner`] until it stops for input, sleeps, or completes, and records the
+//! outcome on the job. While a job runs, the worker renews its lease with heartbeats;
+//! if the heartbeat reports the job was lost (the lease expired and another worker
+//! took over), the worker stops after the current step without touching the job.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use graph_flow::{
+//!     FlowRunner, Graph, InMemoryJobQueue, InMemorySessionStorage, JobQueue, WorkerPool,
+//! };
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> graph_flow::Result<()> {
+//! let graph = Arc::new(Graph::new("ocr"));
+//! let storage = Arc::new(InMemorySessionStorage::new());
+//! let queue = Arc::new(InMemoryJobQueue::new());
+//! let runner = FlowRunner::new(graph, storage);
+//!
+//! let workers = WorkerPool::new(runner, queue.clone()).with_workers(4).spawn();
+//!
+//! // In a request handler: enqueue and answer 202 Accepted with the job id
+//! let job = queue.enqueue("ocr", "session-1", 3).await?;
+//! println!("poll /jobs/{}", job.id);
+//!
+//! workers.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::Utc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    graph::{ExecutionResult, ExecutionStatus},
+    queue::{Job, JobQueue},
+    runner::FlowRunner,
+    storage::after,
+};
+
+/// Runs jobs from a [`JobQueue`] on a fixed number of background workers.
+#[derive(Clone)]
+pub struct WorkerPool {
+    runner: FlowRunner,
+    queue: Arc<dyn JobQueue>,
+    workers: usize,
+    lease: Duration,
+    heartbeat_interval: Duration,
+    poll_interval: Duration,
+    retry_delay: Duration,
+    max_steps: usize,
+}
+
+impl WorkerPool {
+    /// Create a pool that runs sessions of `runner`'s graph from `queue`.
+    ///
+    /// The pool only claims jobs enqueued under that graph's id.
+    pub fn new(runner: FlowRunner, queue: Arc<dyn JobQueue>) -> Self {
+        Self {
+            runner,
+            queue,
+            workers: 4,
+            lease: Duration::from_secs(60),
+            heartbeat_interval: Duration::from_secs(15),
+            poll_interval: Duration::from_secs(1),
+            retry_delay: Duration::from_secs(10),
+            max_steps: 1000,
+        }
+    }
+
+    /// Number of jobs run concurrently (default: 4).
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// How long a job stays with a worker without a heartbeat (default: 60 seconds).
+    ///
+    /// A crashed job is retried once this runs out.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// How often a running job's lease is renewed (default: 15 seconds).
+    ///
+    /// Should be well below the lease.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// How long an idle worker waits before polling again (default: 1 second).
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Delay before a failed attempt is retried (default: 10 seconds).
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Maximum steps per job before it is recorded as succeeded with the session
+    /// still paused (default: 1000). Guards against graphs that loop forever.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Claim one job as `worker_id` and run it to the end.
+    ///
+    /// Returns the job as stored afterwards, or `None` if nothing was available.
+    pub async fn run_next(&self, worker_id: &str) -> Result<Option<Job>> {
+        let graph_id = &self.runner.graph().id;
+        let Some(job) = self
+            .queue
+            .claim(graph_id, worker_id, Utc::now(), self.lease)
+            .await?
+        else {
+            return Ok(None);
+        };
+        tracing::info!(job_id = %job.id, session_id = %job.session_id, attempt = job.attempts, "Running job");
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let heartbeat = tokio::spawn(self.clone().heartbeat(
+            job.id.clone(),
+            worker_id.to_string(),
+            lost.clone(),
+        ));
+        let outcome = self.drive(&job.session_id, &lost).await;
+        heartbeat.abort();
+
+        if lost.load(Ordering::SeqCst) {
+            tracing::warn!(job_id = %job.id, "Lost the lease on a job, leaving it to its new worker");
+            return self.queue.get(&job.id).await;
+        }
+
+        match outcome {
+            Ok(result) => {
+                self.queue.complete(&job.id, worker_id, result).await?;
+            }
+            Err(error) => {
+                tracing::warn!(job_id = %job.id, error = %error, "Job attempt failed");
+                let retry_at = after(Utc::now(), self.retry_delay);
+                self.queue
+                    .fail(&job.id, worker_id, &error, retry_at)
+                    .await?;
+            }
+        }
+        self.queue.get(&job.id).await
+    }
+
+    /// Run steps until the session stops being paused.
+    async fn drive(
+        &self,
+        session_id: &str,
+        lost: &AtomicBool,
+    ) -> std::result::Result<ExecutionResult, String> {
+        let mut steps = 0;
+        loop {
+            let result = self
+                .runner
+                .run(session_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            steps += 1;
+            match &result.status {
+                ExecutionStatus::Error(message) => return Err(message.clone()),
+                ExecutionStatus::Paused { .. }
+                    if steps < self.max_steps && !lost.load(Ordering::SeqCst) => {}
+                _ => return Ok(result),
+            }
+        }
+    }
+
+    async fn heartbeat(self, job_id: String, worker_id: String, lost: Arc<AtomicBool>) {
+        let mut interval = tokio::time::interval(self.heartbeat_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self
+                .queue
+                .heartbeat(&job_id, &worker_id, Utc::now(), self.lease)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    lost.store(true, Ordering::SeqCst);
+                    return;
+                }
+                Err(e) => tracing::warn!(job_id = %job_id, error = %e, "Heartbeat failed"),
+            }
+        }
+    }
+
+    async fn work(self, worker_id: String, mut shutdown: watch::Receiver<bool>) {
+        while !*shutdown.borrow() {
+            let idle = match self.run_next(&worker_id).await {
+                Ok(job) => job.is_none(),
+                Err(e) => {
+                    tracing::error!(worker_id = %worker_id, error = %e, "Worker failed to process a job");
+                    true
+                }
+            };
+            if idle {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                    // A dropped handle leaves the workers running
+                    Ok(()) = shutdown.changed() => {}
+                }
+            }
+        }
+    }
+
+    /// Start the workers on background tasks.
+    pub fn spawn(self) -> WorkerPoolHandle {
+        let (shutdown, receiver) = watch::channel(false);
+        let prefix = Uuid::new_v4().to_string();
+        let handles = (0..self.workers)
+            .map(|n| tokio::spawn(self.clone().work(format!("{prefix}-{n}"), receiver.clone())))
+            .collect();
+        WorkerPoolHandle { shutdown, handles }
+    }
+}
+
+/// Handle to the workers started by [`WorkerPool::spawn`].
+///
+/// Dropping the handle detaches the workers; they run until the runtime shuts down.
+pub struct WorkerPoolHandle {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPoolHandle {
+    /// Stop claiming jobs and wait for the running ones to finish.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for handle in self.handles {
+            let _ = handle.await;
+        }
+    }
+
+    /// Stop the workers immediately. Interrupted jobs are retried by another
+    /// worker once their lease runs out.
+    pub fn abort(self) {
+        for handle in self.handles {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTask;
+    use crate::{
+        GraphBuilder, InMemoryJobQueue, InMemorySessionStorage, JobStatus, NextAction, Session,
+        SessionStorage, TaskResult,
+    };
+    use chrono::Duration as TimeDelta;
+
+    async fn setup(first: MockTask) -> (WorkerPool, Arc<InMemoryJobQueue>) {
+        let graph = GraphBuilder::new("jobs")
+            .add_task(Arc::new(first))
+            .add_task(Arc::new(
+                MockTask::new("second").then(TaskResult::new(Some("done".into()), NextAction::End)),
+            ))
+            .add_edge("first", "second")
+            .build();
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage
+            .save(Session::new_from_task("s1".to_string(), "first"))
+            .await
+            .unwrap();
+        let queue = Arc::new(InMemoryJobQueue::new());
+        let runner = FlowRunner::new(Arc::new(graph), storage);
+        let pool = WorkerPool::new(runner, queue.clone()).with_retry_delay(Duration::ZERO);
+        (pool, queue)
+    }
+
+    #[tokio::test]
+    async fn runs_the_session_through_paused_steps() {
+        let (pool, queue) =
+            setup(MockTask::new("first").then(TaskResult::new(None, NextAction::Continue))).await;
+        let job = queue.enqueue("jobs", "s1", 3).await.unwrap();
+
+        let done = pool.run_next("w1").await.unwrap().unwrap();
+        assert_eq!(done.id, job.id);
+        assert_eq!(done.status, JobStatus::Succeeded);
+        let result = done.result.unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Completed));
+        assert_eq!(result.response.as_deref(), Some("done"));
+        assert!(pool.run_next("w1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_steps_are_retried_then_give_up() {
+        let (pool, queue) = setup(
+            MockTask::new("first")
+                .then_fail("OCR service unavailable")
+                .then(TaskResult::new(None, NextAction::WaitForInput)),
+        )
+        .await;
+        let job = queue.enqueue("jobs", "s1", 2).await.unwrap();
+
+        let retrying = pool.run_next("w1").await.unwrap().unwrap();
+        assert_eq!(retrying.status, JobStatus::Queued);
+        assert!(
+            retrying
+                .last_error
+                .unwrap()
+                .contains("OCR service unavailable")
+        );
+
+        let done = pool.run_next("w1").await.unwrap().unwrap();
+        assert_eq!(done.id, job.id);
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert!(matches!(
+            done.result.unwrap().status,
+            ExecutionStatus::WaitingForInput
+        ));
+    }
+
+    #[tokio::test]
+    async fn jobs_of_a_crashed_worker_are_picked_up_again() {
+        let (pool, queue) = setup(MockTask::new("first")).await;
+        let job = queue.enqueue("jobs", "s1", 3).await.unwrap();
+
+        // A worker claims the job and dies without heartbeats
+        let lease = Duration::from_millis(20);
+        queue
+            .claim("jobs", "crashed", Utc::now(), lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(pool.run_next("w1").await.unwrap().is_none());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let done = pool.run_next("w1").await.unwrap().unwrap();
+        assert_eq!(done.id, job.id);
+        assert_eq!(done.attempts, 2);
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert!(
+            !queue
+                .heartbeat(&job.id, "crashed", Utc::now(), lease)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn spawned_workers_drain_the_queue() {
+        let (pool, queue) = setup(MockTask::new("first")).await;
+        let job = queue.enqueue("jobs", "s1", 3).await.unwrap();
+        let workers = pool
+            .with_workers(2)
+            .with_poll_interval(Duration::from_millis(5))
+            .spawn();
+
+        let deadline = Utc::now() + TimeDelta::seconds(5);
+        while !queue
+            .get(&job.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .status
+            .is_finished()
+        {
+            assert!(Utc::now() < deadline, "job was not run");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        workers.shutdown().await;
+        assert_eq!(
+            queue.get(&job.id).await.unwrap().unwrap().status,
+            JobStatus::Succeeded
+        );
+    }
+}
@@ -1,20 +1,23 @@
 mod tasks;
 
+use crate::tasks::types::{ADJUSTER_ROLE, CLAIM_APPROVAL_TASK_ID};
 use crate::tasks::{
     ApartmentInsuranceDetailsTask, CarInsuranceDetailsTask, FinalSummaryTask,
-    InitialClaimQueryTask, InsuranceTypeClassifierTask, SmartClaimValidatorTask,
+    INITIAL_CLAIM_QUERY_TASK_ID, InsuranceTypeClassifierTask, SmartClaimValidatorTask,
+    initial_claim_query_task,
 };
 use axum::{
     Router,
     extract::{Path, State},
-    http::{HeaderValue, Request, StatusCode},
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
     middleware::{Next, from_fn},
     response::Json,
     routing::{get, post},
 };
 use graph_flow::{
-    FlowRunner, Graph, GraphBuilder, GraphStorage, InMemoryGraphStorage, InMemorySessionStorage,
-    PostgresSessionStorage, Session, SessionStorage, Task,
+    ApprovalDecision, ApprovalInput, ApprovalTask, Compensation, ContextKey, FlowRunner, Graph,
+    GraphBuilder, GraphError, GraphStorage, InMemoryGraphStorage, InMemorySessionStorage,
+    LlmClient, PostgresSessionStorage, Reviewer, Session, SessionStorage, Task,
 };
 use serde::{Deserialize, Serialize};
 use std::any::type_name;
@@ -25,10 +28,16 @@ use tracing::{Instrument, error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 
+/// Id of the authenticated user, set by the gateway in front of the service
+const USER_ID_HEADER: &str = "x-user-id";
+/// Comma-separated roles of the authenticated user
+const USER_ROLES_HEADER: &str = "x-user-roles";
+
 #[derive(Clone)]
 struct AppState {
     session_storage: Arc<dyn SessionStorage>,
     flow_runner: FlowRunner,
+    claim_approval: Arc<ApprovalTask>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +46,12 @@ struct ExecuteRequest {
     content: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ApprovalRequest {
+    decision: ApprovalDecision,
+    comment: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 struct ExecuteResponse {
     session_id: String,
@@ -105,12 +120,14 @@ async fn main() {
     // Initialize structured JSON tracing
     init_tracing();
 
-    // Check if API key is available
-    // This is required for LLM-based tasks (CollectUserDetailsTask, AnswerUserRequestsTask)
-    if std::env::var("OPENROUTER_API_KEY").is_err() {
-        error!("OPENROUTER_API_KEY not set");
-        std::process::exit(1);
-    }
+    // Shared LLM client for the conversational tasks (requires OPENROUTER_API_KEY)
+    let llm = match tasks::utils::create_llm_client() {
+        Ok(llm) => llm,
+        Err(e) => {
+            error!("Failed to create LLM client: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Create storage instances
     let graph_storage = Arc::new(InMemoryGraphStorage::new());
@@ -135,7 +152,7 @@ async fn main() {
         };
 
     // Create and store a default graph
-    let default_graph = create_default_graph();
+    let default_graph = create_default_graph(llm);
     graph_storage
         .save("default".to_string(), Arc::new(default_graph))
         .await
@@ -154,6 +171,7 @@ async fn main() {
     let app_state = AppState {
         session_storage,
         flow_runner,
+        claim_approval: claim_approval_task(),
     };
 
     // Build the router with CORS and correlation ID middleware
@@ -161,6 +179,8 @@ async fn main() {
         .route("/health", get(health_check))
         .route("/execute", post(execute_graph))
         .route("/session/{id}", get(get_session))
+        .route("/session/{id}/approval", post(submit_approval))
+        .route("/session/{id}/withdraw", post(withdraw_claim))
         .layer(create_cors_layer())
         .layer(from_fn(correlation_id_middleware))
         .with_state(app_state);
@@ -178,6 +198,7 @@ async fn health_check() -> &'static str {
 
 async fn execute_graph(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<ExecuteRequest>,
 ) -> Result<Json<ExecuteResponse>, StatusCode> {
     let correlation_id = tracing::Span::current()
@@ -202,12 +223,32 @@ async fn execute_graph(
     let session = match state.session_storage.get(&session_id).await {
         Ok(Some(session)) => session,
         Ok(None) if is_new => {
+            // Recorded so the claimant cannot approve their own claim
+            let Some(claimant_id) = header_str(&headers, USER_ID_HEADER) else {
+                info!(
+                    correlation_id = %correlation_id,
+                    "Refusing to open a claim without a claimant id"
+                );
+                return Err(StatusCode::UNAUTHORIZED);
+            };
             info!(
                 correlation_id = %correlation_id,
                 session_id = %session_id,
                 "Creating new session"
             );
-            Session::new_from_task(session_id.clone(), type_name::<InitialClaimQueryTask>())
+            let mut session =
+                Session::new_from_task(session_id.clone(), INITIAL_CLAIM_QUERY_TASK_ID);
+            session
+                .context
+                .set_key(session_keys::SESSION_ID, session_id.clone())
+                .await;
+            session
+                .context
+                .set_key(session_keys::CLAIMANT_ID, claimant_id.to_string())
+                .await;
+            // The first task takes the opening message as its input
+            session.wait_for_input();
+            session
         }
         Ok(None) => {
             error!(
@@ -228,28 +269,49 @@ async fn execute_graph(
         }
     };
 
-    // set the current user input and session ID in the session context
-    session
-        .context
-        .set(session_keys::USER_INPUT, request.content)
-        .await;
-
-    session.context.set("session_id", session_id.clone()).await;
-
-    // Save the session with updated context before execution
-    if let Err(e) = state.session_storage.save(session).await {
-        error!(
+    // Only adjusters answer the approval step, through /session/{id}/approval
+    if awaits_adjuster(&session) {
+        info!(
             correlation_id = %correlation_id,
             session_id = %session_id,
-            error = %e,
-            "Failed to save session before execution"
+            "Claim is awaiting an adjuster's decision"
         );
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(StatusCode::CONFLICT);
     }
 
-    // Execute the workflow using FlowRunner (handles load → execute → save automatically)
-    let result = match state.flow_runner.run(&session_id).await {
+    let execution = if session.pending_input.is_some() {
+        if is_new && let Err(e) = state.session_storage.save(session).await {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to create session"
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        // Input and the resulting step are saved together by FlowRunner
+        state.flow_runner.resume(&session_id, request.content).await
+    } else {
+        // Paused after a reply (`NextAction::Continue`): the message is for the next task
+        session
+            .context
+            .set_key(session_keys::USER_INPUT, request.content)
+            .await;
+        if let Err(e) = state.session_storage.save(session).await {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to save session before execution"
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        state.flow_runner.run(&session_id).await
+    };
+
+    let result = match execution {
         Ok(result) => result,
+        Err(GraphError::SessionConflict(_)) => return Err(StatusCode::CONFLICT),
         Err(e) => {
             error!(
                 correlation_id = %correlation_id,
@@ -275,6 +337,182 @@ async fn execute_graph(
     }))
 }
 
+async fn submit_approval(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<ApprovalRequest>,
+) -> Result<Json<ExecuteResponse>, StatusCode> {
+    let correlation_id = tracing::Span::current()
+        .field("correlation_id")
+        .map(|f| f.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let Some(reviewer_id) = header_str(&headers, USER_ID_HEADER) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let roles: Vec<&str> = header_str(&headers, USER_ROLES_HEADER)
+        .map(|roles| {
+            roles
+                .split(',')
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let reviewer = Reviewer::new(reviewer_id, roles);
+
+    info!(
+        correlation_id = %correlation_id,
+        session_id = %session_id,
+        reviewer = %reviewer.id,
+        decision = ?request.decision,
+        "Processing approval decision"
+    );
+
+    let session = match state.session_storage.get(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to get session"
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if !awaits_adjuster(&session) {
+        return Err(StatusCode::CONFLICT);
+    }
+    if let Err(e) = state
+        .claim_approval
+        .authorize(&session.context, &reviewer)
+        .await
+    {
+        info!(
+            correlation_id = %correlation_id,
+            session_id = %session_id,
+            error = %e,
+            "Approval decision refused"
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let input = ApprovalInput {
+        reviewer,
+        decision: request.decision,
+        comment: request.comment,
+    };
+    let result = match state.flow_runner.resume(&session_id, input).await {
+        Ok(result) => result,
+        Err(GraphError::SessionNotFound(_)) => return Err(StatusCode::NOT_FOUND),
+        Err(GraphError::NotWaitingForInput(_) | GraphError::SessionConflict(_)) => {
+            return Err(StatusCode::CONFLICT);
+        }
+        Err(GraphError::InvalidInput(_)) => return Err(StatusCode::BAD_REQUEST),
+        Err(GraphError::Unauthorized(_)) => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to apply approval decision"
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(Json(ExecuteResponse {
+        session_id,
+        response: result.response,
+        status: format!("{:?}", result.status),
+    }))
+}
+
+/// Let the claimant withdraw their claim: the session's completed steps are
+/// compensated, which withdraws an auto-approval, and the session is closed.
+async fn withdraw_claim(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Compensation>, StatusCode> {
+    let correlation_id = tracing::Span::current()
+        .field("correlation_id")
+        .map(|f| f.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let Some(user_id) = header_str(&headers, USER_ID_HEADER) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let session = match state.session_storage.get(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to get session"
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let claimant_id = session
+        .context
+        .get_key(session_keys::CLAIMANT_ID)
+        .await
+        .ok()
+        .flatten();
+    if claimant_id.as_deref() != Some(user_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    info!(
+        correlation_id = %correlation_id,
+        session_id = %session_id,
+        "Withdrawing claim"
+    );
+
+    match state
+        .flow_runner
+        .abort(&session_id, "Claim withdrawn by the claimant")
+        .await
+    {
+        Ok(compensation) => Ok(Json(compensation)),
+        Err(GraphError::SessionNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(GraphError::SessionCompensated(_) | GraphError::SessionConflict(_)) => {
+            Err(StatusCode::CONFLICT)
+        }
+        Err(e) => {
+            error!(
+                correlation_id = %correlation_id,
+                session_id = %session_id,
+                error = %e,
+                "Failed to withdraw claim"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Whether the session is waiting on the adjuster's approval step
+fn awaits_adjuster(session: &Session) -> bool {
+    session
+        .pending_input
+        .as_ref()
+        .is_some_and(|pending| pending.task_id == CLAIM_APPROVAL_TASK_ID)
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+}
+
 async fn get_session(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
@@ -319,18 +557,42 @@ async fn get_session(
     }
 }
 
-fn create_default_graph() -> Graph {
+/// Adjuster review of claims over the auto-approval threshold
+fn claim_approval_task() -> Arc<ApprovalTask> {
+    ApprovalTask::new(
+        CLAIM_APPROVAL_TASK_ID,
+        "This claim is over the $1000 auto-approval threshold and is awaiting a claims adjuster's decision.",
+    )
+    .with_required_roles([ADJUSTER_ROLE])
+    .with_requester_key(session_keys::CLAIMANT_ID.name())
+    .on_approve(type_name::<FinalSummaryTask>())
+    .on_reject(type_name::<FinalSummaryTask>())
+}
+
+/// Read a typed key in an edge condition, logging values that cannot be decoded.
+fn edge_key<T: serde::de::DeserializeOwned>(
+    context: &graph_flow::Context,
+    key: ContextKey<T>,
+) -> Option<T> {
+    context.get_key_sync(key).unwrap_or_else(|e| {
+        error!(key = key.name(), error = %e, "Failed to read edge condition key");
+        None
+    })
+}
+
+fn create_default_graph(llm: Arc<dyn LlmClient>) -> Graph {
     use crate::tasks::session_keys;
 
     let mut builder = GraphBuilder::new("simplified_insurance_claims");
 
     // Create simplified task instances
-    let initial_claim_query = Arc::new(InitialClaimQueryTask);
-    let insurance_type_classifier = Arc::new(InsuranceTypeClassifierTask);
-    let car_insurance_details = Arc::new(CarInsuranceDetailsTask);
-    let apartment_insurance_details = Arc::new(ApartmentInsuranceDetailsTask);
+    let initial_claim_query = Arc::new(initial_claim_query_task(&llm));
+    let insurance_type_classifier = Arc::new(InsuranceTypeClassifierTask::new(llm.clone()));
+    let car_insurance_details = Arc::new(CarInsuranceDetailsTask::new(llm.clone()));
+    let apartment_insurance_details = Arc::new(ApartmentInsuranceDetailsTask::new(llm));
     let smart_claim_validator = Arc::new(SmartClaimValidatorTask);
     let final_summary = Arc::new(FinalSummaryTask);
+    let claim_approval = claim_approval_task();
 
     // Get task IDs
     let initial_id = initial_claim_query.id().to_string();
@@ -347,31 +609,246 @@ fn create_default_graph() -> Graph {
         .add_task(car_insurance_details)
         .add_task(apartment_insurance_details)
         .add_task(smart_claim_validator)
+        .add_task(claim_approval)
         .add_task(final_summary);
 
     // Linear flow from initial query to classifier
     builder = builder.add_edge(initial_id, classifier_id.clone());
 
     // Conditional routing from classifier to specific details collectors
+    let is_car = |context: &graph_flow::Context| {
+        edge_key(context, session_keys::INSURANCE_TYPE)
+            .map(|t| t == "car")
+            .unwrap_or(false)
+    };
     builder = builder.add_conditional_edge(
         classifier_id.clone(),
-        |context| {
-            context
-                .get_sync::<String>(session_keys::INSURANCE_TYPE)
-                .map(|t| t == "car")
-                .unwrap_or(false)
-        },
+        is_car,
         car_details_id.clone(),       // yes – car branch
         apartment_details_id.clone(), // else – apartment branch
     );
 
     // Both details collectors flow to smart validator
     builder = builder
-        .add_edge(car_details_id, smart_validator_id.clone())
-        .add_edge(apartment_details_id, smart_validator_id.clone());
+        .add_edge(car_details_id.clone(), smart_validator_id.clone())
+        .add_edge(apartment_details_id.clone(), smart_validator_id.clone());
+
+    // Auto-approved claims go to the final summary, the rest to an adjuster
+    builder = builder.add_conditional_edge(
+        smart_validator_id,
+        |context| edge_key(context, session_keys::CLAIM_DECISION).is_some(),
+        final_summary_id,
+        CLAIM_APPROVAL_TASK_ID,
+    );
 
-    // Smart validator flows to final summary
-    builder = builder.add_edge(smart_validator_id, final_summary_id);
+    // Approvals and rejections jump to the final summary; requested changes
+    // send the claimant back to the details collector
+    builder = builder.add_conditional_edge(
+        CLAIM_APPROVAL_TASK_ID,
+        is_car,
+        car_details_id,
+        apartment_details_id,
+    );
 
     builder.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::types::ClaimDecision;
+    use graph_flow::testing::{MockTask, Scenario};
+    use graph_flow::{
+        Cassette, CassetteLlmClient, CassetteMode, ExecutionStatus, MockLlmClient, TaskResult,
+    };
+
+    /// Replays a recorded car claim conversation through the whole graph.
+    /// Re-record with: GRAPH_FLOW_CASSETTE=record OPENROUTER_API_KEY=key cargo test car_claim
+    #[tokio::test]
+    async fn car_claim_conversation_replays_from_cassette() -> anyhow::Result<()> {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/car_claim.jsonl"
+        );
+        let cassette = Arc::new(Cassette::from_env(path).await?);
+        let llm: Arc<dyn LlmClient> = match cassette.mode() {
+            CassetteMode::Replay => Arc::new(CassetteLlmClient::replay_only(cassette)),
+            _ => Arc::new(CassetteLlmClient::new(
+                cassette,
+                tasks::utils::create_llm_client()?,
+            )),
+        };
+
+        let session_storage: Arc<dyn SessionStorage> = Arc::new(InMemorySessionStorage::new());
+        let flow_runner =
+            FlowRunner::new(Arc::new(create_default_graph(llm)), session_storage.clone());
+        let session_id = "car-claim".to_string();
+        let mut session = Session::new_from_task(session_id.clone(), INITIAL_CLAIM_QUERY_TASK_ID);
+        session.wait_for_input();
+        session_storage.save(session).await?;
+
+        let turns = [
+            "Hi, I need to file a claim",
+            "Someone rear-ended my car at a traffic light yesterday",
+            "The rear bumper and trunk are damaged, the repair shop quoted $800",
+            "Yes, please proceed",
+        ];
+        let mut status = ExecutionStatus::WaitingForInput;
+        for turn in turns {
+            let session = session_storage.get(&session_id).await?.unwrap();
+            status = if session.pending_input.is_some() {
+                flow_runner.resume(&session_id, turn).await?.status
+            } else {
+                session
+                    .context
+                    .set_key(session_keys::USER_INPUT, turn.to_string())
+                    .await;
+                session_storage.save(session).await?;
+                flow_runner.run(&session_id).await?.status
+            };
+            if matches!(status, ExecutionStatus::Completed) {
+                break;
+            }
+        }
+
+        let session = session_storage.get(&session_id).await?.unwrap();
+        assert_eq!(
+            session
+                .context
+                .get_key(session_keys::INSURANCE_TYPE)
+                .await?,
+            Some("car".to_string())
+        );
+        assert!(matches!(status, ExecutionStatus::Completed), "{status:?}");
+        Ok(())
+    }
+
+    /// Runs the graph with a stubbed classifier and a claim that needs an adjuster's approval.
+    #[tokio::test]
+    async fn car_claim_over_threshold_waits_for_adjuster() -> anyhow::Result<()> {
+        let llm = Arc::new(MockLlmClient::new().with_responses([
+            "Sorry to hear that! Let's get your claim started.",
+            r#"{"description": "Rear-ended at a light", "estimated_cost": 2500.0}"#,
+        ]));
+        let graph = Arc::new(create_default_graph(llm));
+
+        let outcome = Scenario::new(graph.clone())
+            .stub(
+                MockTask::replacing::<InsuranceTypeClassifierTask>()
+                    .writes(session_keys::INSURANCE_TYPE.name(), "car")
+                    .then(TaskResult::move_to_next_direct()),
+            )
+            .input_key(session_keys::USER_INPUT.name())
+            .with_value(session_keys::CLAIMANT_ID.name(), "claimant-1")
+            .inputs(["My car was rear-ended, the repair is $2500"])
+            .run()
+            .await;
+
+        outcome.assert_path(&[
+            INITIAL_CLAIM_QUERY_TASK_ID,
+            type_name::<InsuranceTypeClassifierTask>(),
+            type_name::<CarInsuranceDetailsTask>(),
+            type_name::<SmartClaimValidatorTask>(),
+            CLAIM_APPROVAL_TASK_ID,
+        ]);
+        assert!(matches!(outcome.status, ExecutionStatus::WaitingForInput));
+
+        let mut session = outcome.session;
+        session.id = "claim".to_string();
+        assert!(awaits_adjuster(&session));
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage.save(session).await?;
+        let runner = FlowRunner::new(graph, storage.clone());
+
+        // A claimant's message is not a decision, and claimants cannot approve
+        assert!(runner.resume("claim", "approved").await.is_err());
+        let claimant = Reviewer::new("claimant-1", [ADJUSTER_ROLE]);
+        let input = ApprovalInput {
+            reviewer: claimant,
+            decision: ApprovalDecision::Approve,
+            comment: None,
+        };
+        assert!(runner.resume("claim", input).await.is_err());
+
+        let input = ApprovalInput {
+            reviewer: Reviewer::new("adjuster-7", [ADJUSTER_ROLE]),
+            decision: ApprovalDecision::Reject,
+            comment: Some("Pre-existing damage".to_string()),
+        };
+        runner.resume("claim", input).await?;
+        let result = runner.run("claim").await?;
+        assert!(
+            matches!(result.status, ExecutionStatus::Completed),
+            "{:?}",
+            result.status
+        );
+
+        let session = storage.get("claim").await?.unwrap();
+        let approval = session
+            .context
+            .get_key(session_keys::CLAIM_APPROVAL)
+            .await?
+            .unwrap();
+        let decision = ClaimDecision::from(&approval);
+        assert!(!decision.approved);
+        assert!(decision.decision_reason.contains("Pre-existing damage"));
+        Ok(())
+    }
+
+    /// Withdrawing an auto-approved claim compensates the validator's approval.
+    #[tokio::test]
+    async fn withdrawn_claim_loses_its_auto_approval() -> anyhow::Result<()> {
+        let llm = Arc::new(MockLlmClient::new().with_responses([
+            "Sorry to hear that! Let's get your claim started.",
+            r#"{"description": "Scratched door", "estimated_cost": 400.0}"#,
+        ]));
+        let graph = Arc::new(create_default_graph(llm));
+
+        let outcome = Scenario::new(graph.clone())
+            .stub(
+                MockTask::replacing::<InsuranceTypeClassifierTask>()
+                    .writes(session_keys::INSURANCE_TYPE.name(), "car")
+                    .then(TaskResult::move_to_next_direct()),
+            )
+            .input_key(session_keys::USER_INPUT.name())
+            .inputs(["Someone scratched my car door, the repair is $400"])
+            .run()
+            .await;
+        outcome.assert_path(&[
+            INITIAL_CLAIM_QUERY_TASK_ID,
+            type_name::<InsuranceTypeClassifierTask>(),
+            type_name::<CarInsuranceDetailsTask>(),
+            type_name::<SmartClaimValidatorTask>(),
+            type_name::<FinalSummaryTask>(),
+        ]);
+
+        let mut session = outcome.session;
+        session.id = "claim".to_string();
+        let decision = session
+            .context
+            .get_key(session_keys::CLAIM_DECISION)
+            .await?;
+        assert!(decision.unwrap().approved);
+        let storage = Arc::new(InMemorySessionStorage::new());
+        storage.save(session).await?;
+        let runner = FlowRunner::new(graph, storage.clone());
+
+        let compensation = runner
+            .abort("claim", "Claim withdrawn by the claimant")
+            .await?;
+        assert!(compensation.is_complete());
+        assert!(
+            compensation
+                .steps
+                .iter()
+                .any(|step| step.task_id == type_name::<SmartClaimValidatorTask>())
+        );
+        let session = storage.get("claim").await?.unwrap();
+        let decision = session
+            .context
+            .get_key(session_keys::CLAIM_DECISION)
+            .await?;
+        assert!(!decision.unwrap().approved);
+        Ok(())
+    }
+}
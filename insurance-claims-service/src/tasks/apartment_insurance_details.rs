@@ -1,12 +1,16 @@
 use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
+use graph_flow::{Context, GraphError, LlmClient, NextAction, Result, Task, TaskResult};
 use rig::completion::Chat;
 use serde::Deserialize;
+use std::sync::Arc;
 use tracing::info;
 
 use crate::tasks::session_keys;
 
-use super::{types::ClaimDetails, utils::get_llm_agent};
+use super::{
+    types::ClaimDetails,
+    utils::{get_chat_history, get_llm_agent},
+};
 
 #[derive(Deserialize)]
 struct ApartmentDetailsResponse {
@@ -54,18 +58,22 @@ fn parse_apartment_details_from_response(response: &str) -> Option<(String, f64,
 }
 
 /// Task that collects detailed information for apartment insurance claims
-pub struct ApartmentInsuranceDetailsTask;
+pub struct ApartmentInsuranceDetailsTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl ApartmentInsuranceDetailsTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for ApartmentInsuranceDetailsTask {
-
     async fn run(&self, context: Context) -> Result<TaskResult> {
         info!("running task: {}", self.id());
 
-        let user_input: String = context
-            .get(session_keys::USER_INPUT)
-            .await
-            .ok_or_else(|| GraphError::ContextError("user_input not found".to_string()))?;
+        let user_input = session_keys::user_input(&context).await?;
 
         info!(
             "Collecting apartment insurance details from input: {}",
@@ -73,9 +81,9 @@ impl Task for ApartmentInsuranceDetailsTask {
         );
 
         // Get message history from context in rig format
-        let chat_history = context.get_rig_messages().await;
+        let chat_history = get_chat_history(&context).await;
         // Create agent with apartment details collection prompt
-        let agent = get_llm_agent(APARTMENT_INSURANCE_DETAILS_PROMPT)?;
+        let agent = get_llm_agent(&self.llm, APARTMENT_INSURANCE_DETAILS_PROMPT);
 
         // Use chat to get response with history
         let response = agent
@@ -92,8 +100,8 @@ impl Task for ApartmentInsuranceDetailsTask {
         {
             // Get existing claim details and update them
             let mut claim_details: ClaimDetails = context
-                .get(session_keys::CLAIM_DETAILS)
-                .await
+                .get_key(session_keys::CLAIM_DETAILS)
+                .await?
                 .unwrap_or_default();
 
             claim_details.description = Some(description.clone());
@@ -102,7 +110,7 @@ impl Task for ApartmentInsuranceDetailsTask {
 
             // Store updated claim details
             context
-                .set(session_keys::CLAIM_DETAILS, claim_details)
+                .set_key(session_keys::CLAIM_DETAILS, claim_details)
                 .await;
 
             let status_message = format!(
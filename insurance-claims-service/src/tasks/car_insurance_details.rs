@@ -1,12 +1,16 @@
 use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
+use graph_flow::{Context, GraphError, LlmClient, NextAction, Result, Task, TaskResult};
 use rig::completion::Chat;
 use serde::Deserialize;
+use std::sync::Arc;
 use tracing::info;
 
 use crate::tasks::session_keys;
 
-use super::{types::ClaimDetails, utils::get_llm_agent};
+use super::{
+    types::ClaimDetails,
+    utils::{get_chat_history, get_llm_agent},
+};
 
 #[derive(Deserialize)]
 struct CarDetailsResponse {
@@ -54,23 +58,28 @@ fn parse_car_details_from_response(response: &str) -> Option<(String, f64, Optio
 }
 
 /// Task that collects detailed information for car insurance claims
-pub struct CarInsuranceDetailsTask;
+pub struct CarInsuranceDetailsTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl CarInsuranceDetailsTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for CarInsuranceDetailsTask {
     async fn run(&self, context: Context) -> Result<TaskResult> {
         info!("running task: {}", self.id());
 
-        let user_input: String = context
-            .get(session_keys::USER_INPUT)
-            .await
-            .ok_or_else(|| GraphError::ContextError("user_input not found".to_string()))?;
+        let user_input = session_keys::user_input(&context).await?;
 
         // Get message history from context in rig format
-        let chat_history = context.get_rig_messages().await;
+        let chat_history = get_chat_history(&context).await;
 
         // Create agent with car details collection prompt
-        let agent = get_llm_agent(CAR_INSURANCE_DETAILS_PROMPT)?;
+        let agent = get_llm_agent(&self.llm, CAR_INSURANCE_DETAILS_PROMPT);
 
         // Use chat to get response with history
         let response = agent
@@ -86,8 +95,8 @@ impl Task for CarInsuranceDetailsTask {
         {
             // Get existing claim details and update them
             let mut claim_details: ClaimDetails = context
-                .get(session_keys::CLAIM_DETAILS)
-                .await
+                .get_key(session_keys::CLAIM_DETAILS)
+                .await?
                 .unwrap_or_default();
 
             claim_details.description = Some(description.clone());
@@ -96,7 +105,7 @@ impl Task for CarInsuranceDetailsTask {
 
             // Store updated claim details
             context
-                .set(session_keys::CLAIM_DETAILS, claim_details)
+                .set_key(session_keys::CLAIM_DETAILS, claim_details)
                 .await;
 
             let status_message = format!(
@@ -4,36 +4,50 @@ use tracing::info;
 
 use crate::tasks::session_keys;
 
-use super::types::{ClaimDetails, ClaimDecision};
+use super::types::{ClaimDecision, ClaimDetails};
 
 /// Single endpoint task for all claim outcomes (approved/rejected)
 pub struct FinalSummaryTask;
 
 #[async_trait]
 impl Task for FinalSummaryTask {
-
     async fn run(&self, context: Context) -> Result<TaskResult> {
         info!("running task: {}", self.id());
 
         let claim_details: ClaimDetails = context
-            .get(session_keys::CLAIM_DETAILS)
-            .await
+            .get_key(session_keys::CLAIM_DETAILS)
+            .await?
             .ok_or_else(|| GraphError::ContextError("claim_details not found".to_string()))?;
 
-        let claim_decision: ClaimDecision = context
-            .get(session_keys::CLAIM_DECISION)
-            .await
-            .ok_or_else(|| GraphError::ContextError("claim_decision not found".to_string()))?;
+        // Auto-approved by the validator, or decided by an adjuster
+        let claim_decision: ClaimDecision = match context
+            .get_key(session_keys::CLAIM_DECISION)
+            .await?
+        {
+            Some(decision) => decision,
+            None => context
+                .get_key(session_keys::CLAIM_APPROVAL)
+                .await?
+                .as_ref()
+                .map(ClaimDecision::from)
+                .ok_or_else(|| GraphError::ContextError("claim_decision not found".to_string()))?,
+        };
 
         let insurance_type = claim_details.insurance_type.as_deref().unwrap_or("unknown");
-        let description = claim_details.description.as_deref().unwrap_or("No description provided");
+        let description = claim_details
+            .description
+            .as_deref()
+            .unwrap_or("No description provided");
         let additional_info = claim_details.additional_info.as_deref().unwrap_or("");
         let claim_amount = claim_details.estimated_cost.unwrap_or(0.0);
 
         let summary = if claim_decision.approved {
             // Generate approved summary
-            info!("Generating approved summary for amount: ${:.2}", claim_amount);
-            
+            info!(
+                "Generating approved summary for amount: ${:.2}",
+                claim_amount
+            );
+
             format!(
                 "🎉 **CLAIM APPROVED** 🎉
 
@@ -79,8 +93,11 @@ Thank you for choosing our insurance services!",
             )
         } else {
             // Generate rejected summary
-            info!("Generating rejected summary for amount: ${:.2}", claim_amount);
-            
+            info!(
+                "Generating rejected summary for amount: ${:.2}",
+                claim_amount
+            );
+
             format!(
                 "❌ **CLAIM REJECTED** ❌
 
@@ -139,7 +156,11 @@ Thank you for choosing our insurance services.",
         let status_message = format!(
             "Claim processing completed - {} insurance claim {} for ${:.2}",
             insurance_type,
-            if claim_decision.approved { "APPROVED" } else { "REJECTED" },
+            if claim_decision.approved {
+                "APPROVED"
+            } else {
+                "REJECTED"
+            },
             claim_amount
         );
 
@@ -149,4 +170,4 @@ Thank you for choosing our insurance services.",
             Some(status_message),
         ))
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,12 @@
-use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
-use rig::completion::Prompt;
-use tracing::info;
+use graph_flow::{LlmAgent, LlmClient, NextAction, PromptTemplate, RigAgentTask};
+use std::sync::Arc;
 
 use crate::tasks::session_keys;
 
-use super::{types::ClaimDetails, utils::get_llm_agent};
+use super::utils::get_llm_agent;
+
+/// Id of the task that welcomes the user and starts the claim
+pub const INITIAL_CLAIM_QUERY_TASK_ID: &str = "initial_claim_query";
 
 const INITIAL_CLAIM_PROMPT: &str = r#"You are a helpful insurance claims assistant. Welcome the user and help them start their insurance claim process.
 
@@ -18,57 +19,18 @@ Be friendly, professional, and reassuring. Let them know this is the beginning o
 
 If they provide initial claim information, acknowledge it and let them know you'll help them provide more details in the next steps."#;
 
-/// Task that handles the initial claim query and welcomes the user
-pub struct InitialClaimQueryTask;
-
-#[async_trait]
-impl Task for InitialClaimQueryTask {
-    async fn run(&self, context: Context) -> Result<TaskResult> {
-        let session_id = context.get::<String>("session_id").await.unwrap_or_else(|| "unknown".to_string());
-        
-        info!(
-            session_id = %session_id,
-            task_id = %self.id(),
-            "Starting task execution"
-        );
-        
-        let user_input: String = context
-            .get(session_keys::USER_INPUT)
-            .await
-            .ok_or_else(|| GraphError::ContextError("user_input not found".to_string()))?;
-
-        info!(
-            session_id = %session_id,
-            task_id = %self.id(),
-            input_length = %user_input.len(),
-            "Processing initial claim query"
-        );
-
-        // Use LLM to welcome the user and gather initial information
-        let response = process_initial_claim(&user_input)
-            .await
-            .map_err(|e| GraphError::TaskExecutionFailed(e.to_string()))?;
-
-        // Initialize claim details in context
-        let claim_details = ClaimDetails::default();
-        context
-            .set(session_keys::CLAIM_DETAILS, claim_details)
-            .await;
-
-        // Add user message and assistant response to chat history
-        context.add_user_message(user_input.clone()).await;
-        context.add_assistant_message(response.clone()).await;
-
-        Ok(TaskResult::new_with_status(
-            Some(response),
-            NextAction::Continue,
-            Some("Claim processing started - proceeding to insurance type classification".to_string()),
-        ))
-    }
+/// Task that handles the initial claim query and welcomes the user.
+///
+/// The user's message is sent without earlier history, and the exchange is
+/// recorded in the chat history for the classifier.
+pub fn initial_claim_query_task(llm: &Arc<dyn LlmClient>) -> RigAgentTask<LlmAgent> {
+    let prompt = PromptTemplate::new(format!("{{{{{}}}}}", session_keys::USER_INPUT.name()));
+    RigAgentTask::new(
+        INITIAL_CLAIM_QUERY_TASK_ID,
+        get_llm_agent(llm, INITIAL_CLAIM_PROMPT),
+        prompt,
+    )
+    .with_history(false)
+    .with_next_action(|_| NextAction::Continue)
+    .with_status_message("Claim processing started - proceeding to insurance type classification")
 }
-
-async fn process_initial_claim(user_input: &str) -> anyhow::Result<String> {
-    let agent = get_llm_agent(INITIAL_CLAIM_PROMPT)?;
-    let response = agent.prompt(user_input).await?;
-    Ok(response)
-}
\ No newline at end of file
@@ -1,16 +1,38 @@
 use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
-use rig::completion::Chat;
+use graph_flow::{
+    Context, Extraction, LlmClient, NextAction, Result, StructuredOutput, Task, TaskResult,
+};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::sync::Arc;
 use tracing::info;
 
 use crate::tasks::session_keys;
 
-use super::{types::ClaimDetails, utils::get_llm_agent};
+use super::{
+    types::ClaimDetails,
+    utils::{get_chat_history, get_llm_agent},
+};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum InsuranceType {
+    Car,
+    Apartment,
+}
+
+impl InsuranceType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InsuranceType::Car => "car",
+            InsuranceType::Apartment => "apartment",
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
 struct InsuranceTypeResponse {
-    insurance_type: String,
+    insurance_type: InsuranceType,
 }
 
 const INSURANCE_TYPE_PROMPT: &str = r#"You are an insurance claims assistant specialized in determining the type of insurance claim.
@@ -33,19 +55,16 @@ Be specific and helpful in your questions.
 Do not mix text and JSON in your response. If you know the type, respond with the JSON format above ONLY.
 "#;
 
-/// Attempts to parse insurance type from LLM response
-fn parse_insurance_type_from_response(response: &str) -> Option<String> {
-    let parsed = serde_json::from_str::<InsuranceTypeResponse>(response.trim()).ok()?;
-    if parsed.insurance_type == "car" || parsed.insurance_type == "apartment" {
-        info!("Parsed insurance type: {}", parsed.insurance_type);
-        Some(parsed.insurance_type)
-    } else {
-        None
-    }
+/// Task that determines whether this is a car or apartment insurance claim
+pub struct InsuranceTypeClassifierTask {
+    llm: Arc<dyn LlmClient>,
 }
 
-/// Task that determines whether this is a car or apartment insurance claim
-pub struct InsuranceTypeClassifierTask;
+impl InsuranceTypeClassifierTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for InsuranceTypeClassifierTask {
@@ -61,69 +80,67 @@ impl Task for InsuranceTypeClassifierTask {
             "Starting insurance type classification"
         );
 
-        let user_input: String = context
-            .get(session_keys::USER_INPUT)
-            .await
-            .ok_or_else(|| GraphError::ContextError("user_input not found".to_string()))?;
+        let user_input = session_keys::user_input(&context).await?;
 
         // Get message history from context in rig format
-        let chat_history = context.get_rig_messages().await;
+        let chat_history = get_chat_history(&context).await;
         context.add_user_message(user_input.clone()).await;
 
         // Create agent with classification prompt
-        let agent = get_llm_agent(INSURANCE_TYPE_PROMPT)?;
+        let agent = get_llm_agent(&self.llm, INSURANCE_TYPE_PROMPT);
+
+        // Ask for a validated classification, or a clarifying question if unclear
+        let extraction = StructuredOutput::<InsuranceTypeResponse>::new()
+            .extract(&agent, user_input.clone(), chat_history)
+            .await?;
+
+        let parsed = match extraction {
+            Extraction::Value(parsed) => parsed,
+            Extraction::Clarification(question) => {
+                context.add_assistant_message(question.clone()).await;
+                let status_message =
+                    "Waiting for insurance type classification - need more information".to_string();
+                return Ok(TaskResult::new_with_status(
+                    Some(question),
+                    NextAction::WaitForInput,
+                    Some(status_message),
+                ));
+            }
+        };
+
+        let insurance_type = parsed.insurance_type.as_str().to_string();
+        info!("Insurance type determined: {}", insurance_type);
+
+        // Store insurance type in session
+        context
+            .set_key(session_keys::INSURANCE_TYPE, insurance_type.clone())
+            .await;
+
+        // Update claim details with insurance type
+        let mut claim_details: ClaimDetails = context
+            .get_key(session_keys::CLAIM_DETAILS)
+            .await?
+            .unwrap_or_default();
+        claim_details.insurance_type = Some(insurance_type.clone());
+        context
+            .set_key(session_keys::CLAIM_DETAILS, claim_details)
+            .await;
+
+        let status_message = format!(
+            "Insurance type classified as: {} - proceeding to collect specific details",
+            insurance_type
+        );
 
-        // Use chat to get response with history
-        let response = agent
-            .chat(&user_input, chat_history)
-            .await
-            .map_err(|e| GraphError::TaskExecutionFailed(e.to_string()))?;
-
-        // Try to parse insurance type from response
-        if let Some(insurance_type) = parse_insurance_type_from_response(&response) {
-            info!("Insurance type determined: {}", insurance_type);
-
-            // Store insurance type in session
-            context
-                .set(session_keys::INSURANCE_TYPE, insurance_type.clone())
-                .await;
-
-            // Update claim details with insurance type
-            let mut claim_details: ClaimDetails = context
-                .get(session_keys::CLAIM_DETAILS)
-                .await
-                .unwrap_or_default();
-            claim_details.insurance_type = Some(insurance_type.clone());
-            context
-                .set(session_keys::CLAIM_DETAILS, claim_details)
-                .await;
-
-            let status_message = format!(
-                "Insurance type classified as: {} - proceeding to collect specific details",
-                insurance_type
-            );
-
-            info!(
-                task_id = %self.id(),
-                insurance_type = %insurance_type,
-                next_step = "collect_details",
-                "Classification complete, proceeding to details collection"
-            );
-
-            return Ok(TaskResult::new_with_status(
-                None,
-                NextAction::ContinueAndExecute,
-                Some(status_message),
-            ));
-        }
+        info!(
+            task_id = %self.id(),
+            insurance_type = %insurance_type,
+            next_step = "collect_details",
+            "Classification complete, proceeding to details collection"
+        );
 
-        // If we couldn't determine the type, the response should be a clarifying question
-        context.add_assistant_message(response.clone()).await;
-        let status_message =
-            "Waiting for insurance type classification - need more information".to_string();
         Ok(TaskResult::new_with_status(
-            Some(response),
-            NextAction::WaitForInput,
+            None,
+            NextAction::ContinueAndExecute,
             Some(status_message),
         ))
     }
@@ -1,3 +1,4 @@
+use graph_flow::{ApprovalDecision, ApprovalRecord};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -15,11 +16,57 @@ pub struct ClaimDecision {
     pub timestamp: String,
 }
 
+impl From<&ApprovalRecord> for ClaimDecision {
+    fn from(record: &ApprovalRecord) -> Self {
+        let verdict = match record.decision {
+            ApprovalDecision::Approve => "approved",
+            ApprovalDecision::Reject => "rejected",
+            ApprovalDecision::RequestChanges => "returned for changes",
+        };
+        let mut decision_reason = format!("Claim {} by adjuster {}", verdict, record.reviewer.id);
+        if let Some(comment) = &record.comment {
+            decision_reason.push_str(&format!(": {}", comment));
+        }
+        Self {
+            approved: record.decision == ApprovalDecision::Approve,
+            decision_reason,
+            timestamp: record.decided_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Id of the `ApprovalTask` reviewing claims over the auto-approval threshold
+pub const CLAIM_APPROVAL_TASK_ID: &str = "claim_approval";
+/// Role a reviewer needs to decide on a claim
+pub const ADJUSTER_ROLE: &str = "claims_adjuster";
+
 // Session keys for the insurance claims workflow
 pub mod session_keys {
-    pub const USER_INPUT: &str = "user_input";
-    pub const CLAIM_DETAILS: &str = "claim_details";
-    pub const CLAIM_DECISION: &str = "claim_decision";
-    pub const INSURANCE_TYPE: &str = "insurance_type";
-    pub const APPROVAL_STATE: &str = "approval_state";
+    use super::{ClaimDecision, ClaimDetails};
+    use graph_flow::{ApprovalRecord, Context, ContextKey, GraphError, Result};
+
+    pub const SESSION_ID: ContextKey<String> = ContextKey::new("session_id");
+    /// The latest user message, delivered by `FlowRunner::resume`
+    pub const USER_INPUT: ContextKey<String> = ContextKey::new(graph_flow::INPUT_KEY);
+    /// Where sessions saved before `FlowRunner::resume` keep the latest user message
+    pub const LEGACY_USER_INPUT: ContextKey<String> = ContextKey::new("user_input");
+    pub const CLAIM_DETAILS: ContextKey<ClaimDetails> = ContextKey::new("claim_details");
+    pub const CLAIM_DECISION: ContextKey<ClaimDecision> = ContextKey::new("claim_decision");
+    pub const INSURANCE_TYPE: ContextKey<String> = ContextKey::new("insurance_type");
+    /// Id of the user who filed the claim; they may not approve it
+    pub const CLAIMANT_ID: ContextKey<String> = ContextKey::new("claimant_id");
+    /// Latest adjuster decision, written by the `claim_approval` task
+    pub const CLAIM_APPROVAL: ContextKey<ApprovalRecord> =
+        ContextKey::new("claim_approval.decision");
+
+    /// The latest user message, falling back to [`LEGACY_USER_INPUT`]
+    pub async fn user_input(context: &Context) -> Result<String> {
+        if let Some(input) = context.get_key(USER_INPUT).await? {
+            return Ok(input);
+        }
+        context
+            .get_key(LEGACY_USER_INPUT)
+            .await?
+            .ok_or_else(|| GraphError::ContextError("user_input not found".to_string()))
+    }
 }
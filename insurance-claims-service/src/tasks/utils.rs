@@ -1,10 +1,30 @@
-use rig::{client::CompletionClient, providers::openrouter};
+use std::sync::Arc;
 
-pub fn get_llm_agent(prompt: &str) -> anyhow::Result<impl rig::completion::Chat + rig::completion::Prompt> {
+use graph_flow::{ApproxTokenEstimator, Context, LlmAgent, LlmClient, RigLlmClient};
+use rig::{completion::Message, providers::openrouter};
+
+/// Token budget for chat history sent with each prompt, well within the model's window
+const CHAT_HISTORY_TOKEN_BUDGET: usize = 8_000;
+
+/// Model used when a request doesn't name one
+const DEFAULT_MODEL: &str = "openai/gpt-4o-mini";
+
+/// LLM client backed by OpenRouter, shared by all tasks
+pub fn create_llm_client() -> anyhow::Result<Arc<dyn LlmClient>> {
     let api_key = std::env::var("OPENROUTER_API_KEY")
         .map_err(|_| anyhow::anyhow!("OPENROUTER_API_KEY not set"))?;
     let client = openrouter::Client::new(&api_key)
         .map_err(|e| anyhow::anyhow!("Failed to create OpenRouter client: {}", e))?;
-    let agent = client.agent("openai/gpt-4o-mini").preamble(prompt).build();
-    Ok(agent)
+    Ok(Arc::new(RigLlmClient::new(client, DEFAULT_MODEL)))
+}
+
+pub fn get_llm_agent(llm: &Arc<dyn LlmClient>, prompt: &str) -> LlmAgent {
+    LlmAgent::new(llm.clone()).with_preamble(prompt)
+}
+
+/// Recent chat history in rig format, trimmed to fit the prompt's token budget.
+pub async fn get_chat_history(context: &Context) -> Vec<Message> {
+    context
+        .get_rig_messages_within_budget(CHAT_HISTORY_TOKEN_BUDGET, &ApproxTokenEstimator::default())
+        .await
 }
@@ -2,8 +2,8 @@ use anyhow::Result;
 use medical_document_service::tasks::pdf_extract::{
     generate_medical_summary, process_pdf_with_llm_ocr,
 };
+use medical_document_service::tasks::utils::create_llm_client;
 use std::env;
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -30,10 +30,12 @@ async fn main() -> Result<()> {
         std::process::exit(1);
     }
 
+    let llm = create_llm_client()?;
+
     println!("Processing PDF: {}", pdf_path);
     println!(
         "API Key: {}...",
-        env::var("OPENROUTER_API_KEY").unwrap()[..10].to_string()
+        &env::var("OPENROUTER_API_KEY").unwrap()[..10]
     );
     println!();
 
@@ -42,7 +44,7 @@ async fn main() -> Result<()> {
     println!("   Converting PDF to images...");
     println!("   Processing images with GPT-4V...");
 
-    match process_pdf_with_llm_ocr(pdf_path).await {
+    match process_pdf_with_llm_ocr(&llm, pdf_path).await {
         Ok(extracted_text) => {
             println!(
                 "OCR completed: {} characters extracted",
@@ -66,7 +68,7 @@ async fn main() -> Result<()> {
             println!("Step 2: Generating Medical Summary");
             println!("   Processing with medical AI...");
 
-            match generate_medical_summary(&extracted_text).await {
+            match generate_medical_summary(&llm, &extracted_text).await {
                 Ok(summary) => {
                     println!("Summary generated: {} characters", summary.len());
                     println!();
@@ -1,9 +1,9 @@
 use graph_flow::{Context, Task};
 use medical_document_service::models::MedicalDocument;
 use medical_document_service::tasks::research_search::ResearchSearchTask;
+use medical_document_service::tasks::utils::create_llm_client;
 use std::env;
 use tracing::{error, info};
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -52,7 +52,7 @@ async fn main() -> anyhow::Result<()> {
     info!("Context prepared, running research search task...");
 
     // Create and run the research search task
-    let research_task = ResearchSearchTask;
+    let research_task = ResearchSearchTask::new(create_llm_client()?);
 
     match research_task.run(context.clone()).await {
         Ok(result) => {
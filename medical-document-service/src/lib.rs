@@ -1,8 +1,8 @@
 pub mod models;
+pub mod service;
 pub mod tasks;
 pub mod workflow;
-pub mod service;
 
-pub use service::{create_app, AppState};
-pub use workflow::{build_medical_workflow, create_medical_analysis_session, create_flow_runner};
-pub use models::*;
\ No newline at end of file
+pub use models::*;
+pub use service::{AppState, create_app};
+pub use workflow::{build_medical_workflow, create_flow_runner, create_medical_analysis_session};
@@ -43,4 +43,4 @@ pub struct SessionResponse {
     pub status_message: Option<String>,
     pub context: HashMap<String, serde_json::Value>,
     pub waiting_for_input: bool,
-}
\ No newline at end of file
+}
@@ -5,7 +5,11 @@ use axum::{
     response::Json,
     routing::{get, post},
 };
-use graph_flow::{ExecutionStatus, FlowRunner, PostgresSessionStorage, SessionStorage};
+use graph_flow::{
+    BlobStore, ContextCipher, DEFAULT_MAX_ATTEMPTS, EncryptedBlobStore, ExecutionStatus,
+    FileBlobStore, FlowRunner, GraphError, Job, JobQueue, PostgresBlobStore, PostgresJobQueue,
+    PostgresSessionStorage, Session, SessionStorage, StaticKeyProvider, WorkerPool,
+};
 use serde_json::{Value, json};
 use std::sync::Arc;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -13,7 +17,11 @@ use tracing::{error, info};
 
 use crate::{
     models::{AnalyzeDocumentRequest, HumanFeedbackRequest, MedicalDocument, SessionResponse},
-    workflow::{create_flow_runner, create_medical_analysis_session},
+    tasks::utils::create_llm_client,
+    workflow::{
+        BLOB_THRESHOLD_BYTES, MEDICAL_WORKFLOW_ID, create_flow_runner,
+        create_medical_analysis_session,
+    },
 };
 
 type ApiResult<T> = Result<Json<T>, (StatusCode, Json<Value>)>;
@@ -46,7 +54,9 @@ fn internal_error(message: &str, details: &str) -> ApiError {
 #[derive(Clone)]
 pub struct AppState {
     pub session_storage: Arc<dyn SessionStorage>,
+    pub blob_store: Arc<dyn BlobStore>,
     pub flow_runner: FlowRunner,
+    pub job_queue: Arc<dyn JobQueue>,
 }
 
 pub async fn create_app() -> Router {
@@ -55,27 +65,114 @@ pub async fn create_app() -> Router {
 }
 
 async fn create_app_state() -> AppState {
-    let session_storage = create_session_storage().await;
-    let flow_runner = create_flow_runner(session_storage.clone());
+    let cipher = create_cipher();
+    let postgres = connect_postgres().await;
+    let blob_store = create_blob_store(&postgres, cipher.clone()).await;
+    let job_queue = create_job_queue(&postgres).await;
+    let session_storage = create_session_storage(postgres, cipher);
+    let llm = create_llm_client().unwrap_or_else(|e| {
+        error!("Failed to create LLM client: {}", e);
+        std::process::exit(1);
+    });
+    let flow_runner = create_flow_runner(session_storage.clone(), blob_store.clone(), llm);
+
+    // Detached: the workers live as long as the server
+    WorkerPool::new(flow_runner.clone(), job_queue.clone())
+        .with_workers(worker_count())
+        .spawn();
 
     AppState {
         session_storage,
+        blob_store,
         flow_runner,
+        job_queue,
     }
 }
 
-async fn create_session_storage() -> Arc<dyn SessionStorage> {
+/// Sessions, jobs and blobs share the session storage's connection pool.
+async fn connect_postgres() -> PostgresSessionStorage {
     let database_url =
         std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable must be set");
 
-    let pg_session_storage = PostgresSessionStorage::connect(&database_url)
+    PostgresSessionStorage::connect(&database_url)
         .await
         .unwrap_or_else(|e| {
             error!("Failed to connect to PostgreSQL: {}", e);
             std::process::exit(1);
+        })
+}
+
+async fn create_job_queue(postgres: &PostgresSessionStorage) -> Arc<dyn JobQueue> {
+    let queue = PostgresJobQueue::from_pool(postgres.pool())
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to set up the job queue: {}", e);
+            std::process::exit(1);
         });
+    Arc::new(queue)
+}
+
+/// Number of analyses run concurrently by this process, from `ANALYSIS_WORKERS` (default 2).
+fn worker_count() -> usize {
+    std::env::var("ANALYSIS_WORKERS")
+        .ok()
+        .and_then(|workers| workers.parse().ok())
+        .unwrap_or(2)
+}
+
+/// Patient data is encrypted at rest when `CONTEXT_ENCRYPTION_KEYS` is set
+/// (`id:base64-key` entries, current key first).
+fn create_cipher() -> Option<ContextCipher> {
+    std::env::var("CONTEXT_ENCRYPTION_KEYS").ok()?;
+    let provider = StaticKeyProvider::from_env("CONTEXT_ENCRYPTION_KEYS").unwrap_or_else(|e| {
+        error!("Invalid encryption keys: {}", e);
+        std::process::exit(1);
+    });
+    info!("Encrypting session contexts at rest");
+    Some(ContextCipher::new(Arc::new(provider)))
+}
+
+/// Large document text is kept out of the session row: in `BLOB_DIR` if set,
+/// otherwise as Postgres large objects.
+async fn create_blob_store(
+    postgres: &PostgresSessionStorage,
+    cipher: Option<ContextCipher>,
+) -> Arc<dyn BlobStore> {
+    let store: Arc<dyn BlobStore> = if let Ok(blob_dir) = std::env::var("BLOB_DIR") {
+        let store = FileBlobStore::new(blob_dir).await.unwrap_or_else(|e| {
+            error!("Failed to create blob directory: {}", e);
+            std::process::exit(1);
+        });
+        Arc::new(store)
+    } else {
+        Arc::new(PostgresBlobStore::from_pool(postgres.pool()))
+    };
+
+    match cipher {
+        Some(cipher) => Arc::new(EncryptedBlobStore::new(store, cipher)),
+        None => store,
+    }
+}
 
-    Arc::new(pg_session_storage)
+/// Load a session with the blob store attached so offloaded values can be read.
+async fn load_session(state: &AppState, session_id: &str) -> graph_flow::Result<Option<Session>> {
+    let session = state.session_storage.get(session_id).await?;
+    if let Some(session) = &session {
+        session
+            .context
+            .set_blob_store(state.blob_store.clone(), BLOB_THRESHOLD_BYTES);
+    }
+    Ok(session)
+}
+
+fn create_session_storage(
+    pg_session_storage: PostgresSessionStorage,
+    cipher: Option<ContextCipher>,
+) -> Arc<dyn SessionStorage> {
+    match cipher {
+        Some(cipher) => Arc::new(pg_session_storage.with_encryption(cipher)),
+        None => Arc::new(pg_session_storage),
+    }
 }
 
 fn build_router(app_state: AppState) -> Router {
@@ -85,6 +182,7 @@ fn build_router(app_state: AppState) -> Router {
         .route("/medical/analyze", post(start_analysis))
         .route("/medical/{session_id}", get(get_session_status))
         .route("/medical/{session_id}/resume", post(provide_feedback))
+        .route("/medical/jobs/{job_id}", get(get_job_status))
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
         .with_state(app_state)
@@ -96,7 +194,8 @@ async fn root() -> Json<Value> {
         "version": "1.0.0",
         "description": "AI-powered medical document analysis with human-in-the-loop review",
         "endpoints": {
-            "POST /medical/analyze": "Start new document analysis",
+            "POST /medical/analyze": "Queue new document analysis (202 Accepted)",
+            "GET /medical/jobs/{job_id}": "Get progress of a queued analysis",
             "GET /medical/{session_id}": "Get session status and results",
             "POST /medical/{session_id}/resume": "Provide human feedback to resume workflow",
             "GET /health": "Health check"
@@ -114,7 +213,7 @@ async fn health_check() -> Json<Value> {
 async fn start_analysis(
     State(state): State<AppState>,
     Json(request): Json<AnalyzeDocumentRequest>,
-) -> ApiResult<Value> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     info!(
         "Starting medical document analysis for: {}",
         request.pdf_path
@@ -126,7 +225,7 @@ async fn start_analysis(
     let session_id = session.id.clone();
 
     save_session(&state, session).await?;
-    start_workflow(&state, &session_id).await
+    enqueue_workflow(&state, &session_id).await
 }
 
 fn validate_pdf_path(pdf_path: &str) -> Result<(), ApiError> {
@@ -136,67 +235,91 @@ fn validate_pdf_path(pdf_path: &str) -> Result<(), ApiError> {
     Ok(())
 }
 
-async fn save_session(state: &AppState, session: graph_flow::Session) -> Result<(), ApiError> {
+async fn save_session(state: &AppState, session: Session) -> Result<(), ApiError> {
     state.session_storage.save(session).await.map_err(|e| {
         error!("Failed to create session: {}", e);
         internal_error("Failed to create analysis session", &e.to_string())
     })
 }
 
-async fn start_workflow(state: &AppState, session_id: &str) -> ApiResult<Value> {
+/// OCR of a long document can take minutes, so the workflow runs on the worker
+/// pool and the client polls the job.
+async fn enqueue_workflow(
+    state: &AppState,
+    session_id: &str,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     info!("Session {} created successfully", session_id);
 
-    match state.flow_runner.run(session_id).await {
-        Ok(result) => {
-            info!(
-                "Workflow execution started for session {}: {:?}",
-                session_id, result.status
-            );
-
-            // If workflow completed immediately, update session to reflect completion
-            if matches!(result.status, ExecutionStatus::Completed) {
-                if let Ok(Some(mut session)) = state.session_storage.get(session_id).await {
-                    session.context.set("workflow_completed", true).await;
-                    session.current_task_id = "completed".to_string();
-                    if let Err(e) = state.session_storage.save(session).await {
-                        error!(
-                            "Failed to save completion status for session {}: {}",
-                            session_id, e
-                        );
-                    }
-                }
-            }
+    let job = state
+        .job_queue
+        .enqueue(MEDICAL_WORKFLOW_ID, session_id, DEFAULT_MAX_ATTEMPTS)
+        .await
+        .map_err(|e| {
+            error!("Failed to queue workflow for session {}: {}", session_id, e);
+            internal_error("Failed to start analysis workflow", &e.to_string())
+        })?;
+    info!("Queued job {} for session {}", job.id, session_id);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "session_id": session_id,
+            "job_id": job.id,
+            "status": "queued",
+            "status_url": format!("/medical/jobs/{}", job.id),
+            "message": "Medical document analysis queued"
+        })),
+    ))
+}
 
-            Ok(Json(json!({
-                "session_id": session_id,
-                "status": "started",
-                "message": "Medical document analysis started successfully"
-            })))
-        }
+async fn get_job_status(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Value> {
+    match state.job_queue.get(&job_id).await {
+        Ok(Some(job)) => Ok(Json(build_job_response(job))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Job not found", "job_id": job_id })),
+        )),
         Err(e) => {
-            error!("Failed to start workflow for session {}: {}", session_id, e);
-            Err(internal_error(
-                "Failed to start analysis workflow",
-                &e.to_string(),
-            ))
+            error!("Failed to load job {}: {}", job_id, e);
+            Err(internal_error("Failed to load job", &e.to_string()))
         }
     }
 }
 
+fn build_job_response(job: Job) -> Value {
+    let mut response = json!({
+        "job_id": job.id,
+        "session_id": job.session_id,
+        "status": job.status,
+        "attempts": job.attempts,
+        "max_attempts": job.max_attempts,
+        "last_error": job.last_error,
+    });
+
+    if let Some(result) = job.result {
+        response["execution_status"] = json!(format!("{:?}", result.status));
+        response["waiting_for_input"] =
+            json!(matches!(result.status, ExecutionStatus::WaitingForInput));
+        response["response"] = json!(result.response);
+    }
+
+    response
+}
+
 async fn get_session_status(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
 ) -> ApiResult<SessionResponse> {
     info!("Getting status for session: {}", session_id);
 
-    match state.session_storage.get(&session_id).await {
+    match load_session(&state, &session_id).await {
         Ok(Some(session)) => {
             let context_map = build_context_map(&session).await;
-            let waiting_for_feedback = session
-                .context
-                .get("waiting_for_human_feedback")
-                .await
-                .unwrap_or(false);
+            let waiting_for_feedback =
+                session.pending_input.is_some() || awaits_legacy_feedback(&session).await;
 
             let workflow_completed = session
                 .context
@@ -233,7 +356,7 @@ async fn get_session_status(
 }
 
 async fn build_context_map(
-    session: &graph_flow::Session,
+    session: &Session,
 ) -> std::collections::HashMap<String, serde_json::Value> {
     let mut context_map = std::collections::HashMap::new();
 
@@ -281,19 +404,39 @@ async fn provide_feedback(
     info!("Providing feedback for session: {}", session_id);
 
     validate_feedback(&request.feedback)?;
+    adopt_legacy_feedback_wait(&state, &session_id).await?;
+    resume_workflow_with_feedback(&state, &session_id, request.feedback).await
+}
 
-    match state.session_storage.get(&session_id).await {
-        Ok(Some(session)) => {
-            update_session_with_feedback(&session, &request.feedback).await;
-            save_session_after_feedback(&state, session).await?;
-            resume_workflow_with_feedback(&state, &session_id).await
-        }
-        Ok(None) => Err(not_found_error("Session not found", &session_id)),
-        Err(e) => {
-            error!("Failed to load session {}: {}", session_id, e);
-            Err(internal_error("Failed to load session", &e.to_string()))
-        }
+/// Sessions saved before `FlowRunner::resume` existed flag the review in their
+/// context instead of recording `pending_input`.
+async fn awaits_legacy_feedback(session: &Session) -> bool {
+    session.pending_input.is_none()
+        && session
+            .context
+            .get("waiting_for_human_feedback")
+            .await
+            .unwrap_or(false)
+}
+
+/// Mark a legacy session waiting for review as waiting for input, so
+/// `FlowRunner::resume` accepts the feedback instead of answering 409.
+async fn adopt_legacy_feedback_wait(state: &AppState, session_id: &str) -> Result<(), ApiError> {
+    let mut session = match load_session(state, session_id).await {
+        Ok(Some(session)) => session,
+        // `resume` reports missing sessions and storage errors itself
+        _ => return Ok(()),
+    };
+    if !awaits_legacy_feedback(&session).await {
+        return Ok(());
     }
+    info!("Adopting legacy feedback wait for session {}", session_id);
+    session.context.remove("waiting_for_human_feedback").await;
+    session.wait_for_input();
+    state.session_storage.save(session).await.map_err(|e| {
+        error!("Failed to adopt legacy session {}: {}", session_id, e);
+        internal_error("Failed to prepare session for feedback", &e.to_string())
+    })
 }
 
 fn validate_feedback(feedback: &str) -> Result<(), ApiError> {
@@ -303,36 +446,12 @@ fn validate_feedback(feedback: &str) -> Result<(), ApiError> {
     Ok(())
 }
 
-async fn update_session_with_feedback(session: &graph_flow::Session, feedback: &str) {
-    session
-        .context
-        .set("human_feedback", feedback.to_string())
-        .await;
-
-    // Clear the waiting flag since feedback has been provided
-    session
-        .context
-        .set("waiting_for_human_feedback", false)
-        .await;
-
-    if let Some(mut document) = session.context.get::<MedicalDocument>("document").await {
-        document.human_feedback = Some(feedback.to_string());
-        session.context.set("document", document).await;
-    }
-}
-
-async fn save_session_after_feedback(
+async fn resume_workflow_with_feedback(
     state: &AppState,
-    session: graph_flow::Session,
-) -> Result<(), ApiError> {
-    state.session_storage.save(session).await.map_err(|e| {
-        error!("Failed to save session with feedback: {}", e);
-        internal_error("Failed to save feedback", &e.to_string())
-    })
-}
-
-async fn resume_workflow_with_feedback(state: &AppState, session_id: &str) -> ApiResult<Value> {
-    match state.flow_runner.run(session_id).await {
+    session_id: &str,
+    feedback: String,
+) -> ApiResult<Value> {
+    match state.flow_runner.resume(session_id, feedback).await {
         Ok(result) => {
             info!(
                 "Workflow resumed for session {}: {:?}",
@@ -340,21 +459,46 @@ async fn resume_workflow_with_feedback(state: &AppState, session_id: &str) -> Ap
             );
 
             // If workflow completed, update session to reflect completion
-            if matches!(result.status, ExecutionStatus::Completed) {
-                if let Ok(Some(mut session)) = state.session_storage.get(session_id).await {
-                    session.context.set("workflow_completed", true).await;
-                    session.current_task_id = "completed".to_string();
-                    if let Err(e) = state.session_storage.save(session).await {
-                        error!(
-                            "Failed to save completion status for session {}: {}",
-                            session_id, e
-                        );
-                    }
+            if matches!(result.status, ExecutionStatus::Completed)
+                && let Ok(Some(mut session)) = load_session(state, session_id).await
+            {
+                session.context.set("workflow_completed", true).await;
+                session.current_task_id = "completed".to_string();
+                if let Err(e) = state.session_storage.save(session).await {
+                    error!(
+                        "Failed to save completion status for session {}: {}",
+                        session_id, e
+                    );
                 }
             }
 
             Ok(Json(build_feedback_response(session_id, result)))
         }
+        Err(GraphError::SessionNotFound(_)) => {
+            Err(not_found_error("Session not found", session_id))
+        }
+        Err(GraphError::InvalidInput(message)) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid feedback",
+                "details": message,
+                "session_id": session_id
+            })),
+        )),
+        Err(GraphError::NotWaitingForInput(_)) => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "Session is not waiting for feedback",
+                "session_id": session_id
+            })),
+        )),
+        Err(GraphError::SessionConflict(_)) => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "Session was updated by another request",
+                "session_id": session_id
+            })),
+        )),
         Err(e) => {
             error!(
                 "Failed to resume workflow for session {}: {}",
@@ -376,11 +520,11 @@ fn build_feedback_response(session_id: &str, result: graph_flow::ExecutionResult
         "execution_status": format!("{:?}", result.status)
     });
 
-    if matches!(result.status, ExecutionStatus::Completed) {
-        if let Some(research_summary) = result.response {
-            response["research_summary"] = json!(research_summary);
-            response["message"] = json!("Medical document analysis completed successfully");
-        }
+    if matches!(result.status, ExecutionStatus::Completed)
+        && let Some(research_summary) = result.response
+    {
+        response["research_summary"] = json!(research_summary);
+        response["message"] = json!("Medical document analysis completed successfully");
     }
 
     response
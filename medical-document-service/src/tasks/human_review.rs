@@ -1,6 +1,8 @@
 use crate::models::MedicalDocument;
 use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
+use graph_flow::{
+    Context, GraphError, INPUT_KEY, InputRequest, NextAction, Result, Task, TaskResult,
+};
 use tracing::{error, info};
 
 pub struct HumanReviewTask;
@@ -10,7 +12,7 @@ impl Task for HumanReviewTask {
     async fn run(&self, context: Context) -> Result<TaskResult> {
         info!("Starting human review checkpoint");
 
-        let document: MedicalDocument = context
+        let mut document: MedicalDocument = context
             .get("document")
             .await
             .ok_or_else(|| GraphError::ContextError("Document not found in context".to_string()))?;
@@ -23,6 +25,14 @@ impl Task for HumanReviewTask {
             ));
         }
 
+        // Feedback submitted through `FlowRunner::resume`
+        if document.human_feedback.is_none()
+            && let Some(feedback) = context.get::<String>(INPUT_KEY).await
+        {
+            document.human_feedback = Some(feedback);
+            context.set("document", &document).await;
+        }
+
         // Check if human feedback has already been provided
         if let Some(feedback) = document.human_feedback {
             info!("Human feedback already provided: {}", feedback);
@@ -36,13 +46,13 @@ impl Task for HumanReviewTask {
         // Store current state and wait for human input
         info!("Waiting for human review of initial summary");
 
-        // Set the flag that the service looks for
-        context.set("waiting_for_human_feedback", true).await;
-
         Ok(TaskResult::new_with_status(
             Some("Summary Ready, Waiting for Doctor Review".to_string()),
             NextAction::WaitForInput,
             Some("Please provide feedback on the initial summary".to_string()),
-        ))
+        )
+        .with_input_request(InputRequest::text(
+            "Please provide feedback on the initial summary",
+        )))
     }
 }
@@ -2,13 +2,35 @@ use super::utils::get_llm_agent;
 use crate::models::{MedicalDocument, ResearchArticle};
 use async_trait::async_trait;
 use chrono::Datelike;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
+use graph_flow::{
+    Cassette, Context, GraphError, LlmClient, NextAction, Result, StructuredOutput, Task,
+    TaskResult,
+};
 use reqwest;
 use rig::completion::Prompt;
-use serde_json::Value;
+use serde_json::{Value, json};
+use std::sync::Arc;
 use tracing::{error, info, warn};
 
-pub struct ResearchSearchTask;
+pub struct ResearchSearchTask {
+    llm: Arc<dyn LlmClient>,
+    pubmed_cassette: Option<Arc<Cassette>>,
+}
+
+impl ResearchSearchTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self {
+            llm,
+            pubmed_cassette: None,
+        }
+    }
+
+    /// Record or replay PubMed requests, for regression tests
+    pub fn with_pubmed_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.pubmed_cassette = Some(cassette);
+        self
+    }
+}
 
 #[async_trait]
 impl Task for ResearchSearchTask {
@@ -26,7 +48,7 @@ impl Task for ResearchSearchTask {
             .ok_or_else(|| GraphError::ContextError("Integrated summary not found".to_string()))?;
 
         // Generate search queries from the integrated summary
-        let search_queries = match generate_search_queries(integrated_summary).await {
+        let search_queries = match generate_search_queries(&self.llm, integrated_summary).await {
             Ok(queries) => queries,
             Err(e) => {
                 error!("Failed to generate search queries: {}", e);
@@ -40,15 +62,16 @@ impl Task for ResearchSearchTask {
         info!("Generated search queries: {:?}", search_queries);
 
         // Search PubMed for relevant articles
-        let research_articles = match search_pubmed(&search_queries).await {
-            Ok(articles) => articles,
-            Err(e) => {
-                error!("PubMed search failed: {}", e);
-                // Continue with empty research rather than failing
-                warn!("Continuing without research articles due to search failure");
-                Vec::new()
-            }
-        };
+        let research_articles =
+            match search_pubmed(self.pubmed_cassette.as_deref(), &search_queries).await {
+                Ok(articles) => articles,
+                Err(e) => {
+                    error!("PubMed search failed: {}", e);
+                    // Continue with empty research rather than failing
+                    warn!("Continuing without research articles due to search failure");
+                    Vec::new()
+                }
+            };
 
         info!("Found {} research articles", research_articles.len());
 
@@ -56,7 +79,8 @@ impl Task for ResearchSearchTask {
         let research_summary = if research_articles.is_empty() {
             "No recent relevant medical literature found for this case.".to_string()
         } else {
-            match generate_research_summary(integrated_summary, &research_articles).await {
+            match generate_research_summary(&self.llm, integrated_summary, &research_articles).await
+            {
                 Ok(summary) => summary,
                 Err(e) => {
                     error!("Failed to generate research summary: {}", e);
@@ -74,6 +98,8 @@ impl Task for ResearchSearchTask {
         //updated_document.research_articles = Some(research_articles);
         updated_document.research_summary = Some(research_summary.clone());
         context.set("document", updated_document).await;
+        // Set here rather than by the caller, since analyses run on the worker pool
+        context.set("workflow_completed", true).await;
 
         info!("Medical research search completed");
 
@@ -85,7 +111,10 @@ impl Task for ResearchSearchTask {
     }
 }
 
-async fn generate_search_queries(summary: &str) -> anyhow::Result<Vec<String>> {
+async fn generate_search_queries(
+    llm: &Arc<dyn LlmClient>,
+    summary: &str,
+) -> anyhow::Result<Vec<String>> {
     let prompt = format!(
         r#"You are a medical research assistant specializing in PubMed literature search.
         
@@ -116,31 +145,33 @@ async fn generate_search_queries(summary: &str) -> anyhow::Result<Vec<String>> {
         summary
     );
 
-    let agent =
-        get_llm_agent("You are a medical research assistant specializing in literature search.")?;
-    let response = agent.prompt(&prompt).await?;
-
-    info!("LLM response for search queries: {}", response);
+    let agent = get_llm_agent(
+        llm,
+        "You are a medical research assistant specializing in literature search.",
+    );
+    let queries = StructuredOutput::<Vec<String>>::new()
+        .with_clarifications(false)
+        .with_validator(|queries| {
+            if queries.is_empty() {
+                Err("Return at least one search query.".to_string())
+            } else {
+                Ok(())
+            }
+        })
+        .extract(&agent, prompt, Vec::new())
+        .await?
+        .value()
+        .ok_or_else(|| anyhow::anyhow!("No search queries returned"))?;
 
-    // Try to extract JSON array from the response
-    let queries = if let Some(json_start) = response.find('[') {
-        if let Some(json_end) = response.rfind(']') {
-            let json_str = &response[json_start..=json_end];
-            serde_json::from_str::<Vec<String>>(json_str)
-                .map_err(|e| anyhow::anyhow!("Failed to parse extracted JSON: {}", e))?
-        } else {
-            return Err(anyhow::anyhow!("No closing bracket found in response"));
-        }
-    } else {
-        // Fallback: try to parse the entire response as JSON
-        serde_json::from_str::<Vec<String>>(&response)
-            .map_err(|e| anyhow::anyhow!("Failed to parse response as JSON: {}", e))?
-    };
+    info!("Generated search queries: {:?}", queries);
 
     Ok(queries)
 }
 
-async fn search_pubmed(search_queries: &[String]) -> anyhow::Result<Vec<ResearchArticle>> {
+async fn search_pubmed(
+    cassette: Option<&Cassette>,
+    search_queries: &[String],
+) -> anyhow::Result<Vec<ResearchArticle>> {
     let client = reqwest::Client::new();
     let base_url = "https://eutils.ncbi.nlm.nih.gov/entrez/eutils";
     let current_year = chrono::Utc::now().year();
@@ -166,15 +197,12 @@ async fn search_pubmed(search_queries: &[String]) -> anyhow::Result<Vec<Research
             current_year
         );
 
-        let search_response = client
-            .get(&search_url)
-            .send()
+        let search_request = json!({ "endpoint": "esearch", "term": search_term });
+        let search_text = get_text(&client, cassette, &search_request, &search_url)
             .await
             .map_err(|e| anyhow::anyhow!("PubMed search request failed: {}", e))?;
 
-        let search_data: Value = search_response
-            .json()
-            .await
+        let search_data: Value = serde_json::from_str(&search_text)
             .map_err(|e| anyhow::anyhow!("Failed to parse search response: {}", e))?;
 
         let pmids = search_data["esearchresult"]["idlist"]
@@ -200,17 +228,11 @@ async fn search_pubmed(search_queries: &[String]) -> anyhow::Result<Vec<Research
                 base_url, pmid_list
             );
 
-            let fetch_response = client
-                .get(&fetch_url)
-                .send()
+            let fetch_request = json!({ "endpoint": "efetch", "ids": pmid_list });
+            let xml_content = get_text(&client, cassette, &fetch_request, &fetch_url)
                 .await
                 .map_err(|e| anyhow::anyhow!("PubMed fetch request failed: {}", e))?;
 
-            let xml_content = fetch_response
-                .text()
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to get fetch response text: {}", e))?;
-
             // For simplicity, we'll parse key information from XML manually
             // In a production system, you'd use a proper XML parser
             let articles = parse_pubmed_xml(&xml_content)?;
@@ -227,6 +249,31 @@ async fn search_pubmed(search_queries: &[String]) -> anyhow::Result<Vec<Research
     Ok(Vec::new())
 }
 
+/// GET `url` as text, through the cassette when one is attached.
+/// `request` identifies the call in the cassette independently of the date range in the URL.
+async fn get_text(
+    client: &reqwest::Client,
+    cassette: Option<&Cassette>,
+    request: &Value,
+    url: &str,
+) -> Result<String> {
+    let fetch = || async {
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| GraphError::TaskExecutionFailed(e.to_string()))?;
+        response
+            .text()
+            .await
+            .map_err(|e| GraphError::TaskExecutionFailed(e.to_string()))
+    };
+    match cassette {
+        Some(cassette) => cassette.call("pubmed", request, fetch).await,
+        None => fetch().await,
+    }
+}
+
 fn parse_pubmed_xml(xml: &str) -> anyhow::Result<Vec<ResearchArticle>> {
     // This is a simplified XML parsing - in production use a proper XML parser
     let mut articles = Vec::new();
@@ -278,6 +325,7 @@ fn extract_xml_value(xml: &str, tag: &str) -> Option<String> {
 }
 
 async fn generate_research_summary(
+    llm: &Arc<dyn LlmClient>,
     summary: &str,
     articles: &[ResearchArticle],
 ) -> anyhow::Result<String> {
@@ -312,8 +360,9 @@ async fn generate_research_summary(
     );
 
     let agent = get_llm_agent(
+        llm,
         "You are a medical research analyst specializing in clinical literature review.",
-    )?;
+    );
     let response = agent.prompt(&prompt).await?;
     Ok(response)
 }
@@ -1,11 +1,20 @@
 use super::utils::get_llm_agent;
 use crate::models::MedicalDocument;
 use async_trait::async_trait;
-use graph_flow::{Context, GraphError, NextAction, Result, Task, TaskResult};
+use graph_flow::{Context, GraphError, LlmClient, NextAction, Result, Task, TaskResult};
 use rig::completion::Prompt;
+use std::sync::Arc;
 use tracing::{error, info};
 
-pub struct SummaryIntegrationTask;
+pub struct SummaryIntegrationTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl SummaryIntegrationTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for SummaryIntegrationTask {
@@ -31,7 +40,8 @@ impl Task for SummaryIntegrationTask {
 
         // Use LLM to integrate human feedback with initial summary
         let integrated_summary =
-            match integrate_feedback_with_summary(initial_summary, human_feedback).await {
+            match integrate_feedback_with_summary(&self.llm, initial_summary, human_feedback).await
+            {
                 Ok(summary) => summary,
                 Err(e) => {
                     error!("Failed to integrate feedback: {}", e);
@@ -58,6 +68,7 @@ impl Task for SummaryIntegrationTask {
 }
 
 async fn integrate_feedback_with_summary(
+    llm: &Arc<dyn LlmClient>,
     initial_summary: &str,
     human_feedback: &str,
 ) -> anyhow::Result<String> {
@@ -83,8 +94,9 @@ async fn integrate_feedback_with_summary(
             );
 
     let agent = get_llm_agent(
+        llm,
         "You are a medical AI assistant specializing in document analysis and human feedback integration.",
-    )?;
+    );
     let response = agent.prompt(&prompt).await?;
     Ok(response)
 }
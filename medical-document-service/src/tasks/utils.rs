@@ -1,10 +1,31 @@
-use rig::{client::CompletionClient, providers::openrouter};
+use std::sync::Arc;
 
-pub fn get_llm_agent(prompt: &str) -> anyhow::Result<impl rig::completion::Chat + rig::completion::Prompt> {
+use graph_flow::{Cassette, CassetteLlmClient, CassetteMode, LlmAgent, LlmClient, RigLlmClient};
+use rig::providers::openrouter;
+
+/// Model used when a request doesn't name one
+const DEFAULT_MODEL: &str = "openai/gpt-4o";
+
+/// Vision-capable model used for OCR and the initial summary
+pub const VISION_MODEL: &str = "openai/gpt-4.1-mini";
+
+/// LLM client backed by OpenRouter, shared by all tasks
+pub fn create_llm_client() -> anyhow::Result<Arc<dyn LlmClient>> {
     let api_key = std::env::var("OPENROUTER_API_KEY")
         .map_err(|_| anyhow::anyhow!("OPENROUTER_API_KEY not set"))?;
     let client = openrouter::Client::new(&api_key)
         .map_err(|e| anyhow::anyhow!("Failed to create OpenRouter client: {}", e))?;
-    let agent = client.agent("openai/gpt-4o").preamble(prompt).build();
-    Ok(agent)
+    Ok(Arc::new(RigLlmClient::new(client, DEFAULT_MODEL)))
+}
+
+/// LLM client for regression tests: replays `cassette`, or records it against OpenRouter
+pub fn cassette_llm_client(cassette: Arc<Cassette>) -> anyhow::Result<Arc<dyn LlmClient>> {
+    Ok(match cassette.mode() {
+        CassetteMode::Replay => Arc::new(CassetteLlmClient::replay_only(cassette)),
+        _ => Arc::new(CassetteLlmClient::new(cassette, create_llm_client()?)),
+    })
+}
+
+pub fn get_llm_agent(llm: &Arc<dyn LlmClient>, prompt: &str) -> LlmAgent {
+    LlmAgent::new(llm.clone()).with_preamble(prompt)
 }
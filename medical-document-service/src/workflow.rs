@@ -1,23 +1,29 @@
 use crate::models::MedicalDocument;
 use crate::tasks::*;
-use graph_flow::{FlowRunner, Graph, GraphBuilder, Session, SessionStorage, Task};
+use graph_flow::{
+    BlobStore, FlowRunner, Graph, GraphBuilder, LlmClient, Session, SessionStorage, Task,
+};
+use std::any::type_name;
 use std::sync::Arc;
 use uuid::Uuid;
 
-pub fn build_medical_workflow() -> Graph {
-    let pdf_extract_task = Arc::new(PdfExtractTask);
+/// Id of the graph built by [`build_medical_workflow`]
+pub const MEDICAL_WORKFLOW_ID: &str = "medical_workflow";
+
+pub fn build_medical_workflow(llm: Arc<dyn LlmClient>) -> Graph {
+    let pdf_extract_task = Arc::new(PdfExtractTask::new(llm.clone()));
     let pdf_extract_id = pdf_extract_task.id().to_string();
 
     let human_review_task = Arc::new(HumanReviewTask);
     let human_review_id = human_review_task.id().to_string();
 
-    let summary_integration_task = Arc::new(SummaryIntegrationTask);
+    let summary_integration_task = Arc::new(SummaryIntegrationTask::new(llm.clone()));
     let summary_integration_id = summary_integration_task.id().to_string();
 
-    let research_search_task = Arc::new(ResearchSearchTask);
+    let research_search_task = Arc::new(ResearchSearchTask::new(llm));
     let research_search_id = research_search_task.id().to_string();
 
-    GraphBuilder::new("medical_workflow")
+    GraphBuilder::new(MEDICAL_WORKFLOW_ID)
         .add_task(pdf_extract_task)
         .add_task(human_review_task)
         .add_task(summary_integration_task)
@@ -43,16 +49,95 @@ pub async fn create_medical_analysis_session(pdf_path: String) -> Session {
     };
 
     let session_id = Uuid::new_v4().to_string();
-    let pdf_extract_task = Arc::new(PdfExtractTask);
-    let pdf_extract_id = pdf_extract_task.id().to_string();
-
-    let session = Session::new_from_task(session_id, &pdf_extract_id);
+    let session = Session::new_from_task(session_id, type_name::<PdfExtractTask>());
     session.context.set("document", document).await;
 
     session
 }
 
-pub fn create_flow_runner(session_storage: Arc<dyn SessionStorage>) -> FlowRunner {
-    let graph = Arc::new(build_medical_workflow());
-    FlowRunner::new(graph, session_storage)
+/// Context values larger than this are offloaded to the blob store
+pub const BLOB_THRESHOLD_BYTES: usize = 64 * 1024;
+
+pub fn create_flow_runner(
+    session_storage: Arc<dyn SessionStorage>,
+    blob_store: Arc<dyn BlobStore>,
+    llm: Arc<dyn LlmClient>,
+) -> FlowRunner {
+    let graph = Arc::new(build_medical_workflow(llm));
+    FlowRunner::new(graph, session_storage).with_blob_store(blob_store, BLOB_THRESHOLD_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::utils::cassette_llm_client;
+    use graph_flow::{Cassette, ExecutionStatus, InMemoryBlobStore, InMemorySessionStorage};
+
+    /// Replays the reviewed-summary → research part of the workflow, including PubMed calls.
+    /// Re-record with: GRAPH_FLOW_CASSETTE=record OPENROUTER_API_KEY=key cargo test research_phase
+    #[tokio::test]
+    async fn research_phase_replays_from_cassette() -> anyhow::Result<()> {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/research_phase.jsonl"
+        );
+        let cassette = Arc::new(Cassette::from_env(path).await?);
+        let llm = cassette_llm_client(cassette.clone())?;
+
+        let research_search_task =
+            Arc::new(ResearchSearchTask::new(llm.clone()).with_pubmed_cassette(cassette));
+        let graph = GraphBuilder::new("medical_research_phase")
+            .add_task(Arc::new(HumanReviewTask))
+            .add_task(Arc::new(SummaryIntegrationTask::new(llm)))
+            .add_task(research_search_task)
+            .add_edge(
+                type_name::<HumanReviewTask>(),
+                type_name::<SummaryIntegrationTask>(),
+            )
+            .add_edge(
+                type_name::<SummaryIntegrationTask>(),
+                type_name::<ResearchSearchTask>(),
+            )
+            .build();
+
+        let document = MedicalDocument {
+            id: "doc-1".to_string(),
+            pdf_path: "discharge_letter.pdf".to_string(),
+            extracted_text: None,
+            initial_summary: Some(
+                "72-year-old patient with gait disturbance, urinary incontinence and \
+                 enlarged ventricles on MRI, consistent with normal pressure hydrocephalus."
+                    .to_string(),
+            ),
+            human_feedback: Some("Also note mild cognitive decline over 6 months.".to_string()),
+            integrated_summary: None,
+            research_keywords: None,
+            research_articles: None,
+            research_summary: None,
+            final_report: None,
+        };
+        let session =
+            Session::new_from_task("research".to_string(), type_name::<HumanReviewTask>());
+        session.context.set("document", document).await;
+
+        let session_storage: Arc<dyn SessionStorage> = Arc::new(InMemorySessionStorage::new());
+        session_storage.save(session).await?;
+        let runner = FlowRunner::new(Arc::new(graph), session_storage.clone())
+            .with_blob_store(Arc::new(InMemoryBlobStore::new()), BLOB_THRESHOLD_BYTES);
+
+        let result = runner.run("research").await?;
+        assert!(
+            matches!(result.status, ExecutionStatus::Completed),
+            "{:?}",
+            result.status
+        );
+
+        let session = session_storage.get("research").await?.unwrap();
+        let document: MedicalDocument = session.context.get("document").await.unwrap();
+        assert!(document.integrated_summary.is_some());
+        assert!(!document.research_keywords.unwrap_or_default().is_empty());
+        assert!(document.research_summary.is_some());
+        assert_eq!(session.context.get("workflow_completed").await, Some(true));
+        Ok(())
+    }
 }
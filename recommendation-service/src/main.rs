@@ -9,11 +9,13 @@ use axum::{
     Router,
 };
 use graph_flow::{
-    Context, ExecutionStatus, FlowRunner, GraphBuilder, GraphStorage, InMemoryGraphStorage,
-    PostgresSessionStorage, Session, SessionStorage, Task,
+    Cassette, CassetteLlmClient, CassetteMode, Context, ExecutionStatus, FlowRunner, Graph,
+    GraphBuilder, GraphStorage, InMemoryGraphStorage, LlmClient, PostgresSessionStorage, Session,
+    SessionStorage, Task,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tasks::utils::create_llm_client;
 use tasks::{
     AnswerGenerationTask, DeliveryTask, QueryRefinementTask, ValidationTask, VectorSearchTask,
 };
@@ -22,6 +24,9 @@ use tower_http::trace::TraceLayer;
 use tracing::{error, info, Level};
 use uuid::Uuid;
 
+/// Cassette to run the service against instead of live backends, e.g. for an
+/// offline demo; its mode comes from `GRAPH_FLOW_CASSETTE`
+const CASSETTE_PATH_ENV: &str = "RECOMMENDATION_CASSETTE";
 #[derive(Debug, Deserialize)]
 struct RecommendationRequest {
     query: String,
@@ -73,13 +78,9 @@ async fn recommend(
     let context = Context::with_max_chat_messages(50);
     context.set("user_query", params.query.clone()).await;
 
-    let session = Session {
-        id: session_id.clone(),
-        graph_id: "recommendation_flow".to_string(),
-        current_task_id: refine_task_id.to_string(),
-        status_message: None,
-        context,
-    };
+    let session = Session::new_from_task(session_id.clone(), refine_task_id)
+        .with_graph_id("recommendation_flow")
+        .with_context(context);
 
     // Save initial session - FlowRunner will handle persistence during execution
     state.session_storage.save(session).await.map_err(|e| {
@@ -108,8 +109,14 @@ async fn recommend(
                 status: "completed".to_string(),
             }))
         }
-        ExecutionStatus::Paused { next_task_id, reason } => {
-            info!("Workflow unexpectedly paused at task: {} (reason: {})", next_task_id, reason);
+        ExecutionStatus::Paused {
+            next_task_id,
+            reason,
+        } => {
+            info!(
+                "Workflow unexpectedly paused at task: {} (reason: {})",
+                next_task_id, reason
+            );
             Err(internal_error(
                 "Workflow is paused, which is not expected in this flow",
             ))
@@ -120,23 +127,47 @@ async fn recommend(
                 "Workflow is waiting for input, which is not expected in this flow",
             ))
         }
+        ExecutionStatus::Sleeping {
+            next_task_id,
+            until,
+        } => {
+            info!(
+                "Workflow unexpectedly sleeping until {} before task {}",
+                until, next_task_id
+            );
+            Err(internal_error(
+                "Workflow is sleeping, which is not expected in this flow",
+            ))
+        }
+        ExecutionStatus::PausedAtBreakpoint {
+            next_task_id,
+            breakpoint,
+        } => {
+            info!(
+                "Workflow unexpectedly stopped at breakpoint {:?} before task {}",
+                breakpoint, next_task_id
+            );
+            Err(internal_error(
+                "Workflow stopped at a breakpoint, which is not expected in this flow",
+            ))
+        }
         ExecutionStatus::Error(e) => {
             error!("Workflow error: {}", e);
             Err(internal_error(&format!("Workflow failed: {}", e)))
         }
+        status => {
+            info!("Workflow stopped with unexpected status: {:?}", status);
+            Err(internal_error("Workflow stopped unexpectedly"))
+        }
     }
 }
 
-async fn setup_graph(
-    graph_storage: Arc<dyn GraphStorage>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    info!("Setting up recommendation workflow graph");
-
+fn build_graph(llm: Arc<dyn LlmClient>, search_task: VectorSearchTask) -> Graph {
     // Create tasks
-    let refine_task: Arc<dyn Task> = Arc::new(QueryRefinementTask);
-    let search_task: Arc<dyn Task> = Arc::new(VectorSearchTask::new().await?);
-    let answer_task: Arc<dyn Task> = Arc::new(AnswerGenerationTask);
-    let validate_task: Arc<dyn Task> = Arc::new(ValidationTask);
+    let refine_task: Arc<dyn Task> = Arc::new(QueryRefinementTask::new(llm.clone()));
+    let search_task: Arc<dyn Task> = Arc::new(search_task);
+    let answer_task: Arc<dyn Task> = Arc::new(AnswerGenerationTask::new(llm.clone()));
+    let validate_task: Arc<dyn Task> = Arc::new(ValidationTask::new(llm));
     let deliver_task: Arc<dyn Task> = Arc::new(DeliveryTask);
 
     let refine_id = refine_task.id().to_string();
@@ -146,25 +177,56 @@ async fn setup_graph(
     let deliver_id = deliver_task.id().to_string();
 
     // Build graph
-    let graph = Arc::new(
-        GraphBuilder::new("recommendation_flow")
-            .add_task(refine_task)
-            .add_task(search_task)
-            .add_task(answer_task)
-            .add_task(validate_task)
-            .add_task(deliver_task)
-            .add_edge(refine_id.clone(), search_id.clone())
-            .add_edge(search_id.clone(), answer_id.clone())
-            .add_edge(answer_id.clone(), validate_id.clone())
-            // Conditional routing: if validation passes go to delivery, else back to answer generation
-            .add_conditional_edge(
-                validate_id.clone(),
-                |ctx| ctx.get_sync::<bool>("validation_passed").unwrap_or(false),
-                deliver_id.clone(),
-                answer_id.clone(), // Back to answer generation for retry
-            )
-            .build(),
-    );
+    GraphBuilder::new("recommendation_flow")
+        .add_task(refine_task)
+        .add_task(search_task)
+        .add_task(answer_task)
+        .add_task(validate_task)
+        .add_task(deliver_task)
+        .add_edge(refine_id.clone(), search_id.clone())
+        .add_edge(search_id.clone(), answer_id.clone())
+        .add_edge(answer_id.clone(), validate_id.clone())
+        // Conditional routing: if validation passes go to delivery, else back to answer generation
+        .add_conditional_edge(
+            validate_id.clone(),
+            |ctx| ctx.get_sync::<bool>("validation_passed").unwrap_or(false),
+            deliver_id.clone(),
+            answer_id.clone(), // Back to answer generation for retry
+        )
+        .build()
+}
+
+/// LLM client and vector search, recorded to or replayed from `cassette` when one is given.
+async fn create_backends(
+    cassette: Option<Arc<Cassette>>,
+) -> anyhow::Result<(Arc<dyn LlmClient>, VectorSearchTask)> {
+    let Some(cassette) = cassette else {
+        return Ok((create_llm_client()?, VectorSearchTask::new().await?));
+    };
+    let llm: Arc<dyn LlmClient> = match cassette.mode() {
+        CassetteMode::Replay => Arc::new(CassetteLlmClient::replay_only(cassette.clone())),
+        _ => Arc::new(CassetteLlmClient::new(
+            cassette.clone(),
+            create_llm_client()?,
+        )),
+    };
+    Ok((llm, VectorSearchTask::from_cassette(cassette).await?))
+}
+
+async fn setup_graph(
+    graph_storage: Arc<dyn GraphStorage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Setting up recommendation workflow graph");
+
+    let cassette = match std::env::var(CASSETTE_PATH_ENV) {
+        Ok(path) => {
+            info!("Using cassette {}", path);
+            Some(Arc::new(Cassette::from_env(path).await?))
+        }
+        Err(_) => None,
+    };
+    let (llm, search_task) = create_backends(cassette).await?;
+    let graph = Arc::new(build_graph(llm, search_task));
 
     graph_storage
         .save("recommendation_flow".to_string(), graph)
@@ -230,3 +292,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph_flow::InMemorySessionStorage;
+
+    /// Replays a recorded recommendation, including the vector search results.
+    /// Re-record with: GRAPH_FLOW_CASSETTE=record OPENROUTER_API_KEY=key MOVIES_DATABASE_URL=url cargo test recommendation
+    #[tokio::test]
+    async fn recommendation_replays_from_cassette() -> anyhow::Result<()> {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/recommendation.jsonl"
+        );
+        let cassette = Arc::new(Cassette::from_env(path).await?);
+        let (llm, search_task) = create_backends(Some(cassette)).await?;
+        let session_storage: Arc<dyn SessionStorage> = Arc::new(InMemorySessionStorage::new());
+        let flow_runner = FlowRunner::new(
+            Arc::new(build_graph(llm, search_task)),
+            session_storage.clone(),
+        );
+
+        let session = Session::new_from_task(
+            "recommendation".to_string(),
+            std::any::type_name::<QueryRefinementTask>(),
+        );
+        session
+            .context
+            .set(
+                "user_query",
+                "a funny space adventure for the whole family".to_string(),
+            )
+            .await;
+        session_storage.save(session).await?;
+
+        let execution = flow_runner.run("recommendation").await?;
+        assert!(
+            matches!(execution.status, ExecutionStatus::Completed),
+            "{:?}",
+            execution.status
+        );
+        assert!(!execution.response.unwrap_or_default().is_empty());
+        Ok(())
+    }
+}
@@ -1,14 +1,23 @@
 use async_trait::async_trait;
 use graph_flow::GraphError::TaskExecutionFailed;
-use graph_flow::{Context, NextAction, Task, TaskResult};
+use graph_flow::{Context, LlmClient, NextAction, Task, TaskResult};
 use rig::completion::Chat;
+use std::sync::Arc;
 use tracing::info;
 
 use super::types::MAX_RETRIES;
 use super::utils::get_llm_agent;
 
 /// Task to generate answers using retrieved context
-pub struct AnswerGenerationTask;
+pub struct AnswerGenerationTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl AnswerGenerationTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for AnswerGenerationTask {
@@ -40,8 +49,7 @@ impl Task for AnswerGenerationTask {
         // Get the full chat history for conversational memory
         let history = context.get_rig_messages().await;
 
-        let agent = get_llm_agent()
-            .map_err(|e| TaskExecutionFailed(format!("Failed to initialize LLM agent: {}", e)))?;
+        let agent = get_llm_agent(&self.llm);
 
         let prompt = if history.is_empty() {
             format!(
@@ -83,6 +91,9 @@ impl Task for AnswerGenerationTask {
             .await;
         context.set("answer", answer.clone()).await;
 
-        Ok(TaskResult::new(Some(answer), NextAction::ContinueAndExecute))
+        Ok(TaskResult::new(
+            Some(answer),
+            NextAction::ContinueAndExecute,
+        ))
     }
-} 
\ No newline at end of file
+}
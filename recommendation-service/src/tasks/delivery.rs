@@ -27,4 +27,4 @@ impl Task for DeliveryTask {
 
         Ok(TaskResult::new(Some(answer), NextAction::End))
     }
-} 
\ No newline at end of file
+}
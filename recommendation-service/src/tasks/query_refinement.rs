@@ -1,17 +1,25 @@
 use async_trait::async_trait;
 use graph_flow::GraphError::TaskExecutionFailed;
-use graph_flow::{Context, NextAction, Task, TaskResult};
+use graph_flow::{Context, LlmClient, NextAction, Task, TaskResult};
 use rig::completion::{Chat, Message};
+use std::sync::Arc;
 use tracing::info;
 
 use super::utils::get_llm_agent;
 
 /// Task to refine user queries for better vector search
-pub struct QueryRefinementTask;
+pub struct QueryRefinementTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl QueryRefinementTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for QueryRefinementTask {
-
     async fn run(&self, context: Context) -> graph_flow::Result<TaskResult> {
         info!("Starting query refinement task");
         let user_query: String = context
@@ -20,8 +28,7 @@ impl Task for QueryRefinementTask {
 
         info!("Original user query: {}", user_query);
 
-        let agent = get_llm_agent()
-            .map_err(|e| TaskExecutionFailed(format!("Failed to initialize LLM agent: {}", e)))?;
+        let agent = get_llm_agent(&self.llm);
 
         let refined = agent
             .chat(
@@ -45,4 +52,4 @@ impl Task for QueryRefinementTask {
 
         Ok(TaskResult::new(None, NextAction::ContinueAndExecute))
     }
-} 
\ No newline at end of file
+}
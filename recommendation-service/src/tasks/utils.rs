@@ -1,14 +1,23 @@
+use std::sync::Arc;
+
 use anyhow::Result;
-use rig::client::CompletionClient;
+use graph_flow::{LlmAgent, LlmClient, RigLlmClient};
 use tracing::info;
 
-/// Create an LLM agent using OpenRouter
-pub fn get_llm_agent() -> Result<impl rig::completion::Chat> {
+/// Model used for refinement, answers and validation
+const DEFAULT_MODEL: &str = "openai/gpt-4.1-mini";
+
+/// LLM client backed by OpenRouter, shared by all tasks
+pub fn create_llm_client() -> Result<Arc<dyn LlmClient>> {
     let api_key = std::env::var("OPENROUTER_API_KEY")
         .map_err(|_| anyhow::anyhow!("OPENROUTER_API_KEY not set"))?;
     let client = rig::providers::openrouter::Client::new(&api_key)
         .map_err(|e| anyhow::anyhow!("Failed to create OpenRouter client: {}", e))?;
-    Ok(client.agent("openai/gpt-4.1-mini").build())
+    Ok(Arc::new(RigLlmClient::new(client, DEFAULT_MODEL)))
+}
+
+pub fn get_llm_agent(llm: &Arc<dyn LlmClient>) -> LlmAgent {
+    LlmAgent::new(llm.clone())
 }
 
 /// Generate embedding for text using fastembed
@@ -34,4 +43,4 @@ pub async fn embed_query(text: &str) -> Result<Vec<f32>> {
         embedding.len()
     );
     Ok(embedding)
-} 
\ No newline at end of file
+}
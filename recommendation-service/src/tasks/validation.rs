@@ -1,14 +1,23 @@
 use async_trait::async_trait;
 use graph_flow::GraphError::TaskExecutionFailed;
-use graph_flow::{Context, NextAction, Task, TaskResult};
+use graph_flow::{Context, LlmClient, NextAction, Task, TaskResult};
 use rig::completion::{Chat, Message};
+use std::sync::Arc;
 use tracing::{error, info};
 
 use super::types::{ValidationResult, MAX_RETRIES};
 use super::utils::get_llm_agent;
 
 /// Task to validate generated answers
-pub struct ValidationTask;
+pub struct ValidationTask {
+    llm: Arc<dyn LlmClient>,
+}
+
+impl ValidationTask {
+    pub fn new(llm: Arc<dyn LlmClient>) -> Self {
+        Self { llm }
+    }
+}
 
 #[async_trait]
 impl Task for ValidationTask {
@@ -52,8 +61,7 @@ impl Task for ValidationTask {
             Answer: {answer}"#
         );
 
-        let agent = get_llm_agent()
-            .map_err(|e| TaskExecutionFailed(format!("Failed to initialize LLM agent: {}", e)))?;
+        let agent = get_llm_agent(&self.llm);
 
         let raw = agent
             .chat(&prompt, Vec::<Message>::new())
@@ -114,4 +122,4 @@ impl Task for ValidationTask {
         context.set("retry_count", retry_count + 1).await;
         Ok(TaskResult::new(None, NextAction::ContinueAndExecute))
     }
-} 
\ No newline at end of file
+}
@@ -1,46 +1,60 @@
 use async_trait::async_trait;
 use graph_flow::GraphError::TaskExecutionFailed;
-use graph_flow::{Context, NextAction, Task, TaskResult};
+use graph_flow::{Cassette, CassetteMode, Context, NextAction, Task, TaskResult};
 use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
 use tracing::info;
 
+use super::types::Movie;
 use super::utils::embed_query;
 
 /// Task to perform vector search on movie database
 pub struct VectorSearchTask {
-    pool: sqlx::PgPool,
+    pool: Option<sqlx::PgPool>,
+    cassette: Option<Arc<Cassette>>,
 }
 
 impl VectorSearchTask {
     pub async fn new() -> anyhow::Result<Self> {
-        let movies_db_url = std::env::var("MOVIES_DATABASE_URL")
-            .map_err(|_| anyhow::anyhow!("MOVIES_DATABASE_URL not set"))?;
-
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&movies_db_url)
-            .await?;
-
-        Ok(Self { pool })
+        Ok(Self {
+            pool: Some(connect_movies_db().await?),
+            cassette: None,
+        })
     }
-}
 
-#[async_trait]
-impl Task for VectorSearchTask {
-    fn id(&self) -> &str {
-        std::any::type_name::<Self>()
+    /// Record searches to `cassette`, or replay them from it without a
+    /// database or embedding model, depending on its mode
+    pub async fn from_cassette(cassette: Arc<Cassette>) -> anyhow::Result<Self> {
+        let pool = match cassette.mode() {
+            CassetteMode::Replay => None,
+            _ => Some(connect_movies_db().await?),
+        };
+        Ok(Self {
+            pool,
+            cassette: Some(cassette),
+        })
     }
 
-    async fn run(&self, context: Context) -> graph_flow::Result<TaskResult> {
-        info!("Starting vector search task");
-
-        let refined_query: String = context
-            .get_sync("refined_query")
-            .ok_or_else(|| TaskExecutionFailed("refined_query not found in context".into()))?;
+    async fn search(&self, refined_query: &str) -> graph_flow::Result<Vec<Movie>> {
+        match &self.cassette {
+            Some(cassette) => {
+                cassette
+                    .call("vector_search", refined_query, || {
+                        self.search_database(refined_query)
+                    })
+                    .await
+            }
+            None => self.search_database(refined_query).await,
+        }
+    }
 
-        info!("Searching for: {}", refined_query);
+    async fn search_database(&self, refined_query: &str) -> graph_flow::Result<Vec<Movie>> {
+        let pool = self
+            .pool
+            .as_ref()
+            .ok_or_else(|| TaskExecutionFailed("No movies database connection".into()))?;
 
-        let embedding = embed_query(&refined_query)
+        let embedding = embed_query(refined_query)
             .await
             .map_err(|e| TaskExecutionFailed(format!("Embedding generation failed: {}", e)))?;
 
@@ -59,18 +73,56 @@ impl Task for VectorSearchTask {
         );
 
         let rows = sqlx::query_as::<_, (i32, String, String)>(&sql)
-            .fetch_all(&self.pool)
+            .fetch_all(pool)
             .await
             .map_err(|e| TaskExecutionFailed(format!("Database query failed: {}", e)))?;
 
+        Ok(rows
+            .into_iter()
+            .map(|(id, title, overview)| Movie {
+                id,
+                title,
+                overview,
+            })
+            .collect())
+    }
+}
+
+async fn connect_movies_db() -> anyhow::Result<sqlx::PgPool> {
+    let movies_db_url = std::env::var("MOVIES_DATABASE_URL")
+        .map_err(|_| anyhow::anyhow!("MOVIES_DATABASE_URL not set"))?;
+
+    Ok(PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&movies_db_url)
+        .await?)
+}
+
+#[async_trait]
+impl Task for VectorSearchTask {
+    fn id(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    async fn run(&self, context: Context) -> graph_flow::Result<TaskResult> {
+        info!("Starting vector search task");
+
+        let refined_query: String = context
+            .get_sync("refined_query")
+            .ok_or_else(|| TaskExecutionFailed("refined_query not found in context".into()))?;
+
+        info!("Searching for: {}", refined_query);
+
+        let rows = self.search(&refined_query).await?;
+
         info!("Retrieved {} results from vector search", rows.len());
 
         // Concatenate the retrieved documents into a single context string.
         let context_block = rows
             .iter()
-            .map(|(_, title, overview)| {
-                info!(%title, "Retrieved movie");
-                format!("Title: {title} Overview: {overview} \n")
+            .map(|movie| {
+                info!(title = %movie.title, "Retrieved movie");
+                format!("Title: {} Overview: {} \n", movie.title, movie.overview)
             })
             .collect::<Vec<_>>()
             .join("\n---\n");
@@ -82,4 +134,4 @@ impl Task for VectorSearchTask {
 
         Ok(TaskResult::new(None, NextAction::ContinueAndExecute))
     }
-} 
\ No newline at end of file
+}